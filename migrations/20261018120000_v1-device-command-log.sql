@@ -0,0 +1,10 @@
+create table device_command_log (
+    id serial primary key,
+    device_id integer not null references device(id),
+    command text not null,
+    args jsonb not null,
+    error text, -- NULL if the command was sent successfully
+    created_at timestamp not null default (now() at time zone 'utc')
+);
+
+create index device_command_log_device_idx on device_command_log(device_id, created_at);
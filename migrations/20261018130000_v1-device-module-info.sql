@@ -0,0 +1 @@
+alter table device add column module_info jsonb; -- NULL if the module doesn't provide info
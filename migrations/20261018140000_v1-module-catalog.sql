@@ -0,0 +1,9 @@
+create table module_catalog (
+    id serial primary key,
+    name text not null,
+    hash text not null unique, -- SHA-256 of the library file
+    size bigint not null,
+    created_at timestamp not null default (now() at time zone 'utc')
+);
+
+alter table device add column module_hash text references module_catalog(hash); -- NULL if device has its own module copy
@@ -0,0 +1,2 @@
+alter table device add column watchdog_interval integer; -- seconds, NULL if the watchdog is disabled
+alter table device add column watchdog_restart boolean not null default false;
@@ -0,0 +1,2 @@
+-- Built-in modules have no library file: their hash is a reserved module name
+insert into module_catalog (name, hash, size) values ('Simulator', 'builtin:simulator', 0);
@@ -0,0 +1 @@
+insert into module_catalog (name, hash, size) values ('MQTT', 'builtin:mqtt', 0);
@@ -0,0 +1 @@
+insert into module_catalog (name, hash, size) values ('Modbus TCP', 'builtin:modbus', 0);
@@ -0,0 +1,2 @@
+insert into module_catalog (name, hash, size) values ('HTTP push', 'builtin:push', 0);
+alter table device add column ingest_token_hash text; -- SHA-256 of the ingest token of a push device
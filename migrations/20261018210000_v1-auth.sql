@@ -0,0 +1,15 @@
+create table app_user (
+    id serial primary key,
+    username text not null unique check (length(username) <= 255),
+    password_hash text not null,
+    created_at timestamp not null default (now() at time zone 'utc')
+);
+
+create table user_session (
+    token_hash text primary key, -- SHA-256 of the session token
+    user_id integer not null references app_user(id) on delete cascade,
+    created_at timestamp not null default (now() at time zone 'utc'),
+    expires_at timestamp not null
+);
+
+create index user_session_user_idx on user_session(user_id);
@@ -0,0 +1,10 @@
+create table api_key (
+    id serial primary key,
+    user_id integer not null references app_user(id) on delete cascade,
+    name text not null check (length(name) <= 255),
+    key_hash text not null unique, -- SHA-256 of the key
+    scopes jsonb not null, -- e.g. ["READ_DATA", "INGEST"]
+    created_at timestamp not null default (now() at time zone 'utc'),
+    last_used_at timestamp, -- NULL if the key was never used
+    unique (user_id, name)
+);
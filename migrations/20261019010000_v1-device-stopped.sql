@@ -0,0 +1 @@
+alter table device add column stopped boolean not null default false; -- the module isn't started with the service
@@ -0,0 +1,57 @@
+-- Schema of the Postgres migrations up to v1-push-device.
+-- Enums are stored as text and JSON values as JSON text
+create table device (
+    id integer primary key,
+    name text not null check (length(name) <= 255),
+    display_name text not null check (length(display_name) <= 255),
+    module_dir text not null check (length(module_dir) <= 700),
+    data_dir text not null check (length(data_dir) <= 700),
+    init_state text not null check (init_state in ('DEVICE', 'SENSORS')),
+    module_info text, -- NULL if the module doesn't provide info
+    module_hash text references module_catalog(hash), -- NULL if device has its own module copy
+    watchdog_interval integer, -- seconds, NULL if the watchdog is disabled
+    watchdog_restart boolean not null default false,
+    ingest_token_hash text -- SHA-256 of the ingest token of a push device
+);
+
+create table device_sensor (
+    device_id integer not null references device(id),
+    sensor_name text not null,
+    sensor_table_name text not null
+);
+
+create unique index device_sensor_idx on device_sensor(device_id, sensor_name);
+
+create table monitor_conf (
+    id integer primary key autoincrement,
+    device_id integer not null references device(id),
+    sensor text not null,
+    typ text not null check (typ in ('LOG', 'LINE')),
+    config text not null
+);
+
+create table device_command_log (
+    id integer primary key autoincrement,
+    device_id integer not null references device(id),
+    command text not null,
+    args text not null,
+    error text, -- NULL if the command was sent successfully
+    created_at timestamp not null default current_timestamp
+);
+
+create index device_command_log_device_idx on device_command_log(device_id, created_at);
+
+create table module_catalog (
+    id integer primary key autoincrement,
+    name text not null,
+    hash text not null unique, -- SHA-256 of the library file
+    size bigint not null,
+    created_at timestamp not null default current_timestamp
+);
+
+-- Built-in modules have no library file: their hash is a reserved module name
+insert into module_catalog (name, hash, size) values
+    ('Simulator', 'builtin:simulator', 0),
+    ('MQTT', 'builtin:mqtt', 0),
+    ('Modbus TCP', 'builtin:modbus', 0),
+    ('HTTP push', 'builtin:push', 0);
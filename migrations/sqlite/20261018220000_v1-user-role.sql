@@ -0,0 +1,12 @@
+-- Users created before roles were introduced keep full access
+alter table app_user add column role text not null default 'ADMIN'
+    check (role in ('VIEWER', 'OPERATOR', 'ENGINEER', 'ADMIN'));
+
+-- Scoped users have access only to devices from user_device
+alter table app_user add column device_scoped boolean not null default false;
+
+create table user_device (
+    user_id integer not null references app_user(id) on delete cascade,
+    device_id integer not null references device(id) on delete cascade,
+    primary key (user_id, device_id)
+);
@@ -0,0 +1,13 @@
+create table audit_log (
+    id integer primary key autoincrement,
+    user_id integer references app_user(id) on delete set null, -- NULL if the user was deleted
+    username text not null, -- kept after the user is deleted
+    action text not null, -- e.g. START_DEVICE_INIT
+    device_id integer, -- NULL if the action doesn't target a device, kept after the device is deleted
+    summary text not null, -- request parameters without secrets
+    error text, -- NULL if the action succeeded
+    created_at timestamp not null default current_timestamp
+);
+
+create index audit_log_created_at_idx on audit_log(created_at);
+create index audit_log_device_idx on audit_log(device_id, created_at);
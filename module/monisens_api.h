@@ -69,6 +69,28 @@ uint8_t start(void *handler, void *msg_handler, handle_msg_func handle_func);
 // and `handle_func` passed in the `start()` call have been removed from memory.
 uint8_t stop(void *handler);
 
+// -------------------------------------------------------------------------------------------
+// ----------------------------- Device commands (optional) ----------------------------------
+// -------------------------------------------------------------------------------------------
+
+// Get information about commands that can be sent to the device.
+// Uses the same pattern as `obtain_sensor_type_infos`.
+// Returns error codes:
+// - 0 - success,
+// - 1 - connection failed.
+// This function is optional. A module that doesn't export it has no commands.
+uint8_t obtain_command_infos(void *handler, void *obj, command_infos_callback callback);
+
+// Send a command to the device (e.g. set a setpoint or toggle a relay).
+// MoniSens guarantees that the command and its arguments match
+// the ones returned by `obtain_command_infos`.
+// Returns error codes:
+//   - 0 - success,
+//   - 1 - connection failed,
+//   - 2 - invalid parameters.
+// This function is optional and must be exported together with `obtain_command_infos`.
+uint8_t send_command(void *handler, Command *cmd);
+
 // -------------------------------------------------------------------------------------------
 // ----------------------- Functions for module' working process -----------------------------
 // -------------------------------------------------------------------------------------------
@@ -82,3 +104,9 @@ uint8_t mod_version();
 
 // Funciton that returns all module functions
 Functions functions();
+
+// Function for getting information about the module build: name, vendor, version,
+// supported OS and architecture, and capabilities. Uses the same pattern as
+// `obtain_device_conn_info`, but doesn't require a handler, so it's called before `init`.
+// This function is optional.
+void module_info(void *obj, module_info_callback callback);
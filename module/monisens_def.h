@@ -216,6 +216,79 @@ typedef struct
 // Function for handling messages from the module
 typedef void (*handle_msg_func)(void *handler, Message msg_data);
 
+// ------------------------ Device commands ------------------------
+
+// Information about a single argument of a command
+typedef struct
+{
+    // Argument name. Must be written in snake_case
+    // and be unique for each argument within one command.
+    char *name;
+    SensorDataType typ;
+    bool required;
+} CommandArgInfo;
+
+// Information about a command that can be sent to the device
+typedef struct
+{
+    // Command name. Must be written in snake_case
+    // and be unique for each command within one device.
+    char *name;
+    char *description; // Human-readable description. Can be NULL
+    int32_t arg_infos_len;
+    CommandArgInfo *arg_infos; // Command argument array
+} CommandInfo;
+
+typedef struct
+{
+    int32_t command_infos_len;
+    CommandInfo *command_infos; // Array of infos about device commands
+} CommandInfos;
+
+typedef void (*command_infos_callback)(void *obj, CommandInfos *infos);
+
+// Single argument of a command.
+// `data` uses the same types as `SensorMsgData.data`
+// and is NULL if a non-required argument was omitted.
+typedef struct
+{
+    char *name;
+    SensorDataType typ;
+    void *data;
+} CommandArg;
+
+// Command sent from MoniSens to the device
+typedef struct
+{
+    char *name;
+    CommandArg *args;
+    int32_t args_len;
+} Command;
+
+// ------------------------ Module metadata ------------------------
+
+// Capabilities that may be declared by a module. Used as bit flags in `ModuleInfo.capabilities`
+typedef enum
+{
+    ModuleCapabilityCommands = 1,        // Module exports `obtain_command_infos` and `send_command`
+    ModuleCapabilityHotReconfigure = 2,  // Module can be reconfigured without a restart
+    ModuleCapabilityPersistedConfig = 4, // Module stores its configuration in the data folder
+} ModuleCapability;
+
+// Information about a module build
+typedef struct
+{
+    char *name;
+    char *vendor;          // Can be NULL
+    char *version;         // Semantic version of the module (e.g. "1.2.3")
+    char *description;     // Can be NULL
+    char *os;              // Supported OS in terms of Rust's `std::env::consts::OS` (e.g. "linux"). Can be NULL
+    char *arch;            // Supported architecture in terms of Rust's `std::env::consts::ARCH` (e.g. "x86_64"). Can be NULL
+    uint32_t capabilities; // Bit mask of `ModuleCapability`
+} ModuleInfo;
+
+typedef void (*module_info_callback)(void *obj, ModuleInfo *info);
+
 // ---------------------------- Module's working process ----------------------------
 
 typedef uint8_t (*mod_version_fn)();
@@ -235,3 +308,9 @@ typedef struct
 } Functions;
 
 typedef Functions (*functions_fn)();
+
+// Optional functions. They are not a part of `Functions` and are looked up by name
+// so modules built for older versions of the API keep working.
+typedef uint8_t (*obtain_command_infos_fn)(void *handler, void *obj, command_infos_callback callback);
+typedef uint8_t (*send_command_fn)(void *handler, Command *cmd);
+typedef void (*module_info_fn)(void *obj, module_info_callback callback);
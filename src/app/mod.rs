@@ -2,43 +2,54 @@ use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
 
 #[cfg(any(target_os = "macos", target_os = "linux"))]
 use std::str::FromStr;
 
 static IS_PATH_CHECKED: AtomicBool = AtomicBool::new(false);
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
 
-#[cfg(target_os = "macos")]
-pub fn data_dir() -> PathBuf {
-    let path = PathBuf::from_str(
-        &(std::env::var("HOME").expect("no HOME env var")
-            + "/Library/Application Support/monisens/"),
-    )
-    .unwrap();
-    check_and_create_data_dir(&path);
+/// `init_data_dir` replaces the default data dir and creates it if it doesn't exist.
+/// It must be called before the first [`data_dir`] call
+pub fn init_data_dir(path: PathBuf) -> std::io::Result<()> {
+    fs::create_dir_all(&path)?;
+    let _ = DATA_DIR.set(path);
+    IS_PATH_CHECKED.store(true, Ordering::SeqCst);
 
-    path
+    Ok(())
 }
 
-#[cfg(target_os = "linux")]
+/// `data_dir` returns the dir for modules, device files and the SQLite database.
+/// The dir is created if it doesn't exist
 pub fn data_dir() -> PathBuf {
-    let path =
-        PathBuf::from_str(&(std::env::var("HOME").expect("no HOME env var") + "/.monisens/"))
-            .unwrap();
+    let path = DATA_DIR.get_or_init(default_data_dir).clone();
     check_and_create_data_dir(&path);
 
     path
 }
 
+#[cfg(target_os = "macos")]
+pub fn default_data_dir() -> PathBuf {
+    PathBuf::from_str(
+        &(std::env::var("HOME").expect("no HOME env var")
+            + "/Library/Application Support/monisens/"),
+    )
+    .unwrap()
+}
+
+#[cfg(target_os = "linux")]
+pub fn default_data_dir() -> PathBuf {
+    PathBuf::from_str(&(std::env::var("HOME").expect("no HOME env var") + "/.monisens/")).unwrap()
+}
+
 #[cfg(target_os = "windows")]
-pub fn data_dir() -> PathBuf {
+pub fn default_data_dir() -> PathBuf {
     let mut path = std::env::current_exe().expect("failed to get current exe path");
     path.pop();
 
     path.push(".monisens");
 
-    check_and_create_data_dir(&path);
-
     path
 }
 
@@ -48,7 +59,8 @@ fn check_and_create_data_dir<P: AsRef<Path>>(path: P) {
     }
 
     if !path.as_ref().is_dir() {
-        fs::create_dir(&path).expect(&format!("failed to create dir: '{:?}'", path.as_ref()));
+        fs::create_dir_all(&path)
+            .unwrap_or_else(|_| panic!("failed to create dir: '{:?}'", path.as_ref()));
     }
 
     IS_PATH_CHECKED.store(true, Ordering::SeqCst);
@@ -0,0 +1,237 @@
+use std::error::Error;
+
+use getopts::Options;
+use serde_json::json;
+
+use crate::config::Config;
+use crate::controller::interface::module::is_builtin_module;
+use crate::controller::interface::service::IService;
+use crate::controller::{self, DeviceID};
+use crate::service::Service;
+
+use super::{audit, config_opts, load_config, open_service, parse_opts, Parsed, DEVICE};
+
+pub struct DeviceArgs {
+    conf: Config,
+    cmd: DeviceCommand,
+}
+
+enum DeviceCommand {
+    List,
+    Show(i32),
+    Delete(i32),
+    Stop(i32),
+    Start(i32),
+}
+
+pub fn device_args(args: &[String]) -> Result<Parsed<DeviceArgs>, String> {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "print this help menu");
+    config_opts(&mut opts);
+    opts.optflag(
+        "y",
+        "yes",
+        "confirm that the device is deleted with all its sensor data",
+    );
+
+    let brief = format!(
+        "Usage: monisens {DEVICE} list [options]\n       \
+        monisens {DEVICE} show|delete|stop|start [options] ID\n\n\
+        list    lists devices\n\
+        show    prints device's module, watchdog and sensors\n\
+        delete  deletes device with its sensor data. The service must not be running\n\
+        stop    doesn't start device's module with the service anymore\n\
+        start   starts device's module with the service again\n\n\
+        Stopping and starting take effect when the service is started next time"
+    );
+
+    let matches = match parse_opts(&opts, args, &brief)? {
+        Parsed::Help(usage) => return Ok(Parsed::Help(usage)),
+        Parsed::Args(matches) => matches,
+    };
+
+    let parse_id = |v: &str| {
+        v.parse::<i32>()
+            .ok()
+            .filter(|id| *id > 0)
+            .ok_or(format!("invalid device id: '{v}'"))
+    };
+
+    let free: Vec<&str> = matches.free.iter().map(String::as_str).collect();
+    let cmd = match free[..] {
+        ["list"] => DeviceCommand::List,
+        ["show", id] => DeviceCommand::Show(parse_id(id)?),
+        ["delete", id] => {
+            if !matches.opt_present("yes") {
+                return Err(format!(
+                    "deleting device {id} deletes all its sensor data, pass --yes to confirm"
+                ));
+            }
+            DeviceCommand::Delete(parse_id(id)?)
+        }
+        ["stop", id] => DeviceCommand::Stop(parse_id(id)?),
+        ["start", id] => DeviceCommand::Start(parse_id(id)?),
+        _ => {
+            return Err(format!(
+                "unknown {DEVICE} command\n\n{}",
+                opts.usage(&brief)
+            ))
+        }
+    };
+
+    let conf = load_config(&matches, |_| Ok(()))?;
+
+    Ok(Parsed::Args(DeviceArgs { conf, cmd }))
+}
+
+pub async fn run(args: DeviceArgs) -> Result<(), Box<dyn Error>> {
+    let svc = open_service(&args.conf).await?;
+
+    match args.cmd {
+        DeviceCommand::List => list_devices(&svc),
+        DeviceCommand::Show(id) => show_device(&svc, id),
+        DeviceCommand::Delete(id) => delete_device(&svc, id).await,
+        DeviceCommand::Stop(id) => set_device_stopped(&svc, id, true).await,
+        DeviceCommand::Start(id) => set_device_stopped(&svc, id, false).await,
+    }
+}
+
+fn list_devices(svc: &Service) -> Result<(), Box<dyn Error>> {
+    let mut ids = svc.get_device_ids()?;
+    ids.sort_by_key(|id| id.get_raw());
+
+    println!(
+        "{:<6} {:<24} {:<24} {:<8} {:<8} MODULE",
+        "ID", "NAME", "DISPLAY NAME", "STATE", "STOPPED"
+    );
+    for id in ids {
+        let info = svc.get_device_full_info(id)?;
+
+        println!(
+            "{:<6} {:<24} {:<24} {:<8} {:<8} {}",
+            id,
+            info.name,
+            info.display_name,
+            init_state_name(&info.init_state),
+            if info.stopped { "yes" } else { "no" },
+            module_name(&info)
+        );
+    }
+
+    Ok(())
+}
+
+fn show_device(svc: &Service, id: i32) -> Result<(), Box<dyn Error>> {
+    let id = find_device(svc, id)?;
+    let info = svc.get_device_full_info(id)?;
+
+    println!("ID:           {}", id);
+    println!("Name:         {}", info.name);
+    println!("Display name: {}", info.display_name);
+    println!("State:        {}", init_state_name(&info.init_state));
+    println!("Stopped:      {}", if info.stopped { "yes" } else { "no" });
+    println!("Module:       {}", module_name(&info));
+    if let Some(ref module_info) = info.module_info {
+        if let Some(ref vendor) = module_info.vendor {
+            println!("  vendor:      {vendor}");
+        }
+        if let Some(ref description) = module_info.description {
+            println!("  description: {description}");
+        }
+    }
+
+    match info.watchdog_conf.expected_interval {
+        Some(interval) => println!(
+            "Watchdog:     expects a message every {interval}s, restart: {}",
+            if info.watchdog_conf.restart {
+                "yes"
+            } else {
+                "no"
+            }
+        ),
+        None => println!("Watchdog:     disabled"),
+    }
+
+    if info.init_state != controller::DeviceInitState::Sensors {
+        return Ok(());
+    }
+
+    let mut sensors = svc.get_device_sensor_info(id)?;
+    sensors.sort_by(|a, b| a.name.cmp(&b.name));
+
+    println!("Sensors:");
+    for mut sensor in sensors {
+        println!("  {}", sensor.name);
+
+        sensor.data.sort_by(|a, b| a.name.cmp(&b.name));
+        for data in sensor.data {
+            println!("    {}: {:?}", data.name, data.typ);
+        }
+    }
+
+    Ok(())
+}
+
+async fn delete_device(svc: &Service, id: i32) -> Result<(), Box<dyn Error>> {
+    let device_id = find_device(svc, id)?;
+    let info = svc.get_device_full_info(device_id)?;
+
+    let res = svc.delete_device(device_id).await;
+    audit(
+        svc,
+        controller::AuditAction::DeleteDevice,
+        Some(id),
+        json!({ "display_name": info.display_name }),
+        &res,
+    )
+    .await;
+    res?;
+
+    println!("Device {id} '{}' was deleted", info.display_name);
+
+    Ok(())
+}
+
+async fn set_device_stopped(svc: &Service, id: i32, stopped: bool) -> Result<(), Box<dyn Error>> {
+    let device_id = find_device(svc, id)?;
+
+    let res = svc.save_device_stopped(device_id, stopped).await;
+    let action = match stopped {
+        true => controller::AuditAction::StopDevice,
+        false => controller::AuditAction::StartDevice,
+    };
+    audit(svc, action, Some(id), json!({}), &res).await;
+    res?;
+
+    match stopped {
+        true => println!("Device {id} won't be started with the service"),
+        false => println!("Device {id} will be started with the service"),
+    }
+
+    Ok(())
+}
+
+fn find_device(svc: &Service, id: i32) -> Result<DeviceID, Box<dyn Error>> {
+    svc.get_device_ids()?
+        .into_iter()
+        .find(|device_id| device_id.get_raw() == id)
+        .ok_or(format!("device {id} not found").into())
+}
+
+fn init_state_name(state: &controller::DeviceInitState) -> &'static str {
+    match state {
+        controller::DeviceInitState::Device => "device",
+        controller::DeviceInitState::Sensors => "sensors",
+    }
+}
+
+/// `module_name` describes device's module: its build info, a built-in module
+/// or a catalog module
+fn module_name(info: &controller::DeviceFullInfo) -> String {
+    match (&info.module_info, &info.module_hash) {
+        (Some(module_info), _) => format!("{} {}", module_info.name, module_info.version),
+        (None, Some(hash)) if is_builtin_module(hash) => hash.clone(),
+        (None, Some(hash)) => format!("catalog module {}", &hash[..hash.len().min(12)]),
+        (None, None) => "library".to_string(),
+    }
+}
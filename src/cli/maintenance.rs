@@ -0,0 +1,210 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use getopts::Options;
+
+use crate::config::{mask_password, Config};
+use crate::controller::interface::module::is_builtin_module;
+use crate::controller::interface::service::IService;
+use crate::module;
+use crate::service;
+
+use super::{config_opts, load_config, open_repo, parse_opts, Parsed, CHECK, MIGRATE};
+
+pub struct MigrateArgs {
+    conf: Config,
+    dry_run: bool,
+}
+
+pub fn migrate_args(args: &[String]) -> Result<Parsed<MigrateArgs>, String> {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "print this help menu");
+    config_opts(&mut opts);
+    opts.optflag(
+        "",
+        "dry-run",
+        "list pending migrations without applying them",
+    );
+
+    let brief = format!(
+        "Usage: monisens {MIGRATE} [options]\n\n\
+        Applies pending migrations to the database. The service applies them on start too"
+    );
+    let matches = match parse_opts(&opts, args, &brief)? {
+        Parsed::Help(usage) => return Ok(Parsed::Help(usage)),
+        Parsed::Args(matches) => matches,
+    };
+
+    if !matches.free.is_empty() {
+        return Err(format!("unexpected arguments\n\n{}", opts.usage(&brief)));
+    }
+
+    Ok(Parsed::Args(MigrateArgs {
+        conf: load_config(&matches, |_| Ok(()))?,
+        dry_run: matches.opt_present("dry-run"),
+    }))
+}
+
+pub async fn migrate(args: MigrateArgs) -> Result<(), Box<dyn Error>> {
+    let repo = open_repo(&args.conf).await?;
+    let pending = repo.pending_migrations().await?;
+
+    if pending.is_empty() {
+        println!("The database is up to date");
+        return Ok(());
+    }
+
+    if !args.dry_run {
+        repo.migrate().await?;
+    }
+
+    let verb = if args.dry_run { "Pending" } else { "Applied" };
+    for m in pending {
+        println!("{verb} {} {}", m.version, m.description);
+    }
+
+    Ok(())
+}
+
+pub fn check_args(args: &[String]) -> Result<Parsed<Config>, String> {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "print this help menu");
+    config_opts(&mut opts);
+
+    let brief = format!(
+        "Usage: monisens {CHECK} [options]\n\n\
+        Checks that the data dir is writable, the database is reachable and migrated \
+        and the module of every device can be loaded. Only a missing data dir is created"
+    );
+    let matches = match parse_opts(&opts, args, &brief)? {
+        Parsed::Help(usage) => return Ok(Parsed::Help(usage)),
+        Parsed::Args(matches) => matches,
+    };
+
+    if !matches.free.is_empty() {
+        return Err(format!("unexpected arguments\n\n{}", opts.usage(&brief)));
+    }
+
+    Ok(Parsed::Args(load_config(&matches, |_| Ok(()))?))
+}
+
+/// `check` prints the result of every check and fails if any of them failed.
+/// Devices are checked only if the database schema is up to date
+pub async fn check(conf: Config) -> Result<(), Box<dyn Error>> {
+    let mut report = Report::default();
+
+    let data_dir = conf.data_dir();
+    report.add(
+        &format!("data dir {} is writable", data_dir.display()),
+        check_writable(&data_dir),
+    );
+
+    let db = mask_password(conf.db());
+    let repo = match open_repo(&conf).await {
+        Ok(repo) => {
+            report.ok(&format!("database {db} is reachable"));
+            repo
+        }
+        Err(err) => {
+            report.fail(&format!("database {db} is reachable"), &err.to_string());
+            return report.finish();
+        }
+    };
+
+    match repo.pending_migrations().await {
+        Ok(pending) if pending.is_empty() => report.ok("database is migrated"),
+        Ok(pending) => {
+            report.fail(
+                "database is migrated",
+                &format!(
+                    "{} pending migration(s), run `monisens {MIGRATE}`",
+                    pending.len()
+                ),
+            );
+            return report.finish();
+        }
+        Err(err) => {
+            report.fail("database is migrated", &err.to_string());
+            return report.finish();
+        }
+    }
+
+    // Migrations are up to date, so the service doesn't change the database
+    let svc = match service::Service::new(repo).await {
+        Ok(svc) => svc,
+        Err(err) => {
+            report.fail("devices are loaded", &err.to_string());
+            return report.finish();
+        }
+    };
+
+    let mut devices = svc.get_init_data_all_devices()?;
+    devices.sort_by_key(|d| d.id.get_raw());
+
+    for device in devices {
+        let name = format!("module of device {} can be loaded", device.id);
+
+        match device.module_file.to_str() {
+            Some(module_name) if is_builtin_module(module_name) => {
+                report.ok(&format!("{name} (built-in {module_name})"))
+            }
+            _ if !device.module_file.is_file() => report.fail(
+                &name,
+                &format!("{} doesn't exist", device.module_file.display()),
+            ),
+            _ => match module::inspect_lib_module(&device.module_file) {
+                Ok(inspection) => match (inspection.error, inspection.module_info) {
+                    (Some(err), _) => report.fail(&name, &err),
+                    (None, Some(info)) => {
+                        report.ok(&format!("{name} ({} {})", info.name, info.version))
+                    }
+                    (None, None) => report.ok(&name),
+                },
+                Err(err) => report.fail(&name, &module::describe_error(&err)),
+            },
+        }
+    }
+
+    report.finish()
+}
+
+/// `Report` prints results of checks as they are made
+#[derive(Default)]
+struct Report {
+    failed: usize,
+}
+
+impl Report {
+    fn add(&mut self, name: &str, res: Result<(), String>) {
+        match res {
+            Ok(_) => self.ok(name),
+            Err(err) => self.fail(name, &err),
+        }
+    }
+
+    fn ok(&mut self, name: &str) {
+        println!("[ok]   {name}");
+    }
+
+    fn fail(&mut self, name: &str, err: &str) {
+        self.failed += 1;
+        println!("[FAIL] {name}: {err}");
+    }
+
+    fn finish(self) -> Result<(), Box<dyn Error>> {
+        match self.failed {
+            0 => Ok(()),
+            n => Err(format!("{n} check(s) failed").into()),
+        }
+    }
+}
+
+/// `check_writable` creates the dir if it's missing and writes a file to it
+fn check_writable(dir: &Path) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+
+    let path = dir.join(format!(".monisens-check-{}", std::process::id()));
+    fs::write(&path, b"check").map_err(|err| err.to_string())?;
+    fs::remove_file(&path).map_err(|err| err.to_string())
+}
@@ -0,0 +1,394 @@
+mod device;
+mod maintenance;
+mod module;
+mod sensor;
+mod test;
+
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use getopts::{Matches, Options};
+
+use crate::app;
+use crate::config::{Config, CONFIG_ENV_KEY, DEFAULT_POSTGRES_DB};
+use crate::controller::{self, interface::service::IService};
+use crate::logger;
+use crate::repo;
+use crate::service;
+use crate::{kv_any, kvs};
+
+pub const IMPORT_SENSOR_DATA: &str = "import-sensor-data";
+pub const CONFIG: &str = "config";
+pub const DEVICE: &str = "device";
+pub const SENSOR: &str = "sensor";
+pub const MODULE: &str = "module";
+pub const MIGRATE: &str = "migrate";
+pub const CHECK: &str = "check";
+
+/// Name of the user in audit records of actions made from the command line
+const CLI_USERNAME: &str = "cli";
+
+/// `Command` is a subcommand for headless maintenance. It works with the configured storage
+/// and data dir directly and never starts devices' modules
+pub enum Command {
+    ImportSensorData(ImportArgs),
+    PrintConfig(Config),
+    Device(device::DeviceArgs),
+    ExportSensorData(sensor::ExportArgs),
+    InspectModule(PathBuf),
+    Migrate(maintenance::MigrateArgs),
+    Check(Config),
+}
+
+impl Command {
+    /// `failure_msg` describes the failure of the command
+    pub fn failure_msg(&self) -> &'static str {
+        match self {
+            Command::ImportSensorData(_) => "failed to import sensor data",
+            Command::PrintConfig(_) => "failed to print config",
+            Command::Device(_) => "failed to run device command",
+            Command::ExportSensorData(_) => "failed to export sensor data",
+            Command::InspectModule(_) => "failed to inspect module",
+            Command::Migrate(_) => "failed to migrate database",
+            Command::Check(_) => "check failed",
+        }
+    }
+}
+
+/// `parse_command` parses arguments which start with the name of a subcommand.
+/// Returns `None` if they don't
+pub fn parse_command(args: &[String]) -> Option<Result<Parsed<Command>, String>> {
+    let (name, args) = args.split_first()?;
+
+    let res = match name.as_str() {
+        IMPORT_SENSOR_DATA => import_args(args).map(|p| p.map(Command::ImportSensorData)),
+        CONFIG => config_args(args).map(|p| p.map(Command::PrintConfig)),
+        DEVICE => device::device_args(args).map(|p| p.map(Command::Device)),
+        SENSOR => sensor::export_args(args).map(|p| p.map(Command::ExportSensorData)),
+        MODULE => module::inspect_args(args).map(|p| p.map(Command::InspectModule)),
+        MIGRATE => maintenance::migrate_args(args).map(|p| p.map(Command::Migrate)),
+        CHECK => maintenance::check_args(args).map(|p| p.map(Command::Check)),
+        _ => return None,
+    };
+
+    Some(res)
+}
+
+pub async fn run_command(cmd: Command) -> Result<(), Box<dyn Error>> {
+    match cmd {
+        Command::ImportSensorData(args) => import_sensor_data(args).await,
+        Command::PrintConfig(conf) => print_config(conf),
+        Command::Device(args) => device::run(args).await,
+        Command::ExportSensorData(args) => sensor::export_sensor_data(args).await,
+        Command::InspectModule(file) => module::inspect_module(&file),
+        Command::Migrate(args) => maintenance::migrate(args).await,
+        Command::Check(conf) => maintenance::check(conf).await,
+    }
+}
+
+/// `usage_brief` lists the subcommands for the help of the service
+pub fn usage_brief() -> String {
+    [
+        "Usage: monisens [options]".to_string(),
+        format!("monisens {IMPORT_SENSOR_DATA} [options] FILE"),
+        format!("monisens {SENSOR} export [options]"),
+        format!("monisens {DEVICE} list|show|delete|stop|start [options] [ID]"),
+        format!("monisens {MODULE} inspect FILE"),
+        format!("monisens {MIGRATE} [options]"),
+        format!("monisens {CHECK} [options]"),
+        format!("monisens {CONFIG} print [options]"),
+    ]
+    .join("\n       ")
+}
+
+/// `config_opts` adds options which select the config file and the storage of the service
+pub fn config_opts(opts: &mut Options) {
+    opts.optopt(
+        "c",
+        "config",
+        &format!("TOML config file (default: ${CONFIG_ENV_KEY})"),
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "storage",
+        "storage of the service: postgres or sqlite (default: postgres)",
+        "STORAGE",
+    );
+    opts.optopt(
+        "",
+        "db",
+        &format!(
+            "address for PostgreSQL database (default: {DEFAULT_POSTGRES_DB}) \
+            or path to SQLite database file (default: monisens.db in the data dir)"
+        ),
+        "DB",
+    );
+    opts.optopt(
+        "",
+        "data-dir",
+        "dir for modules, device files and the SQLite database (default: ~/.monisens)",
+        "DIR",
+    );
+}
+
+/// `load_config` loads the config selected by [`config_opts`], lets `apply` override
+/// its values with other arguments and validates the result. The log level is set from the config.
+pub fn load_config(
+    matches: &Matches,
+    apply: impl FnOnce(&mut Config) -> Result<(), String>,
+) -> Result<Config, String> {
+    let file = matches
+        .opt_str("config")
+        .or_else(|| env::var(CONFIG_ENV_KEY).ok());
+    let mut conf = Config::load(file.as_deref().map(Path::new))?;
+
+    if let Some(v) = matches.opt_str("storage") {
+        conf.storage.kind =
+            repo::Storage::from_name(&v).ok_or(format!("unknown storage: '{v}'"))?;
+    }
+    if let Some(v) = matches.opt_str("db") {
+        conf.storage.db = Some(v);
+    }
+    if let Some(v) = matches.opt_str("data-dir") {
+        conf.paths.data_dir = Some(v.into());
+    }
+
+    apply(&mut conf)?;
+
+    let conf = conf.validate()?;
+    logger::set_level(conf.log.level.clone());
+
+    Ok(conf)
+}
+
+/// `Parsed` is a result of parsing arguments of a subcommand
+pub enum Parsed<T> {
+    Help(String),
+    Args(T),
+}
+
+impl<T> Parsed<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Parsed<U> {
+        match self {
+            Parsed::Help(usage) => Parsed::Help(usage),
+            Parsed::Args(args) => Parsed::Args(f(args)),
+        }
+    }
+}
+
+/// `parse_opts` parses arguments of a subcommand. The usage made of `brief` is returned
+/// if help is requested, so required options don't fail it
+fn parse_opts(opts: &Options, args: &[String], brief: &str) -> Result<Parsed<Matches>, String> {
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        return Ok(Parsed::Help(opts.usage(brief)));
+    }
+
+    let matches = opts
+        .parse(args)
+        .map_err(|err| format!("failed to parse arguments: {err}"))?;
+
+    Ok(Parsed::Args(matches))
+}
+
+/// `open_repo` connects to the configured storage. The data dir is created first,
+/// because it holds the SQLite database
+async fn open_repo(conf: &Config) -> Result<repo::Repository, Box<dyn Error>> {
+    app::init_data_dir(conf.data_dir())?;
+    let repo = repo::Repository::new(conf.storage.kind, conf.db(), &conf.pool_conf()).await?;
+
+    Ok(repo)
+}
+
+/// `open_service` opens the service without starting devices' modules.
+/// Pending migrations are applied
+async fn open_service(conf: &Config) -> Result<service::Service, Box<dyn Error>> {
+    let repo = open_repo(conf).await?;
+
+    service::Service::new(repo).await
+}
+
+/// `audit` records an action made from the command line. A failure to save the record
+/// is only logged
+async fn audit<T, E: fmt::Display>(
+    svc: &service::Service,
+    action: controller::AuditAction,
+    device_id: Option<i32>,
+    summary: serde_json::Value,
+    res: &Result<T, E>,
+) {
+    let record = controller::NewAuditRecord {
+        user_id: None,
+        username: CLI_USERNAME.to_string(),
+        action,
+        device_id,
+        summary,
+        error: res.as_ref().err().map(|err| err.to_string()),
+    };
+
+    if let Err(err) = svc.save_audit_record(record).await {
+        logger::error_kv(
+            "failed to save audit record",
+            kvs!(
+                "action" => kv_any!(format!("{action:?}")),
+                "error" => kv_any!(err.msg.clone())
+            ),
+        );
+    }
+}
+
+pub struct ImportArgs {
+    conf: Config,
+    file: PathBuf,
+    payload: controller::ImportPayload,
+}
+
+pub fn import_args(args: &[String]) -> Result<Parsed<ImportArgs>, String> {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "print this help menu");
+    config_opts(&mut opts);
+    opts.reqopt("d", "device", "id of the device", "ID");
+    opts.reqopt("s", "sensor", "name of the device's sensor", "NAME");
+    opts.optopt(
+        "f",
+        "format",
+        "format of the file: csv or line_protocol (default: csv)",
+        "FORMAT",
+    );
+    opts.optmulti(
+        "m",
+        "map",
+        "map a CSV column or a line protocol key to a sensor's field",
+        "COLUMN=FIELD",
+    );
+    opts.optopt(
+        "",
+        "precision",
+        "precision of line protocol timestamps: ns, us, ms or s (default: ns)",
+        "PRECISION",
+    );
+    opts.optflag("", "dry-run", "validate the file without saving its data");
+
+    let usage = || {
+        opts.usage(&format!(
+            "Usage: monisens {IMPORT_SENSOR_DATA} [options] FILE"
+        ))
+    };
+
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        return Ok(Parsed::Help(usage()));
+    }
+
+    let matches = opts
+        .parse(args)
+        .map_err(|err| format!("failed to parse arguments: {err}"))?;
+
+    let file = match matches.free[..] {
+        [ref file] => PathBuf::from(file),
+        _ => return Err(format!("exactly one file must be given\n\n{}", usage())),
+    };
+
+    let device_id = matches
+        .opt_str("device")
+        .and_then(|v| v.parse().ok())
+        .ok_or("invalid device id")?;
+
+    let format = match matches.opt_str("format") {
+        Some(v) => {
+            controller::ImportFormat::from_name(&v).ok_or(format!("unknown format: '{v}'"))?
+        }
+        None => controller::ImportFormat::Csv,
+    };
+
+    let precision = match matches.opt_str("precision") {
+        Some(v) => controller::TimestampPrecision::from_name(&v)
+            .ok_or(format!("unknown precision: '{v}'"))?,
+        None => Default::default(),
+    };
+
+    let mut mapping = HashMap::new();
+    for v in matches.opt_strs("map") {
+        let (column, field) = v.split_once('=').ok_or(format!("invalid mapping: '{v}'"))?;
+        mapping.insert(column.to_string(), field.to_string());
+    }
+
+    let conf = load_config(&matches, |_| Ok(()))?;
+
+    Ok(Parsed::Args(ImportArgs {
+        conf,
+        file,
+        payload: controller::ImportPayload {
+            device_id,
+            sensor: matches.opt_str("sensor").unwrap_or_default(),
+            format,
+            mapping,
+            precision,
+            dry_run: matches.opt_present("dry-run"),
+        },
+    }))
+}
+
+/// `import_sensor_data` imports a file to a device's sensor without starting the service.
+pub async fn import_sensor_data(args: ImportArgs) -> Result<(), Box<dyn Error>> {
+    let svc = open_service(&args.conf).await?;
+    let file = std::fs::File::open(&args.file)?;
+
+    let report = controller::import_sensor_data(&svc, args.payload, file).await?;
+
+    for err in report.errors.iter() {
+        println!("line {}: {}", err.line, err.msg);
+    }
+
+    if report.dry_run {
+        println!(
+            "Dry run: {} of {} row(s) are valid, {} invalid. Nothing was saved",
+            report.imported, report.total, report.invalid
+        );
+    } else {
+        println!(
+            "Imported {} of {} row(s), {} invalid",
+            report.imported, report.total, report.invalid
+        );
+    }
+
+    Ok(())
+}
+
+/// `config_args` parses arguments of `config print`
+pub fn config_args(args: &[String]) -> Result<Parsed<Config>, String> {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "print this help menu");
+    config_opts(&mut opts);
+
+    let usage = || {
+        opts.usage(&format!(
+            "Usage: monisens {CONFIG} print [options]\n\n\
+            Prints the effective config: defaults overridden by the config file and env variables"
+        ))
+    };
+
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        return Ok(Parsed::Help(usage()));
+    }
+
+    let matches = opts
+        .parse(args)
+        .map_err(|err| format!("failed to parse arguments: {err}"))?;
+
+    match matches.free[..] {
+        [ref cmd] if cmd == "print" => (),
+        _ => return Err(format!("unknown {CONFIG} command\n\n{}", usage())),
+    }
+
+    Ok(Parsed::Args(load_config(&matches, |_| Ok(()))?))
+}
+
+/// `print_config` prints the effective config in the format of the config file
+pub fn print_config(conf: Config) -> Result<(), Box<dyn Error>> {
+    print!("{}", conf.to_toml()?);
+
+    Ok(())
+}
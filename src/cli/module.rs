@@ -0,0 +1,118 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use getopts::Options;
+
+use crate::controller::{ConfInfo, ConfInfoEntryType};
+use crate::module::inspect_lib_module;
+
+use super::{parse_opts, Parsed, MODULE};
+
+pub fn inspect_args(args: &[String]) -> Result<Parsed<PathBuf>, String> {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "print this help menu");
+
+    let brief = format!(
+        "Usage: monisens {MODULE} inspect FILE\n\n\
+        Loads the module library and prints its version, functions, info \
+        and connection parameters. No device is created"
+    );
+    let matches = match parse_opts(&opts, args, &brief)? {
+        Parsed::Help(usage) => return Ok(Parsed::Help(usage)),
+        Parsed::Args(matches) => matches,
+    };
+
+    match matches.free[..] {
+        [ref cmd, ref file] if cmd == "inspect" => Ok(Parsed::Args(PathBuf::from(file))),
+        _ => Err(format!(
+            "unknown {MODULE} command\n\n{}",
+            opts.usage(&brief)
+        )),
+    }
+}
+
+pub fn inspect_module(file: &Path) -> Result<(), Box<dyn Error>> {
+    let inspection = inspect_lib_module(file)?;
+
+    match inspection.version {
+        Some(version) => println!(
+            "Module API version: {version} (supported: {})",
+            inspection.supported_version
+        ),
+        None => println!("Module API version: unknown"),
+    }
+
+    if !inspection.functions.is_empty() {
+        println!("Functions:");
+    }
+    for func in inspection.functions.iter() {
+        let presence = match (func.present, func.required) {
+            (true, _) => "present",
+            (false, true) => "missing",
+            (false, false) => "missing (optional)",
+        };
+        println!("  {:<26} {presence}", func.name);
+    }
+
+    match inspection.module_info {
+        Some(info) => {
+            println!("Module info:");
+            println!("  name:        {}", info.name);
+            println!("  version:     {}", info.version);
+            if let Some(vendor) = info.vendor {
+                println!("  vendor:      {vendor}");
+            }
+            if let Some(description) = info.description {
+                println!("  description: {description}");
+            }
+            if let Some(os) = info.os {
+                println!("  os:          {os}");
+            }
+            if let Some(arch) = info.arch {
+                println!("  arch:        {arch}");
+            }
+            println!(
+                "  commands: {}, hot reconfigure: {}, persisted config: {}",
+                info.capabilities.commands,
+                info.capabilities.hot_reconfigure,
+                info.capabilities.persisted_config
+            );
+        }
+        None if inspection.error.is_none() => println!("Module info: not provided"),
+        None => (),
+    }
+
+    if let Some(ref conn_info) = inspection.conn_info {
+        println!("Connection parameters:");
+        print_conf_info(conn_info, 1);
+    }
+
+    match inspection.error {
+        Some(err) => Err(format!("the module can't be used: {err}").into()),
+        None => Ok(()),
+    }
+}
+
+fn print_conf_info(info: &ConfInfo, depth: usize) {
+    let indent = "  ".repeat(depth);
+
+    for entry in info {
+        let (typ, required) = match entry.data {
+            ConfInfoEntryType::Section(ref section) => {
+                println!("{indent}{} (section)", entry.name);
+                print_conf_info(section, depth + 1);
+                continue;
+            }
+            ConfInfoEntryType::String(ref v) => ("string", v.required),
+            ConfInfoEntryType::Int(ref v) => ("int", v.required),
+            ConfInfoEntryType::IntRange(ref v) => ("int range", v.required),
+            ConfInfoEntryType::Float(ref v) => ("float", v.required),
+            ConfInfoEntryType::FloatRange(ref v) => ("float range", v.required),
+            ConfInfoEntryType::JSON(ref v) => ("JSON", v.required),
+            ConfInfoEntryType::ChoiceList(ref v) => ("choice list", v.required),
+        };
+
+        let required = if required { ", required" } else { "" };
+        println!("{indent}{} ({typ}{required})", entry.name);
+    }
+}
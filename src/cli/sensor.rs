@@ -0,0 +1,190 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use getopts::Options;
+use tokio::sync::mpsc;
+
+use crate::config::Config;
+use crate::controller;
+use crate::logger::{self, LogLevel};
+
+use super::{config_opts, load_config, open_service, parse_opts, Parsed, SENSOR};
+
+/// Number of encoded chunks which may wait to be written
+const EXPORT_QUEUE_LEN: usize = 8;
+
+pub struct ExportArgs {
+    conf: Config,
+    /// Standard output is used if it's not set
+    output: Option<PathBuf>,
+    payload: controller::ExportPayload,
+}
+
+pub fn export_args(args: &[String]) -> Result<Parsed<ExportArgs>, String> {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "print this help menu");
+    config_opts(&mut opts);
+    opts.reqopt("d", "device", "id of the device", "ID");
+    opts.reqopt("s", "sensor", "name of the device's sensor", "NAME");
+    opts.optopt(
+        "f",
+        "format",
+        "format of the file: csv, jsonl or parquet (default: csv)",
+        "FORMAT",
+    );
+    opts.optmulti(
+        "",
+        "field",
+        "field to export, all fields are exported if not given. Can be repeated",
+        "FIELD",
+    );
+    opts.optopt(
+        "",
+        "time-field",
+        "timestamp field which the range and the order of rows apply to",
+        "FIELD",
+    );
+    opts.optopt(
+        "",
+        "from",
+        "start of the range, inclusive",
+        "2024-01-01T00:00:00Z",
+    );
+    opts.optopt(
+        "",
+        "to",
+        "end of the range, exclusive",
+        "2024-02-01T00:00:00Z",
+    );
+    opts.optopt(
+        "",
+        "timezone",
+        "IANA timezone of timestamps in CSV and JSON Lines (default: UTC)",
+        "TZ",
+    );
+    opts.optopt(
+        "o",
+        "output",
+        "file to write, standard output if not given",
+        "FILE",
+    );
+
+    let brief = format!("Usage: monisens {SENSOR} export [options]");
+    let matches = match parse_opts(&opts, args, &brief)? {
+        Parsed::Help(usage) => return Ok(Parsed::Help(usage)),
+        Parsed::Args(matches) => matches,
+    };
+
+    match matches.free[..] {
+        [ref cmd] if cmd == "export" => (),
+        _ => {
+            return Err(format!(
+                "unknown {SENSOR} command\n\n{}",
+                opts.usage(&brief)
+            ))
+        }
+    }
+
+    let device_id = matches
+        .opt_str("device")
+        .and_then(|v| v.parse().ok())
+        .ok_or("invalid device id")?;
+
+    let format = match matches.opt_str("format") {
+        Some(v) => {
+            controller::ExportFormat::from_name(&v).ok_or(format!("unknown format: '{v}'"))?
+        }
+        None => controller::ExportFormat::Csv,
+    };
+
+    let parse_time = |name: &str| match matches.opt_str(name) {
+        Some(v) => chrono::DateTime::parse_from_rfc3339(&v)
+            .map(|t| Some(t.naive_utc()))
+            .map_err(|_| format!("invalid {name}: '{v}', expected RFC 3339 time")),
+        None => Ok(None),
+    };
+
+    let timezone = match matches.opt_str("timezone") {
+        Some(v) => v
+            .parse::<chrono_tz::Tz>()
+            .map_err(|_| format!("unknown timezone '{v}'"))?,
+        None => chrono_tz::UTC,
+    };
+
+    let payload = controller::ExportPayload {
+        device_id,
+        sensor: matches.opt_str("sensor").unwrap_or_default(),
+        format,
+        fields: matches.opt_strs("field"),
+        time_field: matches.opt_str("time-field"),
+        from: parse_time("from")?,
+        to: parse_time("to")?,
+        timezone,
+    };
+
+    let conf = load_config(&matches, |_| Ok(()))?;
+    let output = matches.opt_str("output").map(PathBuf::from);
+
+    // The log is written to standard output too, so only errors may get into the data
+    if output.is_none() && conf.log.level < LogLevel::Error {
+        logger::set_level(LogLevel::Error);
+    }
+
+    Ok(Parsed::Args(ExportArgs {
+        conf,
+        output,
+        payload,
+    }))
+}
+
+/// `export_sensor_data` writes the sensor's data to a file without starting the service.
+/// The file is removed if the export fails
+pub async fn export_sensor_data(args: ExportArgs) -> Result<(), Box<dyn Error>> {
+    let svc = open_service(&args.conf).await?;
+    let export = controller::prepare_export(&svc, args.payload)?;
+
+    let out: Box<dyn Write> = match args.output {
+        Some(ref path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout()),
+    };
+
+    let (tx, rx) = mpsc::channel::<Vec<u8>>(EXPORT_QUEUE_LEN);
+    let (res, written) = tokio::join!(export.run(&svc, tx), write_chunks(out, rx));
+    let res = written
+        .map_err(|err| -> Box<dyn Error> { err.into() })
+        .and_then(|_| res.map_err(|err| err.into()));
+
+    match res {
+        // Data may go to standard output, so the summary is printed to standard error
+        Ok(rows) => eprintln!("Exported {rows} row(s)"),
+        Err(err) => {
+            if let Some(ref path) = args.output {
+                let _ = std::fs::remove_file(path);
+            }
+
+            return Err(err);
+        }
+    }
+
+    Ok(())
+}
+
+/// `write_chunks` writes encoded chunks to `out` until the export ends.
+///
+/// If the reader of standard output is gone, e.g. `head` got enough lines, writing stops
+/// without an error. The receiver is dropped then, so the export stops too
+pub(super) async fn write_chunks(
+    mut out: impl Write,
+    mut rx: mpsc::Receiver<Vec<u8>>,
+) -> io::Result<()> {
+    while let Some(chunk) = rx.recv().await {
+        match out.write_all(&chunk) {
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            res => res?,
+        }
+    }
+
+    out.flush()
+}
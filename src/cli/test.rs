@@ -0,0 +1,194 @@
+#[cfg(test)]
+use std::io::{self, Write};
+
+#[cfg(test)]
+use tokio::sync::mpsc;
+
+#[cfg(test)]
+use crate::controller::{self, Controller};
+#[cfg(test)]
+use crate::{app, repo, service::Service, sink::Sinks};
+
+#[cfg(test)]
+use super::{parse_command, sensor, Command, Parsed};
+
+#[cfg(test)]
+fn parse(args: &str) -> Option<Result<Parsed<Command>, String>> {
+    let data_dir = std::env::temp_dir().join(format!("monisens-cli-test-{}", std::process::id()));
+    let args: Vec<String> = args
+        .split_whitespace()
+        .map(String::from)
+        .chain(
+            ["--storage", "sqlite", "--data-dir"]
+                .map(String::from)
+                .into_iter()
+                .chain([data_dir.display().to_string()]),
+        )
+        .collect();
+
+    parse_command(&args)
+}
+
+#[test]
+fn command_parsing() {
+    let args = |res: Option<Result<Parsed<Command>, String>>| match res {
+        Some(Ok(Parsed::Args(cmd))) => cmd,
+        Some(Ok(Parsed::Help(_))) => panic!("help instead of arguments"),
+        Some(Err(err)) => panic!("{err}"),
+        None => panic!("not a subcommand"),
+    };
+    let failed = |res: Option<Result<Parsed<Command>, String>>| matches!(res, Some(Err(_)));
+
+    // Options of the service aren't subcommands
+    assert!(parse_command(&["--port".to_string(), "8888".to_string()]).is_none());
+    assert!(parse_command(&[]).is_none());
+
+    assert!(matches!(parse("device --help"), Some(Ok(Parsed::Help(_)))));
+
+    assert!(matches!(
+        args(parse("config print")),
+        Command::PrintConfig(_)
+    ));
+    assert!(failed(parse("config show")));
+
+    assert!(matches!(args(parse("device list")), Command::Device(_)));
+    assert!(matches!(args(parse("device stop 3")), Command::Device(_)));
+    assert!(failed(parse("device stop 0")));
+    assert!(failed(parse("device show x")));
+    assert!(failed(parse("device delete 3")));
+    assert!(matches!(
+        args(parse("device delete 3 --yes")),
+        Command::Device(_)
+    ));
+
+    assert!(matches!(
+        args(parse(
+            "sensor export -d 1 -s room -f jsonl --from 2024-01-01T00:00:00Z"
+        )),
+        Command::ExportSensorData(_)
+    ));
+    assert!(failed(parse("sensor export -d 1")));
+    assert!(failed(parse("sensor export -d 1 -s room -f xml")));
+    assert!(failed(parse("sensor export -d 1 -s room --from yesterday")));
+    assert!(failed(parse("sensor import -d 1 -s room")));
+
+    assert!(matches!(
+        args(parse_command(&[
+            "module".to_string(),
+            "inspect".to_string(),
+            "lib.so".to_string()
+        ])),
+        Command::InspectModule(file) if file == std::path::Path::new("lib.so")
+    ));
+    assert!(matches!(
+        parse_command(&["module".to_string(), "inspect".to_string()]),
+        Some(Err(_))
+    ));
+
+    assert!(matches!(
+        args(parse("migrate --dry-run")),
+        Command::Migrate(_)
+    ));
+    assert!(failed(parse("migrate now")));
+    assert!(matches!(args(parse("check")), Command::Check(_)));
+}
+
+/// `ClosedPipe` accepts one write and then fails like a pipe without a reader
+#[cfg(test)]
+struct ClosedPipe {
+    writes: usize,
+}
+
+#[cfg(test)]
+impl Write for ClosedPipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writes += 1;
+        match self.writes {
+            1 => Ok(buf.len()),
+            _ => Err(io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn export_to_closed_pipe() {
+    app::init_data_dir(std::env::temp_dir().join(format!("monisens-test-{}", std::process::id())))
+        .unwrap();
+    let pool_conf = repo::PoolConf {
+        max_connections: 1,
+        min_connections: 1,
+        ..Default::default()
+    };
+    let repo = repo::Repository::new(repo::Storage::Sqlite, "sqlite::memory:", &pool_conf)
+        .await
+        .unwrap();
+    let svc = Service::new(repo).await.unwrap();
+    let ctrl: Controller<_, crate::module::Module, crate::module::Module, _> = Controller::new(
+        tokio::runtime::Handle::current(),
+        svc.clone(),
+        Sinks::default(),
+        std::time::Duration::from_secs(5),
+    )
+    .await
+    .unwrap();
+    ctrl.bootstrap_admin("admin".to_string(), "password".to_string())
+        .await
+        .unwrap();
+    let admin = ctrl.get_user_list().await.unwrap().remove(0);
+    let device = ctrl
+        .create_push_device(
+            &admin,
+            "Export".to_string(),
+            r#"[{"name": "room", "fields": [{"name": "temperature", "type": "float64"}]}]"#
+                .to_string(),
+        )
+        .await
+        .unwrap();
+
+    let mut csv = "timestamp,temperature\n".to_string();
+    for i in 0..3000 {
+        csv += &format!("{},{i}.5\n", 1760788800 + i);
+    }
+    ctrl.import_sensor_data(
+        &admin,
+        controller::ImportPayload {
+            device_id: device.id.get_raw(),
+            sensor: "room".to_string(),
+            format: controller::ImportFormat::Csv,
+            mapping: Default::default(),
+            precision: controller::TimestampPrecision::Seconds,
+            dry_run: false,
+        },
+        io::Cursor::new(csv.into_bytes()),
+    )
+    .await
+    .unwrap();
+
+    let export = controller::prepare_export(
+        &svc,
+        controller::ExportPayload {
+            device_id: device.id.get_raw(),
+            sensor: "room".to_string(),
+            format: controller::ExportFormat::Csv,
+            fields: vec![],
+            time_field: None,
+            from: None,
+            to: None,
+            timezone: chrono_tz::UTC,
+        },
+    )
+    .unwrap();
+
+    // The reader is gone after the header, so the export stops, but doesn't fail
+    let (tx, rx) = mpsc::channel(1);
+    let (res, written) = tokio::join!(
+        export.run(&svc, tx),
+        sensor::write_chunks(ClosedPipe { writes: 0 }, rx)
+    );
+    written.unwrap();
+    res.unwrap();
+}
@@ -1,5 +1,14 @@
+use std::time::Duration;
+
+/// Default maximum duration of a single call to a module
+pub const DEFAULT_MODULE_TIMEOUT: Duration = Duration::from_secs(30);
+/// Default interval between checks of devices' health
+pub const DEFAULT_WATCHDOG_INTERVAL: Duration = Duration::from_secs(5);
+
 pub struct Conf {
     repo_dsn: String,
+    module_timeout: Duration,
+    watchdog_interval: Duration,
 }
 
 impl Conf {
@@ -16,12 +25,34 @@ impl Conf {
     pub fn get_repo_dsn(&self) -> &String {
         &self.repo_dsn
     }
+
+    pub fn with_module_timeout(mut self, module_timeout: Duration) -> Self {
+        self.module_timeout = module_timeout;
+
+        self
+    }
+
+    pub fn get_module_timeout(&self) -> Duration {
+        self.module_timeout
+    }
+
+    pub fn with_watchdog_interval(mut self, watchdog_interval: Duration) -> Self {
+        self.watchdog_interval = watchdog_interval;
+
+        self
+    }
+
+    pub fn get_watchdog_interval(&self) -> Duration {
+        self.watchdog_interval
+    }
 }
 
 impl Default for Conf {
     fn default() -> Self {
         Self {
             repo_dsn: Default::default(),
+            module_timeout: DEFAULT_MODULE_TIMEOUT,
+            watchdog_interval: DEFAULT_WATCHDOG_INTERVAL,
         }
     }
 }
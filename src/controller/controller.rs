@@ -1,48 +1,113 @@
 use std::{
     collections::HashMap,
+    future::Future,
+    io::Read,
     path::Path,
-    sync::{Arc, Mutex, RwLock},
+    sync::{Arc, RwLock},
+    time::Duration,
 };
 
-use tokio::{io::AsyncRead, runtime::Handle};
+use lazy_static::lazy_static;
+use serde_json::json;
+use tokio::{
+    io::AsyncRead,
+    runtime::Handle,
+    sync::{mpsc, Mutex},
+};
 
 use crate::logger;
+use crate::tool::secret;
 use crate::{kv_any, kv_val, kvs};
 
 use super::error::*;
+use super::executor::{CallError, ModuleExecutor};
+use super::export;
+use super::import;
 use super::interface::{
-    module::{IModule, IModuleFactory},
+    module::{IModule, IModuleFactory, PUSH_MODULE_NAME, PUSH_SENSORS_CONF_ID},
     service::IService,
+    sink::ISink,
 };
+use super::limiter::LoginLimiter;
 use super::model::internal::*;
 use super::model::*;
 use super::msg;
 
-pub struct Controller<S: IService, M: IModule, MF: IModuleFactory<M>> {
+/// Sensors of push devices have this field. It's set to the time of receipt
+/// if an ingested row doesn't contain it
+const INGEST_TIMESTAMP_FIELD: &str = "timestamp";
+/// How long a login session lasts
+const SESSION_TTL: chrono::Duration = chrono::Duration::days(7);
+const MIN_PASSWORD_LEN: usize = 8;
+/// API keys start with it, so they are told apart from session and ingest tokens
+const API_KEY_PREFIX: &str = "msk_";
+/// Last use of an API key is saved not more often than this
+const API_KEY_LAST_USED_PRECISION: chrono::Duration = chrono::Duration::minutes(1);
+/// Number of audit records returned if the limit isn't given
+const AUDIT_LOG_DEFAULT_LIMIT: i32 = 100;
+const AUDIT_LOG_MAX_LIMIT: i32 = 1000;
+/// Name of the user in audit records made by the watchdog
+const WATCHDOG_USERNAME: &str = "watchdog";
+
+lazy_static! {
+    /// Passwords of unknown users are verified against this hash
+    static ref DUMMY_PASSWORD_HASH: String = secret::hash_password("");
+}
+
+/// Devices by their ids
+type Devices<S, M, K> = HashMap<i32, Arc<Mutex<Device<S, M, K>>>>;
+
+pub struct Controller<S: IService, M: IModule, MF: IModuleFactory<M>, K: ISink> {
     _module_factory: std::marker::PhantomData<MF>,
     svc: S,
+    sink: K,
     tokio_handle: Handle,
-    devices: Arc<RwLock<HashMap<i32, Arc<Mutex<Device<S, M>>>>>>,
+    devices: Arc<RwLock<Devices<S, M, K>>>,
+    /// Maximum duration of a single call to a module
+    module_timeout: Duration,
+    login_limiter: LoginLimiter,
 }
 
-impl<S: IService + 'static, M: IModule + 'static, MF: IModuleFactory<M>> Controller<S, M, MF> {
-    pub async fn new(tokio_handle: Handle, svc: S) -> Result<Self, ControllerError> {
+impl<S, M, MF, K> Controller<S, M, MF, K>
+where
+    S: IService + 'static,
+    M: IModule + Send + 'static,
+    MF: IModuleFactory<M> + 'static,
+    K: ISink + 'static,
+{
+    pub async fn new(
+        tokio_handle: Handle,
+        svc: S,
+        sink: K,
+        module_timeout: Duration,
+    ) -> Result<Self, ControllerError> {
         let device_init_datas = svc.get_init_data_all_devices()?;
         let mut mods = HashMap::with_capacity(device_init_datas.len());
 
         for data in device_init_datas {
-            let m = MF::create_module(&data.module_file, &data.full_data_dir)?;
+            let m = ModuleExecutor::create::<MF>(
+                data.id,
+                data.module_file,
+                data.full_data_dir,
+                module_timeout,
+            )
+            .await?;
+            let module_info = m.call("module_info", |m| Ok(m.module_info())).await?;
+            svc.save_device_module_info(data.id, module_info).await?;
             let device = Arc::new(Mutex::new(Device {
                 id: data.id,
                 module: m,
                 msg_handler: None,
             }));
 
-            if data.init_state == DeviceInitState::Sensors {
-                let mut device = device.lock().unwrap();
-                let msg_handler = msg::Handler::new(data.id, svc.clone(), tokio_handle.clone());
+            // A stopped device is loaded, but its module isn't started
+            if data.init_state == DeviceInitState::Sensors && !data.stopped {
+                let mut device = device.lock().await;
+                let msg_handler =
+                    msg::Handler::new(data.id, svc.clone(), sink.clone(), tokio_handle.clone());
 
-                device.module.start(msg_handler.clone())?;
+                let h = msg_handler.clone();
+                device.module.call("start", move |m| m.start(h)).await?;
 
                 device.msg_handler = Some(msg_handler);
             }
@@ -53,81 +118,401 @@ impl<S: IService + 'static, M: IModule + 'static, MF: IModuleFactory<M>> Control
         Ok(Self {
             _module_factory: std::marker::PhantomData,
             svc,
+            sink,
             tokio_handle,
             devices: Arc::new(RwLock::new(mods)),
+            module_timeout,
+            login_limiter: LoginLimiter::default(),
         })
     }
 
     pub async fn start_device_init<'f, F: AsyncRead + Unpin + ?Sized>(
         &self,
+        user: &User,
         name: String,
         module_file: &'f mut F,
+    ) -> Result<DeviceConnData, ControllerError> {
+        let summary = json!({ "display_name": name });
+        let res = match self.svc.start_device_init(name.clone(), module_file).await {
+            Ok(device_init_data) => self.finish_device_init(name, device_init_data).await,
+            Err(err) => Err(err.into()),
+        };
+
+        let device_id = res.as_ref().ok().map(|data| data.id.get_raw());
+        self.audit(user, AuditAction::StartDeviceInit, device_id, summary, &res)
+            .await;
+
+        res
+    }
+
+    pub async fn start_device_init_from_catalog(
+        &self,
+        user: &User,
+        name: String,
+        module_id: i32,
+    ) -> Result<DeviceConnData, ControllerError> {
+        let summary = json!({ "display_name": name, "module_id": module_id });
+        let res = self.init_device_from_catalog(name, module_id).await;
+
+        let device_id = res.as_ref().ok().map(|data| data.id.get_raw());
+        self.audit(user, AuditAction::StartDeviceInit, device_id, summary, &res)
+            .await;
+
+        res
+    }
+
+    async fn init_device_from_catalog(
+        &self,
+        name: String,
+        module_id: i32,
     ) -> Result<DeviceConnData, ControllerError> {
         let device_init_data = self
             .svc
-            .start_device_init(name.clone(), module_file)
+            .start_device_init_from_catalog(name.clone(), module_id)
             .await?;
 
-        let res = self.init_device(
-            &device_init_data.module_file,
-            &device_init_data.full_data_dir,
-            device_init_data.id.clone(),
-        );
+        self.finish_device_init(name, device_init_data).await
+    }
+
+    pub async fn add_catalog_module<F: AsyncRead + Unpin + ?Sized>(
+        &self,
+        user: &User,
+        name: String,
+        module_file: &mut F,
+    ) -> Result<CatalogModule, ControllerError> {
+        let summary = json!({ "name": name });
+        let add = async { Ok(self.svc.add_catalog_module(name, module_file).await?) };
+
+        self.audited(user, AuditAction::UploadModule, None, summary, add)
+            .await
+    }
+
+    pub async fn get_catalog_module_list(&self) -> Result<Vec<CatalogModule>, ControllerError> {
+        self.svc
+            .get_catalog_module_list()
+            .await
+            .map_err(|err| err.into())
+    }
+
+    pub async fn delete_catalog_module(
+        &self,
+        user: &User,
+        module_id: i32,
+    ) -> Result<(), ControllerError> {
+        let summary = json!({ "module_id": module_id });
+        let delete = async { Ok(self.svc.delete_catalog_module(module_id).await?) };
+
+        self.audited(user, AuditAction::DeleteModule, None, summary, delete)
+            .await
+    }
+
+    /// `inspect_module` describes a module library without creating a device for it.
+    ///
+    /// The library is loaded on a blocking thread, so a module which hangs in `init` or
+    /// `obtain_device_conn_info` fails the inspection after the module timeout.
+    /// The thread can't be interrupted: it holds a thread of the blocking pool and
+    /// the library's scratch dir until the module returns, which is logged.
+    pub async fn inspect_module(
+        &self,
+        module_file: &Path,
+    ) -> Result<ModuleInspection, ControllerError> {
+        let module_file = module_file.to_path_buf();
+        let file_name = module_file.display().to_string();
+        let mut inspect = self
+            .tokio_handle
+            .spawn_blocking(move || MF::inspect_module(module_file).map_err(CallError::from));
+
+        let res = match tokio::time::timeout(self.module_timeout, &mut inspect).await {
+            Ok(res) => res,
+            Err(_) => {
+                logger::error_kv(
+                    "module inspection timed out, its thread is left until the module returns",
+                    kvs!("module_file" => kv_any!(file_name.clone())),
+                );
+                self.tokio_handle.spawn(async move {
+                    let _ = inspect.await;
+                    logger::info_kv(
+                        "timed out module inspection has returned, its scratch dir is removed",
+                        kvs!("module_file" => kv_any!(file_name)),
+                    );
+                });
+
+                return Err(CommonError::new(
+                    ErrorType::Timeout,
+                    format!(
+                        "module inspection timed out after {:?}",
+                        self.module_timeout
+                    ),
+                )
+                .into());
+            }
+        };
+
+        let inspection = res
+            .map_err(|err| {
+                CommonError::new(ErrorType::Internal, "failed to inspect module").with_source(err)
+            })?
+            .map_err(CommonError::from)?;
+
+        Ok(inspection)
+    }
+
+    /// `upgrade_device_module` replaces the module library of a configured device.
+    ///
+    /// The new library is loaded against device's data dir and must declare the same sensors
+    /// as the current one. The running module is stopped while the upgrade is in progress and
+    /// is started again if the new library is rejected. If the new module fails to start,
+    /// the previous library is restored.
+    pub async fn upgrade_device_module<F: AsyncRead + Unpin + ?Sized>(
+        &self,
+        user: &User,
+        id: i32,
+        module_file: &mut F,
+    ) -> Result<(), ControllerError> {
+        let upgrade = async {
+            let device_id = self.get_device_id(&id).await?;
+
+            if self.svc.get_device_full_info(device_id)?.init_state != DeviceInitState::Sensors {
+                return Err(CommonError::new(
+                    ErrorType::FailedPrecondition,
+                    "device is not configured",
+                )
+                .into());
+            }
+
+            let upgrade = self.svc.stage_device_module(device_id, module_file).await?;
+
+            let res = self.swap_device_module(id, &upgrade).await;
+            match res {
+                Ok(_) => logger::info_kv(
+                    "device module upgraded",
+                    kvs!("device_id" => kv_any!(device_id)),
+                ),
+                Err(ref err) => {
+                    logger::error_kv(
+                        "failed to upgrade device module",
+                        kvs!("device_id" => kv_any!(device_id), "error" => kv_val!(err)),
+                    );
+
+                    if upgrade.module_file.is_file() {
+                        self.svc.discard_device_module(&upgrade).await?;
+                    }
+                }
+            }
+
+            res?;
+
+            self.save_device_module_info(device_id).await
+        };
+
+        self.audited(
+            user,
+            AuditAction::UpgradeDeviceModule,
+            Some(id),
+            json!({}),
+            upgrade,
+        )
+        .await
+    }
+
+    async fn swap_device_module(
+        &self,
+        id: i32,
+        upgrade: &ModuleUpgradeData,
+    ) -> Result<(), ControllerError> {
+        let sensors = self.svc.get_device_sensor_info(upgrade.id)?;
+        let device_lock = self.get_device(&id)?;
+
+        // The device stays locked until the upgrade is done,
+        // so the watchdog can't restart the current module in the middle of it
+        let mut device = device_lock.lock().await;
+
+        // Stop the current module and check that the new one is able to replace it
+        if device.msg_handler.is_some() {
+            device.module.call("stop", |m| m.stop()).await?;
+        }
+
+        let res = async {
+            let m = ModuleExecutor::create::<MF>(
+                upgrade.id,
+                upgrade.module_file.clone(),
+                upgrade.full_data_dir.clone(),
+                self.module_timeout,
+            )
+            .await?;
+            let new_sensors = m
+                .call("obtain_sensor_type_infos", |m| m.obtain_sensor_type_infos())
+                .await?;
+            check_sensors_compatible(&sensors, &new_sensors)?;
+
+            Ok::<_, ControllerError>(m)
+        }
+        .await;
+
+        let module = match res {
+            Ok(m) => m,
+            Err(err) => {
+                restart_module(&device).await?;
+                return Err(err);
+            }
+        };
+
+        if let Err(err) = self.svc.commit_device_module(upgrade).await {
+            drop(module);
+            restart_module(&device).await?;
+
+            return Err(err.into());
+        }
+
+        let prev_module = std::mem::replace(&mut device.module, module);
+        if let Err(err) = restart_module(&device).await {
+            // The new module doesn't start, so the device goes back to the previous one
+            drop(std::mem::replace(&mut device.module, prev_module));
+            self.svc.rollback_device_module(upgrade).await?;
+            restart_module(&device).await?;
+
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    async fn finish_device_init(
+        &self,
+        name: String,
+        device_init_data: DeviceInitData,
+    ) -> Result<DeviceConnData, ControllerError> {
+        let res = match self
+            .init_device(
+                &device_init_data.module_file,
+                &device_init_data.full_data_dir,
+                device_init_data.id,
+            )
+            .await
+        {
+            Ok(conn_data) => self
+                .save_device_module_info(conn_data.id)
+                .await
+                .map(|_| conn_data),
+            Err(err) => Err(err),
+        };
 
         if let Err(ref err) = res {
             logger::error_kv(
                 "failed to init device",
                 kvs!("name" => kv_any!(&name), "error" => kv_val!(err)),
             );
+            self.devices
+                .write()
+                .unwrap()
+                .remove(&device_init_data.id.get_raw());
             self.svc.interrupt_device_init(device_init_data.id).await?;
         }
 
         res
     }
 
-    pub fn connect_device(&self, id: i32, conf: Vec<ConfEntry>) -> Result<(), ControllerError> {
+    pub async fn connect_device(
+        &self,
+        user: &User,
+        id: i32,
+        conf: Vec<ConfEntry>,
+    ) -> Result<(), ControllerError> {
+        let summary = conf_summary(&conf);
+        let connect = self.connect_device_module(id, conf);
+
+        self.audited(user, AuditAction::ConnectDevice, Some(id), summary, connect)
+            .await
+    }
+
+    async fn connect_device_module(
+        &self,
+        id: i32,
+        conf: Vec<ConfEntry>,
+    ) -> Result<(), ControllerError> {
         let device_lock = self.get_device(&id)?;
-        let mut device = device_lock.lock().unwrap();
+        let device = device_lock.lock().await;
 
-        device.module.connect_device(conf)?;
+        device
+            .module
+            .call("connect_device", move |m| m.connect_device(conf))
+            .await?;
 
         Ok(())
     }
 
-    pub fn obtain_device_conf_info(&self, id: i32) -> Result<ConfInfo, ControllerError> {
+    pub async fn obtain_device_conf_info(&self, id: i32) -> Result<ConfInfo, ControllerError> {
         let device_lock = self.get_device(&id)?;
-        let mut device = device_lock.lock().unwrap();
+        let device = device_lock.lock().await;
 
-        let device_conf_info = device.module.obtain_device_conf_info()?;
+        let device_conf_info = device
+            .module
+            .call("obtain_device_conf_info", |m| m.obtain_device_conf_info())
+            .await?;
 
         Ok(device_conf_info.into())
     }
 
     pub async fn configure_device(
+        &self,
+        user: &User,
+        id: i32,
+        confs: Vec<ConfEntry>,
+    ) -> Result<(), ControllerError> {
+        let summary = conf_summary(&confs);
+        self.audited(
+            user,
+            AuditAction::ConfigureDevice,
+            Some(id),
+            summary,
+            self.configure_device_module(id, confs),
+        )
+        .await
+    }
+
+    async fn configure_device_module(
         &self,
         id: i32,
         confs: Vec<ConfEntry>,
     ) -> Result<(), ControllerError> {
         {
             let device_lock = self.get_device(&id)?;
-            let mut device = device_lock.lock().unwrap();
+            let device = device_lock.lock().await;
 
-            device.module.configure_device(confs)?;
+            device
+                .module
+                .call("configure_device", move |m| m.configure_device(confs))
+                .await?;
 
-            let sensor_infos = device.module.obtain_sensor_type_infos()?;
+            let sensor_infos = device
+                .module
+                .call("obtain_sensor_type_infos", |m| m.obtain_sensor_type_infos())
+                .await?;
 
             self.svc.device_sensor_init(device.id, sensor_infos).await?;
         }
 
         // Start receiving data from device's sensors
-        self.start_device(id)?;
+        self.start_device(id).await?;
 
         Ok(())
     }
 
-    pub async fn interrupt_device_init(&self, id: i32) -> Result<(), ControllerError> {
+    pub async fn interrupt_device_init(&self, user: &User, id: i32) -> Result<(), ControllerError> {
+        self.audited(
+            user,
+            AuditAction::InterruptDeviceInit,
+            Some(id),
+            json!({}),
+            self.remove_device(id),
+        )
+        .await
+    }
+
+    /// `remove_device` deletes device which isn't configured yet
+    async fn remove_device(&self, id: i32) -> Result<(), ControllerError> {
         let device_lock = self.get_device(&id)?;
-        let device = device_lock.lock().unwrap();
+        let device = device_lock.lock().await;
 
         self.svc.interrupt_device_init(device.id).await?;
 
@@ -136,14 +521,112 @@ impl<S: IService + 'static, M: IModule + 'static, MF: IModuleFactory<M>> Control
         Ok(())
     }
 
-    fn init_device<P: AsRef<Path>>(
+    pub async fn get_device_command_info(
+        &self,
+        id: i32,
+    ) -> Result<Vec<CommandInfo>, ControllerError> {
+        let device_lock = self.get_device(&id)?;
+        let device = device_lock.lock().await;
+
+        let res = device
+            .module
+            .call("obtain_command_infos", |m| m.obtain_command_infos())
+            .await?;
+
+        Ok(res)
+    }
+
+    pub async fn send_device_command(
+        &self,
+        user: &User,
+        id: i32,
+        cmd: Command,
+    ) -> Result<(), ControllerError> {
+        let summary = json!({ "command": cmd.name });
+        let send = async {
+            let (device_id, res) = {
+                let device_lock = self.get_device(&id)?;
+                let device = device_lock.lock().await;
+
+                if device.msg_handler.is_none() {
+                    return Err(CommonError::new(
+                        ErrorType::FailedPrecondition,
+                        "device is not started",
+                    )
+                    .into());
+                }
+
+                let command_infos = device
+                    .module
+                    .call("obtain_command_infos", |m| m.obtain_command_infos())
+                    .await?;
+                validate_command(&command_infos, &cmd)?;
+
+                let c = cmd.clone();
+                (
+                    device.id,
+                    device
+                        .module
+                        .call("send_command", move |m| m.send_command(&c))
+                        .await,
+                )
+            };
+
+            match res {
+                Ok(_) => logger::info_kv(
+                    "device command sent",
+                    kvs!("device_id" => kv_any!(device_id), "command" => kv_any!(cmd.name.clone())),
+                ),
+                Err(ref err) => logger::error_kv(
+                    "failed to send device command",
+                    kvs!(
+                        "device_id" => kv_any!(device_id),
+                        "command" => kv_any!(cmd.name.clone()),
+                        "error" => kv_any!(err.msg.clone())
+                    ),
+                ),
+            }
+
+            let error = res.as_ref().err().map(|err| err.msg.clone());
+            self.svc
+                .save_device_command_log(DeviceCommandLog {
+                    device_id,
+                    command: cmd,
+                    error,
+                })
+                .await?;
+
+            res?;
+
+            Ok(())
+        };
+
+        self.audited(
+            user,
+            AuditAction::SendDeviceCommand,
+            Some(id),
+            summary,
+            send,
+        )
+        .await
+    }
+
+    async fn init_device<P: AsRef<Path>>(
         &self,
         mod_path: P,
         data_dir: P,
         device_id: DeviceID,
     ) -> Result<DeviceConnData, ControllerError> {
-        let mut m = MF::create_module(mod_path, data_dir)?;
-        let device_info = m.obtain_device_conn_info()?;
+        let m = ModuleExecutor::create::<MF>(
+            device_id,
+            mod_path.as_ref().to_path_buf(),
+            data_dir.as_ref().to_path_buf(),
+            self.module_timeout,
+        )
+        .await?;
+        let device_info = m
+            .call("obtain_device_conn_info", |m| m.obtain_device_conn_info())
+            .await?;
 
         self.devices.write().unwrap().insert(
             device_id.get_raw(),
@@ -160,31 +643,54 @@ impl<S: IService + 'static, M: IModule + 'static, MF: IModuleFactory<M>> Control
         })
     }
 
-    fn start_device(&self, id: i32) -> Result<(), ControllerError> {
+    async fn save_device_module_info(&self, id: DeviceID) -> Result<(), ControllerError> {
+        let module_info = {
+            let device_lock = self.get_device(&id.get_raw())?;
+            let device = device_lock.lock().await;
+
+            device
+                .module
+                .call("module_info", |m| Ok(m.module_info()))
+                .await?
+        };
+
+        self.svc.save_device_module_info(id, module_info).await?;
+
+        Ok(())
+    }
+
+    async fn start_device(&self, id: i32) -> Result<(), ControllerError> {
         let device_lock = self.get_device(&id)?;
-        let mut device = device_lock.lock().unwrap();
+        let mut device = device_lock.lock().await;
 
         // Get device's handler (with lazy loading)
         let msg_handler = if let Some(ref msg_handler) = device.msg_handler {
             msg_handler.clone()
         } else {
-            let msg_handler =
-                msg::Handler::new(device.id, self.svc.clone(), self.tokio_handle.clone());
+            let msg_handler = msg::Handler::new(
+                device.id,
+                self.svc.clone(),
+                self.sink.clone(),
+                self.tokio_handle.clone(),
+            );
             device.msg_handler = Some(msg_handler.clone());
 
             msg_handler
         };
 
-        device.module.start(msg_handler.clone())?;
+        device
+            .module
+            .call("start", move |m| m.start(msg_handler))
+            .await?;
 
         Ok(())
     }
 
-    fn stop_device(&self, id: i32) -> Result<(), ControllerError> {
+    async fn stop_device(&self, id: i32) -> Result<(), ControllerError> {
         let device_lock = self.get_device(&id)?;
-        let mut device = device_lock.lock().unwrap();
+        let device = device_lock.lock().await;
 
-        device.module.stop()?;
+        device.module.call("stop", |m| m.stop()).await?;
 
         Ok(())
     }
@@ -197,7 +703,7 @@ impl<S: IService + 'static, M: IModule + 'static, MF: IModuleFactory<M>> Control
             return Err(ControllerError::IncorrectPayload("data.fields is empty".into()).into());
         }
 
-        let device_id = self.get_device_id(&data.device_id)?;
+        let device_id = self.get_device_id(&data.device_id).await?;
 
         let res = self
             .svc
@@ -212,15 +718,184 @@ impl<S: IService + 'static, M: IModule + 'static, MF: IModuleFactory<M>> Control
         Ok(sensor_data_result_from_service(res))
     }
 
-    pub fn get_device_info_list(&self) -> Result<Vec<DeviceInfo>, ControllerError> {
-        self.svc.get_device_info_list().map_err(|err| err.into())
+    pub async fn get_device_info_list(&self) -> Result<Vec<DeviceInfo>, ControllerError> {
+        let mut res = self.svc.get_device_info_list()?;
+
+        for info in res.iter_mut() {
+            if let Ok(device_lock) = self.get_device(&info.id.get_raw()) {
+                let device = device_lock.lock().await;
+
+                info.health = device.msg_handler.as_ref().map(|h| h.health());
+                info.faulted = device.module.is_faulted()
+                    || device
+                        .msg_handler
+                        .as_ref()
+                        .map_or(false, |h| h.is_faulted());
+            }
+        }
+
+        Ok(res)
     }
 
-    pub fn get_device_sensor_info(
+    pub async fn save_device_watchdog_conf(
+        &self,
+        user: &User,
+        id: i32,
+        conf: WatchdogConf,
+    ) -> Result<(), ControllerError> {
+        let summary = json!({
+            "expected_interval": conf.expected_interval,
+            "restart": conf.restart,
+        });
+        let save = async {
+            let device_id = self.get_device_id(&id).await?;
+            Ok(self.svc.save_device_watchdog_conf(device_id, conf).await?)
+        };
+
+        self.audited(
+            user,
+            AuditAction::SetDeviceWatchdogConf,
+            Some(id),
+            summary,
+            save,
+        )
+        .await
+    }
+
+    /// `check_devices_health` flags running devices that haven't sent messages for longer
+    /// than their expected interval as stale. Stale devices are restarted if it's enabled
+    /// in their watchdog conf.
+    ///
+    /// Devices becoming stale or recovering and their restarts are recorded in the audit log.
+    pub async fn check_devices_health(&self) {
+        let devices: Vec<_> = self.devices.read().unwrap().values().cloned().collect();
+        let mut events = Vec::new();
+
+        for device_lock in devices {
+            let device = device_lock.lock().await;
+
+            let msg_handler = match device.msg_handler {
+                Some(ref h) => h.clone(),
+                None => continue,
+            };
+
+            let conf = match self.svc.get_device_watchdog_conf(device.id) {
+                Ok(conf) => conf,
+                Err(err) => {
+                    logger::error_kv(
+                        "watchdog failed to get device conf",
+                        kvs!("device_id" => kv_any!(device.id), "error" => kv_any!(err)),
+                    );
+                    continue;
+                }
+            };
+
+            let interval = match conf.expected_interval {
+                Some(v) => Duration::from_secs(v as u64),
+                None => continue,
+            };
+
+            let (stale, changed) = msg_handler.check_stale(interval);
+            if !changed {
+                continue;
+            }
+
+            let device_id = device.id.get_raw();
+            let summary = json!({
+                "expected_interval": conf.expected_interval,
+                "last_msg_at": msg_handler.health().last_msg_at,
+            });
+
+            if !stale {
+                logger::info_kv("device recovered", kvs!("device_id" => kv_any!(device.id)));
+                events.push((AuditAction::DeviceRecovered, device_id, summary, None));
+                continue;
+            }
+
+            logger::warn_kv(
+                "device is stale",
+                kvs!("device_id" => kv_any!(device.id), "expected_interval" => kv_any!(interval)),
+            );
+            events.push((AuditAction::DeviceStale, device_id, summary, None));
+
+            if conf.restart && msg_handler.is_faulted() {
+                let error = format!(
+                    "device sent no messages after {} restarts and isn't restarted anymore",
+                    msg::MAX_RESTARTS
+                );
+                logger::error_kv(
+                    "stale device is faulted",
+                    kvs!("device_id" => kv_any!(device.id), "restarts" => kv_any!(msg::MAX_RESTARTS)),
+                );
+                events.push((
+                    AuditAction::RestartStaleDevice,
+                    device_id,
+                    json!({}),
+                    Some(error),
+                ));
+            } else if conf.restart {
+                let h = msg_handler.clone();
+                let res = device
+                    .module
+                    .call("restart", move |m| {
+                        m.stop()?;
+                        m.start(h)
+                    })
+                    .await;
+
+                let error = match res {
+                    Ok(_) => {
+                        msg_handler.mark_restarted();
+                        logger::info_kv(
+                            "stale device restarted",
+                            kvs!("device_id" => kv_any!(device.id)),
+                        );
+
+                        None
+                    }
+                    Err(err) => {
+                        let error = err.to_string();
+                        logger::error_kv(
+                            "failed to restart stale device",
+                            kvs!("device_id" => kv_any!(device.id), "error" => kv_any!(err)),
+                        );
+
+                        Some(error)
+                    }
+                };
+                events.push((AuditAction::RestartStaleDevice, device_id, json!({}), error));
+            }
+        }
+
+        for (action, device_id, summary, error) in events {
+            let record = NewAuditRecord {
+                user_id: None,
+                username: WATCHDOG_USERNAME.to_string(),
+                action,
+                device_id: Some(device_id),
+                summary,
+                error,
+            };
+            self.save_audit_record(record).await;
+        }
+    }
+
+    pub async fn get_device_full_info(
+        &self,
+        device_id: i32,
+    ) -> Result<DeviceFullInfo, ControllerError> {
+        let device_id = self.get_device_id(&device_id).await?;
+
+        self.svc
+            .get_device_full_info(device_id)
+            .map_err(|err| err.into())
+    }
+
+    pub async fn get_device_sensor_info(
         &self,
         device_id: i32,
     ) -> Result<Vec<SensorInfo>, ControllerError> {
-        let device_id = self.get_device_id(&device_id)?;
+        let device_id = self.get_device_id(&device_id).await?;
 
         self.svc
             .get_device_sensor_info(device_id)
@@ -229,12 +904,21 @@ impl<S: IService + 'static, M: IModule + 'static, MF: IModuleFactory<M>> Control
 
     pub async fn save_monitor_conf(
         &self,
+        user: &User,
         monitor_conf: MonitorConf,
     ) -> Result<i32, ControllerError> {
-        self.svc
-            .save_monitor_conf(monitor_conf)
-            .await
-            .map_err(|err| err.into())
+        let device_id = monitor_conf.device_id;
+        let summary = json!({ "sensor": monitor_conf.sensor });
+        let save = async { Ok(self.svc.save_monitor_conf(monitor_conf).await?) };
+
+        self.audited(
+            user,
+            AuditAction::SaveMonitorConf,
+            Some(device_id),
+            summary,
+            save,
+        )
+        .await
     }
 
     pub async fn get_monitor_conf_list(
@@ -247,7 +931,726 @@ impl<S: IService + 'static, M: IModule + 'static, MF: IModuleFactory<M>> Control
             .map_err(|err| err.into())
     }
 
-    fn get_device(&self, id: &i32) -> Result<Arc<Mutex<Device<S, M>>>, ControllerError> {
+    /// `create_push_device` creates and starts a device which sends its data to the ingest
+    /// endpoint itself. `sensors` is a JSON schema of device's sensors.
+    pub async fn create_push_device(
+        &self,
+        user: &User,
+        name: String,
+        sensors: String,
+    ) -> Result<PushDevice, ControllerError> {
+        let summary = json!({ "display_name": name });
+        let res = self.init_push_device(name, sensors).await;
+
+        let device_id = res.as_ref().ok().map(|device| device.id.get_raw());
+        self.audit(
+            user,
+            AuditAction::CreatePushDevice,
+            device_id,
+            summary,
+            &res,
+        )
+        .await;
+
+        res
+    }
+
+    async fn init_push_device(
+        &self,
+        name: String,
+        sensors: String,
+    ) -> Result<PushDevice, ControllerError> {
+        let module = self
+            .svc
+            .get_catalog_module_list()
+            .await?
+            .into_iter()
+            .find(|m| m.hash == PUSH_MODULE_NAME)
+            .ok_or_else(|| {
+                CommonError::new(
+                    ErrorType::NotFound,
+                    "push module is not in the module catalog",
+                )
+            })?;
+
+        let id = self
+            .init_device_from_catalog(name, module.id)
+            .await?
+            .id
+            .get_raw();
+
+        let conf = vec![ConfEntry {
+            id: PUSH_SENSORS_CONF_ID,
+            data: Some(ConfType::JSON(sensors)),
+        }];
+        let res = match self.connect_device_module(id, vec![]).await {
+            Ok(_) => self.configure_device_module(id, conf).await,
+            Err(err) => Err(err),
+        };
+
+        if let Err(err) = res {
+            self.remove_device(id).await?;
+            return Err(err);
+        }
+
+        let ingest_token = self.generate_ingest_token(id).await?;
+
+        Ok(PushDevice {
+            id: self.get_device_id(&id).await?,
+            ingest_token,
+        })
+    }
+
+    /// `reset_device_ingest_token` generates a new ingest token of a push device.
+    /// The previous token stops working.
+    pub async fn reset_device_ingest_token(
+        &self,
+        user: &User,
+        id: i32,
+    ) -> Result<String, ControllerError> {
+        self.audited(
+            user,
+            AuditAction::ResetIngestToken,
+            Some(id),
+            json!({}),
+            self.generate_ingest_token(id),
+        )
+        .await
+    }
+
+    async fn generate_ingest_token(&self, id: i32) -> Result<String, ControllerError> {
+        let device_id = self.get_device_id(&id).await?;
+
+        let info = self.svc.get_device_full_info(device_id)?;
+        if info.module_hash.as_deref() != Some(PUSH_MODULE_NAME) {
+            return Err(CommonError::new(
+                ErrorType::FailedPrecondition,
+                "device is not a push device",
+            )
+            .into());
+        }
+
+        let token = secret::generate_token();
+        self.svc
+            .save_device_ingest_token_hash(device_id, Some(secret::hash_token(&token)))
+            .await?;
+
+        logger::info_kv(
+            "device ingest token reset",
+            kvs!("device_id" => kv_any!(device_id)),
+        );
+
+        Ok(token)
+    }
+
+    /// `ingest_sensor_data` validates rows sent by a push device against its sensors
+    /// and saves them one by one. Nothing is saved if any row is invalid.
+    ///
+    /// It returns the number of saved rows.
+    pub async fn ingest_sensor_data(
+        &self,
+        id: i32,
+        token: &str,
+        rows: Vec<IngestRow>,
+    ) -> Result<usize, ControllerError> {
+        let device_id = if token.starts_with(API_KEY_PREFIX) {
+            let user = self.authenticate_api_key(token).await?;
+            user.authorize_device(Permission::IngestData, id)?;
+
+            let device_id = self.get_device_id(&id).await?;
+            let info = self.svc.get_device_full_info(device_id)?;
+            if info.module_hash.as_deref() != Some(PUSH_MODULE_NAME) {
+                return Err(CommonError::new(
+                    ErrorType::FailedPrecondition,
+                    "device is not a push device",
+                )
+                .into());
+            }
+
+            device_id
+        } else {
+            // Unknown devices are reported the same way as invalid tokens
+            self.get_device_id(&id)
+                .await
+                .ok()
+                .filter(|device_id| {
+                    matches!(
+                        self.svc.get_device_ingest_token_hash(*device_id),
+                        Ok(Some(hash)) if secret::verify_token(token, &hash)
+                    )
+                })
+                .ok_or_else(|| {
+                    CommonError::new(ErrorType::Unauthenticated, "invalid ingest token")
+                })?
+        };
+
+        let msg_handler = {
+            let device_lock = self.get_device(&id)?;
+            let device = device_lock.lock().await;
+
+            device.msg_handler.clone().ok_or_else(|| {
+                CommonError::new(ErrorType::FailedPrecondition, "device is not started")
+            })?
+        };
+
+        if rows.is_empty() {
+            return Err(ControllerError::IncorrectPayload(
+                "no rows were given".into(),
+            ));
+        }
+
+        let sensors = self.svc.get_device_sensor_info(device_id)?;
+        let msgs = rows
+            .into_iter()
+            .enumerate()
+            .map(|(i, row)| {
+                ingest_row_to_msg(&sensors, row)
+                    .map_err(|err| ControllerError::IncorrectPayload(format!("row {i}: {err}")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Rows are saved the same way as messages of module devices
+        let count = msgs.len();
+        for msg in msgs {
+            self.svc.save_sensor_data(device_id, msg.clone()).await?;
+            msg_handler.register_msg();
+            self.sink.forward(device_id, &msg);
+        }
+
+        Ok(count)
+    }
+
+    /// `prepare_sensor_data_export` validates an export of device's sensor data.
+    /// See [`export::prepare_export`].
+    pub fn prepare_sensor_data_export(
+        &self,
+        payload: export::ExportPayload,
+    ) -> Result<export::SensorDataExport, ControllerError> {
+        export::prepare_export(&self.svc, payload)
+    }
+
+    /// `export_sensor_data` runs the export and sends the file by chunks to `out`.
+    /// See [`export::SensorDataExport::run`].
+    pub async fn export_sensor_data(
+        &self,
+        export: export::SensorDataExport,
+        out: mpsc::Sender<Vec<u8>>,
+    ) -> Result<usize, ControllerError> {
+        let (device_id, sensor, format) = (
+            export.device_id(),
+            export.sensor().to_string(),
+            export.format(),
+        );
+
+        let res = export.run(&self.svc, out).await;
+        match res {
+            Ok(rows) => logger::info_kv(
+                "sensor data exported",
+                kvs!(
+                    "device_id" => kv_any!(device_id.get_raw()),
+                    "sensor" => kv_any!(sensor),
+                    "format" => kv_any!(format.extension()),
+                    "rows" => kv_any!(rows)
+                ),
+            ),
+            Err(ref err) => logger::error_kv(
+                "failed to export sensor data",
+                kvs!(
+                    "device_id" => kv_any!(device_id.get_raw()),
+                    "sensor" => kv_any!(sensor),
+                    "error" => kv_val!(err)
+                ),
+            ),
+        }
+
+        res
+    }
+
+    /// `import_sensor_data` imports historical data of device's sensor from a file.
+    /// See [`import::import_sensor_data`].
+    pub async fn import_sensor_data<R: Read + Send + 'static>(
+        &self,
+        user: &User,
+        payload: import::ImportPayload,
+        reader: R,
+    ) -> Result<import::ImportReport, ControllerError> {
+        let (device_id, sensor) = (payload.device_id, payload.sensor.clone());
+        let dry_run = payload.dry_run;
+        let res = import::import_sensor_data(&self.svc, payload, reader).await;
+
+        // Dry runs don't change anything
+        if !dry_run {
+            let summary = match &res {
+                Ok(report) => json!({
+                    "sensor": sensor,
+                    "imported": report.imported,
+                    "invalid": report.invalid,
+                }),
+                Err(_) => json!({ "sensor": sensor }),
+            };
+            self.audit(
+                user,
+                AuditAction::ImportSensorData,
+                Some(device_id),
+                summary,
+                &res,
+            )
+            .await;
+        }
+
+        let report = res?;
+        if !report.dry_run {
+            logger::info_kv(
+                "sensor data imported",
+                kvs!(
+                    "device_id" => kv_any!(device_id),
+                    "sensor" => kv_any!(sensor),
+                    "imported" => kv_any!(report.imported),
+                    "invalid" => kv_any!(report.invalid)
+                ),
+            );
+        }
+
+        Ok(report)
+    }
+
+    /// `bootstrap_admin` creates the first user of the service. Nothing is done
+    /// if users already exist.
+    ///
+    /// It returns `true` if the user was created.
+    pub async fn bootstrap_admin(
+        &self,
+        username: String,
+        password: String,
+    ) -> Result<bool, ControllerError> {
+        if !self.svc.get_user_list().await?.is_empty() {
+            return Ok(false);
+        }
+
+        self.save_new_user(username, password, Role::Admin, None)
+            .await?;
+
+        Ok(true)
+    }
+
+    /// `create_user` creates a user with the role. If `devices` are given, the user
+    /// has access only to them.
+    pub async fn create_user(
+        &self,
+        user: &User,
+        username: String,
+        password: String,
+        role: Role,
+        devices: Option<Vec<i32>>,
+    ) -> Result<User, ControllerError> {
+        let summary = json!({
+            "username": username,
+            "role": format!("{role:?}"),
+            "device_ids": devices,
+        });
+        self.audited(
+            user,
+            AuditAction::CreateUser,
+            None,
+            summary,
+            self.save_new_user(username, password, role, devices),
+        )
+        .await
+    }
+
+    async fn save_new_user(
+        &self,
+        username: String,
+        password: String,
+        role: Role,
+        devices: Option<Vec<i32>>,
+    ) -> Result<User, ControllerError> {
+        if username.trim().is_empty() || username.trim() != username {
+            return Err(ControllerError::IncorrectPayload(
+                "username must be non-empty and must not start or end with whitespace".into(),
+            ));
+        }
+
+        if password.chars().count() < MIN_PASSWORD_LEN {
+            return Err(ControllerError::IncorrectPayload(format!(
+                "password must contain at least {MIN_PASSWORD_LEN} characters"
+            )));
+        }
+
+        self.check_devices_exist(&devices)?;
+
+        let password_hash = self
+            .tokio_handle
+            .spawn_blocking(move || secret::hash_password(&password))
+            .await
+            .map_err(|err| {
+                CommonError::new(ErrorType::Internal, "failed to hash password").with_source(err)
+            })?;
+
+        let user = self
+            .svc
+            .create_user(NewUser {
+                username,
+                password_hash,
+                role,
+                devices,
+            })
+            .await?;
+
+        logger::info_kv(
+            "user created",
+            kvs!(
+                "user_id" => kv_any!(user.id),
+                "username" => kv_any!(user.username.clone()),
+                "role" => kv_any!(format!("{:?}", user.role))
+            ),
+        );
+
+        Ok(user)
+    }
+
+    /// `update_user` changes user's role and devices which it has access to.
+    /// Users can't change their own access, so an admin can't lock itself out.
+    pub async fn update_user(
+        &self,
+        user: &User,
+        id: i32,
+        role: Role,
+        devices: Option<Vec<i32>>,
+    ) -> Result<User, ControllerError> {
+        let summary = json!({
+            "user_id": id,
+            "role": format!("{role:?}"),
+            "device_ids": devices,
+        });
+        let update = async {
+            if user.id == id {
+                return Err(CommonError::new(
+                    ErrorType::FailedPrecondition,
+                    "user can't change its own access",
+                )
+                .into());
+            }
+
+            self.check_devices_exist(&devices)?;
+
+            let user = self.svc.update_user(id, role, devices).await?;
+
+            logger::info_kv(
+                "user updated",
+                kvs!(
+                    "user_id" => kv_any!(user.id),
+                    "role" => kv_any!(format!("{:?}", user.role))
+                ),
+            );
+
+            Ok(user)
+        };
+
+        self.audited(user, AuditAction::UpdateUser, None, summary, update)
+            .await
+    }
+
+    pub async fn get_user_list(&self) -> Result<Vec<User>, ControllerError> {
+        self.svc.get_user_list().await.map_err(|err| err.into())
+    }
+
+    /// `delete_user` deletes a user with its sessions. Users can't delete themselves,
+    /// so the service always keeps at least one user.
+    pub async fn delete_user(&self, user: &User, id: i32) -> Result<(), ControllerError> {
+        let delete = async {
+            if user.id == id {
+                return Err(CommonError::new(
+                    ErrorType::FailedPrecondition,
+                    "user can't delete itself",
+                )
+                .into());
+            }
+
+            self.svc.delete_user(id).await?;
+
+            logger::info_kv("user deleted", kvs!("user_id" => kv_any!(id)));
+
+            Ok(())
+        };
+
+        self.audited(
+            user,
+            AuditAction::DeleteUser,
+            None,
+            json!({ "user_id": id }),
+            delete,
+        )
+        .await
+    }
+
+    /// `login` checks user's password and creates a login session.
+    ///
+    /// After several failed logins of a user from the `client`, its next attempts
+    /// are rejected for a while, see [`LoginLimiter`].
+    pub async fn login(
+        &self,
+        username: &str,
+        password: String,
+        client: &str,
+    ) -> Result<LoginSession, ControllerError> {
+        if let Some(wait) = self
+            .login_limiter
+            .check(client, username, std::time::Instant::now())
+        {
+            return Err(CommonError::new(
+                ErrorType::ResourceExhausted,
+                format!(
+                    "too many failed logins, try again in {} second(s)",
+                    wait.as_secs().max(1)
+                ),
+            )
+            .into());
+        }
+
+        let user = match self.svc.get_user_by_name(username).await {
+            Ok(user) => Some(user),
+            Err(err) if err.error_type == ErrorType::NotFound => None,
+            Err(err) => return Err(err.into()),
+        };
+
+        // Unknown users are checked against a dummy hash, so they can't be told apart
+        // from the known ones by response time
+        let hash = user.as_ref().map(|user| user.password_hash.clone());
+        let valid = self
+            .tokio_handle
+            .spawn_blocking(move || {
+                secret::verify_password(&password, hash.as_deref().unwrap_or(&DUMMY_PASSWORD_HASH))
+            })
+            .await
+            .map_err(|err| {
+                CommonError::new(ErrorType::Internal, "failed to verify password").with_source(err)
+            })?;
+
+        let Some(user) = user.filter(|_| valid) else {
+            self.login_limiter
+                .register_failure(client, username, std::time::Instant::now());
+            logger::warn_kv(
+                "failed login",
+                kvs!("username" => kv_any!(username.to_string()), "client" => kv_any!(client.to_string())),
+            );
+
+            return Err(CommonError::new(
+                ErrorType::Unauthenticated,
+                "invalid username or password",
+            )
+            .into());
+        };
+        self.login_limiter.reset(client, username);
+
+        self.svc.delete_expired_sessions().await?;
+
+        let token = secret::generate_token();
+        let expires_at = chrono::Utc::now().naive_utc() + SESSION_TTL;
+        self.svc
+            .save_session(Session {
+                token_hash: secret::hash_token(&token),
+                user_id: user.id,
+                expires_at,
+            })
+            .await?;
+
+        logger::info_kv(
+            "user logged in",
+            kvs!("user_id" => kv_any!(user.id), "username" => kv_any!(user.username.clone())),
+        );
+
+        Ok(LoginSession {
+            token,
+            expires_at,
+            user,
+        })
+    }
+
+    /// `logout` ends the login session with the token.
+    pub async fn logout(&self, token: &str) -> Result<(), ControllerError> {
+        self.svc
+            .delete_session(&secret::hash_token(token))
+            .await
+            .map_err(|err| err.into())
+    }
+
+    /// `create_api_key` creates an API key of the user with the scopes.
+    pub async fn create_api_key(
+        &self,
+        user: &User,
+        name: String,
+        scopes: Vec<ApiKeyScope>,
+    ) -> Result<CreatedApiKey, ControllerError> {
+        let summary = json!({
+            "name": name,
+            "scopes": scopes.iter().map(|scope| format!("{scope:?}")).collect::<Vec<_>>(),
+        });
+        let create = async {
+            if name.trim().is_empty() {
+                return Err(ControllerError::IncorrectPayload(
+                    "name of API key must be non-empty".into(),
+                ));
+            }
+
+            if scopes.is_empty() {
+                return Err(ControllerError::IncorrectPayload(
+                    "API key must have at least one scope".into(),
+                ));
+            }
+
+            let key = format!("{API_KEY_PREFIX}{}", secret::generate_token());
+            let api_key = self
+                .svc
+                .create_api_key(NewApiKey {
+                    user_id: user.id,
+                    name,
+                    key_hash: secret::hash_token(&key),
+                    scopes,
+                })
+                .await?;
+
+            logger::info_kv(
+                "API key created",
+                kvs!("api_key_id" => kv_any!(api_key.id), "user_id" => kv_any!(user.id)),
+            );
+
+            Ok(CreatedApiKey { key, api_key })
+        };
+
+        self.audited(user, AuditAction::CreateApiKey, None, summary, create)
+            .await
+    }
+
+    /// `get_api_key_list` returns API keys of the user. Users who manage users get all keys.
+    pub async fn get_api_key_list(&self, user: &User) -> Result<Vec<ApiKey>, ControllerError> {
+        let user_id = match user.authorize(Permission::ManageUsers) {
+            Ok(_) => None,
+            Err(_) => Some(user.id),
+        };
+
+        Ok(self.svc.get_api_key_list(user_id).await?)
+    }
+
+    /// `revoke_api_key` deletes an API key. Keys of other users can be revoked only by
+    /// the ones who manage users.
+    pub async fn revoke_api_key(&self, user: &User, id: i32) -> Result<(), ControllerError> {
+        let revoke = async {
+            let api_key = self.svc.get_api_key(id).await?;
+            if api_key.user_id != user.id {
+                user.authorize(Permission::ManageUsers)?;
+            }
+
+            self.svc.delete_api_key(id).await?;
+
+            logger::info_kv(
+                "API key revoked",
+                kvs!("api_key_id" => kv_any!(id), "user_id" => kv_any!(user.id)),
+            );
+
+            Ok(())
+        };
+
+        self.audited(
+            user,
+            AuditAction::RevokeApiKey,
+            None,
+            json!({ "api_key_id": id }),
+            revoke,
+        )
+        .await
+    }
+
+    /// `get_audit_log` returns audit records selected by the filter from the newest ones.
+    pub async fn get_audit_log(
+        &self,
+        mut filter: AuditLogFilter,
+    ) -> Result<Vec<AuditRecord>, ControllerError> {
+        filter.limit = Some(
+            filter
+                .limit
+                .unwrap_or(AUDIT_LOG_DEFAULT_LIMIT)
+                .clamp(1, AUDIT_LOG_MAX_LIMIT),
+        );
+
+        Ok(self.svc.get_audit_log(filter).await?)
+    }
+
+    /// `audited` makes the action and saves a record of it with its outcome.
+    async fn audited<T>(
+        &self,
+        user: &User,
+        action: AuditAction,
+        device_id: Option<i32>,
+        summary: serde_json::Value,
+        make: impl Future<Output = Result<T, ControllerError>>,
+    ) -> Result<T, ControllerError> {
+        let res = make.await;
+        self.audit(user, action, device_id, summary, &res).await;
+
+        res
+    }
+
+    /// `audit` saves a record of the action made by the user with its outcome.
+    /// The action is already made, so a failure to save the record is only logged.
+    async fn audit<T>(
+        &self,
+        user: &User,
+        action: AuditAction,
+        device_id: Option<i32>,
+        summary: serde_json::Value,
+        res: &Result<T, ControllerError>,
+    ) {
+        let record = NewAuditRecord {
+            user_id: Some(user.id),
+            username: user.username.clone(),
+            action,
+            device_id,
+            summary,
+            error: res.as_ref().err().map(|err| err.to_string()),
+        };
+
+        self.save_audit_record(record).await;
+    }
+
+    /// `save_audit_record` saves the record of an action which is already made,
+    /// so a failure to save it is only logged.
+    async fn save_audit_record(&self, record: NewAuditRecord) {
+        let (action, username) = (record.action, record.username.clone());
+
+        if let Err(err) = self.svc.save_audit_record(record).await {
+            logger::error_kv(
+                "failed to save audit record",
+                kvs!(
+                    "action" => kv_any!(format!("{action:?}")),
+                    "username" => kv_any!(username),
+                    "error" => kv_any!(err.msg.clone())
+                ),
+            );
+        }
+    }
+
+    /// `authenticate` returns the user of the login session or of the API key with the token.
+    pub async fn authenticate(&self, token: &str) -> Result<User, ControllerError> {
+        if token.starts_with(API_KEY_PREFIX) {
+            return self.authenticate_api_key(token).await;
+        }
+
+        let session = self
+            .svc
+            .get_session(&secret::hash_token(token))
+            .await
+            .map_err(|err| match err.error_type {
+                ErrorType::NotFound => {
+                    CommonError::new(ErrorType::Unauthenticated, "invalid or expired session")
+                }
+                _ => err,
+            })?;
+
+        Ok(self.svc.get_user(session.user_id).await?)
+    }
+
+    fn get_device(&self, id: &i32) -> Result<Arc<Mutex<Device<S, M, K>>>, ControllerError> {
         self.devices
             .read()
             .unwrap()
@@ -256,21 +1659,353 @@ impl<S: IService + 'static, M: IModule + 'static, MF: IModuleFactory<M>> Control
             .cloned()
     }
 
-    fn get_device_id(&self, id: &i32) -> Result<DeviceID, ControllerError> {
+    /// `authenticate_api_key` returns the user of the API key limited to key's scopes.
+    async fn authenticate_api_key(&self, key: &str) -> Result<User, ControllerError> {
+        let api_key = self
+            .svc
+            .get_api_key_by_hash(&secret::hash_token(key))
+            .await
+            .map_err(|err| match err.error_type {
+                ErrorType::NotFound => {
+                    CommonError::new(ErrorType::Unauthenticated, "invalid API key")
+                }
+                _ => err,
+            })?;
+
+        let now = chrono::Utc::now().naive_utc();
+        if api_key
+            .last_used_at
+            .is_none_or(|at| now - at >= API_KEY_LAST_USED_PRECISION)
+        {
+            self.svc.save_api_key_last_used(api_key.id, now).await?;
+        }
+
+        let mut user = self.svc.get_user(api_key.user_id).await?;
+        user.scopes = Some(api_key.scopes);
+
+        Ok(user)
+    }
+
+    fn check_devices_exist(&self, devices: &Option<Vec<i32>>) -> Result<(), ControllerError> {
+        for id in devices.iter().flatten() {
+            self.get_device(id)?;
+        }
+
+        Ok(())
+    }
+
+    async fn get_device_id(&self, id: &i32) -> Result<DeviceID, ControllerError> {
         let device_lock = self.get_device(id)?;
-        let device = device_lock.lock().unwrap();
+        let device = device_lock.lock().await;
 
         Ok(device.id)
     }
 }
 
-impl<S: IService, M: IModule, MF: IModuleFactory<M>> Clone for Controller<S, M, MF> {
+impl<S, M, MF, K> Controller<S, M, MF, K>
+where
+    S: IService + 'static,
+    M: IModule + Send + 'static,
+    MF: IModuleFactory<M> + Send + 'static,
+    K: ISink + 'static,
+{
+    /// `start_watchdog` starts a background task that checks devices' health every `interval`.
+    pub fn start_watchdog(&self, interval: Duration) {
+        let ctrl = self.clone();
+
+        self.tokio_handle.spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            loop {
+                interval.tick().await;
+
+                // Futures of the service aren't `Send`, so the check is run on its own thread
+                let ctrl = ctrl.clone();
+                let handle = ctrl.tokio_handle.clone();
+                if let Err(err) = tokio::task::spawn_blocking(move || {
+                    handle.block_on(ctrl.check_devices_health())
+                })
+                .await
+                {
+                    logger::error_kv("watchdog check failed", kvs!("error" => kv_any!(err)));
+                }
+            }
+        });
+    }
+
+    /// `shutdown` stops running modules, waits until the data they have sent is saved,
+    /// unloads all modules and flushes the sinks. Devices are removed from the controller,
+    /// so it mustn't be used afterwards.
+    ///
+    /// Every step waits no longer than the module timeout, so a hanging module can't
+    /// block the shutdown. Failures are logged and counted in the report.
+    pub async fn shutdown(&self) -> ShutdownReport {
+        let devices: Vec<_> = self.devices.write().unwrap().drain().collect();
+
+        let mut report = ShutdownReport::default();
+        for (id, device_lock) in devices {
+            shutdown_device(id, device_lock, &mut report).await;
+        }
+
+        self.sink.close(self.module_timeout).await;
+
+        report
+    }
+}
+
+/// `shutdown_device` stops device's module if it's running, closes its message handler
+/// and drops the module.
+pub(super) async fn shutdown_device<
+    S: IService + 'static,
+    M: IModule + Send + 'static,
+    K: ISink + 'static,
+>(
+    id: i32,
+    device_lock: Arc<Mutex<Device<S, M, K>>>,
+    report: &mut ShutdownReport,
+) {
+    report.devices += 1;
+
+    {
+        let device = device_lock.lock().await;
+
+        if let Some(ref msg_handler) = device.msg_handler {
+            match device.module.call("stop", |m| m.stop()).await {
+                Ok(_) => report.stopped += 1,
+                Err(err) => {
+                    report.failed += 1;
+                    logger::error_kv(
+                        "failed to stop device",
+                        kvs!("device_id" => kv_any!(id), "error" => kv_any!(err)),
+                    );
+                }
+            }
+
+            // Even if the module didn't stop, its data isn't saved anymore
+            msg_handler.close();
+        }
+    }
+
+    // A request which still holds the device drops the module when it finishes
+    let Ok(device) = Arc::try_unwrap(device_lock) else {
+        logger::warn_kv(
+            "device is in use, its module is dropped later",
+            kvs!("device_id" => kv_any!(id)),
+        );
+        return;
+    };
+
+    match device.into_inner().module.close().await {
+        Ok(_) => report.unloaded += 1,
+        Err(err) => logger::error_kv(
+            "failed to unload module",
+            kvs!("device_id" => kv_any!(id), "error" => kv_any!(err)),
+        ),
+    }
+}
+
+impl<S: IService, M: IModule, MF: IModuleFactory<M>, K: ISink> Clone for Controller<S, M, MF, K> {
     fn clone(&self) -> Self {
         Self {
             _module_factory: std::marker::PhantomData,
             svc: self.svc.clone(),
+            sink: self.sink.clone(),
             tokio_handle: self.tokio_handle.clone(),
             devices: self.devices.clone(),
+            module_timeout: self.module_timeout,
+            login_limiter: self.login_limiter.clone(),
         }
     }
 }
+
+/// `restart_module` starts device's module again if the device was running.
+async fn restart_module<S: IService + 'static, M: IModule + Send + 'static, K: ISink + 'static>(
+    device: &Device<S, M, K>,
+) -> Result<(), ControllerError> {
+    if let Some(msg_handler) = device.msg_handler.clone() {
+        device
+            .module
+            .call("start", move |m| m.start(msg_handler))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// `check_sensors_compatible` checks that a new module declares exactly the same sensors
+/// as the ones that device's sensor tables were created for.
+fn check_sensors_compatible(current: &[SensorInfo], new: &[Sensor]) -> Result<(), ControllerError> {
+    if current.len() != new.len() {
+        return Err(ControllerError::IncorrectPayload(format!(
+            "module declares {} sensor(s), but device has {}",
+            new.len(),
+            current.len()
+        )));
+    }
+
+    for sensor in current {
+        let new_sensor = new.iter().find(|s| s.name == sensor.name).ok_or_else(|| {
+            ControllerError::IncorrectPayload(format!(
+                "module doesn't declare sensor '{}'",
+                sensor.name
+            ))
+        })?;
+
+        if new_sensor.data_map.len() != sensor.data.len() {
+            return Err(ControllerError::IncorrectPayload(format!(
+                "sensor '{}' has a different set of data types",
+                sensor.name
+            )));
+        }
+
+        for data in sensor.data.iter() {
+            match new_sensor.data_map.get(&data.name) {
+                Some(new_data) if new_data.typ == data.typ => {}
+                _ => {
+                    return Err(ControllerError::IncorrectPayload(format!(
+                        "data '{}' of sensor '{}' is missing or has a different type",
+                        data.name, sensor.name
+                    )))
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `ingest_row_to_msg` checks that the row matches one of device's sensors
+/// and converts its values to the types of sensor's fields.
+pub(super) fn ingest_row_to_msg(
+    sensors: &[SensorInfo],
+    row: IngestRow,
+) -> Result<SensorMsg, String> {
+    let sensor = sensors
+        .iter()
+        .find(|s| s.name == row.sensor)
+        .ok_or_else(|| format!("unknown sensor '{}'", row.sensor))?;
+
+    let mut data = Vec::with_capacity(row.data.len() + 1);
+    for (name, value) in row.data.iter() {
+        let entry = sensor
+            .data
+            .iter()
+            .find(|e| e.name == *name)
+            .ok_or_else(|| format!("unknown field '{name}' of sensor '{}'", sensor.name))?;
+
+        if value.is_null() {
+            continue;
+        }
+
+        let value = SensorDataTypeValue::from_json(&entry.typ, value).ok_or_else(|| {
+            format!(
+                "value '{value}' of field '{name}' can't be converted to {:?}",
+                entry.typ
+            )
+        })?;
+
+        data.push(SensorData {
+            name: name.clone(),
+            data: value,
+        });
+    }
+
+    for entry in sensor.data.iter() {
+        if data.iter().any(|d| d.name == entry.name) {
+            continue;
+        }
+
+        // Only the timestamp may be omitted: every field of a sensor is required to be saved
+        if entry.name == INGEST_TIMESTAMP_FIELD && entry.typ == SensorDataType::Timestamp {
+            data.push(SensorData {
+                name: INGEST_TIMESTAMP_FIELD.to_string(),
+                data: SensorDataTypeValue::Timestamp(chrono::Utc::now().naive_utc()),
+            });
+        } else {
+            return Err(format!("value of field '{}' is missing", entry.name));
+        }
+    }
+
+    Ok(SensorMsg {
+        name: sensor.name.clone(),
+        data,
+    })
+}
+
+/// `conf_summary` describes device's conf for the audit log. Only ids of conf entries
+/// are kept, because their values may contain credentials
+fn conf_summary(conf: &[ConfEntry]) -> serde_json::Value {
+    json!({ "conf_ids": conf.iter().map(|entry| entry.id).collect::<Vec<_>>() })
+}
+
+/// `validate_command` checks that the command is supported by the device
+/// and its arguments match the ones declared by the module.
+pub(super) fn validate_command(
+    infos: &[CommandInfo],
+    cmd: &Command,
+) -> Result<(), ControllerError> {
+    let info = infos
+        .iter()
+        .find(|info| info.name == cmd.name)
+        .ok_or_else(|| {
+            ControllerError::IncorrectPayload(format!("unknown command '{}'", cmd.name))
+        })?;
+
+    for arg in cmd.args.iter() {
+        let arg_info = info
+            .args
+            .iter()
+            .find(|arg_info| arg_info.name == arg.name)
+            .ok_or_else(|| {
+                ControllerError::IncorrectPayload(format!("unknown argument '{}'", arg.name))
+            })?;
+
+        if cmd.args.iter().filter(|a| a.name == arg.name).count() > 1 {
+            return Err(ControllerError::IncorrectPayload(format!(
+                "argument '{}' is given more than once",
+                arg.name
+            )));
+        }
+
+        match arg.data {
+            Some(ref data) => {
+                if data.typ() != arg_info.typ {
+                    return Err(ControllerError::IncorrectPayload(format!(
+                        "argument '{}' must be of type '{:?}'",
+                        arg.name, arg_info.typ
+                    )));
+                }
+
+                if let SensorDataTypeValue::String(ref v) | SensorDataTypeValue::JSON(ref v) = data
+                {
+                    if v.contains('\0') {
+                        return Err(ControllerError::IncorrectPayload(format!(
+                            "argument '{}' contains a nul byte",
+                            arg.name
+                        )));
+                    }
+                }
+            }
+            None => {
+                if arg_info.required {
+                    return Err(ControllerError::IncorrectPayload(format!(
+                        "argument '{}' is required",
+                        arg.name
+                    )));
+                }
+            }
+        }
+    }
+
+    for arg_info in info.args.iter().filter(|arg_info| arg_info.required) {
+        if !cmd.args.iter().any(|arg| arg.name == arg_info.name) {
+            return Err(ControllerError::IncorrectPayload(format!(
+                "argument '{}' is required",
+                arg_info.name
+            )));
+        }
+    }
+
+    Ok(())
+}
@@ -10,6 +10,12 @@ pub enum ErrorType {
     AlreadyExists,
     FailedPrecondition,
     Timeout,
+    /// Request has no valid credentials
+    Unauthenticated,
+    /// Authenticated user isn't allowed to perform the operation
+    PermissionDenied,
+    /// Too many requests were made, e.g. failed logins. It may be retried later
+    ResourceExhausted,
     /// E.g. connection lost, disk corruption, etc.
     IO,
 }
@@ -0,0 +1,244 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+
+use crate::logger;
+use crate::{kv_any, kvs};
+
+use super::error::{CommonError, ErrorType};
+use super::interface::module::{IModule, IModuleFactory};
+use super::model::DeviceID;
+
+type Job<M> = Box<dyn FnOnce(&mut M) + Send>;
+
+/// States of a single call. The caller and the module's thread agree on whether
+/// the call has timed out by swapping the state from `CALL_PENDING`
+const CALL_PENDING: u8 = 0;
+const CALL_DONE: u8 = 1;
+const CALL_TIMED_OUT: u8 = 2;
+
+/// `ModuleExecutor` owns device's module and runs every call to it on a dedicated thread.
+///
+/// A caller awaits the result no longer than the configured timeout, so a hanging module
+/// doesn't block the async runtime. If a call overruns, the device is marked as faulted and
+/// all the following calls fail immediately until the hanging call returns.
+pub struct ModuleExecutor<M: IModule> {
+    device_id: DeviceID,
+    jobs: mpsc::Sender<Job<M>>,
+    /// Closed when the module's thread has dropped the module and exited
+    done: oneshot::Receiver<()>,
+    faulted: Arc<AtomicBool>,
+    timeout: Duration,
+}
+
+impl<M: IModule + Send + 'static> ModuleExecutor<M> {
+    /// `create` creates a module with `MF` on a new thread which then serves calls to the module.
+    pub async fn create<MF: IModuleFactory<M> + 'static>(
+        device_id: DeviceID,
+        mod_path: PathBuf,
+        data_dir: PathBuf,
+        timeout: Duration,
+    ) -> Result<Self, CommonError> {
+        let (jobs_tx, jobs_rx) = mpsc::channel::<Job<M>>();
+        let (res_tx, res_rx) = oneshot::channel();
+        let (done_tx, done_rx) = oneshot::channel::<()>();
+
+        thread::Builder::new()
+            .name(format!("module-{device_id}"))
+            .spawn(move || {
+                // Declared before the module, so it's dropped after the module
+                let _done = done_tx;
+                let module = MF::create_module(mod_path, data_dir).map_err(CallError::from);
+                let mut module = match module {
+                    Ok(m) => {
+                        if res_tx.send(Ok(())).is_err() {
+                            return;
+                        }
+                        m
+                    }
+                    Err(err) => {
+                        let _ = res_tx.send(Err(err));
+                        return;
+                    }
+                };
+
+                for job in jobs_rx {
+                    job(&mut module);
+                }
+            })
+            .map_err(|err| {
+                CommonError::new(ErrorType::Internal, "failed to spawn module thread")
+                    .with_source(err)
+            })?;
+
+        let executor = Self {
+            device_id,
+            jobs: jobs_tx,
+            done: done_rx,
+            faulted: Arc::new(AtomicBool::new(false)),
+            timeout,
+        };
+
+        // Nobody waits for the module after a timeout: the executor is dropped
+        let state = AtomicU8::new(CALL_PENDING);
+        executor
+            .wait("create_module", res_rx, &state)
+            .await?
+            .map_err(|err| err.into())
+            .map(|_| executor)
+    }
+
+    /// `call` runs `f` on the module's thread and waits for its result.
+    pub async fn call<T, F>(&self, name: &'static str, f: F) -> Result<T, CommonError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut M) -> Result<T, CommonError> + Send + 'static,
+    {
+        if self.is_faulted() {
+            return Err(CommonError::new(
+                ErrorType::FailedPrecondition,
+                "device is faulted: a previous module call has not returned yet",
+            ));
+        }
+
+        let (res_tx, res_rx) = oneshot::channel();
+        let state = Arc::new(AtomicU8::new(CALL_PENDING));
+        let job_state = state.clone();
+        let faulted = self.faulted.clone();
+        let device_id = self.device_id;
+
+        self.jobs
+            .send(Box::new(move |m: &mut M| {
+                let _ = res_tx.send(f(m).map_err(CallError::from));
+
+                // The caller has given up on the call, so it's the one the device is faulted by
+                let timed_out = job_state
+                    .compare_exchange(CALL_PENDING, CALL_DONE, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_err();
+                if timed_out {
+                    faulted.store(false, Ordering::SeqCst);
+                    logger::info_kv(
+                        "module call returned after timeout, device is not faulted anymore",
+                        kvs!("device_id" => kv_any!(device_id), "call" => kv_any!(name)),
+                    );
+                }
+            }))
+            .map_err(|_| CommonError::new(ErrorType::Internal, "module thread has stopped"))?;
+
+        self.wait(name, res_rx, &state)
+            .await?
+            .map_err(|err| err.into())
+    }
+
+    /// `close` stops serving calls and waits until the module is dropped on its thread,
+    /// so a library module is destroyed when it returns. The wait is limited by the timeout.
+    pub async fn close(self) -> Result<(), CommonError> {
+        let Self {
+            device_id,
+            jobs,
+            done,
+            timeout,
+            ..
+        } = self;
+        drop(jobs);
+
+        match tokio::time::timeout(timeout, done).await {
+            Err(_) => {
+                logger::error_kv(
+                    "module wasn't dropped in time",
+                    kvs!("device_id" => kv_any!(device_id)),
+                );
+
+                Err(CommonError::new(
+                    ErrorType::Timeout,
+                    format!("module wasn't dropped after {timeout:?}"),
+                ))
+            }
+            Ok(_) => Ok(()),
+        }
+    }
+
+    pub fn is_faulted(&self) -> bool {
+        self.faulted.load(Ordering::SeqCst)
+    }
+
+    async fn wait<T>(
+        &self,
+        name: &'static str,
+        mut res_rx: oneshot::Receiver<Result<T, CallError>>,
+        state: &AtomicU8,
+    ) -> Result<Result<T, CallError>, CommonError> {
+        let stopped = || {
+            CommonError::new(
+                ErrorType::Internal,
+                format!("module thread has stopped during '{name}' call"),
+            )
+        };
+
+        match tokio::time::timeout(self.timeout, &mut res_rx).await {
+            Ok(res) => res.map_err(|_| stopped()),
+            Err(_) => {
+                // The flag is set before the call is marked as timed out, so the module's
+                // thread, which clears the flag when the call returns, can't clear it earlier
+                self.faulted.store(true, Ordering::SeqCst);
+
+                let returned = state
+                    .compare_exchange(
+                        CALL_PENDING,
+                        CALL_TIMED_OUT,
+                        Ordering::SeqCst,
+                        Ordering::SeqCst,
+                    )
+                    .is_err();
+                if returned {
+                    // The call has returned right after the timeout
+                    self.faulted.store(false, Ordering::SeqCst);
+                    return res_rx.try_recv().map_err(|_| stopped());
+                }
+
+                logger::error_kv(
+                    "module call timed out, device is faulted",
+                    kvs!("device_id" => kv_any!(self.device_id), "call" => kv_any!(name)),
+                );
+
+                Err(CommonError::new(
+                    ErrorType::Timeout,
+                    format!("module call '{name}' timed out after {:?}", self.timeout),
+                ))
+            }
+        }
+    }
+}
+
+/// `CallError` carries [`CommonError`] between threads: its source isn't `Send`,
+/// so the source is passed as a message.
+pub(super) struct CallError {
+    error_type: ErrorType,
+    msg: String,
+    source: Option<String>,
+}
+
+impl From<CommonError> for CallError {
+    fn from(err: CommonError) -> Self {
+        Self {
+            error_type: err.error_type,
+            msg: err.msg,
+            source: err.source.map(|e| e.to_string()),
+        }
+    }
+}
+
+impl From<CallError> for CommonError {
+    fn from(err: CallError) -> Self {
+        let res = CommonError::new(err.error_type, err.msg);
+
+        match err.source {
+            Some(source) => res.with_source(source),
+            None => res,
+        }
+    }
+}
@@ -0,0 +1,524 @@
+use std::sync::Arc;
+
+use chrono::{SecondsFormat, TimeZone};
+use parquet::basic::{LogicalType, Repetition, TimeUnit, Type as PhysicalType};
+use parquet::data_type::{ByteArray, ByteArrayType, DoubleType, FloatType, Int32Type, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::types::Type as SchemaType;
+use tokio::sync::mpsc;
+
+use super::error::{CommonError, ControllerError, ErrorType};
+use super::interface::service::IService;
+use super::model::*;
+
+/// Number of rows fetched from the database at once. Every batch is a row group in Parquet
+const EXPORT_BATCH_SIZE: usize = 10000;
+/// Number of fetched batches waiting to be encoded
+const EXPORT_QUEUE_LEN: usize = 2;
+/// Field the range applies to unless another one is given
+const DEFAULT_TIME_FIELD: &str = "timestamp";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    /// CSV with a header
+    Csv,
+    /// A JSON object per line
+    JsonLines,
+    /// Apache Parquet with a row group per fetched batch
+    Parquet,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "csv" => Some(Self::Csv),
+            "jsonl" => Some(Self::JsonLines),
+            "parquet" => Some(Self::Parquet),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv",
+            Self::JsonLines => "application/jsonl",
+            Self::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::JsonLines => "jsonl",
+            Self::Parquet => "parquet",
+        }
+    }
+}
+
+pub struct ExportPayload {
+    pub device_id: i32,
+    pub sensor: String,
+    pub format: ExportFormat,
+    /// Fields to export in the given order. All sensor's fields are exported if it's empty
+    pub fields: Vec<String>,
+    /// Timestamp field which the range and the order of rows apply to.
+    /// It's `timestamp` or the first timestamp field of the sensor if not given
+    pub time_field: Option<String>,
+    /// Start of the range in UTC, inclusive
+    pub from: Option<chrono::NaiveDateTime>,
+    /// End of the range in UTC, exclusive
+    pub to: Option<chrono::NaiveDateTime>,
+    /// Timezone of timestamps in CSV and JSON Lines. Parquet always stores them in UTC
+    pub timezone: chrono_tz::Tz,
+}
+
+/// `SensorDataExport` is a validated export of sensor data ready to be run
+pub struct SensorDataExport {
+    device_id: DeviceID,
+    sensor: String,
+    format: ExportFormat,
+    columns: Vec<SensorDataEntry>,
+    range: SensorDataRange,
+    timezone: chrono_tz::Tz,
+}
+
+/// `prepare_export` checks that the device, its sensor and the fields exist
+/// so that the export doesn't fail before any data is written.
+///
+/// Like the import, it doesn't need devices' modules.
+pub fn prepare_export<S: IService>(
+    svc: &S,
+    payload: ExportPayload,
+) -> Result<SensorDataExport, ControllerError> {
+    let device_id = svc
+        .get_device_ids()?
+        .into_iter()
+        .find(|id| id.get_raw() == payload.device_id)
+        .ok_or(ControllerError::UnknownDevice(payload.device_id))?;
+
+    let sensor = svc
+        .get_device_sensor_info(device_id)?
+        .into_iter()
+        .find(|s| s.name == payload.sensor)
+        .ok_or_else(|| {
+            ControllerError::IncorrectPayload(format!("unknown sensor '{}'", payload.sensor))
+        })?;
+
+    let columns = if payload.fields.is_empty() {
+        sensor.data.clone()
+    } else {
+        let mut columns: Vec<SensorDataEntry> = Vec::with_capacity(payload.fields.len());
+        for name in payload.fields.iter() {
+            let entry = sensor
+                .data
+                .iter()
+                .find(|e| e.name == *name)
+                .ok_or_else(|| {
+                    ControllerError::IncorrectPayload(format!(
+                        "unknown field '{name}' of sensor '{}'",
+                        sensor.name
+                    ))
+                })?;
+            if columns.iter().any(|c| c.name == *name) {
+                return Err(ControllerError::IncorrectPayload(format!(
+                    "field '{name}' is given more than once"
+                )));
+            }
+
+            columns.push(entry.clone());
+        }
+
+        columns
+    };
+
+    let time_field = match payload.time_field {
+        Some(ref name) => sensor
+            .data
+            .iter()
+            .find(|e| e.name == *name && e.typ == SensorDataType::Timestamp)
+            .ok_or_else(|| {
+                ControllerError::IncorrectPayload(format!(
+                    "sensor '{}' doesn't have timestamp field '{name}'",
+                    sensor.name
+                ))
+            })?,
+        None => sensor
+            .data
+            .iter()
+            .filter(|e| e.typ == SensorDataType::Timestamp)
+            .min_by_key(|e| e.name != DEFAULT_TIME_FIELD)
+            .ok_or_else(|| {
+                ControllerError::IncorrectPayload(format!(
+                    "sensor '{}' doesn't have timestamp fields",
+                    sensor.name
+                ))
+            })?,
+    };
+
+    if let (Some(from), Some(to)) = (payload.from, payload.to) {
+        if from >= to {
+            return Err(ControllerError::IncorrectPayload(
+                "start of the range must be before its end".into(),
+            ));
+        }
+    }
+
+    Ok(SensorDataExport {
+        device_id,
+        sensor: sensor.name,
+        format: payload.format,
+        columns,
+        range: SensorDataRange {
+            field: time_field.name.clone(),
+            from: payload.from,
+            to: payload.to,
+        },
+        timezone: payload.timezone,
+    })
+}
+
+impl SensorDataExport {
+    pub fn device_id(&self) -> DeviceID {
+        self.device_id
+    }
+
+    pub fn sensor(&self) -> &str {
+        &self.sensor
+    }
+
+    pub fn format(&self) -> ExportFormat {
+        self.format
+    }
+
+    /// `run` reads the data from the service batch by batch, encodes it and sends the chunks
+    /// of the file to `out`. The export stops without an error if `out` is closed.
+    /// Returns the number of exported rows.
+    ///
+    /// On error, the end of the file is not written, so a partial export can't be taken
+    /// for a complete one.
+    pub async fn run<S: IService>(
+        self,
+        svc: &S,
+        out: mpsc::Sender<Vec<u8>>,
+    ) -> Result<usize, ControllerError> {
+        let mut encoder = Encoder::new(self.format, &self.columns, self.timezone)?;
+        if out.send(encoder.begin()).await.is_err() {
+            return Ok(0);
+        }
+
+        let (tx, rx) = mpsc::channel(EXPORT_QUEUE_LEN);
+        let fields = self.columns.iter().map(|c| c.name.clone()).collect();
+
+        let read = svc.export_sensor_data(
+            self.device_id,
+            self.sensor.clone(),
+            fields,
+            self.range,
+            EXPORT_BATCH_SIZE,
+            tx,
+        );
+        let write = async {
+            // The receiver is moved here, so it's dropped as soon as writing stops
+            let mut rx = rx;
+            let mut count = 0;
+            while let Some(batch) = rx.recv().await {
+                count += batch.len();
+
+                let chunk = encoder.encode(&batch)?;
+                if out.send(chunk).await.is_err() {
+                    // Dropping the receiver stops reading
+                    return Ok((count, false));
+                }
+            }
+
+            Ok::<_, ControllerError>((count, true))
+        };
+
+        let (read_res, write_res) = tokio::join!(read, write);
+        read_res?;
+        let (count, open) = write_res?;
+
+        if open {
+            let _ = out.send(encoder.finish()?).await;
+        }
+
+        Ok(count)
+    }
+}
+
+pub(super) enum Encoder<'a> {
+    Csv(&'a [SensorDataEntry], chrono_tz::Tz),
+    JsonLines(&'a [SensorDataEntry], chrono_tz::Tz),
+    Parquet(&'a [SensorDataEntry], Box<SerializedFileWriter<Vec<u8>>>),
+}
+
+impl<'a> Encoder<'a> {
+    pub(super) fn new(
+        format: ExportFormat,
+        columns: &'a [SensorDataEntry],
+        tz: chrono_tz::Tz,
+    ) -> Result<Self, ControllerError> {
+        Ok(match format {
+            ExportFormat::Csv => Self::Csv(columns, tz),
+            ExportFormat::JsonLines => Self::JsonLines(columns, tz),
+            ExportFormat::Parquet => {
+                let schema = parquet_schema(columns).map_err(encode_err)?;
+                let props = Arc::new(WriterProperties::builder().build());
+                let writer =
+                    SerializedFileWriter::new(Vec::new(), schema, props).map_err(encode_err)?;
+
+                Self::Parquet(columns, Box::new(writer))
+            }
+        })
+    }
+
+    /// `begin` returns the beginning of the file
+    pub(super) fn begin(&mut self) -> Vec<u8> {
+        match self {
+            Self::Csv(columns, _) => {
+                let header: Vec<_> = columns.iter().map(|c| c.name.as_str()).collect();
+                csv_record(&header)
+            }
+            Self::JsonLines(..) => Vec::new(),
+            // The writer has already written the magic number
+            Self::Parquet(_, writer) => std::mem::take(writer.inner_mut()),
+        }
+    }
+
+    pub(super) fn encode(&mut self, rows: &[SensorDataList]) -> Result<Vec<u8>, ControllerError> {
+        for row in rows.iter() {
+            if row.len() != self.columns().len() {
+                return Err(encode_err("unexpected number of columns"));
+            }
+        }
+
+        Ok(match self {
+            Self::Csv(_, tz) => rows
+                .iter()
+                .flat_map(|row| {
+                    let record: Vec<_> = row.iter().map(|v| text_value(&v.data, tz)).collect();
+                    csv_record(&record)
+                })
+                .collect(),
+            Self::JsonLines(columns, tz) => {
+                // Objects are written by hand to keep the order of the fields
+                let mut res = Vec::new();
+                for row in rows.iter() {
+                    res.push(b'{');
+                    for (i, (c, v)) in columns.iter().zip(row.iter()).enumerate() {
+                        if i > 0 {
+                            res.push(b',');
+                        }
+                        serde_json::to_writer(&mut res, &c.name).map_err(encode_err)?;
+                        res.push(b':');
+                        serde_json::to_writer(&mut res, &json_value(&v.data, tz))
+                            .map_err(encode_err)?;
+                    }
+                    res.extend_from_slice(b"}\n");
+                }
+
+                res
+            }
+            Self::Parquet(columns, writer) => {
+                write_row_group(writer, columns, rows).map_err(encode_err)?;
+                std::mem::take(writer.inner_mut())
+            }
+        })
+    }
+
+    /// `finish` returns the end of the file
+    pub(super) fn finish(self) -> Result<Vec<u8>, ControllerError> {
+        match self {
+            Self::Csv(..) | Self::JsonLines(..) => Ok(Vec::new()),
+            Self::Parquet(_, writer) => writer.into_inner().map_err(encode_err),
+        }
+    }
+
+    fn columns(&self) -> &[SensorDataEntry] {
+        match self {
+            Self::Csv(columns, _) | Self::JsonLines(columns, _) | Self::Parquet(columns, _) => {
+                columns
+            }
+        }
+    }
+}
+
+fn encode_err<E: Into<Box<dyn std::error::Error>>>(err: E) -> ControllerError {
+    CommonError::new(ErrorType::Internal, "failed to encode sensor data")
+        .with_source(err)
+        .into()
+}
+
+fn csv_record<T: AsRef<[u8]>>(record: &[T]) -> Vec<u8> {
+    let mut w = csv::Writer::from_writer(Vec::new());
+    // Writing to a vector can't fail
+    let _ = w.write_record(record);
+
+    w.into_inner().unwrap_or_default()
+}
+
+fn format_timestamp(v: &chrono::NaiveDateTime, tz: &chrono_tz::Tz) -> String {
+    tz.from_utc_datetime(v)
+        .to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+/// `text_value` formats a value for CSV. JSON is written as is
+fn text_value(v: &SensorDataTypeValue, tz: &chrono_tz::Tz) -> String {
+    match v {
+        SensorDataTypeValue::Int16(v) => v.to_string(),
+        SensorDataTypeValue::Int32(v) => v.to_string(),
+        SensorDataTypeValue::Int64(v) => v.to_string(),
+        SensorDataTypeValue::Float32(v) => v.to_string(),
+        SensorDataTypeValue::Float64(v) => v.to_string(),
+        SensorDataTypeValue::Timestamp(v) => format_timestamp(v, tz),
+        SensorDataTypeValue::String(v) | SensorDataTypeValue::JSON(v) => v.clone(),
+    }
+}
+
+/// `json_value` converts a value for JSON Lines. Floats which aren't finite become `null`
+fn json_value(v: &SensorDataTypeValue, tz: &chrono_tz::Tz) -> serde_json::Value {
+    use serde_json::Value;
+
+    match v {
+        SensorDataTypeValue::Int16(v) => Value::from(*v),
+        SensorDataTypeValue::Int32(v) => Value::from(*v),
+        SensorDataTypeValue::Int64(v) => Value::from(*v),
+        SensorDataTypeValue::Float32(v) => Value::from(*v as f64),
+        SensorDataTypeValue::Float64(v) => Value::from(*v),
+        SensorDataTypeValue::Timestamp(v) => Value::String(format_timestamp(v, tz)),
+        SensorDataTypeValue::String(v) => Value::String(v.clone()),
+        SensorDataTypeValue::JSON(v) => {
+            serde_json::from_str(v).unwrap_or_else(|_| Value::String(v.clone()))
+        }
+    }
+}
+
+fn parquet_schema(columns: &[SensorDataEntry]) -> parquet::errors::Result<Arc<SchemaType>> {
+    let mut fields = Vec::with_capacity(columns.len());
+
+    for c in columns.iter() {
+        let (typ, logical) = match c.typ {
+            SensorDataType::Int16 => (
+                PhysicalType::INT32,
+                Some(LogicalType::Integer {
+                    bit_width: 16,
+                    is_signed: true,
+                }),
+            ),
+            SensorDataType::Int32 => (PhysicalType::INT32, None),
+            SensorDataType::Int64 => (PhysicalType::INT64, None),
+            SensorDataType::Float32 => (PhysicalType::FLOAT, None),
+            SensorDataType::Float64 => (PhysicalType::DOUBLE, None),
+            SensorDataType::Timestamp => (
+                PhysicalType::INT64,
+                Some(LogicalType::Timestamp {
+                    is_adjusted_to_u_t_c: true,
+                    unit: TimeUnit::MICROS(Default::default()),
+                }),
+            ),
+            SensorDataType::String => (PhysicalType::BYTE_ARRAY, Some(LogicalType::String)),
+            SensorDataType::JSON => (PhysicalType::BYTE_ARRAY, Some(LogicalType::Json)),
+        };
+
+        // Sensor data can't contain nulls
+        let field = SchemaType::primitive_type_builder(&c.name, typ)
+            .with_repetition(Repetition::REQUIRED)
+            .with_logical_type(logical)
+            .build()?;
+        fields.push(Arc::new(field));
+    }
+
+    Ok(Arc::new(
+        SchemaType::group_type_builder("sensor_data")
+            .with_fields(fields)
+            .build()?,
+    ))
+}
+
+fn write_row_group(
+    writer: &mut SerializedFileWriter<Vec<u8>>,
+    columns: &[SensorDataEntry],
+    rows: &[SensorDataList],
+) -> parquet::errors::Result<()> {
+    let mut rg = writer.next_row_group()?;
+
+    for (i, c) in columns.iter().enumerate() {
+        let mut col = rg
+            .next_column()?
+            .ok_or_else(|| parquet::errors::ParquetError::General("no column writer".into()))?;
+
+        match c.typ {
+            SensorDataType::Int16 | SensorDataType::Int32 => {
+                let values = column_values(rows, i, |v| match v {
+                    SensorDataTypeValue::Int16(v) => Some(*v as i32),
+                    SensorDataTypeValue::Int32(v) => Some(*v),
+                    _ => None,
+                })?;
+                col.typed::<Int32Type>().write_batch(&values, None, None)?;
+            }
+            SensorDataType::Int64 => {
+                let values = column_values(rows, i, |v| match v {
+                    SensorDataTypeValue::Int64(v) => Some(*v),
+                    _ => None,
+                })?;
+                col.typed::<Int64Type>().write_batch(&values, None, None)?;
+            }
+            SensorDataType::Float32 => {
+                let values = column_values(rows, i, |v| match v {
+                    SensorDataTypeValue::Float32(v) => Some(*v),
+                    _ => None,
+                })?;
+                col.typed::<FloatType>().write_batch(&values, None, None)?;
+            }
+            SensorDataType::Float64 => {
+                let values = column_values(rows, i, |v| match v {
+                    SensorDataTypeValue::Float64(v) => Some(*v),
+                    _ => None,
+                })?;
+                col.typed::<DoubleType>().write_batch(&values, None, None)?;
+            }
+            SensorDataType::Timestamp => {
+                let values = column_values(rows, i, |v| match v {
+                    SensorDataTypeValue::Timestamp(v) => Some(v.and_utc().timestamp_micros()),
+                    _ => None,
+                })?;
+                col.typed::<Int64Type>().write_batch(&values, None, None)?;
+            }
+            SensorDataType::String | SensorDataType::JSON => {
+                let values = column_values(rows, i, |v| match v {
+                    SensorDataTypeValue::String(v) | SensorDataTypeValue::JSON(v) => {
+                        Some(ByteArray::from(v.as_str()))
+                    }
+                    _ => None,
+                })?;
+                col.typed::<ByteArrayType>()
+                    .write_batch(&values, None, None)?;
+            }
+        }
+
+        col.close()?;
+    }
+
+    rg.close()?;
+
+    Ok(())
+}
+
+/// `column_values` collects values of the `i`-th column converted by `f`
+fn column_values<T, F: Fn(&SensorDataTypeValue) -> Option<T>>(
+    rows: &[SensorDataList],
+    i: usize,
+    f: F,
+) -> parquet::errors::Result<Vec<T>> {
+    rows.iter()
+        .map(|row| {
+            f(&row[i].data).ok_or_else(|| {
+                parquet::errors::ParquetError::General(format!(
+                    "unexpected type of column '{}'",
+                    row[i].name
+                ))
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,545 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read};
+
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use super::error::{CommonError, ControllerError, ErrorType};
+use super::interface::service::IService;
+use super::model::*;
+
+/// Number of valid rows saved by a single call to the service
+const IMPORT_BATCH_SIZE: usize = 1000;
+/// Number of parsed batches waiting to be saved
+const IMPORT_CHANNEL_LEN: usize = 2;
+/// Maximum number of row errors in a report. The rest of the errors are only counted
+const MAX_REPORTED_ERRORS: usize = 1000;
+/// Field which gets the timestamp of a line in line protocol unless it's mapped
+const DEFAULT_TIME_FIELD: &str = "timestamp";
+/// Key of the line protocol timestamp in [`ImportPayload::mapping`]
+const TIME_KEY: &str = "time";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImportFormat {
+    /// CSV with a header. Columns are matched to sensor's fields by their names
+    Csv,
+    /// InfluxDB line protocol. Measurement must be equal to the sensor's name,
+    /// tags and fields are matched to sensor's fields by their keys
+    LineProtocol,
+}
+
+impl ImportFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "csv" => Some(Self::Csv),
+            "line_protocol" => Some(Self::LineProtocol),
+            _ => None,
+        }
+    }
+}
+
+/// TimestampPrecision is a precision of timestamps in line protocol
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TimestampPrecision {
+    #[default]
+    Nanoseconds,
+    Microseconds,
+    Milliseconds,
+    Seconds,
+}
+
+impl TimestampPrecision {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ns" => Some(Self::Nanoseconds),
+            "us" => Some(Self::Microseconds),
+            "ms" => Some(Self::Milliseconds),
+            "s" => Some(Self::Seconds),
+            _ => None,
+        }
+    }
+
+    fn to_datetime(self, ts: i64) -> Option<chrono::NaiveDateTime> {
+        let per_sec = match self {
+            Self::Nanoseconds => 1_000_000_000,
+            Self::Microseconds => 1_000_000,
+            Self::Milliseconds => 1_000,
+            Self::Seconds => 1,
+        };
+        let nanos = ts.rem_euclid(per_sec) * (1_000_000_000 / per_sec);
+
+        chrono::DateTime::from_timestamp(ts.div_euclid(per_sec), nanos as u32)
+            .map(|v| v.naive_utc())
+    }
+}
+
+pub struct ImportPayload {
+    pub device_id: i32,
+    pub sensor: String,
+    pub format: ImportFormat,
+    /// Sensor's field names by CSV columns or line protocol keys. Unmapped columns and keys
+    /// are matched to the fields with the same names. The line protocol timestamp has `time` key
+    pub mapping: HashMap<String, String>,
+    pub precision: TimestampPrecision,
+    /// Validate the data without saving it
+    pub dry_run: bool,
+}
+
+pub struct ImportRowError {
+    /// Line in the file starting from 1
+    pub line: u64,
+    pub msg: String,
+}
+
+pub struct ImportReport {
+    pub dry_run: bool,
+    /// Number of data rows in the file
+    pub total: usize,
+    /// Number of saved rows. In dry-run mode, it's the number of valid rows
+    pub imported: usize,
+    pub invalid: usize,
+    /// Errors of the first invalid rows
+    pub errors: Vec<ImportRowError>,
+}
+
+pub(super) type ImportRow = (u64, Result<Vec<SensorData>, String>);
+
+/// `import_sensor_data` reads rows of device's sensor from a file in the given format,
+/// validates them and saves in batches. Every row must contain values of all sensor's fields.
+/// Invalid rows are skipped and reported.
+///
+/// The file is read and parsed on a blocking thread. Every batch is saved in its own
+/// transaction, so if saving fails, the error tells how many rows were saved before.
+///
+/// It doesn't need devices' modules, so it's available without [`Controller`](super::Controller).
+pub async fn import_sensor_data<S: IService, R: Read + Send + 'static>(
+    svc: &S,
+    payload: ImportPayload,
+    reader: R,
+) -> Result<ImportReport, ControllerError> {
+    let device_id = svc
+        .get_device_ids()?
+        .into_iter()
+        .find(|id| id.get_raw() == payload.device_id)
+        .ok_or(ControllerError::UnknownDevice(payload.device_id))?;
+
+    let sensor = svc
+        .get_device_sensor_info(device_id)?
+        .into_iter()
+        .find(|s| s.name == payload.sensor)
+        .ok_or_else(|| {
+            ControllerError::IncorrectPayload(format!("unknown sensor '{}'", payload.sensor))
+        })?;
+
+    let (tx, mut rx) = mpsc::channel(IMPORT_CHANNEL_LEN);
+    let read = tokio::task::spawn_blocking(move || read_batches(&payload, &sensor, reader, tx));
+
+    // Reading stops when the receiver is dropped
+    let mut saved = 0;
+    while let Some(batch) = rx.recv().await {
+        let len = batch.len();
+        if let Err(mut err) = svc.save_sensor_data_batch(device_id, batch).await {
+            err.msg = format!("{}: {saved} row(s) were imported before it", err.msg);
+            return Err(err.into());
+        }
+
+        saved += len;
+    }
+
+    read.await
+        .map_err(|err| {
+            CommonError::new(ErrorType::Internal, "failed to read import file").with_source(err)
+        })?
+        .map_err(ControllerError::IncorrectPayload)
+}
+
+/// `read_batches` reads rows from `reader` and sends valid ones by batches to `batches`.
+/// Nothing is sent in dry-run mode. It fails if the file can't be parsed at all.
+fn read_batches<R: Read>(
+    payload: &ImportPayload,
+    sensor: &SensorInfo,
+    reader: R,
+    batches: mpsc::Sender<Vec<SensorMsg>>,
+) -> Result<ImportReport, String> {
+    let rows = import_rows(payload, sensor, reader)?;
+
+    let mut report = ImportReport {
+        dry_run: payload.dry_run,
+        total: 0,
+        imported: 0,
+        invalid: 0,
+        errors: Vec::new(),
+    };
+    let mut batch = Vec::new();
+
+    for (line, row) in rows {
+        report.total += 1;
+
+        match row {
+            Ok(data) => {
+                report.imported += 1;
+                if payload.dry_run {
+                    continue;
+                }
+
+                batch.push(SensorMsg {
+                    name: sensor.name.clone(),
+                    data,
+                });
+                if batch.len() == IMPORT_BATCH_SIZE
+                    && batches.blocking_send(std::mem::take(&mut batch)).is_err()
+                {
+                    // Saving has failed, and the error is returned by the receiver
+                    return Ok(report);
+                }
+            }
+            Err(msg) => {
+                report.invalid += 1;
+                if report.errors.len() < MAX_REPORTED_ERRORS {
+                    report.errors.push(ImportRowError { line, msg });
+                }
+            }
+        }
+    }
+
+    if !batch.is_empty() {
+        let _ = batches.blocking_send(batch);
+    }
+
+    Ok(report)
+}
+
+/// `import_rows` returns an iterator over rows of the file with their line numbers
+pub(super) fn import_rows<'a, R: Read + 'a>(
+    payload: &'a ImportPayload,
+    sensor: &'a SensorInfo,
+    reader: R,
+) -> Result<Box<dyn Iterator<Item = ImportRow> + 'a>, String> {
+    Ok(match payload.format {
+        ImportFormat::Csv => Box::new(CsvRows::new(reader, sensor, &payload.mapping)?),
+        ImportFormat::LineProtocol => Box::new(LineProtocolRows {
+            lines: BufReader::new(reader).lines().enumerate(),
+            sensor,
+            mapping: &payload.mapping,
+            precision: payload.precision,
+        }),
+    })
+}
+
+struct CsvRows<'a, R: Read> {
+    records: csv::StringRecordsIntoIter<R>,
+    /// Sensor's fields by column index
+    columns: Vec<&'a SensorDataEntry>,
+}
+
+impl<'a, R: Read> CsvRows<'a, R> {
+    fn new(
+        reader: R,
+        sensor: &'a SensorInfo,
+        mapping: &HashMap<String, String>,
+    ) -> Result<Self, String> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .flexible(true)
+            .from_reader(reader);
+
+        let headers = reader
+            .headers()
+            .map_err(|err| format!("failed to read CSV header: {err}"))?;
+
+        let mut columns: Vec<&SensorDataEntry> = Vec::with_capacity(headers.len());
+        for column in headers.iter() {
+            let field =
+                find_field(sensor, mapping, column).map_err(|err| format!("CSV header: {err}"))?;
+
+            if columns.iter().any(|c| c.name == field.name) {
+                return Err(format!(
+                    "CSV header: field '{}' is mapped more than once",
+                    field.name
+                ));
+            }
+
+            columns.push(field);
+        }
+
+        if let Some(field) = sensor
+            .data
+            .iter()
+            .find(|f| !columns.iter().any(|c| c.name == f.name))
+        {
+            return Err(format!("CSV header: no column for field '{}'", field.name));
+        }
+
+        Ok(Self {
+            records: reader.into_records(),
+            columns,
+        })
+    }
+}
+
+impl<R: Read> Iterator for CsvRows<'_, R> {
+    type Item = ImportRow;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = match self.records.next()? {
+            Ok(record) => record,
+            Err(err) => {
+                let line = err.position().map(|p| p.line()).unwrap_or_default();
+                return Some((line, Err(format!("invalid CSV row: {err}"))));
+            }
+        };
+        let line = record.position().map(|p| p.line()).unwrap_or_default();
+
+        if record.len() != self.columns.len() {
+            return Some((
+                line,
+                Err(format!(
+                    "row has {} column(s), but header has {}",
+                    record.len(),
+                    self.columns.len()
+                )),
+            ));
+        }
+
+        let res = self
+            .columns
+            .iter()
+            .zip(record.iter())
+            .map(|(field, cell)| {
+                if cell.is_empty() && field.typ != SensorDataType::String {
+                    return Err(format!("value of field '{}' is missing", field.name));
+                }
+
+                convert_value(field, &Value::String(cell.to_string()))
+            })
+            .collect();
+
+        Some((line, res))
+    }
+}
+
+struct LineProtocolRows<'a, R: Read> {
+    lines: std::iter::Enumerate<io::Lines<BufReader<R>>>,
+    sensor: &'a SensorInfo,
+    mapping: &'a HashMap<String, String>,
+    precision: TimestampPrecision,
+}
+
+impl<R: Read> Iterator for LineProtocolRows<'_, R> {
+    type Item = ImportRow;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (i, line) = self.lines.next()?;
+            let line_num = i as u64 + 1;
+
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => return Some((line_num, Err(format!("failed to read line: {err}")))),
+            };
+
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            return Some((line_num, self.parse(line)));
+        }
+    }
+}
+
+impl<R: Read> LineProtocolRows<'_, R> {
+    /// `parse` parses a line like `measurement,tag=value field=1.5,other="text" 1697630400000000000`
+    fn parse(&self, line: &str) -> Result<Vec<SensorData>, String> {
+        let parts: Vec<_> = split_unescaped(line, ' ')
+            .into_iter()
+            .filter(|p| !p.is_empty())
+            .collect();
+        if parts.len() < 2 || parts.len() > 3 {
+            return Err("line must contain measurement, fields and an optional timestamp".into());
+        }
+
+        let mut head = split_unescaped(parts[0], ',').into_iter();
+        let measurement = unescape(head.next().unwrap_or_default());
+        if measurement != self.sensor.name {
+            return Err(format!(
+                "measurement '{measurement}' doesn't match sensor '{}'",
+                self.sensor.name
+            ));
+        }
+
+        let mut values = Vec::new();
+        for tag in head {
+            let (k, v) = split_key_value(tag)?;
+            values.push((unescape(k), Value::String(unescape(v))));
+        }
+        for field in split_unescaped(parts[1], ',') {
+            let (k, v) = split_key_value(field)?;
+            values.push((unescape(k), field_value(v)?));
+        }
+
+        let mut data = Vec::with_capacity(values.len() + 1);
+        for (key, value) in values {
+            let field = find_field(self.sensor, self.mapping, &key)?;
+            if data.iter().any(|d: &SensorData| d.name == field.name) {
+                return Err(format!("field '{}' is set more than once", field.name));
+            }
+
+            data.push(convert_value(field, &value)?);
+        }
+
+        if let Some(ts) = parts.get(2) {
+            let name = self
+                .mapping
+                .get(TIME_KEY)
+                .map(String::as_str)
+                .unwrap_or(DEFAULT_TIME_FIELD);
+            let field = self
+                .sensor
+                .data
+                .iter()
+                .find(|e| e.name == name && e.typ == SensorDataType::Timestamp)
+                .ok_or_else(|| format!("sensor doesn't have timestamp field '{name}'"))?;
+            if data.iter().any(|d| d.name == field.name) {
+                return Err(format!("field '{}' is set more than once", field.name));
+            }
+
+            let ts = ts
+                .parse()
+                .ok()
+                .and_then(|ts| self.precision.to_datetime(ts))
+                .ok_or_else(|| format!("invalid timestamp '{ts}'"))?;
+            data.push(SensorData {
+                name: field.name.clone(),
+                data: SensorDataTypeValue::Timestamp(ts),
+            });
+        }
+
+        check_complete(self.sensor, data)
+    }
+}
+
+fn find_field<'a>(
+    sensor: &'a SensorInfo,
+    mapping: &HashMap<String, String>,
+    key: &str,
+) -> Result<&'a SensorDataEntry, String> {
+    let name = mapping.get(key).map(String::as_str).unwrap_or(key);
+
+    sensor.data.iter().find(|e| e.name == name).ok_or_else(|| {
+        format!(
+            "'{key}' doesn't match any field of sensor '{}'",
+            sensor.name
+        )
+    })
+}
+
+/// `convert_value` converts a value to the field's type. A string is taken as is for a JSON field
+/// if it contains valid JSON.
+fn convert_value(field: &SensorDataEntry, value: &Value) -> Result<SensorData, String> {
+    let data = match (&field.typ, value) {
+        (SensorDataType::JSON, Value::String(s)) if serde_json::from_str::<Value>(s).is_ok() => {
+            Some(SensorDataTypeValue::JSON(s.clone()))
+        }
+        _ => SensorDataTypeValue::from_json(&field.typ, value),
+    };
+
+    data.map(|data| SensorData {
+        name: field.name.clone(),
+        data,
+    })
+    .ok_or_else(|| {
+        format!(
+            "value {value} of field '{}' can't be converted to {:?}",
+            field.name, field.typ
+        )
+    })
+}
+
+/// `check_complete` checks that the row has values of all sensor's fields
+fn check_complete(sensor: &SensorInfo, data: Vec<SensorData>) -> Result<Vec<SensorData>, String> {
+    match sensor
+        .data
+        .iter()
+        .find(|f| !data.iter().any(|d| d.name == f.name))
+    {
+        Some(field) => Err(format!("value of field '{}' is missing", field.name)),
+        None => Ok(data),
+    }
+}
+
+/// `split_unescaped` splits a line protocol string by a separator which is neither escaped
+/// with a backslash nor quoted.
+fn split_unescaped(s: &str, sep: char) -> Vec<&str> {
+    let mut res = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    let mut quoted = false;
+
+    for (i, c) in s.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+
+        match c {
+            '\\' => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == sep && !quoted => {
+                res.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    res.push(&s[start..]);
+
+    res
+}
+
+fn split_key_value(s: &str) -> Result<(&str, &str), String> {
+    match split_unescaped(s, '=')[..] {
+        [k, v] if !k.is_empty() && !v.is_empty() => Ok((k, v)),
+        _ => Err(format!("invalid key-value pair '{s}'")),
+    }
+}
+
+fn unescape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => res.extend(chars.next()),
+            c => res.push(c),
+        }
+    }
+
+    res
+}
+
+/// `field_value` parses a line protocol field value: a float, an integer with `i` or `u` suffix,
+/// a boolean or a quoted string.
+fn field_value(v: &str) -> Result<Value, String> {
+    if v.len() >= 2 && v.starts_with('"') && v.ends_with('"') {
+        return Ok(Value::String(unescape(&v[1..v.len() - 1])));
+    }
+
+    if let Some(n) = v.strip_suffix('i').and_then(|n| n.parse::<i64>().ok()) {
+        return Ok(Value::from(n));
+    }
+    if let Some(n) = v.strip_suffix('u').and_then(|n| n.parse::<u64>().ok()) {
+        return Ok(Value::from(n));
+    }
+
+    match v {
+        "t" | "T" | "true" | "True" | "TRUE" => Ok(Value::Bool(true)),
+        "f" | "F" | "false" | "False" | "FALSE" => Ok(Value::Bool(false)),
+        _ => v
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(|| format!("invalid field value '{v}'")),
+    }
+}
@@ -1,2 +1,3 @@
 pub mod module;
 pub mod service;
+pub mod sink;
@@ -9,12 +9,48 @@ pub trait IModule {
     fn obtain_device_conf_info(&mut self) -> Result<model::ConfInfo, CommonError>;
     fn configure_device(&mut self, confs: Vec<model::ConfEntry>) -> Result<(), CommonError>;
     fn obtain_sensor_type_infos(&mut self) -> Result<Vec<model::Sensor>, CommonError>;
+    /// `obtain_command_infos` returns commands supported by the device.
+    /// A module without command support returns an empty list.
+    fn obtain_command_infos(&mut self) -> Result<Vec<model::CommandInfo>, CommonError>;
+    fn send_command(&mut self, cmd: &model::Command) -> Result<(), CommonError>;
     fn start<H: MsgHandler + 'static>(&mut self, msg_handler: H) -> Result<(), CommonError>;
     fn stop(&mut self) -> Result<(), CommonError>;
+    /// `module_info` returns info about the module build if the module provides it.
+    fn module_info(&self) -> Option<model::ModuleInfo>;
+}
+
+/// `BUILTIN_MODULE_PREFIX` starts names reserved for modules built into MoniSens.
+/// [`IModuleFactory`] gets such a name instead of a library path.
+pub const BUILTIN_MODULE_PREFIX: &str = "builtin:";
+
+/// `PUSH_MODULE_NAME` is a reserved name of the built-in module of push devices.
+/// Such devices send their data to the ingest endpoint themselves.
+pub const PUSH_MODULE_NAME: &str = "builtin:push";
+/// `PUSH_SENSORS_CONF_ID` is the id of the push module's configuration entry
+/// with the JSON schema of device's sensors.
+pub const PUSH_SENSORS_CONF_ID: i32 = 1;
+
+/// `MODULE_FILE_EXT` is the extension of module libraries on the platform.
+/// Libraries are loaded from files with it, so the loader treats them as libraries.
+#[cfg(target_os = "macos")]
+pub const MODULE_FILE_EXT: &str = ".dylib";
+
+#[cfg(target_os = "linux")]
+pub const MODULE_FILE_EXT: &str = ".so";
+
+#[cfg(target_os = "windows")]
+pub const MODULE_FILE_EXT: &str = ".dll";
+
+pub fn is_builtin_module(name: &str) -> bool {
+    name.starts_with(BUILTIN_MODULE_PREFIX)
 }
 
 pub trait IModuleFactory<M: IModule> {
     fn create_module<P: AsRef<Path>>(mod_path: P, data_dir: P) -> Result<M, CommonError>;
+    /// `inspect_module` loads a copy of the module library in a scratch dir, describes it
+    /// and discards it. Problems of the library are reported in the inspection, an error
+    /// is returned only if the inspection itself fails.
+    fn inspect_module<P: AsRef<Path>>(mod_path: P) -> Result<model::ModuleInspection, CommonError>;
 }
 
 pub trait MsgHandler: Send + Sync {
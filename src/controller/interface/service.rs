@@ -1,4 +1,5 @@
 use tokio::io::AsyncRead;
+use tokio::sync::mpsc;
 
 use super::super::error::CommonError;
 use super::super::model;
@@ -14,6 +15,61 @@ pub trait IService: Sync + Send + Clone {
         module_file: &'f mut F,
     ) -> Result<model::DeviceInitData, CommonError>;
 
+    /// `start_device_init_from_catalog` starts device initialization like
+    /// [`IService::start_device_init`], but the device uses the module from the module catalog
+    /// instead of its own copy.
+    ///
+    /// It must set device's init state to `Device`
+    async fn start_device_init_from_catalog(
+        &self,
+        display_name: String,
+        module_id: i32,
+    ) -> Result<model::DeviceInitData, CommonError>;
+
+    /// `add_catalog_module` saves module library to the module catalog. If the same library
+    /// is already in the catalog, the existing entry is returned.
+    async fn add_catalog_module<F: AsyncRead + Unpin + ?Sized>(
+        &self,
+        name: String,
+        module_file: &mut F,
+    ) -> Result<model::CatalogModule, CommonError>;
+
+    /// `get_catalog_module_list` returns all modules from the module catalog.
+    async fn get_catalog_module_list(&self) -> Result<Vec<model::CatalogModule>, CommonError>;
+
+    /// `delete_catalog_module` deletes module from the module catalog.
+    ///
+    /// It must ensure that the module isn't used by any device.
+    async fn delete_catalog_module(&self, module_id: i32) -> Result<(), CommonError>;
+
+    /// `stage_device_module` saves a new module library for an existing device without
+    /// replacing its current module.
+    async fn stage_device_module<F: AsyncRead + Unpin + ?Sized>(
+        &self,
+        device_id: model::DeviceID,
+        module_file: &mut F,
+    ) -> Result<model::ModuleUpgradeData, CommonError>;
+
+    /// `commit_device_module` atomically replaces device's module with the staged one.
+    ///
+    /// It must keep the previous module for rollback.
+    async fn commit_device_module(
+        &self,
+        data: &model::ModuleUpgradeData,
+    ) -> Result<(), CommonError>;
+
+    /// `rollback_device_module` reverts a committed upgrade and restores the previous module.
+    async fn rollback_device_module(
+        &self,
+        data: &model::ModuleUpgradeData,
+    ) -> Result<(), CommonError>;
+
+    /// `discard_device_module` deletes the staged module library.
+    async fn discard_device_module(
+        &self,
+        data: &model::ModuleUpgradeData,
+    ) -> Result<(), CommonError>;
+
     /// `device_sensor_init` initializes device's sensors by saving them in a storage.
     ///
     /// It must set device's init state to `Sensors`
@@ -30,6 +86,12 @@ pub trait IService: Sync + Send + Clone {
     /// It must delete device's data from disk and storage.
     async fn interrupt_device_init(&self, id: model::DeviceID) -> Result<(), CommonError>;
 
+    /// `delete_device` deletes device in any init state with its sensor data,
+    /// monitor confs and command log.
+    ///
+    /// It must delete device's data from disk and storage.
+    async fn delete_device(&self, id: model::DeviceID) -> Result<(), CommonError>;
+
     /// `get_device_ids` returns all device ids.
     fn get_device_ids(&self) -> Result<Vec<model::DeviceID>, CommonError>;
 
@@ -43,6 +105,15 @@ pub trait IService: Sync + Send + Clone {
         msg: model::SensorMsg,
     ) -> Result<(), CommonError>;
 
+    /// `save_sensor_data_batch` saves several messages of device's sensors at once.
+    ///
+    /// It must save either all the messages or none of them.
+    async fn save_sensor_data_batch(
+        &self,
+        id: model::DeviceID,
+        msgs: Vec<model::SensorMsg>,
+    ) -> Result<(), CommonError>;
+
     /// `get_sensor_data` returns sensor data for device.
     async fn get_sensor_data(
         &self,
@@ -52,9 +123,68 @@ pub trait IService: Sync + Send + Clone {
         filter: model::SensorDataFilter,
     ) -> Result<Vec<model::SensorDataList>, CommonError>;
 
+    /// `export_sensor_data` reads sensor data in the range with a server-side cursor
+    /// and sends it to `tx` by batches of `batch_size` rows. Reading stops if `tx` is closed.
+    async fn export_sensor_data(
+        &self,
+        id: model::DeviceID,
+        sensor_name: String,
+        fields: Vec<String>,
+        range: model::SensorDataRange,
+        batch_size: usize,
+        tx: mpsc::Sender<Vec<model::SensorDataList>>,
+    ) -> Result<(), CommonError>;
+
     /// `get_device_info_list` returns device info list.
     fn get_device_info_list(&self) -> Result<Vec<model::DeviceInfo>, CommonError>;
 
+    /// `get_device_full_info` returns detailed info about device.
+    fn get_device_full_info(
+        &self,
+        device_id: model::DeviceID,
+    ) -> Result<model::DeviceFullInfo, CommonError>;
+
+    /// `save_device_module_info` saves info about the module build used by device.
+    async fn save_device_module_info(
+        &self,
+        device_id: model::DeviceID,
+        module_info: Option<model::ModuleInfo>,
+    ) -> Result<(), CommonError>;
+
+    /// `save_device_watchdog_conf` saves how the watchdog must check device.
+    async fn save_device_watchdog_conf(
+        &self,
+        device_id: model::DeviceID,
+        conf: model::WatchdogConf,
+    ) -> Result<(), CommonError>;
+
+    /// `get_device_watchdog_conf` returns device's watchdog conf.
+    fn get_device_watchdog_conf(
+        &self,
+        device_id: model::DeviceID,
+    ) -> Result<model::WatchdogConf, CommonError>;
+
+    /// `save_device_ingest_token_hash` saves the hash of push device's ingest token.
+    /// `None` revokes the token.
+    async fn save_device_ingest_token_hash(
+        &self,
+        device_id: model::DeviceID,
+        hash: Option<String>,
+    ) -> Result<(), CommonError>;
+
+    /// `save_device_stopped` saves whether device's module must be started with the service.
+    async fn save_device_stopped(
+        &self,
+        device_id: model::DeviceID,
+        stopped: bool,
+    ) -> Result<(), CommonError>;
+
+    /// `get_device_ingest_token_hash` returns the hash of push device's ingest token.
+    fn get_device_ingest_token_hash(
+        &self,
+        device_id: model::DeviceID,
+    ) -> Result<Option<String>, CommonError>;
+
     /// `get_device_sensor_info` returns device sensor info.
     fn get_device_sensor_info(
         &self,
@@ -70,4 +200,83 @@ pub trait IService: Sync + Send + Clone {
         &self,
         filter: model::MonitorConfListFilter,
     ) -> Result<Vec<model::MonitorConf>, CommonError>;
+
+    /// `save_device_command_log` saves an audit record of a command sent to a device.
+    async fn save_device_command_log(
+        &self,
+        log: model::DeviceCommandLog,
+    ) -> Result<(), CommonError>;
+
+    /// `save_audit_record` saves a record of an action made by a user.
+    async fn save_audit_record(&self, record: model::NewAuditRecord) -> Result<(), CommonError>;
+
+    /// `get_audit_log` returns audit records selected by the filter from the newest ones.
+    async fn get_audit_log(
+        &self,
+        filter: model::AuditLogFilter,
+    ) -> Result<Vec<model::AuditRecord>, CommonError>;
+
+    /// `create_user` saves a new user.
+    ///
+    /// It must return `AlreadyExists` error if a user with the same name exists.
+    async fn create_user(&self, user: model::NewUser) -> Result<model::User, CommonError>;
+
+    /// `update_user` changes user's role and devices which it has access to.
+    async fn update_user(
+        &self,
+        id: i32,
+        role: model::Role,
+        devices: Option<Vec<i32>>,
+    ) -> Result<model::User, CommonError>;
+
+    /// `get_user` returns a user by id.
+    async fn get_user(&self, id: i32) -> Result<model::User, CommonError>;
+
+    /// `get_user_by_name` returns a user by name.
+    async fn get_user_by_name(&self, username: &str) -> Result<model::User, CommonError>;
+
+    /// `get_user_list` returns all users.
+    async fn get_user_list(&self) -> Result<Vec<model::User>, CommonError>;
+
+    /// `delete_user` deletes a user with all its sessions.
+    async fn delete_user(&self, id: i32) -> Result<(), CommonError>;
+
+    /// `create_api_key` saves a new API key.
+    ///
+    /// It must return `AlreadyExists` error if the user has a key with the same name.
+    async fn create_api_key(&self, key: model::NewApiKey) -> Result<model::ApiKey, CommonError>;
+
+    /// `get_api_key` returns an API key by id.
+    async fn get_api_key(&self, id: i32) -> Result<model::ApiKey, CommonError>;
+
+    /// `get_api_key_by_hash` returns an API key by the hash of the key.
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<model::ApiKey, CommonError>;
+
+    /// `get_api_key_list` returns API keys of the user or all keys if `user_id` is `None`.
+    async fn get_api_key_list(
+        &self,
+        user_id: Option<i32>,
+    ) -> Result<Vec<model::ApiKey>, CommonError>;
+
+    /// `delete_api_key` deletes an API key.
+    async fn delete_api_key(&self, id: i32) -> Result<(), CommonError>;
+
+    /// `save_api_key_last_used` saves when an API key was used last time.
+    async fn save_api_key_last_used(
+        &self,
+        id: i32,
+        last_used_at: chrono::NaiveDateTime,
+    ) -> Result<(), CommonError>;
+
+    /// `save_session` saves a login session.
+    async fn save_session(&self, session: model::Session) -> Result<(), CommonError>;
+
+    /// `get_session` returns a session by the hash of its token if it hasn't expired.
+    async fn get_session(&self, token_hash: &str) -> Result<model::Session, CommonError>;
+
+    /// `delete_session` deletes a session by the hash of its token.
+    async fn delete_session(&self, token_hash: &str) -> Result<(), CommonError>;
+
+    /// `delete_expired_sessions` deletes all expired sessions.
+    async fn delete_expired_sessions(&self) -> Result<(), CommonError>;
 }
@@ -0,0 +1,14 @@
+use super::super::model;
+
+/// `ISink` forwards sensor data to external destinations after it's saved.
+pub trait ISink: Sync + Send + Clone {
+    /// `forward` passes a saved message of device's sensor to the destinations it's routed to.
+    /// It's called from modules' threads, so it must not block.
+    fn forward(&self, device_id: model::DeviceID, msg: &model::SensorMsg);
+    /// `is_empty` returns `true` if there are no destinations, so messages needn't be kept
+    /// for `forward` after they're saved.
+    fn is_empty(&self) -> bool;
+    /// `close` sends the points which are waiting in buffers and stops forwarding.
+    /// It waits for every destination no longer than `timeout`.
+    async fn close(&self, timeout: std::time::Duration);
+}
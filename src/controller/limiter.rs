@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Failed logins allowed before the next attempts are delayed
+const FREE_ATTEMPTS: u32 = 5;
+/// Delay after the first failure over the free attempts. It doubles with every next failure
+const BASE_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(15 * 60);
+/// Failures are forgotten if there were no attempts for this long
+const FORGET_AFTER: Duration = Duration::from_secs(60 * 60);
+
+/// `LoginLimiter` slows down guessing of passwords. After a few failed logins of a user
+/// from a client, the next attempts are rejected until an exponentially growing delay passes.
+///
+/// Failures are counted per client, so others can't lock a user out.
+#[derive(Clone, Default)]
+pub struct LoginLimiter {
+    failures: Arc<Mutex<HashMap<(String, String), Failures>>>,
+}
+
+struct Failures {
+    count: u32,
+    last_at: Instant,
+    retry_at: Instant,
+}
+
+impl LoginLimiter {
+    /// `check` returns the time to wait if the attempt must be rejected
+    pub fn check(&self, client: &str, username: &str, now: Instant) -> Option<Duration> {
+        let failures = self.failures.lock().unwrap();
+
+        failures
+            .get(&(client.to_string(), username.to_string()))
+            .map(|f| f.retry_at.saturating_duration_since(now))
+            .filter(|wait| !wait.is_zero())
+    }
+
+    pub fn register_failure(&self, client: &str, username: &str, now: Instant) {
+        let mut failures = self.failures.lock().unwrap();
+        failures.retain(|_, f| now.saturating_duration_since(f.last_at) < FORGET_AFTER);
+
+        let f = failures
+            .entry((client.to_string(), username.to_string()))
+            .or_insert(Failures {
+                count: 0,
+                last_at: now,
+                retry_at: now,
+            });
+        f.count += 1;
+        f.last_at = now;
+
+        if f.count >= FREE_ATTEMPTS {
+            let delay = BASE_DELAY
+                .checked_mul(1 << (f.count - FREE_ATTEMPTS).min(20))
+                .map_or(MAX_DELAY, |delay| delay.min(MAX_DELAY));
+            f.retry_at = now + delay;
+        }
+    }
+
+    pub fn reset(&self, client: &str, username: &str) {
+        self.failures
+            .lock()
+            .unwrap()
+            .remove(&(client.to_string(), username.to_string()));
+    }
+}
@@ -1,11 +1,18 @@
 mod conf;
 mod controller;
+mod executor;
+mod export;
+mod import;
+mod limiter;
 mod model;
 mod msg;
+mod test;
 
 pub mod error;
 pub mod interface;
 
 pub use conf::*;
 pub use controller::*;
+pub use export::*;
+pub use import::*;
 pub use model::*;
@@ -1,4 +1,5 @@
-use super::super::interface::{module::IModule, service::IService};
+use super::super::executor::ModuleExecutor;
+use super::super::interface::{module::IModule, service::IService, sink::ISink};
 use super::super::msg;
 
 // TODO: issue #81
@@ -8,10 +9,10 @@ use super::super::msg;
 //     Configurated,
 // }
 
-pub struct Device<S: IService, M: IModule> {
+pub struct Device<S: IService, M: IModule, K: ISink> {
     pub id: super::DeviceID,
-    pub module: M,
-    pub msg_handler: Option<msg::Handler<S>>,
+    pub module: ModuleExecutor<M>,
+    pub msg_handler: Option<msg::Handler<S, K>>,
     // TODO: issue #81
     // pub state: DeviceState,
 }
@@ -5,6 +5,8 @@ pub mod internal;
 
 use std::collections::HashMap;
 
+use super::error::{CommonError, ErrorType};
+
 pub use module::*;
 pub use service::*;
 
@@ -13,6 +15,158 @@ pub struct DeviceConnData {
     pub conn_params: ConfInfo,
 }
 
+/// ShutdownReport summarizes stopping of devices on shutdown
+#[derive(Debug, Default)]
+pub struct ShutdownReport {
+    pub devices: usize,
+    /// Running modules which were stopped
+    pub stopped: usize,
+    /// Running modules which failed to stop or didn't stop in time
+    pub failed: usize,
+    /// Modules which were dropped, so their libraries were destroyed
+    pub unloaded: usize,
+}
+
+/// PushDevice is a created push device with its ingest token.
+/// The token is returned only once, only its hash is stored
+pub struct PushDevice {
+    pub id: DeviceID,
+    pub ingest_token: String,
+}
+
+/// LoginSession is a created login session of a user with its token.
+/// The token is returned only once, only its hash is stored
+pub struct LoginSession {
+    pub token: String,
+    pub expires_at: chrono::NaiveDateTime,
+    pub user: User,
+}
+
+/// Permission is an operation which only some roles may perform
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Permission {
+    /// View devices, their data and panels
+    ViewData,
+    /// Save panels
+    EditPanels,
+    /// Send commands to devices
+    SendCommands,
+    /// Add, configure and delete devices and modules
+    ManageDevices,
+    /// Send sensor data of push devices to the ingest endpoint
+    IngestData,
+    /// Manage own API keys
+    ManageApiKeys,
+    ManageUsers,
+    ViewAuditLog,
+}
+
+impl Permission {
+    /// `min_role` returns the lowest role which has the permission
+    pub fn min_role(&self) -> Role {
+        match self {
+            Permission::ViewData | Permission::ManageApiKeys => Role::Viewer,
+            Permission::EditPanels => Role::Operator,
+            Permission::SendCommands | Permission::ManageDevices | Permission::IngestData => {
+                Role::Engineer
+            }
+            Permission::ManageUsers | Permission::ViewAuditLog => Role::Admin,
+        }
+    }
+}
+
+impl ApiKeyScope {
+    /// `grants` returns whether the scope allows the permission
+    pub fn grants(&self, permission: Permission) -> bool {
+        match self {
+            ApiKeyScope::ReadData => permission == Permission::ViewData,
+            ApiKeyScope::Ingest => permission == Permission::IngestData,
+            ApiKeyScope::Admin => true,
+        }
+    }
+}
+
+impl User {
+    /// `authorize` checks that user's role has the permission. If user is authenticated
+    /// with an API key, one of key's scopes must grant the permission too.
+    pub fn authorize(&self, permission: Permission) -> Result<(), CommonError> {
+        if self.role < permission.min_role() {
+            return Err(CommonError::new(
+                ErrorType::PermissionDenied,
+                format!(
+                    "role {:?} doesn't have permission {permission:?}",
+                    self.role
+                ),
+            ));
+        }
+
+        if let Some(scopes) = &self.scopes {
+            if !scopes.iter().any(|scope| scope.grants(permission)) {
+                return Err(CommonError::new(
+                    ErrorType::PermissionDenied,
+                    format!("API key's scopes don't grant permission {permission:?}"),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `authorize_device` checks that user has the permission for the device.
+    pub fn authorize_device(
+        &self,
+        permission: Permission,
+        device_id: i32,
+    ) -> Result<(), CommonError> {
+        self.authorize(permission)?;
+
+        if !self.can_access_device(device_id) {
+            return Err(CommonError::new(
+                ErrorType::PermissionDenied,
+                format!("user has no access to device with id {device_id}"),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// `authorize_all_devices` checks that user has the permission and isn't limited
+    /// to some devices. It's required for operations which affect devices beyond any scope,
+    /// e.g. adding new devices.
+    pub fn authorize_all_devices(&self, permission: Permission) -> Result<(), CommonError> {
+        self.authorize(permission)?;
+
+        if self.devices.is_some() {
+            return Err(CommonError::new(
+                ErrorType::PermissionDenied,
+                "user has access only to some devices",
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn can_access_device(&self, device_id: i32) -> bool {
+        match self.devices {
+            Some(ref devices) => devices.contains(&device_id),
+            None => true,
+        }
+    }
+}
+
+/// CreatedApiKey is a created API key. The key is returned only once, only its hash is stored
+pub struct CreatedApiKey {
+    pub key: String,
+    pub api_key: ApiKey,
+}
+
+/// IngestRow is a row of sensor data sent by a push device
+pub struct IngestRow {
+    pub sensor: String,
+    /// Values of sensor's fields by their names
+    pub data: serde_json::Map<String, serde_json::Value>,
+}
+
 pub type GetSensorDataResult = Vec<HashMap<String, SensorData>>;
 
 pub struct GetSensorDataPayload {
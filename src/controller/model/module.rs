@@ -109,7 +109,7 @@ pub enum MessageType {
     Common(CommonMsg),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SensorMsg {
     pub name: String,
     pub data: Vec<SensorData>,
@@ -117,7 +117,7 @@ pub struct SensorMsg {
 
 pub type SensorDataList = Vec<SensorData>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SensorData {
     pub name: String,
     pub data: SensorDataTypeValue,
@@ -135,6 +135,112 @@ pub enum SensorDataTypeValue {
     JSON(String),
 }
 
+impl SensorDataTypeValue {
+    pub fn typ(&self) -> super::SensorDataType {
+        match self {
+            SensorDataTypeValue::Int16(_) => super::SensorDataType::Int16,
+            SensorDataTypeValue::Int32(_) => super::SensorDataType::Int32,
+            SensorDataTypeValue::Int64(_) => super::SensorDataType::Int64,
+            SensorDataTypeValue::Float32(_) => super::SensorDataType::Float32,
+            SensorDataTypeValue::Float64(_) => super::SensorDataType::Float64,
+            SensorDataTypeValue::Timestamp(_) => super::SensorDataType::Timestamp,
+            SensorDataTypeValue::String(_) => super::SensorDataType::String,
+            SensorDataTypeValue::JSON(_) => super::SensorDataType::JSON,
+        }
+    }
+
+    /// `from_json` converts a JSON value to a value of the given type.
+    /// Numbers may be sent as strings. A timestamp is either UNIX time in seconds,
+    /// an RFC 3339 string or UTC date and time like `2026-10-18 12:00:00`.
+    /// `None` is returned if the value can't be converted.
+    pub fn from_json(typ: &super::SensorDataType, v: &serde_json::Value) -> Option<Self> {
+        use serde_json::Value;
+
+        let res = match typ {
+            super::SensorDataType::Int16 => {
+                SensorDataTypeValue::Int16(i16::try_from(json_i64(v)?).ok()?)
+            }
+            super::SensorDataType::Int32 => {
+                SensorDataTypeValue::Int32(i32::try_from(json_i64(v)?).ok()?)
+            }
+            super::SensorDataType::Int64 => SensorDataTypeValue::Int64(json_i64(v)?),
+            super::SensorDataType::Float32 => SensorDataTypeValue::Float32(json_f64(v)? as f32),
+            super::SensorDataType::Float64 => SensorDataTypeValue::Float64(json_f64(v)?),
+            super::SensorDataType::Timestamp => SensorDataTypeValue::Timestamp(match v {
+                Value::String(s) => parse_timestamp(s)?,
+                _ => timestamp_from_secs(json_f64(v)?)?,
+            }),
+            super::SensorDataType::String => SensorDataTypeValue::String(match v {
+                Value::String(s) => s.clone(),
+                _ => v.to_string(),
+            }),
+            super::SensorDataType::JSON => SensorDataTypeValue::JSON(v.to_string()),
+        };
+
+        Some(res)
+    }
+
+    /// `to_json` converts the value to JSON. Timestamps become RFC 3339 strings in UTC,
+    /// floats which aren't finite become `null`.
+    pub fn to_json(&self) -> serde_json::Value {
+        use serde_json::Value;
+
+        match self {
+            SensorDataTypeValue::Int16(v) => Value::from(*v),
+            SensorDataTypeValue::Int32(v) => Value::from(*v),
+            SensorDataTypeValue::Int64(v) => Value::from(*v),
+            SensorDataTypeValue::Float32(v) => Value::from(*v as f64),
+            SensorDataTypeValue::Float64(v) => Value::from(*v),
+            SensorDataTypeValue::Timestamp(v) => Value::String(
+                v.and_utc()
+                    .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true),
+            ),
+            SensorDataTypeValue::String(v) => Value::String(v.clone()),
+            SensorDataTypeValue::JSON(v) => {
+                serde_json::from_str(v).unwrap_or_else(|_| Value::String(v.clone()))
+            }
+        }
+    }
+}
+
+fn parse_timestamp(s: &str) -> Option<chrono::NaiveDateTime> {
+    let s = s.trim();
+
+    if let Ok(v) = chrono::DateTime::parse_from_rfc3339(s) {
+        return Some(v.naive_utc());
+    }
+
+    ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+        .iter()
+        .find_map(|f| chrono::NaiveDateTime::parse_from_str(s, f).ok())
+        .or_else(|| timestamp_from_secs(s.parse().ok()?))
+}
+
+fn timestamp_from_secs(secs: f64) -> Option<chrono::NaiveDateTime> {
+    chrono::DateTime::from_timestamp(secs.floor() as i64, (secs.fract() * 1e9) as u32)
+        .map(|v| v.naive_utc())
+}
+
+fn json_i64(v: &serde_json::Value) -> Option<i64> {
+    match v {
+        serde_json::Value::Number(n) => n
+            .as_i64()
+            .or_else(|| n.as_f64().filter(|f| f.fract() == 0.0).map(|f| f as i64)),
+        serde_json::Value::String(s) => s.trim().parse().ok(),
+        serde_json::Value::Bool(b) => Some(*b as i64),
+        _ => None,
+    }
+}
+
+fn json_f64(v: &serde_json::Value) -> Option<f64> {
+    match v {
+        serde_json::Value::Number(n) => n.as_f64(),
+        serde_json::Value::String(s) => s.trim().parse().ok(),
+        serde_json::Value::Bool(b) => Some(*b as i64 as f64),
+        _ => None,
+    }
+}
+
 #[derive(Debug)]
 pub struct CommonMsg {
     pub code: MsgCode,
@@ -147,3 +253,72 @@ pub enum MsgCode {
     Warn,
     Error,
 }
+
+#[derive(Debug)]
+pub struct CommandInfo {
+    pub name: String,
+    pub description: Option<String>,
+    pub args: Vec<CommandArgInfo>,
+}
+
+#[derive(Debug)]
+pub struct CommandArgInfo {
+    pub name: String,
+    pub typ: super::SensorDataType,
+    pub required: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub name: String,
+    pub args: Vec<CommandArg>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandArg {
+    pub name: String,
+    pub data: Option<SensorDataTypeValue>,
+}
+
+/// ModuleInfo describes a module build. It's provided by modules that export `module_info`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleInfo {
+    pub name: String,
+    pub vendor: Option<String>,
+    /// Semantic version of the module
+    pub version: String,
+    pub description: Option<String>,
+    pub os: Option<String>,
+    pub arch: Option<String>,
+    pub capabilities: ModuleCapabilities,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModuleCapabilities {
+    pub commands: bool,
+    pub hot_reconfigure: bool,
+    pub persisted_config: bool,
+}
+
+/// ModuleInspection describes a module library which was loaded without creating a device for it.
+#[derive(Debug)]
+pub struct ModuleInspection {
+    /// Version returned by `mod_version`. `None` if the library can't be loaded or doesn't export it
+    pub version: Option<u8>,
+    /// Version of the module API supported by MoniSens
+    pub supported_version: u8,
+    /// Empty if the library can't be loaded or has another version
+    pub functions: Vec<FunctionPresence>,
+    pub module_info: Option<ModuleInfo>,
+    /// Parameters required to connect to a device. `None` if the module can't be initialized
+    pub conn_info: Option<ConfInfo>,
+    /// Why a device can't be created with the library. `None` if the library is valid
+    pub error: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct FunctionPresence {
+    pub name: &'static str,
+    pub required: bool,
+    pub present: bool,
+}
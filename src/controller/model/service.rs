@@ -45,6 +45,19 @@ pub struct DeviceInitData {
     pub data_dir: PathBuf,
     pub full_data_dir: PathBuf,
     pub init_state: DeviceInitState,
+    /// `true` if device's module mustn't be started with the service
+    pub stopped: bool,
+}
+
+/// ModuleUpgradeData describes a new module library staged for an existing device
+#[derive(Debug)]
+pub struct ModuleUpgradeData {
+    pub id: DeviceID,
+    /// Staged module file. It becomes device's module only after the upgrade is committed
+    pub module_file: PathBuf,
+    pub full_data_dir: PathBuf,
+    /// Hash of the catalog module device used before the upgrade. It is restored on rollback
+    pub prev_module_hash: Option<String>,
 }
 
 #[derive(Default)]
@@ -55,6 +68,13 @@ pub struct SensorDataFilter {
     pub sort: Option<Sort>,
 }
 
+/// SensorDataRange selects sensor data with `field` in `[from, to)` ordered by the field
+pub struct SensorDataRange {
+    pub field: String,
+    pub from: Option<chrono::NaiveDateTime>,
+    pub to: Option<chrono::NaiveDateTime>,
+}
+
 #[derive(Clone)]
 pub struct Sort {
     pub field: String,
@@ -65,6 +85,130 @@ pub struct Sort {
 pub struct DeviceInfo {
     pub id: DeviceID,
     pub display_name: String,
+    /// `None` if device is not started
+    pub health: Option<DeviceHealth>,
+    /// `true` if a call to device's module has timed out and hasn't returned yet
+    /// or if the watchdog has given up restarting the device
+    pub faulted: bool,
+    /// `true` if device was stopped by an administrator and isn't started with the service
+    pub stopped: bool,
+}
+
+/// DeviceHealth describes messages received from a running device
+#[derive(Clone, Debug, Default)]
+pub struct DeviceHealth {
+    /// UTC time of the last message
+    pub last_msg_at: Option<chrono::NaiveDateTime>,
+    pub msg_count: u64,
+    /// `true` if device hasn't sent messages for longer than its expected interval
+    pub stale: bool,
+    /// Number of restarts made by the watchdog
+    pub restart_count: u64,
+}
+
+/// WatchdogConf configures how the watchdog checks device
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WatchdogConf {
+    /// Maximum interval between device's messages in seconds. `None` disables the watchdog
+    pub expected_interval: Option<i32>,
+    /// Restart device's module when device becomes stale
+    pub restart: bool,
+}
+
+/// DeviceFullInfo contains detailed info about device including its module build
+pub struct DeviceFullInfo {
+    pub id: DeviceID,
+    pub name: String,
+    pub display_name: String,
+    pub init_state: DeviceInitState,
+    pub module_info: Option<module::ModuleInfo>,
+    /// Hash of the catalog module used by device. `None` if device has its own module copy
+    pub module_hash: Option<String>,
+    pub watchdog_conf: WatchdogConf,
+    pub stopped: bool,
+}
+
+/// CatalogModule is a module library stored once in the module catalog and shared between devices
+#[derive(Clone, Debug)]
+pub struct CatalogModule {
+    pub id: i32,
+    pub name: String,
+    /// SHA-256 of the library file
+    pub hash: String,
+    pub size: i64,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// User is an account which has access to the service API
+#[derive(Clone, Debug)]
+pub struct User {
+    pub id: i32,
+    pub username: String,
+    /// Hash of the password made by [`crate::tool::secret::hash_password`]
+    pub password_hash: String,
+    pub role: Role,
+    /// Ids of devices which the user has access to. `None` gives access to all devices
+    pub devices: Option<Vec<i32>>,
+    /// Scopes of the API key which the user is authenticated with. `None` if the user
+    /// isn't authenticated with an API key
+    pub scopes: Option<Vec<ApiKeyScope>>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+pub struct NewUser {
+    pub username: String,
+    pub password_hash: String,
+    pub role: Role,
+    pub devices: Option<Vec<i32>>,
+}
+
+/// Role defines what a user may do. Every role has all permissions of the lower ones
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Viewer,
+    Operator,
+    Engineer,
+    Admin,
+}
+
+/// ApiKey is a key for machine-to-machine access on behalf of its user.
+/// Only the hash of the key is stored
+#[derive(Clone, Debug)]
+pub struct ApiKey {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub created_at: chrono::NaiveDateTime,
+    /// `None` if the key was never used
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+}
+
+pub struct NewApiKey {
+    pub user_id: i32,
+    pub name: String,
+    /// SHA-256 of the key
+    pub key_hash: String,
+    pub scopes: Vec<ApiKeyScope>,
+}
+
+/// ApiKeyScope limits what an API key may do. The key can't do more than its user
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ApiKeyScope {
+    /// Read devices and their data
+    ReadData,
+    /// Send sensor data to the ingest endpoint
+    Ingest,
+    /// Everything its user may do
+    Admin,
+}
+
+/// Session is a login session of a user. Only the hash of its token is stored
+pub struct Session {
+    /// SHA-256 of the session token
+    pub token_hash: String,
+    pub user_id: i32,
+    pub expires_at: chrono::NaiveDateTime,
 }
 
 #[derive(Clone)]
@@ -88,7 +232,7 @@ pub struct SensorInfo {
     pub data: Vec<SensorDataEntry>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum SensorDataType {
     Int16,
     Int32,
@@ -100,6 +244,96 @@ pub enum SensorDataType {
     JSON,
 }
 
+/// DeviceCommandLog is an audit record of a command sent to a device
+pub struct DeviceCommandLog {
+    pub device_id: DeviceID,
+    pub command: module::Command,
+    /// `None` if the command was sent successfully
+    pub error: Option<String>,
+}
+
+/// AuditRecord is a record of a configuration or control action made by a user
+#[derive(Clone, Debug)]
+pub struct AuditRecord {
+    pub id: i32,
+    /// `None` if the user was deleted or the action was made from the command line
+    pub user_id: Option<i32>,
+    pub username: String,
+    pub action: AuditAction,
+    /// Device which the action targets
+    pub device_id: Option<i32>,
+    /// Parameters of the action without secrets
+    pub summary: serde_json::Value,
+    /// `None` if the action succeeded
+    pub error: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+pub struct NewAuditRecord {
+    /// `None` if the action was made from the command line
+    pub user_id: Option<i32>,
+    pub username: String,
+    pub action: AuditAction,
+    pub device_id: Option<i32>,
+    pub summary: serde_json::Value,
+    pub error: Option<String>,
+}
+
+/// `audit_actions` declares [`AuditAction`] with the list of all its variants,
+/// so conversions of actions are written once and can't miss any of them
+macro_rules! audit_actions {
+    ($($action:ident),* $(,)?) => {
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        pub enum AuditAction {
+            $($action,)*
+        }
+
+        impl AuditAction {
+            pub const ALL: &'static [AuditAction] = &[$(AuditAction::$action,)*];
+        }
+    };
+}
+
+audit_actions!(
+    StartDeviceInit,
+    ConnectDevice,
+    ConfigureDevice,
+    InterruptDeviceInit,
+    DeleteDevice,
+    StopDevice,
+    StartDevice,
+    UpgradeDeviceModule,
+    SetDeviceWatchdogConf,
+    DeviceStale,
+    DeviceRecovered,
+    RestartStaleDevice,
+    SendDeviceCommand,
+    CreatePushDevice,
+    ResetIngestToken,
+    ImportSensorData,
+    UploadModule,
+    DeleteModule,
+    SaveMonitorConf,
+    CreateUser,
+    UpdateUser,
+    DeleteUser,
+    CreateApiKey,
+    RevokeApiKey,
+);
+
+/// AuditLogFilter selects audit records. Records are returned from the newest ones
+#[derive(Default)]
+pub struct AuditLogFilter {
+    pub user_id: Option<i32>,
+    pub device_id: Option<i32>,
+    pub action: Option<AuditAction>,
+    /// Records made at this time or later
+    pub from: Option<chrono::NaiveDateTime>,
+    /// Records made before this time
+    pub to: Option<chrono::NaiveDateTime>,
+    pub limit: Option<i32>,
+}
+
 pub struct MonitorConf {
     pub id: i32,
     pub device_id: i32,
@@ -1,53 +1,194 @@
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use tokio::{runtime::Handle, task};
 
-use super::interface::{module, service};
+use crate::logger;
+use crate::{kv_any, kvs};
+
+use super::interface::{module, service, sink};
 use super::model;
 
+/// `MAX_RESTARTS` is the number of watchdog restarts in a row after which a device
+/// that still sends no messages is considered faulted and isn't restarted anymore
+pub const MAX_RESTARTS: u32 = 5;
+
 #[derive(Clone)]
-pub struct Handler<S: service::IService>(Arc<Mutex<HandlerImpl<S>>>);
+pub struct Handler<S: service::IService, K: sink::ISink> {
+    h: Arc<Mutex<HandlerImpl<S, K>>>,
+    stats: Arc<Mutex<Stats>>,
+}
 
-impl<S: service::IService> Handler<S> {
-    pub fn new(device_id: model::DeviceID, svc: S, tokio_handle: Handle) -> Self {
-        Handler(Arc::new(Mutex::new(HandlerImpl::new(
-            device_id,
-            svc,
-            tokio_handle,
-        ))))
+impl<S: service::IService, K: sink::ISink> Handler<S, K> {
+    pub fn new(device_id: model::DeviceID, svc: S, sink: K, tokio_handle: Handle) -> Self {
+        Handler {
+            h: Arc::new(Mutex::new(HandlerImpl::new(
+                device_id,
+                svc,
+                sink,
+                tokio_handle,
+            ))),
+            stats: Arc::new(Mutex::new(Stats::new())),
+        }
+    }
+
+    /// `health` returns device's health based on the messages received by the handler.
+    pub fn health(&self) -> model::DeviceHealth {
+        let stats = self.stats.lock().unwrap();
+
+        model::DeviceHealth {
+            last_msg_at: stats.last_msg_at,
+            msg_count: stats.msg_count,
+            stale: stats.stale,
+            restart_count: stats.restart_count,
+        }
+    }
+
+    /// `check_stale` updates and returns device's stale flag: the device is stale if it hasn't
+    /// sent any message for longer than `expected_interval`. The interval doubles with every
+    /// watchdog restart that isn't followed by a message.
+    ///
+    /// The returned tuple contains the new flag and whether it has changed.
+    pub fn check_stale(&self, expected_interval: std::time::Duration) -> (bool, bool) {
+        let mut stats = self.stats.lock().unwrap();
+
+        let interval = expected_interval * 2u32.pow(stats.restart_streak);
+        let stale = stats.last_activity.elapsed() > interval;
+        let changed = stale != stats.stale;
+        stats.stale = stale;
+
+        (stale, changed)
+    }
+
+    /// `register_msg` counts a message which was received and saved bypassing the handler,
+    /// e.g. a row sent by a push device.
+    pub fn register_msg(&self) {
+        self.stats.lock().unwrap().register_msg();
+    }
+
+    /// `close` waits for the message being saved and makes the handler drop further messages.
+    /// It's called when the module is stopped on shutdown, so no data is saved after it.
+    pub fn close(&self) {
+        self.h.lock().unwrap().closed = true;
+    }
+
+    /// `mark_restarted` is called when the device's module is restarted by the watchdog.
+    /// It gives the module a full interval to send a new message.
+    pub fn mark_restarted(&self) {
+        let mut stats = self.stats.lock().unwrap();
+
+        stats.last_activity = Instant::now();
+        stats.stale = false;
+        stats.restart_count += 1;
+        stats.restart_streak += 1;
+    }
+
+    /// `is_faulted` returns `true` if the device hasn't sent any message
+    /// after [`MAX_RESTARTS`] watchdog restarts.
+    pub fn is_faulted(&self) -> bool {
+        self.stats.lock().unwrap().restart_streak >= MAX_RESTARTS
     }
 }
 
-impl<S: service::IService> module::MsgHandler for Handler<S> {
+impl<S: service::IService, K: sink::ISink> module::MsgHandler for Handler<S, K> {
     fn handle_msg(&self, msg: model::Message) {
-        let mut h = self.0.lock().unwrap();
+        self.stats.lock().unwrap().register_msg();
+
+        let mut h = self.h.lock().unwrap();
         h.handle_msg(msg);
     }
 }
 
-struct HandlerImpl<S: service::IService> {
+struct Stats {
+    /// Time of the last message or of the last restart
+    last_activity: Instant,
+    last_msg_at: Option<chrono::NaiveDateTime>,
+    msg_count: u64,
+    stale: bool,
+    restart_count: u64,
+    /// Number of watchdog restarts since the last message
+    restart_streak: u32,
+}
+
+impl Stats {
+    fn new() -> Self {
+        Self {
+            last_activity: Instant::now(),
+            last_msg_at: None,
+            msg_count: 0,
+            stale: false,
+            restart_count: 0,
+            restart_streak: 0,
+        }
+    }
+
+    fn register_msg(&mut self) {
+        self.last_activity = Instant::now();
+        self.last_msg_at = Some(chrono::Utc::now().naive_utc());
+        self.msg_count += 1;
+        self.restart_streak = 0;
+    }
+}
+
+struct HandlerImpl<S: service::IService, K: sink::ISink> {
     device_id: model::DeviceID,
     svc: S,
+    sink: K,
     tokio_handle: Handle,
+    /// Set on shutdown, messages aren't saved anymore
+    closed: bool,
 }
 
-impl<S: service::IService> HandlerImpl<S> {
-    fn new(device_id: model::DeviceID, svc: S, tokio_handle: Handle) -> Self {
+impl<S: service::IService, K: sink::ISink> HandlerImpl<S, K> {
+    fn new(device_id: model::DeviceID, svc: S, sink: K, tokio_handle: Handle) -> Self {
         Self {
             device_id,
             svc,
+            sink,
             tokio_handle,
+            closed: false,
         }
     }
 
     fn handle_msg(&mut self, msg: model::Message) {
-        if let model::MessageType::Sensor(mut msg) = msg.msg {
-            // TODO: error handling
-            task::block_in_place(move || {
-                self.tokio_handle
-                    .block_on(self.svc.save_sensor_data(self.device_id, msg))
-            })
-            .unwrap();
+        if self.closed {
+            logger::warn_kv(
+                "message received after shutdown is dropped",
+                kvs!("device_id" => kv_any!(self.device_id)),
+            );
+            return;
+        }
+
+        match msg.msg {
+            model::MessageType::Sensor(msg) => {
+                // A copy of the message is kept only if it's forwarded after saving
+                let forwarded = (!self.sink.is_empty()).then(|| msg.clone());
+                let res = task::block_in_place(|| {
+                    self.tokio_handle
+                        .block_on(self.svc.save_sensor_data(self.device_id, msg))
+                });
+
+                match res {
+                    Ok(()) => {
+                        if let Some(msg) = forwarded {
+                            self.sink.forward(self.device_id, &msg);
+                        }
+                    }
+                    Err(err) => logger::error_kv(
+                        "failed to save sensor data",
+                        kvs!("device_id" => kv_any!(self.device_id), "error" => kv_any!(err)),
+                    ),
+                }
+            }
+            model::MessageType::Common(msg) => {
+                let kvs = kvs!("device_id" => kv_any!(self.device_id), "msg" => kv_any!(msg.msg));
+
+                match msg.code {
+                    model::MsgCode::Error => logger::error_kv("device error", kvs),
+                    model::MsgCode::Warn => logger::warn_kv("device warning", kvs),
+                    model::MsgCode::Info => logger::info_kv("device info", kvs),
+                }
+            }
         }
     }
 }
@@ -0,0 +1,1544 @@
+#[cfg(test)]
+use crate::{app, module::Module, repo, service::Service, sink::Sinks};
+
+#[cfg(test)]
+use super::controller::{ingest_row_to_msg, shutdown_device, validate_command, Controller};
+#[cfg(test)]
+use super::error::{CommonError, ControllerError, ErrorType};
+#[cfg(test)]
+use super::executor::ModuleExecutor;
+#[cfg(test)]
+use super::export::{Encoder, ExportFormat};
+#[cfg(test)]
+use super::import::{import_rows, ImportFormat, ImportPayload, TimestampPrecision};
+#[cfg(test)]
+use super::interface::module::{is_builtin_module, IModule, IModuleFactory, MsgHandler};
+#[cfg(test)]
+use super::interface::service::IService;
+#[cfg(test)]
+use super::limiter::LoginLimiter;
+#[cfg(test)]
+use super::model::internal::Device;
+#[cfg(test)]
+use super::model::CatalogModule;
+#[cfg(test)]
+use super::model::{
+    ApiKeyScope, AuditAction, AuditLogFilter, Command, CommandArg, CommandArgInfo, CommandInfo,
+    DeviceCommandLog, DeviceHealth, DeviceID, GetSensorDataPayload, IngestRow, MonitorConf,
+    MonitorConfListFilter, MonitorLogConf, MonitorType, MonitorTypeConf, NewAuditRecord,
+    Permission, PushDevice, Role, SensorData, SensorDataEntry, SensorDataType, SensorDataTypeValue,
+    SensorInfo, Session, ShutdownReport, Sort, SortDir, User, WatchdogConf,
+};
+#[cfg(test)]
+use super::msg;
+
+#[test]
+fn ingest_row_validation() {
+    let sensors = vec![SensorInfo {
+        name: "room".to_string(),
+        data: vec![
+            SensorDataEntry {
+                name: "timestamp".to_string(),
+                typ: SensorDataType::Timestamp,
+            },
+            SensorDataEntry {
+                name: "temperature".to_string(),
+                typ: SensorDataType::Float64,
+            },
+            SensorDataEntry {
+                name: "count".to_string(),
+                typ: SensorDataType::Int16,
+            },
+        ],
+    }];
+    let row = |sensor: &str, data: serde_json::Value| IngestRow {
+        sensor: sensor.to_string(),
+        data: data.as_object().unwrap().clone(),
+    };
+
+    // Absent timestamp is set
+    let msg = ingest_row_to_msg(
+        &sensors,
+        row(
+            "room",
+            serde_json::json!({"temperature": 21.5, "count": "3"}),
+        ),
+    )
+    .unwrap();
+    assert_eq!(msg.name, "room");
+    assert_eq!(msg.data.len(), 3);
+    assert!(msg
+        .data
+        .iter()
+        .any(|d| d.name == "timestamp" && matches!(d.data, SensorDataTypeValue::Timestamp(_))));
+    assert!(msg
+        .data
+        .iter()
+        .any(|d| d.name == "count" && matches!(d.data, SensorDataTypeValue::Int16(3))));
+
+    // Given timestamp is kept
+    let msg = ingest_row_to_msg(
+        &sensors,
+        row(
+            "room",
+            serde_json::json!({"temperature": 20, "count": 1, "timestamp": "2026-10-18T12:00:00Z"}),
+        ),
+    )
+    .unwrap();
+    let ts = msg.data.iter().find(|d| d.name == "timestamp").unwrap();
+    match ts.data {
+        SensorDataTypeValue::Timestamp(v) => assert_eq!(v.to_string(), "2026-10-18 12:00:00"),
+        _ => panic!("timestamp has wrong type"),
+    }
+
+    // Failure
+    assert!(ingest_row_to_msg(
+        &sensors,
+        row("kitchen", serde_json::json!({"temperature": 1}))
+    )
+    .is_err());
+    assert!(ingest_row_to_msg(
+        &sensors,
+        row(
+            "room",
+            serde_json::json!({"temperature": 1, "count": 1, "pressure": 1})
+        )
+    )
+    .is_err());
+    assert!(ingest_row_to_msg(
+        &sensors,
+        row(
+            "room",
+            serde_json::json!({"temperature": 1, "count": 100000})
+        )
+    )
+    .is_err());
+    assert!(ingest_row_to_msg(
+        &sensors,
+        row(
+            "room",
+            serde_json::json!({"temperature": "hot", "count": 1})
+        )
+    )
+    .is_err());
+    assert!(ingest_row_to_msg(
+        &sensors,
+        row("room", serde_json::json!({"temperature": null, "count": 1}))
+    )
+    .is_err());
+    // All fields except the timestamp are required
+    assert!(
+        ingest_row_to_msg(&sensors, row("room", serde_json::json!({"temperature": 1}))).is_err()
+    );
+}
+
+#[test]
+fn import_rows_parsing() {
+    let sensor = SensorInfo {
+        name: "room".to_string(),
+        data: vec![
+            SensorDataEntry {
+                name: "timestamp".to_string(),
+                typ: SensorDataType::Timestamp,
+            },
+            SensorDataEntry {
+                name: "temperature".to_string(),
+                typ: SensorDataType::Float64,
+            },
+            SensorDataEntry {
+                name: "place".to_string(),
+                typ: SensorDataType::String,
+            },
+        ],
+    };
+    let payload = |format, mapping: &[(&str, &str)]| ImportPayload {
+        device_id: 1,
+        sensor: "room".to_string(),
+        format,
+        mapping: mapping
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+        precision: TimestampPrecision::Seconds,
+        dry_run: true,
+    };
+
+    // CSV
+    let csv = "time,temperature,place\n\
+        2026-10-18 12:00:00,21.5,kitchen\n\
+        2026-10-18 12:01:00,hot,kitchen\n\
+        1760788920,,hall\n";
+    let p = payload(ImportFormat::Csv, &[("time", "timestamp")]);
+    let rows: Vec<_> = import_rows(&p, &sensor, csv.as_bytes()).unwrap().collect();
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[0].0, 2);
+    assert_eq!(rows[0].1.as_ref().unwrap().len(), 3);
+    assert_eq!(rows[1].0, 3);
+    assert!(rows[1].1.is_err());
+    assert!(rows[2].1.is_err());
+
+    // Unknown or missing columns
+    let p = payload(ImportFormat::Csv, &[]);
+    assert!(import_rows(&p, &sensor, csv.as_bytes()).is_err());
+    let p = payload(ImportFormat::Csv, &[]);
+    assert!(import_rows(&p, &sensor, "timestamp,temperature\n".as_bytes()).is_err());
+
+    // Line protocol
+    let lp = "# comment\n\
+        room,place=living\\ room temperature=21.5 1760788800\n\
+        room,place=hall temperature=22i\n\
+        kitchen,place=hall temperature=20 1760788800\n\
+        room place=\"hall\",temperature=t 1760788800\n";
+    let p = payload(ImportFormat::LineProtocol, &[]);
+    let rows: Vec<_> = import_rows(&p, &sensor, lp.as_bytes()).unwrap().collect();
+    assert_eq!(rows.len(), 4);
+    assert_eq!(rows[0].0, 2);
+    let data = rows[0].1.as_ref().unwrap();
+    assert!(data.iter().any(|d| d.name == "place"
+        && matches!(d.data, SensorDataTypeValue::String(ref v) if v == "living room")));
+    assert!(data.iter().any(|d| d.name == "timestamp"
+        && matches!(d.data, SensorDataTypeValue::Timestamp(v) if v.and_utc().timestamp() == 1760788800)));
+    // No timestamp
+    assert!(rows[1].1.is_err());
+    // Another measurement
+    assert!(rows[2].1.is_err());
+    // Boolean is converted to a number
+    assert!(rows[3].1.is_ok());
+}
+
+#[test]
+fn export_encoding() {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    let columns = vec![
+        SensorDataEntry {
+            name: "timestamp".to_string(),
+            typ: SensorDataType::Timestamp,
+        },
+        SensorDataEntry {
+            name: "temperature".to_string(),
+            typ: SensorDataType::Float32,
+        },
+        SensorDataEntry {
+            name: "place".to_string(),
+            typ: SensorDataType::String,
+        },
+    ];
+    let row = |ts: i64, temperature: f32, place: &str| {
+        vec![
+            SensorData {
+                name: "timestamp".to_string(),
+                data: SensorDataTypeValue::Timestamp(
+                    chrono::DateTime::from_timestamp(ts, 0).unwrap().naive_utc(),
+                ),
+            },
+            SensorData {
+                name: "temperature".to_string(),
+                data: SensorDataTypeValue::Float32(temperature),
+            },
+            SensorData {
+                name: "place".to_string(),
+                data: SensorDataTypeValue::String(place.to_string()),
+            },
+        ]
+    };
+    let batches = [
+        vec![row(1760788800, 21.5, "hall, left")],
+        vec![
+            row(1760788860, 22.0, "kitchen"),
+            row(1760788920, 22.5, "hall"),
+        ],
+    ];
+    let encode = |format| {
+        let mut encoder = Encoder::new(format, &columns, chrono_tz::Europe::Berlin).unwrap();
+        let mut res = encoder.begin();
+        for batch in batches.iter() {
+            res.extend(encoder.encode(batch).unwrap());
+        }
+        res.extend(encoder.finish().unwrap());
+
+        res
+    };
+
+    let csv = String::from_utf8(encode(ExportFormat::Csv)).unwrap();
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], "timestamp,temperature,place");
+    assert_eq!(lines[1], "2025-10-18T14:00:00+02:00,21.5,\"hall, left\"");
+
+    let jsonl = String::from_utf8(encode(ExportFormat::JsonLines)).unwrap();
+    assert_eq!(
+        jsonl.lines().nth(1).unwrap(),
+        r#"{"timestamp":"2025-10-18T14:01:00+02:00","temperature":22.0,"place":"kitchen"}"#
+    );
+
+    let parquet = bytes::Bytes::from(encode(ExportFormat::Parquet));
+    let reader = SerializedFileReader::new(parquet).unwrap();
+    assert_eq!(reader.metadata().num_row_groups(), 2);
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 3);
+
+    let rows: Vec<_> = reader
+        .get_row_iter(None)
+        .unwrap()
+        .map(|r| r.unwrap().to_string())
+        .collect();
+    assert_eq!(rows.len(), 3);
+    assert!(rows[2].contains("temperature: 22.5"));
+    assert!(rows[2].contains("place: \"hall\""));
+}
+
+#[test]
+fn user_permissions() {
+    let user = |role, devices| User {
+        id: 1,
+        username: "user".to_string(),
+        password_hash: String::new(),
+        role,
+        devices,
+        scopes: None,
+        created_at: chrono::NaiveDateTime::default(),
+    };
+
+    let viewer = user(Role::Viewer, None);
+    assert!(viewer.authorize(Permission::ViewData).is_ok());
+    assert!(viewer.authorize(Permission::EditPanels).is_err());
+
+    let operator = user(Role::Operator, None);
+    assert!(operator.authorize(Permission::EditPanels).is_ok());
+    assert!(operator.authorize(Permission::SendCommands).is_err());
+    assert!(operator.authorize(Permission::ManageDevices).is_err());
+
+    let engineer = user(Role::Engineer, Some(vec![2]));
+    assert!(engineer
+        .authorize_device(Permission::SendCommands, 2)
+        .is_ok());
+    assert!(engineer
+        .authorize_device(Permission::SendCommands, 3)
+        .is_err());
+    // Scoped users can't add devices
+    assert!(engineer
+        .authorize_all_devices(Permission::ManageDevices)
+        .is_err());
+    assert!(engineer.authorize(Permission::ManageUsers).is_err());
+
+    let admin = user(Role::Admin, None);
+    assert!(admin.authorize_device(Permission::ManageUsers, 3).is_ok());
+    assert!(admin
+        .authorize_all_devices(Permission::ManageDevices)
+        .is_ok());
+
+    // Users limited to no devices have access to none of them
+    assert!(!user(Role::Admin, Some(vec![])).can_access_device(1));
+
+    // API keys can't do more than their scopes nor than their users
+    let mut key_user = user(Role::Engineer, None);
+    key_user.scopes = Some(vec![ApiKeyScope::Ingest]);
+    assert!(key_user.authorize(Permission::IngestData).is_ok());
+    assert!(key_user.authorize(Permission::ViewData).is_err());
+
+    key_user.scopes = Some(vec![ApiKeyScope::Admin]);
+    assert!(key_user.authorize(Permission::SendCommands).is_ok());
+    assert!(key_user.authorize(Permission::ManageUsers).is_err());
+}
+
+#[test]
+fn command_validation() {
+    let infos = vec![CommandInfo {
+        name: "set_interval".to_string(),
+        description: None,
+        args: vec![
+            CommandArgInfo {
+                name: "interval".to_string(),
+                typ: SensorDataType::Int32,
+                required: true,
+            },
+            CommandArgInfo {
+                name: "label".to_string(),
+                typ: SensorDataType::String,
+                required: false,
+            },
+        ],
+    }];
+    let cmd = |name: &str, args: Vec<(&str, Option<SensorDataTypeValue>)>| Command {
+        name: name.to_string(),
+        args: args
+            .into_iter()
+            .map(|(name, data)| CommandArg {
+                name: name.to_string(),
+                data,
+            })
+            .collect(),
+    };
+
+    assert!(validate_command(
+        &infos,
+        &cmd(
+            "set_interval",
+            vec![("interval", Some(SensorDataTypeValue::Int32(10)))]
+        )
+    )
+    .is_ok());
+    assert!(validate_command(
+        &infos,
+        &cmd(
+            "set_interval",
+            vec![
+                ("interval", Some(SensorDataTypeValue::Int32(10))),
+                ("label", None),
+            ]
+        )
+    )
+    .is_ok());
+
+    // Unknown command and argument
+    assert!(validate_command(&infos, &cmd("reboot", vec![])).is_err());
+    assert!(validate_command(
+        &infos,
+        &cmd(
+            "set_interval",
+            vec![
+                ("interval", Some(SensorDataTypeValue::Int32(10))),
+                ("speed", Some(SensorDataTypeValue::Int32(1))),
+            ]
+        )
+    )
+    .is_err());
+    // Missing required argument, given either as absent or as null
+    assert!(validate_command(&infos, &cmd("set_interval", vec![])).is_err());
+    assert!(validate_command(&infos, &cmd("set_interval", vec![("interval", None)])).is_err());
+    // Wrong type
+    assert!(validate_command(
+        &infos,
+        &cmd(
+            "set_interval",
+            vec![("interval", Some(SensorDataTypeValue::Int64(10)))]
+        )
+    )
+    .is_err());
+    // Duplicated argument
+    assert!(validate_command(
+        &infos,
+        &cmd(
+            "set_interval",
+            vec![
+                ("interval", Some(SensorDataTypeValue::Int32(10))),
+                ("interval", Some(SensorDataTypeValue::Int32(20))),
+            ]
+        )
+    )
+    .is_err());
+    // Strings are passed to modules as C strings
+    assert!(validate_command(
+        &infos,
+        &cmd(
+            "set_interval",
+            vec![
+                ("interval", Some(SensorDataTypeValue::Int32(10))),
+                (
+                    "label",
+                    Some(SensorDataTypeValue::String("a\0b".to_string()))
+                ),
+            ]
+        )
+    )
+    .is_err());
+}
+
+#[cfg(test)]
+type TestController = Controller<Service, Module, Module, Sinks>;
+
+/// `test_service` creates a service over a new in-memory SQLite database.
+/// Device files are kept in a temporary data dir shared by all tests of the process,
+/// so tests must give their devices distinct names
+#[cfg(test)]
+async fn test_service() -> Service {
+    Service::new(test_repo().await).await.unwrap()
+}
+
+#[cfg(test)]
+async fn test_repo() -> repo::Repository {
+    app::init_data_dir(std::env::temp_dir().join(format!("monisens-test-{}", std::process::id())))
+        .unwrap();
+
+    // Every connection opens its own in-memory database, so the pool keeps one connection
+    let pool_conf = repo::PoolConf {
+        max_connections: 1,
+        min_connections: 1,
+        ..Default::default()
+    };
+    repo::Repository::new(repo::Storage::Sqlite, "sqlite::memory:", &pool_conf)
+        .await
+        .unwrap()
+}
+
+#[cfg(test)]
+async fn test_controller(svc: Service) -> TestController {
+    Controller::new(
+        tokio::runtime::Handle::current(),
+        svc,
+        Sinks::default(),
+        std::time::Duration::from_secs(5),
+    )
+    .await
+    .unwrap()
+}
+
+#[cfg(test)]
+async fn test_admin(ctrl: &TestController, username: &str) -> User {
+    ctrl.bootstrap_admin(username.to_string(), "password".to_string())
+        .await
+        .unwrap();
+
+    ctrl.get_user_list()
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|user| user.username == username)
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn audit_actions_round_trip() {
+    let svc = test_service().await;
+
+    for action in AuditAction::ALL.iter().copied() {
+        svc.save_audit_record(NewAuditRecord {
+            user_id: None,
+            username: "test".to_string(),
+            action,
+            device_id: None,
+            summary: serde_json::json!({}),
+            error: None,
+        })
+        .await
+        .unwrap();
+
+        let records = svc
+            .get_audit_log(AuditLogFilter {
+                action: Some(action),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(records.len(), 1, "{action:?}");
+        assert_eq!(records[0].action, action);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn audit_records_outcome() {
+    let ctrl = test_controller(test_service().await).await;
+    let admin = test_admin(&ctrl, "admin").await;
+
+    let user = ctrl
+        .create_user(
+            &admin,
+            "viewer".to_string(),
+            "password".to_string(),
+            Role::Viewer,
+            None,
+        )
+        .await
+        .unwrap();
+    // Users can't delete themselves
+    assert!(ctrl.delete_user(&admin, admin.id).await.is_err());
+    assert!(ctrl
+        .create_user(
+            &admin,
+            "short".to_string(),
+            "pass".to_string(),
+            Role::Viewer,
+            None,
+        )
+        .await
+        .is_err());
+
+    let records = ctrl
+        .get_audit_log(AuditLogFilter {
+            user_id: Some(admin.id),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(records.len(), 3);
+
+    // Records are returned from the newest ones
+    assert_eq!(records[0].action, AuditAction::CreateUser);
+    assert!(records[0].error.as_ref().unwrap().contains("password"));
+    assert_eq!(records[1].action, AuditAction::DeleteUser);
+    assert!(records[1].error.is_some());
+    assert_eq!(records[1].summary["user_id"], admin.id);
+    assert_eq!(records[2].action, AuditAction::CreateUser);
+    assert_eq!(records[2].error, None);
+    assert_eq!(records[2].summary["username"], user.username);
+    // Passwords aren't recorded
+    assert!(!records[2].summary.to_string().contains("password"));
+}
+
+/// Sensors of push devices created by tests
+#[cfg(test)]
+const TEST_PUSH_SENSORS: &str =
+    r#"[{"name": "room", "fields": [{"name": "temperature", "type": "float64"}]}]"#;
+
+#[cfg(test)]
+async fn test_push_device(ctrl: &TestController, user: &User, name: &str) -> PushDevice {
+    ctrl.create_push_device(user, name.to_string(), TEST_PUSH_SENSORS.to_string())
+        .await
+        .unwrap()
+}
+
+#[cfg(test)]
+async fn device_health(ctrl: &TestController, id: i32) -> Option<DeviceHealth> {
+    ctrl.get_device_info_list()
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|info| info.id.get_raw() == id)
+        .unwrap()
+        .health
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn watchdog_checks_devices() {
+    let ctrl = test_controller(test_service().await).await;
+    let admin = test_admin(&ctrl, "admin").await;
+    let device = test_push_device(&ctrl, &admin, "Watchdog").await;
+    let id = device.id.get_raw();
+
+    let check = || ctrl.check_devices_health();
+    let set_conf = |restart| {
+        ctrl.save_device_watchdog_conf(
+            &admin,
+            id,
+            WatchdogConf {
+                expected_interval: Some(1),
+                restart,
+            },
+        )
+    };
+
+    set_conf(false).await.unwrap();
+    check().await;
+    assert!(!device_health(&ctrl, id).await.unwrap().stale);
+
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    check().await;
+    assert!(device_health(&ctrl, id).await.unwrap().stale);
+
+    let row = IngestRow {
+        sensor: "room".to_string(),
+        data: serde_json::json!({"temperature": 21.5})
+            .as_object()
+            .unwrap()
+            .clone(),
+    };
+    ctrl.ingest_sensor_data(id, &device.ingest_token, vec![row])
+        .await
+        .unwrap();
+    check().await;
+    let health = device_health(&ctrl, id).await.unwrap();
+    assert!(!health.stale);
+    assert_eq!(health.msg_count, 1);
+
+    // A restarted device gets a full interval to send a message again
+    set_conf(true).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    check().await;
+    let health = device_health(&ctrl, id).await.unwrap();
+    assert!(!health.stale);
+    assert_eq!(health.restart_count, 1);
+
+    let records = ctrl
+        .get_audit_log(AuditLogFilter {
+            device_id: Some(id),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let events: Vec<_> = records
+        .iter()
+        .filter(|record| record.username == "watchdog")
+        .map(|record| (record.action, record.error.is_some()))
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            (AuditAction::RestartStaleDevice, false),
+            (AuditAction::DeviceStale, false),
+            (AuditAction::DeviceRecovered, false),
+            (AuditAction::DeviceStale, false),
+        ]
+    );
+    assert_eq!(records[1].summary["expected_interval"], 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn watchdog_restart_backoff() {
+    let handler = msg::Handler::new(
+        DeviceID::new(1),
+        test_service().await,
+        Sinks::default(),
+        tokio::runtime::Handle::current(),
+    );
+    let interval = std::time::Duration::from_millis(20);
+    let sleep = |n: u32| tokio::time::sleep(interval * n + std::time::Duration::from_millis(5));
+
+    sleep(1).await;
+    for restarts in 0..msg::MAX_RESTARTS {
+        assert_eq!(handler.check_stale(interval), (true, true));
+        assert!(!handler.is_faulted());
+        handler.mark_restarted();
+
+        // Every restart without a message doubles the time the device gets
+        let backoff = 2u32.pow(restarts + 1);
+        sleep(backoff / 2).await;
+        assert_eq!(handler.check_stale(interval), (false, false));
+        sleep(backoff / 2).await;
+    }
+    assert_eq!(handler.check_stale(interval), (true, true));
+    assert!(handler.is_faulted());
+    assert_eq!(handler.health().restart_count, msg::MAX_RESTARTS as u64);
+
+    // A message resets the backoff
+    handler.register_msg();
+    assert!(!handler.is_faulted());
+    assert_eq!(handler.check_stale(interval), (false, true));
+    sleep(1).await;
+    assert_eq!(handler.check_stale(interval), (true, true));
+}
+
+#[cfg(test)]
+async fn test_executor(timeout_ms: u64) -> ModuleExecutor<Module> {
+    ModuleExecutor::create::<Module>(
+        DeviceID::new(1),
+        super::interface::module::PUSH_MODULE_NAME.into(),
+        std::env::temp_dir().join("monisens-executor-test"),
+        std::time::Duration::from_millis(timeout_ms),
+    )
+    .await
+    .unwrap()
+}
+
+/// `hanging_call` returns a call which doesn't return until the sender is dropped
+#[cfg(test)]
+fn hanging_call() -> (
+    std::sync::mpsc::Sender<()>,
+    impl FnOnce(&mut Module) -> Result<(), CommonError> + Send + 'static,
+) {
+    let (tx, rx) = std::sync::mpsc::channel::<()>();
+    (tx, move |_: &mut Module| {
+        let _ = rx.recv();
+        Ok(())
+    })
+}
+
+#[tokio::test]
+async fn executor_timeout_and_recovery() {
+    let executor = test_executor(100).await;
+
+    assert_eq!(executor.call("value", |_| Ok(42)).await.unwrap(), 42);
+    let err = executor
+        .call("error", |_| -> Result<(), _> {
+            Err(CommonError::new(ErrorType::InvalidInput, "bad call"))
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.error_type, ErrorType::InvalidInput);
+    assert_eq!(err.msg, "bad call");
+    assert!(!executor.is_faulted());
+
+    // A call overruns the timeout
+    let (release, call) = hanging_call();
+    let err = executor.call("hang", call).await.unwrap_err();
+    assert_eq!(err.error_type, ErrorType::Timeout);
+    assert!(executor.is_faulted());
+
+    // Calls are rejected while the device is faulted
+    let err = executor.call("value", |_| Ok(())).await.unwrap_err();
+    assert_eq!(err.error_type, ErrorType::FailedPrecondition);
+
+    // The device recovers when the hanging call returns
+    drop(release);
+    for _ in 0..100 {
+        if !executor.is_faulted() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert!(!executor.is_faulted());
+    assert_eq!(executor.call("value", |_| Ok(1)).await.unwrap(), 1);
+
+    executor.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn executor_close() {
+    let executor = test_executor(100).await;
+    executor.close().await.unwrap();
+
+    // A module stuck in a call isn't dropped in time
+    let executor = test_executor(100).await;
+    let (release, call) = hanging_call();
+    assert!(executor.call("hang", call).await.is_err());
+    let err = executor.close().await.unwrap_err();
+    assert_eq!(err.error_type, ErrorType::Timeout);
+    drop(release);
+
+    // Modules which failed to be created aren't served
+    let res = ModuleExecutor::<Module>::create::<Module>(
+        DeviceID::new(2),
+        "builtin:unknown".into(),
+        std::env::temp_dir(),
+        std::time::Duration::from_millis(100),
+    )
+    .await;
+    assert_eq!(res.err().unwrap().error_type, ErrorType::NotFound);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn import_saves_batches() {
+    let ctrl = test_controller(test_service().await).await;
+    let admin = test_admin(&ctrl, "admin").await;
+    let device = test_push_device(&ctrl, &admin, "Import").await;
+    let id = device.id.get_raw();
+
+    // More rows than in a single batch
+    let mut csv = "timestamp,temperature\n".to_string();
+    for i in 0..1500 {
+        csv += &format!("{},{i}.5\n", 1760788800 + i);
+    }
+    csv += "1760798800,hot\n";
+
+    let payload = |dry_run| ImportPayload {
+        device_id: id,
+        sensor: "room".to_string(),
+        format: ImportFormat::Csv,
+        mapping: Default::default(),
+        precision: TimestampPrecision::Seconds,
+        dry_run,
+    };
+    let import = |dry_run| {
+        ctrl.import_sensor_data(
+            &admin,
+            payload(dry_run),
+            std::io::Cursor::new(csv.clone().into_bytes()),
+        )
+    };
+    let count = || async {
+        ctrl.get_sensor_data(GetSensorDataPayload {
+            device_id: id,
+            sensor: "room".to_string(),
+            fields: vec!["temperature".to_string()],
+            sort: Sort {
+                field: "timestamp".to_string(),
+                order: SortDir::ASC,
+            },
+            from: None,
+            limit: Some(10000),
+        })
+        .await
+        .unwrap()
+        .len()
+    };
+
+    let report = import(true).await.unwrap();
+    assert_eq!(
+        (report.total, report.imported, report.invalid),
+        (1501, 1500, 1)
+    );
+    assert_eq!(count().await, 0);
+
+    let report = import(false).await.unwrap();
+    assert_eq!(
+        (report.total, report.imported, report.invalid),
+        (1501, 1500, 1)
+    );
+    assert_eq!(report.errors[0].line, 1502);
+    assert_eq!(count().await, 1500);
+
+    // A file which can't be parsed is rejected
+    let res = ctrl
+        .import_sensor_data(
+            &admin,
+            payload(false),
+            std::io::Cursor::new(b"temperature\n1\n".to_vec()),
+        )
+        .await;
+    assert!(matches!(res, Err(ControllerError::IncorrectPayload(_))));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sqlite_storage() {
+    let repo = test_repo().await;
+    let ctrl = test_controller(Service::new(repo.clone()).await.unwrap()).await;
+    let admin = test_admin(&ctrl, "admin").await;
+
+    let fields = [
+        ("i16", SensorDataType::Int16),
+        ("i32", SensorDataType::Int32),
+        ("i64", SensorDataType::Int64),
+        ("f32", SensorDataType::Float32),
+        ("f64", SensorDataType::Float64),
+        ("text", SensorDataType::String),
+        ("doc", SensorDataType::JSON),
+    ];
+    let sensors = serde_json::json!([{
+        "name": "all",
+        "fields": [
+            {"name": "i16", "type": "int16"},
+            {"name": "i32", "type": "int32"},
+            {"name": "i64", "type": "int64"},
+            {"name": "f32", "type": "float32"},
+            {"name": "f64", "type": "float64"},
+            {"name": "text", "type": "string"},
+            {"name": "doc", "type": "json"},
+        ],
+    }]);
+    let device = ctrl
+        .create_push_device(&admin, "SQLite".to_string(), sensors.to_string())
+        .await
+        .unwrap();
+
+    let rows = (0..5)
+        .map(|i| IngestRow {
+            sensor: "all".to_string(),
+            data: serde_json::json!({
+                // Rows are saved in the reverse order of their time
+                "timestamp": format!("2026-10-18T12:00:0{}Z", 4 - i),
+                "i16": i,
+                "i32": i * 1000,
+                "i64": i * 1_000_000_000_i64,
+                "f32": i as f32 + 0.5,
+                "f64": i as f64 / 4.0,
+                "text": format!("row {i}"),
+                "doc": {"i": i},
+            })
+            .as_object()
+            .unwrap()
+            .clone(),
+        })
+        .collect();
+    ctrl.ingest_sensor_data(device.id.get_raw(), &device.ingest_token, rows)
+        .await
+        .unwrap();
+
+    // Types of sensor fields are recovered from declared types of table columns
+    let svc = Service::new(repo.clone()).await.unwrap();
+    let sensor = svc
+        .get_device_sensor_info(device.id)
+        .unwrap()
+        .into_iter()
+        .find(|s| s.name == "all")
+        .unwrap();
+    assert_eq!(sensor.data.len(), fields.len() + 1);
+    for (name, typ) in fields.iter() {
+        let field = sensor.data.iter().find(|f| f.name == *name).unwrap();
+        assert_eq!(field.typ, *typ, "field {name}");
+    }
+
+    // Data is read by pages of the emulated cursor and cast to the field types
+    let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+    svc.export_sensor_data(
+        device.id,
+        "all".to_string(),
+        fields.iter().map(|(name, _)| name.to_string()).collect(),
+        super::model::SensorDataRange {
+            field: "timestamp".to_string(),
+            from: None,
+            to: None,
+        },
+        2,
+        tx,
+    )
+    .await
+    .unwrap();
+
+    let mut batches = Vec::new();
+    while let Some(batch) = rx.recv().await {
+        batches.push(batch);
+    }
+    assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), [2, 2, 1]);
+
+    let rows: Vec<_> = batches.into_iter().flatten().collect();
+    for row in rows.iter() {
+        for (name, typ) in fields.iter() {
+            let data = row.iter().find(|d| d.name == *name).unwrap();
+            assert_eq!(data.data.typ(), *typ, "field {name}");
+        }
+    }
+    let value = |i: usize, name: &str| {
+        rows[i]
+            .iter()
+            .find(|d| d.name == name)
+            .map(|d| format!("{:?}", d.data))
+            .unwrap()
+    };
+    assert_eq!(value(1, "i16"), "Int16(3)");
+    assert_eq!(value(0, "i64"), "Int64(4000000000)");
+    assert_eq!(value(3, "f32"), "Float32(1.5)");
+    assert_eq!(value(2, "text"), "String(\"row 2\")");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn login_sessions() {
+    let svc = test_service().await;
+    let ctrl = test_controller(svc.clone()).await;
+    let admin = test_admin(&ctrl, "admin").await;
+
+    let session = ctrl
+        .login("admin", "password".to_string(), "10.0.0.1")
+        .await
+        .unwrap();
+    assert_eq!(session.user.id, admin.id);
+    assert_eq!(
+        ctrl.authenticate(&session.token).await.unwrap().id,
+        admin.id
+    );
+
+    // Wrong password and unknown user fail the same way
+    for (username, password) in [("admin", "wrong"), ("nobody", "password")] {
+        let err = ctrl
+            .login(username, password.to_string(), "10.0.0.1")
+            .await
+            .err()
+            .unwrap();
+        assert!(
+            matches!(err, ControllerError::CommonError(ref e) if e.error_type == ErrorType::Unauthenticated),
+            "{username}: {err:?}"
+        );
+    }
+
+    let err = ctrl.authenticate("unknown-token").await.err().unwrap();
+    assert!(
+        matches!(err, ControllerError::CommonError(ref e) if e.error_type == ErrorType::Unauthenticated)
+    );
+
+    // Expired session is rejected
+    svc.save_session(Session {
+        token_hash: crate::tool::secret::hash_token("expired-token"),
+        user_id: admin.id,
+        expires_at: chrono::Utc::now().naive_utc() - chrono::Duration::minutes(1),
+    })
+    .await
+    .unwrap();
+    let err = ctrl.authenticate("expired-token").await.err().unwrap();
+    assert!(
+        matches!(err, ControllerError::CommonError(ref e) if e.error_type == ErrorType::Unauthenticated)
+    );
+
+    ctrl.logout(&session.token).await.unwrap();
+    assert!(ctrl.authenticate(&session.token).await.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn login_backoff() {
+    let ctrl = test_controller(test_service().await).await;
+    test_admin(&ctrl, "admin").await;
+
+    for _ in 0..5 {
+        ctrl.login("admin", "wrong".to_string(), "10.0.0.1")
+            .await
+            .err()
+            .unwrap();
+    }
+
+    // Even the right password is rejected until the delay passes
+    let err = ctrl
+        .login("admin", "password".to_string(), "10.0.0.1")
+        .await
+        .err()
+        .unwrap();
+    assert!(
+        matches!(err, ControllerError::CommonError(ref e) if e.error_type == ErrorType::ResourceExhausted),
+        "{err:?}"
+    );
+
+    // Other clients aren't affected
+    ctrl.login("admin", "password".to_string(), "10.0.0.2")
+        .await
+        .unwrap();
+}
+
+#[test]
+fn login_limiter_delays() {
+    let limiter = LoginLimiter::default();
+    let start = std::time::Instant::now();
+    let secs = std::time::Duration::from_secs;
+
+    for i in 0..4 {
+        limiter.register_failure("client", "user", start + secs(i));
+        assert_eq!(limiter.check("client", "user", start + secs(i)), None);
+    }
+
+    // The delay doubles with every failure over the free attempts
+    limiter.register_failure("client", "user", start + secs(10));
+    assert_eq!(
+        limiter.check("client", "user", start + secs(10)),
+        Some(secs(1))
+    );
+    assert_eq!(limiter.check("client", "user", start + secs(11)), None);
+    limiter.register_failure("client", "user", start + secs(11));
+    assert_eq!(
+        limiter.check("client", "user", start + secs(11)),
+        Some(secs(2))
+    );
+    assert_eq!(limiter.check("other", "user", start + secs(11)), None);
+
+    // The delay is capped
+    for _ in 0..30 {
+        limiter.register_failure("client", "user", start + secs(20));
+    }
+    assert_eq!(
+        limiter.check("client", "user", start + secs(20)),
+        Some(secs(15 * 60))
+    );
+
+    limiter.reset("client", "user");
+    assert_eq!(limiter.check("client", "user", start + secs(20)), None);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn delete_device_data() {
+    let repo = test_repo().await;
+    let svc = Service::new(repo.clone()).await.unwrap();
+    let ctrl = test_controller(svc.clone()).await;
+    let admin = test_admin(&ctrl, "admin").await;
+    let deleted = test_push_device(&ctrl, &admin, "Deleted").await.id;
+    let kept = test_push_device(&ctrl, &admin, "Kept").await.id;
+
+    for id in [deleted, kept] {
+        svc.save_monitor_conf(MonitorConf {
+            id: 0,
+            device_id: id.get_raw(),
+            sensor: "room".to_string(),
+            typ: MonitorType::Log,
+            config: MonitorTypeConf::Log(MonitorLogConf {
+                fields: vec!["temperature".to_string()],
+                sort_field: "timestamp".to_string(),
+                sort_direction: SortDir::DESC,
+                limit: 10,
+            }),
+        })
+        .await
+        .unwrap();
+        svc.save_device_command_log(DeviceCommandLog {
+            device_id: id,
+            command: Command {
+                name: "reset".to_string(),
+                args: vec![],
+            },
+            error: None,
+        })
+        .await
+        .unwrap();
+    }
+
+    svc.delete_device(deleted).await.unwrap();
+
+    assert!(!svc.get_device_ids().unwrap().contains(&deleted));
+    let monitor_confs = |id: DeviceID| {
+        svc.get_monitor_conf_list(MonitorConfListFilter {
+            device_id: id.get_raw(),
+        })
+    };
+    assert!(monitor_confs(deleted).await.unwrap().is_empty());
+    assert_eq!(monitor_confs(kept).await.unwrap().len(), 1);
+
+    // Only the other device's command log is left to delete
+    let repo = &repo;
+    let exec = |sql: String| async move { repo.exec_raw(&sql).await };
+    let delete_command_log = |id: DeviceID| {
+        exec(format!(
+            "DELETE FROM device_command_log WHERE device_id = {}",
+            id.get_raw()
+        ))
+    };
+    assert_eq!(delete_command_log(deleted).await.unwrap(), 0);
+    assert_eq!(delete_command_log(kept).await.unwrap(), 1);
+
+    let select_sensor = |id: DeviceID| exec(format!("SELECT * FROM \"{}__room\"", id.get_raw()));
+    assert!(select_sensor(deleted).await.is_err());
+    assert!(select_sensor(kept).await.is_ok());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn stopped_device_is_not_started() {
+    let svc = test_service().await;
+    let ctrl = test_controller(svc.clone()).await;
+    let admin = test_admin(&ctrl, "admin").await;
+    let id = test_push_device(&ctrl, &admin, "Stopped").await.id;
+    assert!(device_health(&ctrl, id.get_raw()).await.is_some());
+    drop(ctrl);
+
+    svc.save_device_stopped(id, true).await.unwrap();
+    let ctrl = test_controller(svc.clone()).await;
+    assert!(device_health(&ctrl, id.get_raw()).await.is_none());
+    assert!(
+        svc.get_device_info_list()
+            .unwrap()
+            .into_iter()
+            .find(|info| info.id == id)
+            .unwrap()
+            .stopped
+    );
+    drop(ctrl);
+
+    svc.save_device_stopped(id, false).await.unwrap();
+    let ctrl = test_controller(svc).await;
+    assert!(device_health(&ctrl, id.get_raw()).await.is_some());
+}
+
+#[tokio::test]
+async fn pending_migrations() {
+    let repo = test_repo().await;
+
+    let pending = repo.pending_migrations().await.unwrap();
+    assert!(!pending.is_empty());
+    assert!(pending.windows(2).all(|m| m[0].version < m[1].version));
+
+    repo.migrate().await.unwrap();
+    assert!(repo.pending_migrations().await.unwrap().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn inspect_invalid_module() {
+    let svc = test_service().await;
+    let ctrl = test_controller(svc.clone()).await;
+
+    let file = std::env::temp_dir().join(format!("monisens-not-a-module-{}", std::process::id()));
+    std::fs::write(&file, "not a library").unwrap();
+    let scratch_dirs = || {
+        let prefix = format!("monisens-inspect-{}-", std::process::id());
+        std::fs::read_dir(std::env::temp_dir())
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with(&prefix)
+            })
+            .count()
+    };
+
+    let inspection = ctrl.inspect_module(&file).await.unwrap();
+    assert!(inspection
+        .error
+        .unwrap()
+        .contains("failed to load dynamic library"));
+    assert_eq!(inspection.version, None);
+    assert!(inspection.functions.is_empty());
+    assert!(inspection.conn_info.is_none());
+
+    // The library is inspected in a scratch dir, no device is created for it
+    assert!(svc.get_device_ids().unwrap().is_empty());
+    assert_eq!(scratch_dirs(), 0);
+    assert!(file.is_file());
+
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn controller_shutdown() {
+    let svc = test_service().await;
+    let ctrl = test_controller(svc.clone()).await;
+    let admin = test_admin(&ctrl, "admin").await;
+    let running = test_push_device(&ctrl, &admin, "ShutdownRunning").await.id;
+    let stopped = test_push_device(&ctrl, &admin, "ShutdownStopped").await.id;
+    drop(ctrl);
+
+    svc.save_device_stopped(stopped, true).await.unwrap();
+    let ctrl = test_controller(svc).await;
+
+    // Only the running module is stopped, but both are dropped
+    let report = ctrl.shutdown().await;
+    assert_eq!(
+        (
+            report.devices,
+            report.stopped,
+            report.failed,
+            report.unloaded
+        ),
+        (2, 1, 0, 2)
+    );
+    assert!(device_health(&ctrl, running.get_raw()).await.is_none());
+
+    // Devices are gone, so a repeated shutdown has nothing to do
+    let report = ctrl.shutdown().await;
+    assert_eq!(report.devices, 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn shutdown_hanging_device() {
+    let svc = test_service().await;
+    let device = |module| {
+        std::sync::Arc::new(tokio::sync::Mutex::new(Device {
+            id: DeviceID::new(1),
+            module,
+            msg_handler: Some(msg::Handler::new(
+                DeviceID::new(1),
+                svc.clone(),
+                Sinks::default(),
+                tokio::runtime::Handle::current(),
+            )),
+        }))
+    };
+
+    // A faulted module fails to stop and isn't dropped while it hangs
+    let executor = test_executor(100).await;
+    let (release, call) = hanging_call();
+    assert!(executor.call("hang", call).await.is_err());
+
+    let mut report = ShutdownReport::default();
+    shutdown_device(1, device(executor), &mut report).await;
+    assert_eq!(
+        (
+            report.devices,
+            report.stopped,
+            report.failed,
+            report.unloaded
+        ),
+        (1, 0, 1, 0)
+    );
+    drop(release);
+
+    // A device held by a request is dropped when the request finishes
+    let device = device(test_executor(100).await);
+    let held = device.clone();
+
+    let mut report = ShutdownReport::default();
+    shutdown_device(1, device, &mut report).await;
+    assert_eq!(
+        (
+            report.devices,
+            report.stopped,
+            report.failed,
+            report.unloaded
+        ),
+        (1, 1, 0, 0)
+    );
+    drop(held);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn catalog_modules() {
+    let repo = test_repo().await;
+    let svc = Service::new(repo.clone()).await.unwrap();
+    let ctrl = test_controller(svc.clone()).await;
+    let admin = test_admin(&ctrl, "admin").await;
+    let failed_precondition = |res: Result<(), ControllerError>| matches!(res, Err(ControllerError::CommonError(ref e)) if e.error_type == ErrorType::FailedPrecondition);
+    let find = |list: Vec<CatalogModule>, id: i32| list.into_iter().find(|m| m.id == id);
+
+    let lib = format!("catalog module {}", std::process::id()).into_bytes();
+    let module = ctrl
+        .add_catalog_module(&admin, "Catalog".to_string(), &mut lib.as_slice())
+        .await
+        .unwrap();
+    assert_eq!(module.size, lib.len() as i64);
+    let listed = find(ctrl.get_catalog_module_list().await.unwrap(), module.id).unwrap();
+    assert_eq!(listed.hash, module.hash);
+
+    // The same library under another name is the same catalog entry
+    let dup = ctrl
+        .add_catalog_module(&admin, "Catalog Copy".to_string(), &mut lib.as_slice())
+        .await
+        .unwrap();
+    assert_eq!(dup.id, module.id);
+    assert_eq!(dup.hash, module.hash);
+    let list = ctrl.get_catalog_module_list().await.unwrap();
+    assert_eq!(list.iter().filter(|m| m.hash == module.hash).count(), 1);
+
+    let builtin = list.iter().find(|m| m.hash == "builtin:push").unwrap();
+    assert!(failed_precondition(
+        ctrl.delete_catalog_module(&admin, builtin.id).await
+    ));
+
+    let device = svc
+        .start_device_init_from_catalog("Catalog User".to_string(), module.id)
+        .await
+        .unwrap();
+    assert!(failed_precondition(
+        ctrl.delete_catalog_module(&admin, module.id).await
+    ));
+
+    // The foreign key keeps a referenced module even without the check
+    let err = repo
+        .exec_raw(&format!(
+            "DELETE FROM module_catalog WHERE id = {}",
+            module.id
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(err.get_ctrl_type(), ErrorType::FailedPrecondition);
+    assert!(find(ctrl.get_catalog_module_list().await.unwrap(), module.id).is_some());
+
+    svc.delete_device(device.id).await.unwrap();
+    ctrl.delete_catalog_module(&admin, module.id).await.unwrap();
+    assert!(find(ctrl.get_catalog_module_list().await.unwrap(), module.id).is_none());
+}
+
+/// `UpgradeModule` loads the built-in module named in its "library" file, so tests can
+/// upgrade devices without building dynamic libraries. A file with the ` fail-start` suffix
+/// gives a module that can't be started
+#[cfg(test)]
+struct UpgradeModule {
+    module: Module,
+    fail_start: bool,
+}
+
+#[cfg(test)]
+impl IModule for UpgradeModule {
+    fn obtain_device_conn_info(&mut self) -> Result<super::model::ConfInfo, CommonError> {
+        self.module.obtain_device_conn_info()
+    }
+
+    fn connect_device(&mut self, confs: Vec<super::model::ConfEntry>) -> Result<(), CommonError> {
+        self.module.connect_device(confs)
+    }
+
+    fn obtain_device_conf_info(&mut self) -> Result<super::model::ConfInfo, CommonError> {
+        self.module.obtain_device_conf_info()
+    }
+
+    fn configure_device(&mut self, confs: Vec<super::model::ConfEntry>) -> Result<(), CommonError> {
+        self.module.configure_device(confs)
+    }
+
+    fn obtain_sensor_type_infos(&mut self) -> Result<Vec<super::model::Sensor>, CommonError> {
+        self.module.obtain_sensor_type_infos()
+    }
+
+    fn obtain_command_infos(&mut self) -> Result<Vec<CommandInfo>, CommonError> {
+        self.module.obtain_command_infos()
+    }
+
+    fn send_command(&mut self, cmd: &Command) -> Result<(), CommonError> {
+        self.module.send_command(cmd)
+    }
+
+    fn start<H: MsgHandler + 'static>(&mut self, msg_handler: H) -> Result<(), CommonError> {
+        if self.fail_start {
+            return Err(CommonError::new(
+                ErrorType::Internal,
+                "module failed to start",
+            ));
+        }
+
+        self.module.start(msg_handler)
+    }
+
+    fn stop(&mut self) -> Result<(), CommonError> {
+        self.module.stop()
+    }
+
+    fn module_info(&self) -> Option<super::model::ModuleInfo> {
+        self.module.module_info()
+    }
+}
+
+#[cfg(test)]
+impl IModuleFactory<UpgradeModule> for UpgradeModule {
+    fn create_module<P: AsRef<std::path::Path>>(
+        mod_path: P,
+        data_dir: P,
+    ) -> Result<UpgradeModule, CommonError> {
+        let spec = match mod_path.as_ref().to_str() {
+            Some(name) if is_builtin_module(name) => name.to_string(),
+            _ => std::fs::read_to_string(mod_path).map_err(|err| {
+                CommonError::new(ErrorType::IO, "failed to read module file").with_source(err)
+            })?,
+        };
+        let (name, fail_start) = match spec.strip_suffix(" fail-start") {
+            Some(name) => (name.to_string(), true),
+            None => (spec, false),
+        };
+
+        Ok(UpgradeModule {
+            module: Module::create_module(std::path::Path::new(&name), data_dir.as_ref())?,
+            fail_start,
+        })
+    }
+
+    fn inspect_module<P: AsRef<std::path::Path>>(
+        mod_path: P,
+    ) -> Result<super::model::ModuleInspection, CommonError> {
+        Module::inspect_module(mod_path)
+    }
+}
+
+#[cfg(test)]
+type UpgradeController = Controller<Service, UpgradeModule, UpgradeModule, Sinks>;
+
+#[cfg(test)]
+async fn is_running(ctrl: &UpgradeController, id: DeviceID) -> bool {
+    ctrl.get_device_info_list()
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|info| info.id == id)
+        .unwrap()
+        .health
+        .is_some()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn device_module_upgrade() {
+    let svc = test_service().await;
+    let new_controller = || async {
+        let ctrl: UpgradeController = Controller::new(
+            tokio::runtime::Handle::current(),
+            svc.clone(),
+            Sinks::default(),
+            std::time::Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+
+        ctrl
+    };
+    let ctrl = new_controller().await;
+    ctrl.bootstrap_admin("admin".to_string(), "password".to_string())
+        .await
+        .unwrap();
+    let admin = ctrl.get_user_list().await.unwrap().remove(0);
+    let id = ctrl
+        .create_push_device(
+            &admin,
+            "Upgraded".to_string(),
+            TEST_PUSH_SENSORS.to_string(),
+        )
+        .await
+        .unwrap()
+        .id;
+    let module_hash = || svc.get_device_full_info(id).unwrap().module_hash;
+    // The simulator declares other sensors than the push device
+    let res = ctrl
+        .upgrade_device_module(&admin, id.get_raw(), &mut b"builtin:simulator".as_slice())
+        .await;
+    assert!(matches!(res, Err(ControllerError::IncorrectPayload(_))));
+    assert!(is_running(&ctrl, id).await);
+    assert_eq!(module_hash().as_deref(), Some("builtin:push"));
+
+    // The new module fails to start, so the device goes back to the built-in one
+    assert!(ctrl
+        .upgrade_device_module(
+            &admin,
+            id.get_raw(),
+            &mut b"builtin:push fail-start".as_slice()
+        )
+        .await
+        .is_err());
+    assert!(is_running(&ctrl, id).await);
+    assert_eq!(module_hash().as_deref(), Some("builtin:push"));
+    drop(ctrl);
+    let ctrl = new_controller().await;
+    assert!(is_running(&ctrl, id).await);
+
+    ctrl.upgrade_device_module(&admin, id.get_raw(), &mut b"builtin:push".as_slice())
+        .await
+        .unwrap();
+    assert!(is_running(&ctrl, id).await);
+    assert_eq!(module_hash(), None);
+
+    // The device now starts with its own module copy
+    drop(ctrl);
+    let ctrl = new_controller().await;
+    assert!(is_running(&ctrl, id).await);
+}
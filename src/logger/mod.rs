@@ -1,5 +1,6 @@
 use core::fmt;
 use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
 use std::{
     fmt::Debug,
     sync::{Arc, Mutex, RwLock},
@@ -31,7 +32,13 @@ pub fn register_writer(writer: LogWriterType) {
     LOGGER.clone().register_writer(writer);
 }
 
-#[derive(Clone, Debug)]
+/// `set_level` drops messages with a lower level than `level`
+pub fn set_level(level: LogLevel) {
+    *LOGGER.level.write().unwrap() = level;
+}
+
+#[derive(Clone, Debug, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum LogLevel {
     Info,
     Warn,
@@ -99,12 +106,14 @@ pub type LogWriterType = Arc<Mutex<dyn LogWriter + Send>>;
 #[derive(Clone)]
 struct Logger {
     log_writers: Arc<RwLock<Vec<LogWriterType>>>,
+    level: Arc<RwLock<LogLevel>>,
 }
 
 impl Logger {
     fn new() -> Self {
         Self {
             log_writers: Arc::new(RwLock::new(vec![StdLogger::new()])),
+            level: Arc::new(RwLock::new(LogLevel::Info)),
         }
     }
 
@@ -113,6 +122,10 @@ impl Logger {
     }
 
     fn log(&self, level: LogLevel, msg: &str, kvs: Vec<KV>) {
+        if level < *self.level.read().unwrap() {
+            return;
+        }
+
         for w in self.log_writers.read().unwrap().iter() {
             w.lock().unwrap().log(&level, &msg, &kvs);
         }
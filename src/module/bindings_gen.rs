@@ -1034,6 +1034,375 @@ fn bindgen_test_layout_Message() {
 pub type handle_msg_func = ::std::option::Option<
     unsafe extern "C" fn(handler: *mut ::std::os::raw::c_void, msg_data: Message),
 >;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CommandArgInfo {
+    pub name: *mut ::std::os::raw::c_char,
+    pub typ: SensorDataType,
+    pub required: bool,
+}
+#[test]
+fn bindgen_test_layout_CommandArgInfo() {
+    const UNINIT: ::std::mem::MaybeUninit<CommandArgInfo> = ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<CommandArgInfo>(),
+        16usize,
+        concat!("Size of: ", stringify!(CommandArgInfo))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<CommandArgInfo>(),
+        8usize,
+        concat!("Alignment of ", stringify!(CommandArgInfo))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).name) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(CommandArgInfo),
+            "::",
+            stringify!(name)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).typ) as usize - ptr as usize },
+        8usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(CommandArgInfo),
+            "::",
+            stringify!(typ)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).required) as usize - ptr as usize },
+        12usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(CommandArgInfo),
+            "::",
+            stringify!(required)
+        )
+    );
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CommandInfo {
+    pub name: *mut ::std::os::raw::c_char,
+    pub description: *mut ::std::os::raw::c_char,
+    pub arg_infos_len: i32,
+    pub arg_infos: *mut CommandArgInfo,
+}
+#[test]
+fn bindgen_test_layout_CommandInfo() {
+    const UNINIT: ::std::mem::MaybeUninit<CommandInfo> = ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<CommandInfo>(),
+        32usize,
+        concat!("Size of: ", stringify!(CommandInfo))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<CommandInfo>(),
+        8usize,
+        concat!("Alignment of ", stringify!(CommandInfo))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).name) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(CommandInfo),
+            "::",
+            stringify!(name)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).description) as usize - ptr as usize },
+        8usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(CommandInfo),
+            "::",
+            stringify!(description)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).arg_infos_len) as usize - ptr as usize },
+        16usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(CommandInfo),
+            "::",
+            stringify!(arg_infos_len)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).arg_infos) as usize - ptr as usize },
+        24usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(CommandInfo),
+            "::",
+            stringify!(arg_infos)
+        )
+    );
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CommandInfos {
+    pub command_infos_len: i32,
+    pub command_infos: *mut CommandInfo,
+}
+#[test]
+fn bindgen_test_layout_CommandInfos() {
+    const UNINIT: ::std::mem::MaybeUninit<CommandInfos> = ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<CommandInfos>(),
+        16usize,
+        concat!("Size of: ", stringify!(CommandInfos))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<CommandInfos>(),
+        8usize,
+        concat!("Alignment of ", stringify!(CommandInfos))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).command_infos_len) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(CommandInfos),
+            "::",
+            stringify!(command_infos_len)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).command_infos) as usize - ptr as usize },
+        8usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(CommandInfos),
+            "::",
+            stringify!(command_infos)
+        )
+    );
+}
+pub type command_infos_callback = ::std::option::Option<
+    unsafe extern "C" fn(obj: *mut ::std::os::raw::c_void, infos: *mut CommandInfos),
+>;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CommandArg {
+    pub name: *mut ::std::os::raw::c_char,
+    pub typ: SensorDataType,
+    pub data: *mut ::std::os::raw::c_void,
+}
+#[test]
+fn bindgen_test_layout_CommandArg() {
+    const UNINIT: ::std::mem::MaybeUninit<CommandArg> = ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<CommandArg>(),
+        24usize,
+        concat!("Size of: ", stringify!(CommandArg))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<CommandArg>(),
+        8usize,
+        concat!("Alignment of ", stringify!(CommandArg))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).name) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(CommandArg),
+            "::",
+            stringify!(name)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).typ) as usize - ptr as usize },
+        8usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(CommandArg),
+            "::",
+            stringify!(typ)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).data) as usize - ptr as usize },
+        16usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(CommandArg),
+            "::",
+            stringify!(data)
+        )
+    );
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct Command {
+    pub name: *mut ::std::os::raw::c_char,
+    pub args: *mut CommandArg,
+    pub args_len: i32,
+}
+#[test]
+fn bindgen_test_layout_Command() {
+    const UNINIT: ::std::mem::MaybeUninit<Command> = ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<Command>(),
+        24usize,
+        concat!("Size of: ", stringify!(Command))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<Command>(),
+        8usize,
+        concat!("Alignment of ", stringify!(Command))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).name) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(Command),
+            "::",
+            stringify!(name)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).args) as usize - ptr as usize },
+        8usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(Command),
+            "::",
+            stringify!(args)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).args_len) as usize - ptr as usize },
+        16usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(Command),
+            "::",
+            stringify!(args_len)
+        )
+    );
+}
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum ModuleCapability {
+    ModuleCapabilityCommands = 1,
+    ModuleCapabilityHotReconfigure = 2,
+    ModuleCapabilityPersistedConfig = 4,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct ModuleInfo {
+    pub name: *mut ::std::os::raw::c_char,
+    pub vendor: *mut ::std::os::raw::c_char,
+    pub version: *mut ::std::os::raw::c_char,
+    pub description: *mut ::std::os::raw::c_char,
+    pub os: *mut ::std::os::raw::c_char,
+    pub arch: *mut ::std::os::raw::c_char,
+    pub capabilities: u32,
+}
+#[test]
+fn bindgen_test_layout_ModuleInfo() {
+    const UNINIT: ::std::mem::MaybeUninit<ModuleInfo> = ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<ModuleInfo>(),
+        56usize,
+        concat!("Size of: ", stringify!(ModuleInfo))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<ModuleInfo>(),
+        8usize,
+        concat!("Alignment of ", stringify!(ModuleInfo))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).name) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(ModuleInfo),
+            "::",
+            stringify!(name)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).vendor) as usize - ptr as usize },
+        8usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(ModuleInfo),
+            "::",
+            stringify!(vendor)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).version) as usize - ptr as usize },
+        16usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(ModuleInfo),
+            "::",
+            stringify!(version)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).description) as usize - ptr as usize },
+        24usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(ModuleInfo),
+            "::",
+            stringify!(description)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).os) as usize - ptr as usize },
+        32usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(ModuleInfo),
+            "::",
+            stringify!(os)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).arch) as usize - ptr as usize },
+        40usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(ModuleInfo),
+            "::",
+            stringify!(arch)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).capabilities) as usize - ptr as usize },
+        48usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(ModuleInfo),
+            "::",
+            stringify!(capabilities)
+        )
+    );
+}
+pub type module_info_callback = ::std::option::Option<
+    unsafe extern "C" fn(obj: *mut ::std::os::raw::c_void, info: *mut ModuleInfo),
+>;
 pub type mod_version_fn = ::std::option::Option<unsafe extern "C" fn() -> u8>;
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
@@ -1188,3 +1557,16 @@ fn bindgen_test_layout_Functions() {
     );
 }
 pub type functions_fn = ::std::option::Option<unsafe extern "C" fn() -> Functions>;
+pub type obtain_command_infos_fn = ::std::option::Option<
+    unsafe extern "C" fn(
+        handler: *mut ::std::os::raw::c_void,
+        obj: *mut ::std::os::raw::c_void,
+        callback: command_infos_callback,
+    ) -> u8,
+>;
+pub type send_command_fn = ::std::option::Option<
+    unsafe extern "C" fn(handler: *mut ::std::os::raw::c_void, cmd: *mut Command) -> u8,
+>;
+pub type module_info_fn = ::std::option::Option<
+    unsafe extern "C" fn(obj: *mut ::std::os::raw::c_void, callback: module_info_callback),
+>;
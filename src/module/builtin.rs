@@ -0,0 +1,224 @@
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::controller;
+use crate::controller::error::{CommonError, ErrorType};
+
+/// Every sensor of a built-in module gets a field with the time of a message
+pub const TIMESTAMP_FIELD: &str = "timestamp";
+
+pub fn timestamp_entry() -> (String, controller::SensorDataEntry) {
+    (
+        TIMESTAMP_FIELD.to_string(),
+        controller::SensorDataEntry {
+            name: TIMESTAMP_FIELD.to_string(),
+            typ: controller::SensorDataType::Timestamp,
+        },
+    )
+}
+
+pub fn timestamp_data() -> controller::SensorData {
+    controller::SensorData {
+        name: TIMESTAMP_FIELD.to_string(),
+        data: controller::SensorDataTypeValue::Timestamp(chrono::Utc::now().naive_utc()),
+    }
+}
+
+/// `FieldType` is a type of a sensor field declared in a built-in module's configuration
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldType {
+    Int16,
+    Int32,
+    Int64,
+    Float32,
+    Float64,
+    Timestamp,
+    String,
+    Json,
+}
+
+impl From<FieldType> for controller::SensorDataType {
+    fn from(value: FieldType) -> Self {
+        match value {
+            FieldType::Int16 => controller::SensorDataType::Int16,
+            FieldType::Int32 => controller::SensorDataType::Int32,
+            FieldType::Int64 => controller::SensorDataType::Int64,
+            FieldType::Float32 => controller::SensorDataType::Float32,
+            FieldType::Float64 => controller::SensorDataType::Float64,
+            FieldType::Timestamp => controller::SensorDataType::Timestamp,
+            FieldType::String => controller::SensorDataType::String,
+            FieldType::Json => controller::SensorDataType::JSON,
+        }
+    }
+}
+
+/// `load_conf` reads module's configuration saved by [`save_conf`] to its data dir.
+/// `None` is returned if the module hasn't been configured yet.
+pub fn load_conf<T: DeserializeOwned>(
+    data_dir: &Path,
+    file_name: &str,
+) -> Result<Option<T>, CommonError> {
+    let path = data_dir.join(file_name);
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let data = fs::read(path).map_err(|err| {
+        CommonError::new(ErrorType::IO, "failed to read module configuration").with_source(err)
+    })?;
+
+    serde_json::from_slice(&data).map(Some).map_err(|err| {
+        CommonError::new(ErrorType::Internal, "failed to parse module configuration")
+            .with_source(err)
+    })
+}
+
+pub fn save_conf<T: Serialize>(
+    data_dir: &Path,
+    file_name: &str,
+    conf: &T,
+) -> Result<(), CommonError> {
+    let data = serde_json::to_vec_pretty(conf).map_err(|err| {
+        CommonError::new(
+            ErrorType::Internal,
+            "failed to serialize module configuration",
+        )
+        .with_source(err)
+    })?;
+
+    fs::write(data_dir.join(file_name), data).map_err(|err| {
+        CommonError::new(ErrorType::IO, "failed to save module configuration").with_source(err)
+    })
+}
+
+pub fn string_entry(
+    id: i32,
+    name: &str,
+    required: bool,
+    default: Option<&str>,
+) -> controller::ConfInfoEntry {
+    controller::ConfInfoEntry {
+        id,
+        name: name.to_string(),
+        data: controller::ConfInfoEntryType::String(controller::ConfInfoEntryString {
+            required,
+            default: default.map(|d| d.to_string()),
+            min_len: None,
+            max_len: None,
+            match_regex: None,
+        }),
+    }
+}
+
+/// `int_entry` creates an optional integer entry with a value in range `(gt, lt)`
+pub fn int_entry(id: i32, name: &str, default: i32, gt: i32, lt: i32) -> controller::ConfInfoEntry {
+    controller::ConfInfoEntry {
+        id,
+        name: name.to_string(),
+        data: controller::ConfInfoEntryType::Int(controller::ConfInfoEntryInt {
+            required: false,
+            default: Some(default),
+            lt: Some(lt),
+            gt: Some(gt),
+            neq: None,
+        }),
+    }
+}
+
+pub fn invalid_conf_type(name: &str) -> CommonError {
+    CommonError::new(
+        ErrorType::InvalidInput,
+        format!("invalid type of '{name}' configuration entry"),
+    )
+}
+
+pub fn unknown_conf_entry(id: i32) -> CommonError {
+    CommonError::new(
+        ErrorType::InvalidInput,
+        format!("unknown configuration entry '{id}'"),
+    )
+}
+
+/// `validate_sensor_names` checks sensors of a built-in module. Every sensor is passed
+/// as its name and names of its fields.
+pub fn validate_sensor_names(sensors: &[(&str, Vec<&str>)]) -> Result<(), CommonError> {
+    if sensors.is_empty() {
+        return Err(CommonError::new(
+            ErrorType::InvalidInput,
+            "at least one sensor must be configured",
+        ));
+    }
+
+    for (i, (sensor, fields)) in sensors.iter().enumerate() {
+        if sensors[..i].iter().any(|(s, _)| s == sensor) {
+            return Err(CommonError::new(
+                ErrorType::InvalidInput,
+                format!("duplicate sensor name '{sensor}'"),
+            ));
+        }
+
+        if fields.is_empty() {
+            return Err(CommonError::new(
+                ErrorType::InvalidInput,
+                format!("sensor '{sensor}' must have at least one field"),
+            ));
+        }
+
+        for (j, field) in fields.iter().enumerate() {
+            if *field == TIMESTAMP_FIELD || fields[..j].contains(field) {
+                return Err(CommonError::new(
+                    ErrorType::InvalidInput,
+                    format!("field name '{field}' of sensor '{sensor}' is duplicate or reserved"),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `Worker` is a thread of a built-in module which runs until it's stopped.
+/// The thread gets a receiver which is closed when the worker stops.
+pub struct Worker {
+    stop: mpsc::Sender<()>,
+    handle: thread::JoinHandle<()>,
+}
+
+impl Worker {
+    pub fn spawn<F>(name: &str, f: F) -> Result<Self, CommonError>
+    where
+        F: FnOnce(mpsc::Receiver<()>) + Send + 'static,
+    {
+        let (stop_tx, stop_rx) = mpsc::channel();
+
+        let handle = thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || f(stop_rx))
+            .map_err(|err| {
+                CommonError::new(
+                    ErrorType::Internal,
+                    format!("failed to spawn {name} thread"),
+                )
+                .with_source(err)
+            })?;
+
+        Ok(Self {
+            stop: stop_tx,
+            handle,
+        })
+    }
+
+    pub fn stop(self) -> Result<(), CommonError> {
+        let _ = self.stop.send(());
+
+        self.handle
+            .join()
+            .map_err(|_| CommonError::new(ErrorType::Internal, "module thread has panicked"))
+    }
+}
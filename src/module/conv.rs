@@ -9,6 +9,7 @@ use std::{
 };
 
 use super::bindings_gen as bg;
+use super::error::ModuleError;
 use crate::controller;
 
 pub struct CStringHandle(Vec<*mut i8>);
@@ -74,34 +75,103 @@ pub fn bg_sensor_data_type_to_ctrl(val: &bg::SensorDataType) -> controller::Sens
     }
 }
 
-pub fn bg_message_to_ctrl(val: &bg::Message) -> controller::Message {
-    match val.typ {
-        bg::MessageType::MessageTypeSensor => controller::Message {
-            msg: controller::MessageType::Sensor(bg_sensor_msg_to_ctrl(unsafe {
+pub fn command_args_to_bg(
+    args: &[controller::CommandArg],
+    ts_handle: &mut Vec<i64>,
+    cstring_handle: &mut CStringHandle,
+) -> Vec<bg::CommandArg> {
+    // Timestamps are passed as `int64_t` so they're converted beforehand
+    // to keep their pointers valid until the call is finished
+    ts_handle.clear();
+    ts_handle.extend(args.iter().map(|arg| match arg.data {
+        Some(controller::SensorDataTypeValue::Timestamp(ts)) => ts.and_utc().timestamp(),
+        _ => 0,
+    }));
+
+    let mut args_raw = Vec::with_capacity(args.len());
+    for (i, arg) in args.iter().enumerate() {
+        let (typ, data) = match arg.data {
+            Some(ref d) => (
+                sensor_data_type_to_bg(&d.typ()),
+                sensor_data_value_to_ptr(d, &ts_handle[i], cstring_handle),
+            ),
+            // Type of an omitted argument doesn't matter as its data is NULL
+            None => (bg::SensorDataType::SensorDataTypeInt32, ptr::null_mut()),
+        };
+
+        args_raw.push(bg::CommandArg {
+            name: cstring_handle.save_and_return_str(&arg.name) as _,
+            typ,
+            data,
+        })
+    }
+
+    args_raw
+}
+
+pub fn bg_command_args_to_command(name: *const c_char, args: &[bg::CommandArg]) -> bg::Command {
+    bg::Command {
+        name: name as _,
+        args: args.as_ptr() as _,
+        args_len: args.len() as i32,
+    }
+}
+
+pub fn sensor_data_type_to_bg(val: &controller::SensorDataType) -> bg::SensorDataType {
+    match val {
+        controller::SensorDataType::Int16 => bg::SensorDataType::SensorDataTypeInt16,
+        controller::SensorDataType::Int32 => bg::SensorDataType::SensorDataTypeInt32,
+        controller::SensorDataType::Int64 => bg::SensorDataType::SensorDataTypeInt64,
+        controller::SensorDataType::Float32 => bg::SensorDataType::SensorDataTypeFloat32,
+        controller::SensorDataType::Float64 => bg::SensorDataType::SensorDataTypeFloat64,
+        controller::SensorDataType::Timestamp => bg::SensorDataType::SensorDataTypeTimestamp,
+        controller::SensorDataType::String => bg::SensorDataType::SensorDataTypeString,
+        controller::SensorDataType::JSON => bg::SensorDataType::SensorDataTypeJSON,
+    }
+}
+
+/// `bg_message_to_ctrl` converts a message from a module. All pointers and lengths
+/// in the message are checked, so a malformed message results in an error.
+pub fn bg_message_to_ctrl(val: &bg::Message) -> Result<controller::Message, ModuleError> {
+    if val.data.is_null() {
+        return Err(ModuleError::InvalidPointer("message.data"));
+    }
+
+    let msg = match val.typ {
+        bg::MessageType::MessageTypeSensor => {
+            controller::MessageType::Sensor(bg_sensor_msg_to_ctrl(unsafe {
                 &(*(val.data as *const bg::SensorMsg))
-            })),
-        },
-        bg::MessageType::MessageTypeCommon => controller::Message {
-            msg: controller::MessageType::Common(bg_common_msg_to_ctrl(unsafe {
+            })?)
+        }
+        bg::MessageType::MessageTypeCommon => {
+            controller::MessageType::Common(bg_common_msg_to_ctrl(unsafe {
                 &(*(val.data as *const bg::CommonMsg))
-            })),
-        },
-    }
+            })?)
+        }
+    };
+
+    Ok(controller::Message { msg })
 }
 
-pub fn bg_sensor_msg_to_ctrl(val: &bg::SensorMsg) -> controller::SensorMsg {
-    let data_list = unsafe { std::slice::from_raw_parts(val.data, val.data_len as _) };
+pub fn bg_sensor_msg_to_ctrl(val: &bg::SensorMsg) -> Result<controller::SensorMsg, ModuleError> {
+    let data_list = slice_from_raw(val.data, val.data_len, "sensor_msg.data")?;
 
-    controller::SensorMsg {
-        name: str_from_c_char(val.name),
+    Ok(controller::SensorMsg {
+        name: try_str_from_c_char(val.name, "sensor_msg.name")?,
         data: data_list
-            .into_iter()
-            .map(|v| bg_sensor_data_msg_to_ctrl(v))
-            .collect(),
-    }
+            .iter()
+            .map(bg_sensor_data_msg_to_ctrl)
+            .collect::<Result<_, _>>()?,
+    })
 }
 
-pub fn bg_sensor_data_msg_to_ctrl(val: &bg::SensorMsgData) -> controller::SensorData {
+pub fn bg_sensor_data_msg_to_ctrl(
+    val: &bg::SensorMsgData,
+) -> Result<controller::SensorData, ModuleError> {
+    if val.data.is_null() {
+        return Err(ModuleError::InvalidPointer("sensor_msg_data.data"));
+    }
+
     let data = unsafe {
         match val.typ {
             bg::SensorDataType::SensorDataTypeInt16 => {
@@ -120,7 +190,8 @@ pub fn bg_sensor_data_msg_to_ctrl(val: &bg::SensorMsgData) -> controller::Sensor
                 controller::SensorDataTypeValue::Float64(*(val.data as *mut f64))
             }
             bg::SensorDataType::SensorDataTypeTimestamp => {
-                let ts = chrono::DateTime::from_timestamp(*(val.data as *mut i64), 0).unwrap();
+                let ts = chrono::DateTime::from_timestamp(*(val.data as *mut i64), 0)
+                    .ok_or(ModuleError::InvalidValue("sensor_msg_data.data"))?;
                 controller::SensorDataTypeValue::Timestamp(ts.naive_utc())
             }
             bg::SensorDataType::SensorDataTypeString => {
@@ -132,17 +203,17 @@ pub fn bg_sensor_data_msg_to_ctrl(val: &bg::SensorMsgData) -> controller::Sensor
         }
     };
 
-    controller::SensorData {
-        name: str_from_c_char(val.name),
+    Ok(controller::SensorData {
+        name: try_str_from_c_char(val.name, "sensor_msg_data.name")?,
         data,
-    }
+    })
 }
 
-pub fn bg_common_msg_to_ctrl(val: &bg::CommonMsg) -> controller::CommonMsg {
-    controller::CommonMsg {
+pub fn bg_common_msg_to_ctrl(val: &bg::CommonMsg) -> Result<controller::CommonMsg, ModuleError> {
+    Ok(controller::CommonMsg {
         code: bg_msg_code_to_ctrl(&val.code),
-        msg: str_from_c_char(val.msg),
-    }
+        msg: try_str_from_c_char(val.msg, "common_msg.msg")?,
+    })
 }
 
 pub fn bg_msg_code_to_ctrl(val: &bg::MsgCode) -> controller::MsgCode {
@@ -167,6 +238,37 @@ pub fn option_str_from_c_char(nullable_raw: *mut c_char) -> Option<String> {
     }
 }
 
+/// `try_str_from_c_char` works like [`str_from_c_char`], but returns an error
+/// instead of dereferencing a NULL pointer.
+pub fn try_str_from_c_char(raw: *mut c_char, field: &'static str) -> Result<String, ModuleError> {
+    if raw.is_null() {
+        return Err(ModuleError::InvalidPointer(field));
+    }
+
+    Ok(str_from_c_char(raw))
+}
+
+/// `slice_from_raw` builds a slice from a pointer and a length received from a module.
+///
+/// An empty slice is returned for zero length regardless of the pointer.
+pub fn slice_from_raw<'a, T>(
+    ptr: *const T,
+    len: i32,
+    field: &'static str,
+) -> Result<&'a [T], ModuleError> {
+    if len < 0 {
+        return Err(ModuleError::InvalidLength(field, len));
+    }
+    if len == 0 {
+        return Ok(&[]);
+    }
+    if ptr.is_null() {
+        return Err(ModuleError::InvalidPointer(field));
+    }
+
+    Ok(unsafe { std::slice::from_raw_parts(ptr, len as usize) })
+}
+
 // --------------------------------- private ------------------------------------
 
 fn device_conf_option_to_ptr(
@@ -193,3 +295,20 @@ fn device_conf_type_to_ptr(
         controller::ConfType::ChoiceList(cl) => cl as *const i32 as _,
     }
 }
+
+fn sensor_data_value_to_ptr(
+    val: &controller::SensorDataTypeValue,
+    ts: &i64,
+    cstring_handle: &mut CStringHandle,
+) -> *mut c_void {
+    match val {
+        controller::SensorDataTypeValue::Int16(v) => v as *const i16 as _,
+        controller::SensorDataTypeValue::Int32(v) => v as *const i32 as _,
+        controller::SensorDataTypeValue::Int64(v) => v as *const i64 as _,
+        controller::SensorDataTypeValue::Float32(v) => v as *const f32 as _,
+        controller::SensorDataTypeValue::Float64(v) => v as *const f64 as _,
+        controller::SensorDataTypeValue::Timestamp(_) => ts as *const i64 as _,
+        controller::SensorDataTypeValue::String(v) => cstring_handle.save_and_return_str(v) as _,
+        controller::SensorDataTypeValue::JSON(v) => cstring_handle.save_and_return_str(v) as _,
+    }
+}
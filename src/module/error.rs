@@ -18,6 +18,14 @@ pub enum ModuleError {
     StrError(Box<dyn std::error::Error>),
     #[error("data path is invalid")]
     InvalidDataPath,
+    #[error("module info is invalid: {0}")]
+    InvalidModuleInfo(String),
+    #[error("invalid length '{1}' of field '{0}'")]
+    InvalidLength(&'static str, i32),
+    #[error("invalid value of field '{0}'")]
+    InvalidValue(&'static str),
+    #[error("panic while processing data from module: {0}")]
+    Panic(String),
 }
 
 impl ModuleError {
@@ -27,6 +35,10 @@ impl ModuleError {
             ModuleError::InvalidPointer(_) => ErrorType::Internal,
             ModuleError::StrError(_) => ErrorType::Internal,
             ModuleError::InvalidDataPath => ErrorType::Internal,
+            ModuleError::InvalidModuleInfo(_) => ErrorType::InvalidInput,
+            ModuleError::InvalidLength(_, _) => ErrorType::InvalidInput,
+            ModuleError::InvalidValue(_) => ErrorType::InvalidInput,
+            ModuleError::Panic(_) => ErrorType::Internal,
         }
     }
 
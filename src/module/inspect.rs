@@ -0,0 +1,170 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use libloading::Symbol;
+
+use crate::controller::error::{CommonError, ErrorType};
+use crate::controller::interface::module::{IModule, IModuleFactory, MODULE_FILE_EXT};
+use crate::controller::{FunctionPresence, ModuleInspection};
+
+use super::bindings_gen as bg;
+use super::model::VERSION;
+use super::{required_funcs, LibModule};
+
+/// Name of the library copy in the scratch dir without the extension
+const SCRATCH_MODULE_NAME: &str = "module";
+
+static SCRATCH_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// `inspect_lib_module` copies the module library to a scratch dir, loads it there, obtains its
+/// info and connection parameters and unloads it. The scratch dir is removed afterwards,
+/// so nothing is left in the data dir. A module which hangs keeps its scratch dir
+/// until it returns.
+///
+/// If the library can't be loaded, has another version or lacks required functions,
+/// the reason is saved to [`ModuleInspection::error`] with everything learned before it.
+pub fn inspect_lib_module<P: AsRef<Path>>(mod_path: P) -> Result<ModuleInspection, CommonError> {
+    let scratch = ScratchDir::new()?;
+    let lib_path = scratch
+        .path()
+        .join(SCRATCH_MODULE_NAME.to_string() + MODULE_FILE_EXT);
+    let data_dir = scratch.path().join("data");
+
+    fs::copy(mod_path.as_ref(), &lib_path).map_err(|err| {
+        CommonError::new(ErrorType::IO, "failed to copy module to scratch dir").with_source(err)
+    })?;
+    fs::create_dir(&data_dir).map_err(|err| {
+        CommonError::new(
+            ErrorType::IO,
+            "failed to create scratch data dir for module",
+        )
+        .with_source(err)
+    })?;
+
+    let mut inspection = ModuleInspection {
+        version: None,
+        supported_version: VERSION,
+        functions: Vec::new(),
+        module_info: None,
+        conn_info: None,
+        error: None,
+    };
+
+    if let Err(err) = probe_lib(&lib_path, &mut inspection) {
+        inspection.error = Some(err);
+        return Ok(inspection);
+    }
+
+    let res = LibModule::create_module(&lib_path, &data_dir).and_then(|mut m| {
+        let conn_info = m.obtain_device_conn_info()?;
+        Ok((m.module_info(), conn_info))
+    });
+
+    match res {
+        Ok((module_info, conn_info)) => {
+            inspection.module_info = module_info;
+            inspection.conn_info = Some(conn_info);
+        }
+        Err(err) => inspection.error = Some(describe_error(&err)),
+    }
+
+    Ok(inspection)
+}
+
+/// `probe_lib` reads the version and the exported functions of the library
+/// without initializing the module
+fn probe_lib(lib_path: &Path, inspection: &mut ModuleInspection) -> Result<(), String> {
+    unsafe {
+        let lib = libloading::Library::new(lib_path.as_os_str())
+            .map_err(|err| format!("failed to load dynamic library: {err}"))?;
+
+        let mod_ver_fn: Symbol<bg::mod_version_fn> = lib
+            .get(b"mod_version")
+            .map_err(|err| format!("function 'mod_version' is not present: {err}"))?;
+        let ver = (*mod_ver_fn).ok_or("function 'mod_version' is null")?();
+        inspection.version = Some(ver);
+
+        // Functions of another version may have another layout
+        if ver != VERSION {
+            return Err(format!(
+                "the dynamic library has version {ver}, but version {VERSION} is supported"
+            ));
+        }
+
+        let funcs_fn: Symbol<bg::functions_fn> = lib
+            .get(b"functions")
+            .map_err(|err| format!("function 'functions' is not present: {err}"))?;
+        let funcs = (*funcs_fn).ok_or("function 'functions' is null")?();
+
+        inspection.functions = required_funcs(&funcs)
+            .into_iter()
+            .map(|(name, present)| FunctionPresence {
+                name,
+                required: true,
+                present,
+            })
+            .collect();
+        inspection
+            .functions
+            .extend(
+                ["obtain_command_infos", "send_command", "module_info"].map(|name| {
+                    FunctionPresence {
+                        name,
+                        required: false,
+                        present: lib.get::<*const ()>(name.as_bytes()).is_ok(),
+                    }
+                }),
+            );
+    }
+
+    match inspection
+        .functions
+        .iter()
+        .find(|f| f.required && !f.present)
+    {
+        Some(func) => Err(format!(
+            "function '{}' is not present in the module",
+            func.name
+        )),
+        None => Ok(()),
+    }
+}
+
+/// `describe_error` returns the message of the error with its source, but without the backtrace
+pub fn describe_error(err: &CommonError) -> String {
+    match err.source {
+        Some(ref source) => format!("{}: {source}", err.msg),
+        None => err.msg.clone(),
+    }
+}
+
+/// `ScratchDir` is a temporary dir of an inspected module. It's removed on drop
+struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    fn new() -> Result<Self, CommonError> {
+        let path = std::env::temp_dir().join(format!(
+            "monisens-inspect-{}-{}",
+            std::process::id(),
+            SCRATCH_COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+
+        fs::create_dir_all(&path).map_err(|err| {
+            CommonError::new(ErrorType::IO, "failed to create scratch dir for module")
+                .with_source(err)
+        })?;
+
+        Ok(Self(path))
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -1,7 +1,14 @@
 mod bindings_gen;
+mod builtin;
 mod conv;
 pub mod error;
+mod inspect;
+pub mod modbus;
 mod model;
+pub mod mqtt;
+pub mod push;
+pub mod simulator;
+mod test;
 
 use libc::c_void;
 use libloading::{self, Symbol};
@@ -12,20 +19,171 @@ use model::*;
 
 use crate::controller;
 use crate::controller::error::{CommonError, ErrorType};
-use crate::controller::interface::module::{IModule, IModuleFactory, MsgHandler};
+use crate::controller::interface::module::{
+    is_builtin_module, IModule, IModuleFactory, MsgHandler, PUSH_MODULE_NAME,
+};
 
 pub use self::error::*;
+pub use self::inspect::*;
+use self::modbus::{ModbusModule, MODBUS_MODULE_NAME};
+use self::mqtt::{MqttModule, MQTT_MODULE_NAME};
+use self::push::PushModule;
+use self::simulator::{Simulator, SIMULATOR_MODULE_NAME};
+
+/// `Module` is either a dynamic library or a module built into MoniSens.
+/// Built-in modules are created by [`IModuleFactory`] when it gets their reserved name
+/// instead of a library path.
+pub enum Module {
+    Lib(LibModule),
+    Simulator(Simulator),
+    Mqtt(MqttModule),
+    Modbus(ModbusModule),
+    Push(PushModule),
+}
+
+impl IModule for Module {
+    fn obtain_device_conn_info(&mut self) -> Result<controller::ConfInfo, CommonError> {
+        match self {
+            Module::Lib(m) => m.obtain_device_conn_info(),
+            Module::Simulator(m) => m.obtain_device_conn_info(),
+            Module::Mqtt(m) => m.obtain_device_conn_info(),
+            Module::Modbus(m) => m.obtain_device_conn_info(),
+            Module::Push(m) => m.obtain_device_conn_info(),
+        }
+    }
+
+    fn connect_device(&mut self, confs: Vec<controller::ConfEntry>) -> Result<(), CommonError> {
+        match self {
+            Module::Lib(m) => m.connect_device(confs),
+            Module::Simulator(m) => m.connect_device(confs),
+            Module::Mqtt(m) => m.connect_device(confs),
+            Module::Modbus(m) => m.connect_device(confs),
+            Module::Push(m) => m.connect_device(confs),
+        }
+    }
+
+    fn obtain_device_conf_info(&mut self) -> Result<controller::ConfInfo, CommonError> {
+        match self {
+            Module::Lib(m) => m.obtain_device_conf_info(),
+            Module::Simulator(m) => m.obtain_device_conf_info(),
+            Module::Mqtt(m) => m.obtain_device_conf_info(),
+            Module::Modbus(m) => m.obtain_device_conf_info(),
+            Module::Push(m) => m.obtain_device_conf_info(),
+        }
+    }
+
+    fn configure_device(&mut self, confs: Vec<controller::ConfEntry>) -> Result<(), CommonError> {
+        match self {
+            Module::Lib(m) => m.configure_device(confs),
+            Module::Simulator(m) => m.configure_device(confs),
+            Module::Mqtt(m) => m.configure_device(confs),
+            Module::Modbus(m) => m.configure_device(confs),
+            Module::Push(m) => m.configure_device(confs),
+        }
+    }
+
+    fn obtain_sensor_type_infos(&mut self) -> Result<Vec<controller::Sensor>, CommonError> {
+        match self {
+            Module::Lib(m) => m.obtain_sensor_type_infos(),
+            Module::Simulator(m) => m.obtain_sensor_type_infos(),
+            Module::Mqtt(m) => m.obtain_sensor_type_infos(),
+            Module::Modbus(m) => m.obtain_sensor_type_infos(),
+            Module::Push(m) => m.obtain_sensor_type_infos(),
+        }
+    }
 
-pub struct Module {
+    fn obtain_command_infos(&mut self) -> Result<Vec<controller::CommandInfo>, CommonError> {
+        match self {
+            Module::Lib(m) => m.obtain_command_infos(),
+            Module::Simulator(m) => m.obtain_command_infos(),
+            Module::Mqtt(m) => m.obtain_command_infos(),
+            Module::Modbus(m) => m.obtain_command_infos(),
+            Module::Push(m) => m.obtain_command_infos(),
+        }
+    }
+
+    fn send_command(&mut self, cmd: &controller::Command) -> Result<(), CommonError> {
+        match self {
+            Module::Lib(m) => m.send_command(cmd),
+            Module::Simulator(m) => m.send_command(cmd),
+            Module::Mqtt(m) => m.send_command(cmd),
+            Module::Modbus(m) => m.send_command(cmd),
+            Module::Push(m) => m.send_command(cmd),
+        }
+    }
+
+    fn start<H: MsgHandler + 'static>(&mut self, msg_handler: H) -> Result<(), CommonError> {
+        match self {
+            Module::Lib(m) => m.start(msg_handler),
+            Module::Simulator(m) => m.start(msg_handler),
+            Module::Mqtt(m) => m.start(msg_handler),
+            Module::Modbus(m) => m.start(msg_handler),
+            Module::Push(m) => m.start(msg_handler),
+        }
+    }
+
+    fn stop(&mut self) -> Result<(), CommonError> {
+        match self {
+            Module::Lib(m) => m.stop(),
+            Module::Simulator(m) => m.stop(),
+            Module::Mqtt(m) => m.stop(),
+            Module::Modbus(m) => m.stop(),
+            Module::Push(m) => m.stop(),
+        }
+    }
+
+    fn module_info(&self) -> Option<controller::ModuleInfo> {
+        match self {
+            Module::Lib(m) => m.module_info(),
+            Module::Simulator(m) => m.module_info(),
+            Module::Mqtt(m) => m.module_info(),
+            Module::Modbus(m) => m.module_info(),
+            Module::Push(m) => m.module_info(),
+        }
+    }
+}
+
+impl IModuleFactory<Module> for Module {
+    fn create_module<P: AsRef<Path>>(mod_path: P, data_dir: P) -> Result<Module, CommonError> {
+        match mod_path.as_ref().to_str() {
+            Some(SIMULATOR_MODULE_NAME) => Ok(Module::Simulator(Simulator::new(data_dir)?)),
+            Some(MQTT_MODULE_NAME) => Ok(Module::Mqtt(MqttModule::new(data_dir)?)),
+            Some(MODBUS_MODULE_NAME) => Ok(Module::Modbus(ModbusModule::new(data_dir)?)),
+            Some(PUSH_MODULE_NAME) => Ok(Module::Push(PushModule::new(data_dir)?)),
+            Some(name) if is_builtin_module(name) => Err(CommonError::new(
+                ErrorType::NotFound,
+                format!("unknown built-in module '{name}'"),
+            )),
+            _ => Ok(Module::Lib(LibModule::create_module(mod_path, data_dir)?)),
+        }
+    }
+
+    fn inspect_module<P: AsRef<Path>>(
+        mod_path: P,
+    ) -> Result<controller::ModuleInspection, CommonError> {
+        match mod_path.as_ref().to_str() {
+            Some(name) if is_builtin_module(name) => Err(CommonError::new(
+                ErrorType::InvalidInput,
+                "built-in modules can't be inspected",
+            )),
+            _ => LibModule::inspect_module(mod_path),
+        }
+    }
+}
+
+/// `LibModule` is a module loaded from a dynamic library.
+pub struct LibModule {
     #[allow(dead_code)]
     lib: libloading::Library,
     handle: Handle,
     funcs: bg::Functions,
+    opt_funcs: OptFunctions,
+    info: Option<controller::ModuleInfo>,
 
     msg_handle: Option<MsgHandle>,
 }
 
-impl Drop for Module {
+impl Drop for LibModule {
     fn drop(&mut self) {
         unsafe {
             self.funcs.destroy.unwrap()(self.handle.handler());
@@ -33,7 +191,7 @@ impl Drop for Module {
     }
 }
 
-impl IModule for Module {
+impl IModule for LibModule {
     fn obtain_device_conn_info(&mut self) -> Result<controller::ConfInfo, CommonError> {
         let mut conf_rec: ConfInfoRec = Ok(Vec::new());
         unsafe {
@@ -109,6 +267,50 @@ impl IModule for Module {
         Ok(res)
     }
 
+    fn obtain_command_infos(&mut self) -> Result<Vec<controller::CommandInfo>, CommonError> {
+        let obtain_command_infos = match self.opt_funcs.obtain_command_infos {
+            Some(f) => f,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut infos_rec: CommandInfosRec = Ok(vec![]);
+        let err = unsafe {
+            obtain_command_infos(
+                self.handle.handler(),
+                &mut infos_rec as *mut CommandInfosRec as *mut c_void,
+                Some(command_infos_callback),
+            )
+        };
+
+        convert_com_error(err)
+            .map_err(|err| err.to_ctrl_error("failed to obtain command infos"))?;
+
+        let res = infos_rec.map_err(|err| err.to_ctrl_error("failed to obtain command infos"))?;
+
+        Ok(res)
+    }
+
+    fn send_command(&mut self, cmd: &controller::Command) -> Result<(), CommonError> {
+        let send_command = self.opt_funcs.send_command.ok_or_else(|| {
+            CommonError::new(
+                ErrorType::FailedPrecondition,
+                "the module doesn't support commands",
+            )
+        })?;
+
+        let mut c_string_handle = conv::CStringHandle::new();
+        let mut ts_handle = Vec::new();
+        let args_bg = conv::command_args_to_bg(&cmd.args, &mut ts_handle, &mut c_string_handle);
+        let name = c_string_handle.save_and_return_str(&cmd.name);
+        let mut cmd_raw = conv::bg_command_args_to_command(name, &args_bg);
+
+        let err = unsafe { send_command(self.handle.handler(), &mut cmd_raw as _) };
+
+        convert_com_error(err).map_err(|err| err.to_ctrl_error("failed to send command"))?;
+
+        Ok(())
+    }
+
     fn start<H: MsgHandler + 'static>(&mut self, msg_handler: H) -> Result<(), CommonError> {
         self.msg_handle = Some(MsgHandle::new(msg_handler));
 
@@ -130,10 +332,14 @@ impl IModule for Module {
 
         Ok(())
     }
+
+    fn module_info(&self) -> Option<controller::ModuleInfo> {
+        self.info.clone()
+    }
 }
 
-impl IModuleFactory<Module> for Module {
-    fn create_module<P: AsRef<Path>>(mod_path: P, data_dir: P) -> Result<Module, CommonError> {
+impl IModuleFactory<LibModule> for LibModule {
+    fn create_module<P: AsRef<Path>>(mod_path: P, data_dir: P) -> Result<LibModule, CommonError> {
         // TODO: unsafe {} where it's really unsafe
         unsafe {
             let lib = libloading::Library::new(mod_path.as_ref().as_os_str()).map_err(|err| {
@@ -173,6 +379,40 @@ impl IModuleFactory<Module> for Module {
                 )
             })?;
 
+            let opt_funcs = OptFunctions {
+                obtain_command_infos: lib
+                    .get::<bg::obtain_command_infos_fn>(b"obtain_command_infos")
+                    .ok()
+                    .and_then(|f| *f),
+                send_command: lib
+                    .get::<bg::send_command_fn>(b"send_command")
+                    .ok()
+                    .and_then(|f| *f),
+            };
+
+            let info = match lib
+                .get::<bg::module_info_fn>(b"module_info")
+                .ok()
+                .and_then(|f| *f)
+            {
+                Some(module_info_fn) => {
+                    let mut info_rec: ModuleInfoRec =
+                        Err(ModuleError::InvalidPointer("module_info"));
+                    module_info_fn(
+                        &mut info_rec as *mut ModuleInfoRec as *mut c_void,
+                        Some(module_info_callback),
+                    );
+
+                    let info = info_rec
+                        .map_err(|err| err.to_ctrl_error("failed to obtain module info"))?;
+                    validate_module_info(&info, &opt_funcs)
+                        .map_err(|err| err.to_ctrl_error("module info validation failed"))?;
+
+                    Some(info)
+                }
+                None => None,
+            };
+
             let mut handler = Handle::new();
 
             let data_dir_str = data_dir
@@ -200,44 +440,55 @@ impl IModuleFactory<Module> for Module {
                     .to_ctrl_error("failed to init module (handler is null)"));
             }
 
-            Ok(Module {
+            Ok(LibModule {
                 lib,
                 handle: handler,
                 funcs,
+                opt_funcs,
+                info,
                 msg_handle: None,
             })
         }
     }
+
+    fn inspect_module<P: AsRef<Path>>(
+        mod_path: P,
+    ) -> Result<controller::ModuleInspection, CommonError> {
+        inspect_lib_module(mod_path)
+    }
 }
 
 fn validate_funcs_present(funcs: &bg::Functions) -> Result<(), &str> {
-    if funcs.init.is_none() {
-        return Err("init");
-    }
-    if funcs.destroy.is_none() {
-        return Err("destroy");
-    }
-    if funcs.obtain_device_conn_info.is_none() {
-        return Err("obtain_device_conn_info");
-    }
-    if funcs.connect_device.is_none() {
-        return Err("connect_device");
-    }
-    if funcs.obtain_device_conf_info.is_none() {
-        return Err("obtain_device_conf_info");
-    }
-    if funcs.configure_device.is_none() {
-        return Err("configure_device");
-    }
-    if funcs.obtain_sensor_type_infos.is_none() {
-        return Err("obtain_sensor_type_infos");
-    }
-    if funcs.start.is_none() {
-        return Err("start");
-    }
-    if funcs.stop.is_none() {
-        return Err("stop");
+    match required_funcs(funcs)
+        .into_iter()
+        .find(|(_, present)| !present)
+    {
+        Some((name, _)) => Err(name),
+        None => Ok(()),
     }
+}
 
-    Ok(())
+/// `required_funcs` returns names of the functions every module must export
+/// and whether they are present in `funcs`
+fn required_funcs(funcs: &bg::Functions) -> [(&'static str, bool); 9] {
+    [
+        ("init", funcs.init.is_some()),
+        ("destroy", funcs.destroy.is_some()),
+        (
+            "obtain_device_conn_info",
+            funcs.obtain_device_conn_info.is_some(),
+        ),
+        ("connect_device", funcs.connect_device.is_some()),
+        (
+            "obtain_device_conf_info",
+            funcs.obtain_device_conf_info.is_some(),
+        ),
+        ("configure_device", funcs.configure_device.is_some()),
+        (
+            "obtain_sensor_type_infos",
+            funcs.obtain_sensor_type_infos.is_some(),
+        ),
+        ("start", funcs.start.is_some()),
+        ("stop", funcs.stop.is_some()),
+    ]
 }
@@ -0,0 +1,698 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::controller;
+use crate::controller::error::{CommonError, ErrorType};
+use crate::controller::interface::module::{IModule, MsgHandler};
+
+use super::builtin;
+
+/// `MODBUS_MODULE_NAME` is a reserved module name of the built-in [`ModbusModule`].
+pub const MODBUS_MODULE_NAME: &str = "builtin:modbus";
+
+const CONF_FILE_NAME: &str = "modbus.json";
+
+const CONN_CONF_HOST: i32 = 1;
+const CONN_CONF_PORT: i32 = 2;
+const CONN_CONF_UNIT_ID: i32 = 3;
+const CONN_CONF_TIMEOUT: i32 = 4;
+
+const CONF_GROUPS: i32 = 1;
+const CONF_INTERVAL: i32 = 2;
+
+const DEFAULT_PORT: i32 = 502;
+const DEFAULT_UNIT_ID: i32 = 1;
+const DEFAULT_TIMEOUT_MS: i32 = 3000;
+const DEFAULT_INTERVAL_MS: i32 = 1000;
+const DEFAULT_GROUPS: &str = r#"[
+    {
+        "name": "meter",
+        "table": "holding",
+        "address": 0,
+        "count": 4,
+        "fields": [
+            {"name": "voltage", "address": 0, "type": "int16", "scale": 0.1},
+            {"name": "power", "address": 2, "type": "float32"}
+        ]
+    }
+]"#;
+
+/// Modbus limits of a single read request
+const MAX_REGISTERS: u16 = 125;
+const MAX_BITS: u16 = 2000;
+
+/// `ModbusModule` is a module built into MoniSens which polls a Modbus TCP device.
+///
+/// Registers are read in groups: every group is a sensor which is read with a single request
+/// of `count` registers (or coils) starting at `address`. Fields of a group are decoded from
+/// the read registers as `int16`, `uint16`, `int32`, `uint32` or `float32` values with
+/// configurable byte and word order and are transformed as `raw * scale + offset`.
+/// Coils and discrete inputs are read as `bool` fields.
+///
+/// A failed poll is reported as a device error and the connection is established again
+/// on the next poll. The configuration is stored in the device's data dir.
+pub struct ModbusModule {
+    data_dir: PathBuf,
+    conf: ModbusConf,
+    worker: Option<builtin::Worker>,
+}
+
+impl ModbusModule {
+    pub fn new<P: AsRef<Path>>(data_dir: P) -> Result<Self, CommonError> {
+        let data_dir = data_dir.as_ref().to_path_buf();
+        let conf = builtin::load_conf(&data_dir, CONF_FILE_NAME)?.unwrap_or_default();
+
+        Ok(Self {
+            data_dir,
+            conf,
+            worker: None,
+        })
+    }
+}
+
+impl Drop for ModbusModule {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+impl IModule for ModbusModule {
+    fn obtain_device_conn_info(&mut self) -> Result<controller::ConfInfo, CommonError> {
+        Ok(vec![
+            builtin::string_entry(CONN_CONF_HOST, "Device host", true, Some("127.0.0.1")),
+            builtin::int_entry(CONN_CONF_PORT, "Device port", DEFAULT_PORT, 0, 65536),
+            builtin::int_entry(CONN_CONF_UNIT_ID, "Unit ID", DEFAULT_UNIT_ID, -1, 256),
+            builtin::int_entry(
+                CONN_CONF_TIMEOUT,
+                "Response timeout, ms",
+                DEFAULT_TIMEOUT_MS,
+                0,
+                60001,
+            ),
+        ])
+    }
+
+    fn connect_device(&mut self, confs: Vec<controller::ConfEntry>) -> Result<(), CommonError> {
+        let mut conn = ConnConf::default();
+
+        for entry in confs {
+            match (entry.id, entry.data) {
+                (_, None) => {}
+                (CONN_CONF_HOST, Some(controller::ConfType::String(v))) => conn.host = v,
+                (CONN_CONF_PORT, Some(controller::ConfType::Int(v))) => {
+                    conn.port = u16::try_from(v).map_err(|_| {
+                        CommonError::new(ErrorType::InvalidInput, "invalid device port")
+                    })?;
+                }
+                (CONN_CONF_UNIT_ID, Some(controller::ConfType::Int(v))) => {
+                    conn.unit_id = u8::try_from(v).map_err(|_| {
+                        CommonError::new(ErrorType::InvalidInput, "invalid unit id")
+                    })?;
+                }
+                (CONN_CONF_TIMEOUT, Some(controller::ConfType::Int(v))) => {
+                    if v <= 0 {
+                        return Err(CommonError::new(
+                            ErrorType::InvalidInput,
+                            "response timeout must be positive",
+                        ));
+                    }
+                    conn.timeout_ms = v;
+                }
+                (CONN_CONF_HOST, _) => return Err(builtin::invalid_conf_type("host")),
+                (CONN_CONF_PORT, _) => return Err(builtin::invalid_conf_type("port")),
+                (CONN_CONF_UNIT_ID, _) => return Err(builtin::invalid_conf_type("unit id")),
+                (CONN_CONF_TIMEOUT, _) => return Err(builtin::invalid_conf_type("timeout")),
+                (id, _) => return Err(builtin::unknown_conf_entry(id)),
+            }
+        }
+
+        if conn.host.is_empty() {
+            return Err(CommonError::new(
+                ErrorType::InvalidInput,
+                "device host is required",
+            ));
+        }
+
+        ModbusClient::connect(&conn).map_err(|err| {
+            CommonError::new(ErrorType::IO, "failed to connect to Modbus device").with_source(err)
+        })?;
+        self.conf.conn = conn;
+
+        Ok(())
+    }
+
+    fn obtain_device_conf_info(&mut self) -> Result<controller::ConfInfo, CommonError> {
+        Ok(vec![
+            controller::ConfInfoEntry {
+                id: CONF_GROUPS,
+                name: "Register groups".to_string(),
+                data: controller::ConfInfoEntryType::JSON(controller::ConfInfoEntryJSON {
+                    required: true,
+                    default: Some(DEFAULT_GROUPS.to_string()),
+                }),
+            },
+            builtin::int_entry(
+                CONF_INTERVAL,
+                "Polling interval, ms",
+                DEFAULT_INTERVAL_MS,
+                0,
+                i32::MAX,
+            ),
+        ])
+    }
+
+    fn configure_device(&mut self, confs: Vec<controller::ConfEntry>) -> Result<(), CommonError> {
+        let mut groups = None;
+        let mut interval_ms = DEFAULT_INTERVAL_MS;
+
+        for entry in confs {
+            match (entry.id, entry.data) {
+                (_, None) => {}
+                (CONF_GROUPS, Some(controller::ConfType::JSON(v))) => {
+                    groups = Some(parse_groups(&v)?)
+                }
+                (CONF_INTERVAL, Some(controller::ConfType::Int(v))) => {
+                    if v <= 0 {
+                        return Err(CommonError::new(
+                            ErrorType::InvalidInput,
+                            "polling interval must be positive",
+                        ));
+                    }
+                    interval_ms = v;
+                }
+                (CONF_GROUPS, _) => return Err(builtin::invalid_conf_type("register groups")),
+                (CONF_INTERVAL, _) => return Err(builtin::invalid_conf_type("interval")),
+                (id, _) => return Err(builtin::unknown_conf_entry(id)),
+            }
+        }
+
+        self.conf.groups = groups.ok_or_else(|| {
+            CommonError::new(
+                ErrorType::InvalidInput,
+                "register groups must be configured",
+            )
+        })?;
+        self.conf.interval_ms = interval_ms;
+
+        builtin::save_conf(&self.data_dir, CONF_FILE_NAME, &self.conf)
+    }
+
+    fn obtain_sensor_type_infos(&mut self) -> Result<Vec<controller::Sensor>, CommonError> {
+        let sensors = self
+            .conf
+            .groups
+            .iter()
+            .map(|group| {
+                let mut data_map = HashMap::with_capacity(group.fields.len() + 1);
+                data_map.extend([builtin::timestamp_entry()]);
+
+                for field in group.fields.iter() {
+                    data_map.insert(
+                        field.name.clone(),
+                        controller::SensorDataEntry {
+                            name: field.name.clone(),
+                            typ: field.data_type(),
+                        },
+                    );
+                }
+
+                controller::Sensor {
+                    name: group.name.clone(),
+                    data_map,
+                }
+            })
+            .collect();
+
+        Ok(sensors)
+    }
+
+    fn obtain_command_infos(&mut self) -> Result<Vec<controller::CommandInfo>, CommonError> {
+        Ok(Vec::new())
+    }
+
+    fn send_command(&mut self, _cmd: &controller::Command) -> Result<(), CommonError> {
+        Err(CommonError::new(
+            ErrorType::FailedPrecondition,
+            "the module doesn't support commands",
+        ))
+    }
+
+    fn start<H: MsgHandler + 'static>(&mut self, msg_handler: H) -> Result<(), CommonError> {
+        self.stop()?;
+
+        if self.conf.groups.is_empty() {
+            return Err(CommonError::new(
+                ErrorType::FailedPrecondition,
+                "the module is not configured",
+            ));
+        }
+
+        let conn = self.conf.conn.clone();
+        let groups = self.conf.groups.clone();
+        let interval = Duration::from_millis(self.conf.interval_ms as u64);
+
+        self.worker = Some(builtin::Worker::spawn("modbus", move |stop| {
+            run(conn, groups, interval, stop, msg_handler)
+        })?);
+
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), CommonError> {
+        if let Some(worker) = self.worker.take() {
+            worker.stop()?;
+        }
+
+        Ok(())
+    }
+
+    fn module_info(&self) -> Option<controller::ModuleInfo> {
+        Some(controller::ModuleInfo {
+            name: MODBUS_MODULE_NAME.to_string(),
+            vendor: Some("MoniSens".to_string()),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            description: Some("Built-in Modbus TCP poller".to_string()),
+            os: None,
+            arch: None,
+            capabilities: controller::ModuleCapabilities {
+                commands: false,
+                hot_reconfigure: false,
+                persisted_config: true,
+            },
+        })
+    }
+}
+
+fn run<H: MsgHandler>(
+    conn: ConnConf,
+    groups: Vec<GroupConf>,
+    interval: Duration,
+    stop: mpsc::Receiver<()>,
+    msg_handler: H,
+) {
+    let mut client: Option<ModbusClient> = None;
+    // The last reported error: the same error isn't reported on every poll
+    let mut last_err: Option<String> = None;
+
+    loop {
+        let res = connect_and_poll(&mut client, &conn, &groups);
+
+        match res {
+            Ok(msgs) => {
+                last_err = None;
+                for msg in msgs {
+                    msg_handler.handle_msg(msg);
+                }
+            }
+            Err(err) => {
+                client = None;
+
+                let err = err.to_string();
+                if last_err.as_ref() != Some(&err) {
+                    msg_handler.handle_msg(controller::Message {
+                        msg: controller::MessageType::Common(controller::CommonMsg {
+                            code: controller::MsgCode::Error,
+                            msg: format!("failed to poll Modbus device: {err}"),
+                        }),
+                    });
+                    last_err = Some(err);
+                }
+            }
+        }
+
+        if let Err(mpsc::RecvTimeoutError::Disconnected) | Ok(()) = stop.recv_timeout(interval) {
+            break;
+        }
+    }
+}
+
+fn connect_and_poll(
+    client: &mut Option<ModbusClient>,
+    conn: &ConnConf,
+    groups: &[GroupConf],
+) -> io::Result<Vec<controller::Message>> {
+    if client.is_none() {
+        *client = Some(ModbusClient::connect(conn)?);
+    }
+
+    poll(client.as_mut().unwrap(), groups)
+}
+
+/// `poll` reads all register groups and returns a message for each of them.
+pub(super) fn poll(
+    client: &mut ModbusClient,
+    groups: &[GroupConf],
+) -> io::Result<Vec<controller::Message>> {
+    let mut msgs = Vec::with_capacity(groups.len());
+
+    for group in groups {
+        let data = client.read(group.table, group.address, group.count)?;
+
+        let mut sensor_data = Vec::with_capacity(group.fields.len() + 1);
+        sensor_data.push(builtin::timestamp_data());
+        for field in group.fields.iter() {
+            sensor_data.push(controller::SensorData {
+                name: field.name.clone(),
+                data: field.decode(&data, group.address),
+            });
+        }
+
+        msgs.push(controller::Message {
+            msg: controller::MessageType::Sensor(controller::SensorMsg {
+                name: group.name.clone(),
+                data: sensor_data,
+            }),
+        });
+    }
+
+    Ok(msgs)
+}
+
+pub(super) fn parse_groups(data: &str) -> Result<Vec<GroupConf>, CommonError> {
+    let groups: Vec<GroupConf> = serde_json::from_str(data).map_err(|err| {
+        CommonError::new(ErrorType::InvalidInput, "failed to parse register groups")
+            .with_source(err)
+    })?;
+
+    let names: Vec<_> = groups
+        .iter()
+        .map(|g| {
+            (
+                g.name.as_str(),
+                g.fields.iter().map(|f| f.name.as_str()).collect(),
+            )
+        })
+        .collect();
+    builtin::validate_sensor_names(&names)?;
+
+    for group in groups.iter() {
+        let max_count = if group.table.is_bits() {
+            MAX_BITS
+        } else {
+            MAX_REGISTERS
+        };
+        if group.count == 0 || group.count > max_count {
+            return Err(CommonError::new(
+                ErrorType::InvalidInput,
+                format!(
+                    "count of group '{}' must be in range [1, {max_count}]",
+                    group.name
+                ),
+            ));
+        }
+
+        let end = group.address as u32 + group.count as u32;
+
+        for field in group.fields.iter() {
+            if group.table.is_bits() != (field.typ == FieldType::Bool) {
+                return Err(CommonError::new(
+                    ErrorType::InvalidInput,
+                    format!(
+                        "field '{}' of group '{}': coils and discrete inputs must be read as 'bool' \
+                         and registers as numbers",
+                        field.name, group.name
+                    ),
+                ));
+            }
+
+            let field_end = field.address as u32 + field.typ.size() as u32;
+            if field.address < group.address || field_end > end {
+                return Err(CommonError::new(
+                    ErrorType::InvalidInput,
+                    format!(
+                        "field '{}' is out of range of group '{}'",
+                        field.name, group.name
+                    ),
+                ));
+            }
+        }
+    }
+
+    Ok(groups)
+}
+
+/// `ModbusClient` is a minimal Modbus TCP client which supports read functions only.
+pub(super) struct ModbusClient {
+    stream: TcpStream,
+    unit_id: u8,
+    transaction_id: u16,
+}
+
+impl ModbusClient {
+    fn connect(conf: &ConnConf) -> io::Result<Self> {
+        let timeout = Duration::from_millis(conf.timeout_ms as u64);
+        let addr = (conf.host.as_str(), conf.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "failed to resolve host"))?;
+
+        let stream = TcpStream::connect_timeout(&addr, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        stream.set_nodelay(true)?;
+
+        Ok(Self::new(stream, conf.unit_id))
+    }
+
+    pub(super) fn new(stream: TcpStream, unit_id: u8) -> Self {
+        Self {
+            stream,
+            unit_id,
+            transaction_id: 0,
+        }
+    }
+
+    /// `read` reads `count` registers or bits. Bits are returned as `0` and `1`.
+    fn read(&mut self, table: Table, address: u16, count: u16) -> io::Result<Vec<u16>> {
+        self.transaction_id = self.transaction_id.wrapping_add(1);
+        let function = table.function();
+
+        let mut req = Vec::with_capacity(12);
+        req.extend_from_slice(&self.transaction_id.to_be_bytes());
+        req.extend_from_slice(&0u16.to_be_bytes()); // protocol id
+        req.extend_from_slice(&6u16.to_be_bytes()); // length of the rest
+        req.push(self.unit_id);
+        req.push(function);
+        req.extend_from_slice(&address.to_be_bytes());
+        req.extend_from_slice(&count.to_be_bytes());
+        self.stream.write_all(&req)?;
+
+        let mut header = [0u8; 7];
+        self.stream.read_exact(&mut header)?;
+
+        let transaction_id = u16::from_be_bytes([header[0], header[1]]);
+        let len = u16::from_be_bytes([header[4], header[5]]) as usize;
+        if transaction_id != self.transaction_id || len < 2 {
+            return Err(invalid_data("unexpected response header"));
+        }
+
+        let mut pdu = vec![0u8; len - 1];
+        self.stream.read_exact(&mut pdu)?;
+
+        if pdu[0] == function | 0x80 {
+            return Err(invalid_data(&format!(
+                "device returned exception code {}",
+                pdu.get(1).copied().unwrap_or_default()
+            )));
+        }
+        if pdu[0] != function || pdu.len() < 2 || pdu.len() - 2 != pdu[1] as usize {
+            return Err(invalid_data("unexpected response"));
+        }
+
+        let data = &pdu[2..];
+        if table.is_bits() {
+            if data.len() * 8 < count as usize {
+                return Err(invalid_data("response has too few bits"));
+            }
+
+            Ok((0..count as usize)
+                .map(|i| ((data[i / 8] >> (i % 8)) & 1) as u16)
+                .collect())
+        } else {
+            if data.len() != count as usize * 2 {
+                return Err(invalid_data("response has unexpected number of registers"));
+            }
+
+            Ok(data
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect())
+        }
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ModbusConf {
+    conn: ConnConf,
+    interval_ms: i32,
+    groups: Vec<GroupConf>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct ConnConf {
+    host: String,
+    port: u16,
+    unit_id: u8,
+    timeout_ms: i32,
+}
+
+impl Default for ConnConf {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: DEFAULT_PORT as u16,
+            unit_id: DEFAULT_UNIT_ID as u8,
+            timeout_ms: DEFAULT_TIMEOUT_MS,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(super) struct GroupConf {
+    name: String,
+    table: Table,
+    address: u16,
+    count: u16,
+    fields: Vec<FieldConf>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum Table {
+    Coil,
+    DiscreteInput,
+    Holding,
+    Input,
+}
+
+impl Table {
+    fn function(self) -> u8 {
+        match self {
+            Table::Coil => 0x01,
+            Table::DiscreteInput => 0x02,
+            Table::Holding => 0x03,
+            Table::Input => 0x04,
+        }
+    }
+
+    fn is_bits(self) -> bool {
+        matches!(self, Table::Coil | Table::DiscreteInput)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct FieldConf {
+    name: String,
+    /// Address of the field's first register (or bit)
+    address: u16,
+    #[serde(rename = "type")]
+    typ: FieldType,
+    #[serde(default)]
+    byte_order: Order,
+    /// Order of registers of 32-bit values
+    #[serde(default)]
+    word_order: Order,
+    scale: Option<f64>,
+    offset: Option<f64>,
+}
+
+impl FieldConf {
+    /// `data_type` returns the type of the field's values: scaled fields are always `float64`
+    fn data_type(&self) -> controller::SensorDataType {
+        if self.is_scaled() {
+            return controller::SensorDataType::Float64;
+        }
+
+        match self.typ {
+            FieldType::Bool | FieldType::Int16 => controller::SensorDataType::Int16,
+            FieldType::Uint16 | FieldType::Int32 => controller::SensorDataType::Int32,
+            FieldType::Uint32 => controller::SensorDataType::Int64,
+            FieldType::Float32 => controller::SensorDataType::Float32,
+        }
+    }
+
+    fn is_scaled(&self) -> bool {
+        self.scale.is_some() || self.offset.is_some()
+    }
+
+    /// `decode` decodes the field from registers read starting at `start` address.
+    /// The field is checked to be in range of the read registers by [`parse_groups`].
+    fn decode(&self, regs: &[u16], start: u16) -> controller::SensorDataTypeValue {
+        let i = (self.address - start) as usize;
+        let reg = |n: usize| match self.byte_order {
+            Order::Big => regs[i + n],
+            Order::Little => regs[i + n].swap_bytes(),
+        };
+        let dword = || {
+            let (hi, lo) = match self.word_order {
+                Order::Big => (reg(0), reg(1)),
+                Order::Little => (reg(1), reg(0)),
+            };
+            ((hi as u32) << 16) | lo as u32
+        };
+
+        let value = match self.typ {
+            FieldType::Bool => controller::SensorDataTypeValue::Int16(regs[i] as i16),
+            FieldType::Int16 => controller::SensorDataTypeValue::Int16(reg(0) as i16),
+            FieldType::Uint16 => controller::SensorDataTypeValue::Int32(reg(0) as i32),
+            FieldType::Int32 => controller::SensorDataTypeValue::Int32(dword() as i32),
+            FieldType::Uint32 => controller::SensorDataTypeValue::Int64(dword() as i64),
+            FieldType::Float32 => controller::SensorDataTypeValue::Float32(f32::from_bits(dword())),
+        };
+
+        if !self.is_scaled() {
+            return value;
+        }
+
+        let raw = match value {
+            controller::SensorDataTypeValue::Int16(v) => v as f64,
+            controller::SensorDataTypeValue::Int32(v) => v as f64,
+            controller::SensorDataTypeValue::Int64(v) => v as f64,
+            controller::SensorDataTypeValue::Float32(v) => v as f64,
+            _ => unreachable!(),
+        };
+
+        controller::SensorDataTypeValue::Float64(
+            raw * self.scale.unwrap_or(1.0) + self.offset.unwrap_or(0.0),
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum FieldType {
+    Bool,
+    Int16,
+    Uint16,
+    Int32,
+    Uint32,
+    Float32,
+}
+
+impl FieldType {
+    /// `size` returns the number of registers (or bits) taken by a value
+    fn size(self) -> u16 {
+        match self {
+            FieldType::Bool | FieldType::Int16 | FieldType::Uint16 => 1,
+            FieldType::Int32 | FieldType::Uint32 | FieldType::Float32 => 2,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+enum Order {
+    #[default]
+    Big,
+    Little,
+}
@@ -3,10 +3,14 @@ use super::conv;
 use super::error::{ComError, ModuleError};
 
 use libc::c_void;
+use std::any::Any;
 use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
 
 use crate::controller;
 use crate::controller::interface::module::MsgHandler;
+use crate::tool::validation;
+use crate::{kv_any, kvs, logger};
 
 pub const VERSION: u8 = 1;
 
@@ -32,24 +36,33 @@ impl Handle {
 
 unsafe impl Send for Handle {}
 
+/// Functions that may be absent in a module. They are looked up by name
+/// instead of being a part of [`bg::Functions`] to keep compatibility with older modules.
+#[derive(Default)]
+pub struct OptFunctions {
+    pub obtain_command_infos: bg::obtain_command_infos_fn,
+    pub send_command: bg::send_command_fn,
+}
+
 pub extern "C" fn device_conn_info_callback(obj: *mut c_void, info: *mut bg::ConfInfo) {
     conf_info(obj as _, info);
 }
 
 fn build_conf_info(info: *mut bg::ConfInfo) -> Result<controller::ConfInfo, ModuleError> {
-    if unsafe { (*info).confs }.is_null() {
-        return Err(ModuleError::InvalidPointer("conf_info.confs"));
+    if info.is_null() {
+        return Err(ModuleError::InvalidPointer("conf_info"));
     }
 
-    let confs = unsafe { std::slice::from_raw_parts((*info).confs, (*info).confs_len as _) };
-    let mut res = controller::ConfInfo::with_capacity(unsafe { (*info).confs_len } as _);
+    let info = unsafe { &*info };
+    let confs = conv::slice_from_raw(info.confs, info.confs_len, "conf_info.confs")?;
+    let mut res = controller::ConfInfo::with_capacity(confs.len());
 
     for conf in confs {
         let data = build_conf_info_entry_data(conf)?;
 
         res.push(controller::ConfInfoEntry {
             id: conf.id,
-            name: conv::str_from_c_char(conf.name),
+            name: conv::try_str_from_c_char(conf.name, "conf_info_entry.name")?,
             data: data,
         });
     }
@@ -60,6 +73,10 @@ fn build_conf_info(info: *mut bg::ConfInfo) -> Result<controller::ConfInfo, Modu
 fn build_conf_info_entry_data(
     conf: &bg::ConfInfoEntry,
 ) -> Result<controller::ConfInfoEntryType, ModuleError> {
+    if conf.data.is_null() {
+        return Err(ModuleError::InvalidPointer("conf_info_entry.data"));
+    }
+
     match conf.typ {
         bg::ConfInfoEntryType::ConfInfoEntryTypeSection => {
             let section = build_conf_info(conf.data as *mut bg::ConfInfo)?;
@@ -144,15 +161,23 @@ fn build_conf_info_entry_data(
         bg::ConfInfoEntryType::ConfInfoEntryTypeChoiceList => {
             let data = unsafe { *(conf.data as *mut bg::ConfInfoEntryChoiceList) };
 
+            let choices = conv::slice_from_raw(
+                data.choices,
+                data.chioces_len,
+                "conf_info_entry_choice_list.choices",
+            )?;
+
             let mut entry = controller::ConfInfoEntryChoiceList {
                 required: data.required,
                 default: nullable_into_option(data.def),
-                choices: Vec::with_capacity(data.chioces_len as _),
+                choices: Vec::with_capacity(choices.len()),
             };
 
-            for choice in unsafe { std::slice::from_raw_parts(data.choices, data.chioces_len as _) }
-            {
-                entry.choices.push(conv::str_from_c_char(*choice));
+            for choice in choices {
+                entry.choices.push(conv::try_str_from_c_char(
+                    *choice,
+                    "conf_info_entry_choice_list.choices",
+                )?);
             }
 
             Ok(controller::ConfInfoEntryType::ChoiceList(entry))
@@ -163,16 +188,7 @@ fn build_conf_info_entry_data(
 pub type ConfInfoRec = Result<controller::ConfInfo, ModuleError>;
 
 fn conf_info(res: *mut ConfInfoRec, info: *mut bg::ConfInfo) {
-    if info.is_null() {
-        unsafe {
-            *res = Err(ModuleError::InvalidPointer("conf"));
-        }
-        return;
-    }
-
-    unsafe {
-        *res = build_conf_info(info);
-    }
+    set_rec(res, catch_panic(|| build_conf_info(info)));
 }
 
 pub extern "C" fn device_conf_info_callback(obj: *mut c_void, info: *mut bg::ConfInfo) {
@@ -189,22 +205,29 @@ pub fn build_conf(confs: &Vec<bg::ConfEntry>) -> bg::Conf {
 pub fn bg_sensor_type_infos_to_sensor_vec(
     infos: *mut bg::SensorTypeInfos,
 ) -> Result<Vec<controller::Sensor>, ModuleError> {
-    let infos_slice = unsafe {
-        std::slice::from_raw_parts(
-            (*infos).sensor_type_infos,
-            (*infos).sensor_type_infos_len as _,
-        )
-    };
+    if infos.is_null() {
+        return Err(ModuleError::InvalidPointer("sensor_type_infos"));
+    }
+
+    let infos = unsafe { &*infos };
+    let infos_slice = conv::slice_from_raw(
+        infos.sensor_type_infos,
+        infos.sensor_type_infos_len,
+        "sensor_type_infos.sensor_type_infos",
+    )?;
 
     let mut res_infos = Vec::with_capacity(infos_slice.len());
     for info in infos_slice {
-        let data_type_infos_slice = unsafe {
-            std::slice::from_raw_parts(info.data_type_infos, info.data_type_infos_len as _)
-        };
+        let data_type_infos_slice = conv::slice_from_raw(
+            info.data_type_infos,
+            info.data_type_infos_len,
+            "sensor_type_info.data_type_infos",
+        )?;
 
         let mut res_data_type_infos_map = HashMap::with_capacity(data_type_infos_slice.len());
         for data_type_info in data_type_infos_slice {
-            let name = conv::str_from_c_char(data_type_info.name);
+            let name =
+                conv::try_str_from_c_char(data_type_info.name, "sensor_data_type_info.name")?;
             res_data_type_infos_map.insert(
                 name.clone(),
                 controller::SensorDataEntry {
@@ -215,7 +238,7 @@ pub fn bg_sensor_type_infos_to_sensor_vec(
         }
 
         res_infos.push(controller::Sensor {
-            name: conv::str_from_c_char(info.name),
+            name: conv::try_str_from_c_char(info.name, "sensor_type_info.name")?,
             data_map: res_data_type_infos_map,
         })
     }
@@ -226,20 +249,152 @@ pub fn bg_sensor_type_infos_to_sensor_vec(
 pub type SensorTypeInfosRec = Result<Vec<controller::Sensor>, ModuleError>;
 
 fn sensor_type_infos(res: *mut SensorTypeInfosRec, infos: *mut bg::SensorTypeInfos) {
+    set_rec(
+        res,
+        catch_panic(|| bg_sensor_type_infos_to_sensor_vec(infos)),
+    );
+}
+
+pub extern "C" fn sensor_type_infos_callback(obj: *mut c_void, infos: *mut bg::SensorTypeInfos) {
+    sensor_type_infos(obj as _, infos);
+}
+
+pub fn bg_command_infos_to_ctrl(
+    infos: *mut bg::CommandInfos,
+) -> Result<Vec<controller::CommandInfo>, ModuleError> {
     if infos.is_null() {
-        unsafe {
-            *res = Err(ModuleError::InvalidPointer("sensor_type_infos"));
-        }
-        return;
+        return Err(ModuleError::InvalidPointer("command_infos"));
     }
 
-    unsafe {
-        *res = bg_sensor_type_infos_to_sensor_vec(infos);
+    let infos = unsafe { &*infos };
+    let infos_slice = conv::slice_from_raw(
+        infos.command_infos,
+        infos.command_infos_len,
+        "command_infos.command_infos",
+    )?;
+
+    let mut res_infos = Vec::with_capacity(infos_slice.len());
+    for info in infos_slice {
+        let arg_infos_slice =
+            conv::slice_from_raw(info.arg_infos, info.arg_infos_len, "command_info.arg_infos")?;
+
+        let args = arg_infos_slice
+            .iter()
+            .map(|arg_info| {
+                Ok(controller::CommandArgInfo {
+                    name: conv::try_str_from_c_char(arg_info.name, "command_arg_info.name")?,
+                    typ: conv::bg_sensor_data_type_to_ctrl(&arg_info.typ),
+                    required: arg_info.required,
+                })
+            })
+            .collect::<Result<_, ModuleError>>()?;
+
+        res_infos.push(controller::CommandInfo {
+            name: conv::try_str_from_c_char(info.name, "command_info.name")?,
+            description: conv::option_str_from_c_char(info.description),
+            args,
+        })
     }
+
+    Ok(res_infos)
 }
 
-pub extern "C" fn sensor_type_infos_callback(obj: *mut c_void, infos: *mut bg::SensorTypeInfos) {
-    sensor_type_infos(obj as _, infos);
+pub type CommandInfosRec = Result<Vec<controller::CommandInfo>, ModuleError>;
+
+fn command_infos(res: *mut CommandInfosRec, infos: *mut bg::CommandInfos) {
+    set_rec(res, catch_panic(|| bg_command_infos_to_ctrl(infos)));
+}
+
+pub extern "C" fn command_infos_callback(obj: *mut c_void, infos: *mut bg::CommandInfos) {
+    command_infos(obj as _, infos);
+}
+
+pub type ModuleInfoRec = Result<controller::ModuleInfo, ModuleError>;
+
+pub(super) fn build_module_info(
+    info: *mut bg::ModuleInfo,
+) -> Result<controller::ModuleInfo, ModuleError> {
+    if info.is_null() {
+        return Err(ModuleError::InvalidPointer("module_info"));
+    }
+
+    let info = unsafe { &*info };
+
+    if info.name.is_null() {
+        return Err(ModuleError::InvalidPointer("module_info.name"));
+    }
+    if info.version.is_null() {
+        return Err(ModuleError::InvalidPointer("module_info.version"));
+    }
+
+    let caps = info.capabilities;
+    let has_cap = |cap: bg::ModuleCapability| caps & cap as u32 != 0;
+
+    Ok(controller::ModuleInfo {
+        name: conv::str_from_c_char(info.name),
+        vendor: conv::option_str_from_c_char(info.vendor),
+        version: conv::str_from_c_char(info.version),
+        description: conv::option_str_from_c_char(info.description),
+        os: conv::option_str_from_c_char(info.os),
+        arch: conv::option_str_from_c_char(info.arch),
+        capabilities: controller::ModuleCapabilities {
+            commands: has_cap(bg::ModuleCapability::ModuleCapabilityCommands),
+            hot_reconfigure: has_cap(bg::ModuleCapability::ModuleCapabilityHotReconfigure),
+            persisted_config: has_cap(bg::ModuleCapability::ModuleCapabilityPersistedConfig),
+        },
+    })
+}
+
+fn module_info(res: *mut ModuleInfoRec, info: *mut bg::ModuleInfo) {
+    set_rec(res, catch_panic(|| build_module_info(info)));
+}
+
+pub extern "C" fn module_info_callback(obj: *mut c_void, info: *mut bg::ModuleInfo) {
+    module_info(obj as _, info);
+}
+
+/// `validate_module_info` checks that the module info is well-formed, the module
+/// is built for the current platform and its declared capabilities are consistent.
+pub fn validate_module_info(
+    info: &controller::ModuleInfo,
+    opt_funcs: &OptFunctions,
+) -> Result<(), ModuleError> {
+    if info.name.is_empty() {
+        return Err(ModuleError::InvalidModuleInfo("name is empty".into()));
+    }
+
+    validation::validate_semver(&info.version).map_err(|err| {
+        ModuleError::InvalidModuleInfo(format!("version '{}': {}", info.version, err))
+    })?;
+
+    if let Some(ref os) = info.os {
+        if os != std::env::consts::OS {
+            return Err(ModuleError::InvalidModuleInfo(format!(
+                "module is built for OS '{}', but current OS is '{}'",
+                os,
+                std::env::consts::OS
+            )));
+        }
+    }
+
+    if let Some(ref arch) = info.arch {
+        if arch != std::env::consts::ARCH {
+            return Err(ModuleError::InvalidModuleInfo(format!(
+                "module is built for architecture '{}', but current architecture is '{}'",
+                arch,
+                std::env::consts::ARCH
+            )));
+        }
+    }
+
+    let has_commands = opt_funcs.obtain_command_infos.is_some() && opt_funcs.send_command.is_some();
+    if info.capabilities.commands != has_commands {
+        return Err(ModuleError::InvalidModuleInfo(
+            "'commands' capability doesn't match exported command functions".into(),
+        ));
+    }
+
+    Ok(())
 }
 
 pub struct MsgHandle(Box<dyn MsgHandler>);
@@ -250,18 +405,67 @@ impl MsgHandle {
     }
 }
 
+/// `handle_msg_callback` passes a message from a module to the handler. A malformed message
+/// is passed as an error message, so it's reported as device's error.
 pub extern "C" fn handle_msg_callback(handler: *mut c_void, msg_data: bg::Message) {
-    let h = handler as *const MsgHandle;
+    if handler.is_null() {
+        logger::error_kv("module sent a message with NULL handler", None);
+        return;
+    }
 
-    unsafe {
-        let data = conv::bg_message_to_ctrl(&msg_data);
-        let h = &(*h).0;
+    let h = unsafe { &(*(handler as *const MsgHandle)).0 };
+
+    let res = catch_panic(|| {
+        let data = conv::bg_message_to_ctrl(&msg_data).unwrap_or_else(|err| controller::Message {
+            msg: controller::MessageType::Common(controller::CommonMsg {
+                code: controller::MsgCode::Error,
+                msg: format!("malformed message from module: {err}"),
+            }),
+        });
         h.handle_msg(data);
+
+        Ok(())
+    });
+
+    if let Err(err) = res {
+        logger::error_kv(
+            "failed to handle message from module",
+            kvs!("error" => kv_any!(err.to_string())),
+        );
     }
 }
 
 // ------------------- Utility functions -------------------
 
+/// `catch_panic` runs `f` and turns a panic into an error, so that it never unwinds
+/// across the FFI boundary.
+pub fn catch_panic<T, F: FnOnce() -> Result<T, ModuleError>>(f: F) -> Result<T, ModuleError> {
+    panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|payload| Err(ModuleError::Panic(panic_message(payload.as_ref()))))
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// `set_rec` writes a callback's result to a receiver passed by MoniSens to a module.
+fn set_rec<T>(rec: *mut Result<T, ModuleError>, val: Result<T, ModuleError>) {
+    if rec.is_null() {
+        logger::error_kv("module called a callback with NULL object", None);
+        return;
+    }
+
+    unsafe {
+        *rec = val;
+    }
+}
+
 pub fn convert_com_error(err: u8) -> Result<(), ComError> {
     match err {
         0 => Ok(()),
@@ -0,0 +1,682 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rumqttc::{
+    Client, ConnectionError, Event, MqttOptions, Packet, QoS, RecvTimeoutError, SubscribeFilter,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::controller;
+use crate::controller::error::{CommonError, ErrorType};
+use crate::controller::interface::module::{IModule, MsgHandler};
+
+use super::builtin;
+
+/// `MQTT_MODULE_NAME` is a reserved module name of the built-in [`MqttModule`].
+pub const MQTT_MODULE_NAME: &str = "builtin:mqtt";
+
+const CONF_FILE_NAME: &str = "mqtt.json";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often the receiving thread checks if it must stop
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+const CONN_CONF_HOST: i32 = 1;
+const CONN_CONF_PORT: i32 = 2;
+const CONN_CONF_CLIENT_ID: i32 = 3;
+const CONN_CONF_USERNAME: i32 = 4;
+const CONN_CONF_PASSWORD: i32 = 5;
+const CONN_CONF_KEEP_ALIVE: i32 = 6;
+
+const CONF_SENSORS: i32 = 1;
+const CONF_QOS: i32 = 2;
+const CONF_RECONNECT_INTERVAL: i32 = 3;
+
+const DEFAULT_PORT: i32 = 1883;
+const DEFAULT_KEEP_ALIVE: i32 = 30;
+const DEFAULT_RECONNECT_INTERVAL: i32 = 5;
+const DEFAULT_SENSORS: &str = r#"[
+    {
+        "name": "room",
+        "topic": "home/room/state",
+        "fields": [
+            {"name": "temperature", "path": "temperature", "type": "float64"},
+            {"name": "humidity", "path": "env.humidity", "type": "float32"}
+        ]
+    }
+]"#;
+
+const QOS_CHOICES: [&str; 3] = ["0 - At most once", "1 - At least once", "2 - Exactly once"];
+
+/// `MqttModule` is a module built into MoniSens which subscribes to topics of an MQTT broker
+/// and turns received messages into sensor data.
+///
+/// Every sensor is bound to a topic filter (wildcards are allowed). A field of a sensor is taken
+/// from a JSON payload by a dot-separated path, e.g. `env.humidity` or `values.0`.
+/// An empty path takes the whole payload, so plain values like `21.5` can be received as well.
+/// Fields which are absent in a payload are left empty.
+///
+/// The module reconnects to the broker and subscribes again after the connection is lost.
+/// The configuration is stored in the device's data dir.
+pub struct MqttModule {
+    data_dir: PathBuf,
+    conf: MqttConf,
+    worker: Option<Worker>,
+}
+
+struct Worker {
+    client: Client,
+    stop: Arc<AtomicBool>,
+    handle: thread::JoinHandle<()>,
+}
+
+impl MqttModule {
+    pub fn new<P: AsRef<Path>>(data_dir: P) -> Result<Self, CommonError> {
+        let data_dir = data_dir.as_ref().to_path_buf();
+        let conf = builtin::load_conf(&data_dir, CONF_FILE_NAME)?.unwrap_or_default();
+
+        Ok(Self {
+            data_dir,
+            conf,
+            worker: None,
+        })
+    }
+}
+
+impl Drop for MqttModule {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+impl IModule for MqttModule {
+    fn obtain_device_conn_info(&mut self) -> Result<controller::ConfInfo, CommonError> {
+        Ok(vec![
+            builtin::string_entry(CONN_CONF_HOST, "Broker host", true, Some("localhost")),
+            builtin::int_entry(CONN_CONF_PORT, "Broker port", DEFAULT_PORT, 0, 65536),
+            builtin::string_entry(
+                CONN_CONF_CLIENT_ID,
+                "Client ID (empty for a generated one)",
+                false,
+                None,
+            ),
+            builtin::string_entry(CONN_CONF_USERNAME, "Username", false, None),
+            builtin::string_entry(CONN_CONF_PASSWORD, "Password", false, None),
+            builtin::int_entry(
+                CONN_CONF_KEEP_ALIVE,
+                "Keep alive, s",
+                DEFAULT_KEEP_ALIVE,
+                4,
+                65536,
+            ),
+        ])
+    }
+
+    fn connect_device(&mut self, confs: Vec<controller::ConfEntry>) -> Result<(), CommonError> {
+        let mut broker = BrokerConf::default();
+
+        for entry in confs {
+            match (entry.id, entry.data) {
+                (_, None) => {}
+                (CONN_CONF_HOST, Some(controller::ConfType::String(v))) => broker.host = v,
+                (CONN_CONF_PORT, Some(controller::ConfType::Int(v))) => {
+                    broker.port = u16::try_from(v).map_err(|_| {
+                        CommonError::new(ErrorType::InvalidInput, "invalid broker port")
+                    })?;
+                }
+                (CONN_CONF_CLIENT_ID, Some(controller::ConfType::String(v))) => {
+                    broker.client_id = v
+                }
+                (CONN_CONF_USERNAME, Some(controller::ConfType::String(v))) => {
+                    broker.username = Some(v)
+                }
+                (CONN_CONF_PASSWORD, Some(controller::ConfType::String(v))) => {
+                    broker.password = Some(v)
+                }
+                (CONN_CONF_KEEP_ALIVE, Some(controller::ConfType::Int(v))) => broker.keep_alive = v,
+                (CONN_CONF_HOST, _) => return Err(builtin::invalid_conf_type("host")),
+                (CONN_CONF_PORT, _) => return Err(builtin::invalid_conf_type("port")),
+                (CONN_CONF_CLIENT_ID, _) => return Err(builtin::invalid_conf_type("client id")),
+                (CONN_CONF_USERNAME, _) => return Err(builtin::invalid_conf_type("username")),
+                (CONN_CONF_PASSWORD, _) => return Err(builtin::invalid_conf_type("password")),
+                (CONN_CONF_KEEP_ALIVE, _) => return Err(builtin::invalid_conf_type("keep alive")),
+                (id, _) => return Err(builtin::unknown_conf_entry(id)),
+            }
+        }
+
+        if broker.host.is_empty() {
+            return Err(CommonError::new(
+                ErrorType::InvalidInput,
+                "broker host is required",
+            ));
+        }
+        if broker.keep_alive < 5 {
+            return Err(CommonError::new(
+                ErrorType::InvalidInput,
+                "keep alive must be at least 5 seconds",
+            ));
+        }
+        if broker.client_id.is_empty() {
+            broker.client_id = format!("monisens-{:x}", rand_suffix());
+        }
+
+        check_connection(&broker)?;
+        self.conf.broker = broker;
+
+        Ok(())
+    }
+
+    fn obtain_device_conf_info(&mut self) -> Result<controller::ConfInfo, CommonError> {
+        Ok(vec![
+            controller::ConfInfoEntry {
+                id: CONF_SENSORS,
+                name: "Sensors, topics and fields".to_string(),
+                data: controller::ConfInfoEntryType::JSON(controller::ConfInfoEntryJSON {
+                    required: true,
+                    default: Some(DEFAULT_SENSORS.to_string()),
+                }),
+            },
+            controller::ConfInfoEntry {
+                id: CONF_QOS,
+                name: "QoS".to_string(),
+                data: controller::ConfInfoEntryType::ChoiceList(
+                    controller::ConfInfoEntryChoiceList {
+                        required: false,
+                        default: Some(0),
+                        choices: QOS_CHOICES.iter().map(|c| c.to_string()).collect(),
+                    },
+                ),
+            },
+            builtin::int_entry(
+                CONF_RECONNECT_INTERVAL,
+                "Reconnect interval, s",
+                DEFAULT_RECONNECT_INTERVAL,
+                0,
+                3601,
+            ),
+        ])
+    }
+
+    fn configure_device(&mut self, confs: Vec<controller::ConfEntry>) -> Result<(), CommonError> {
+        let mut sensors = None;
+        let mut qos = 0;
+        let mut reconnect_interval = DEFAULT_RECONNECT_INTERVAL;
+
+        for entry in confs {
+            match (entry.id, entry.data) {
+                (_, None) => {}
+                (CONF_SENSORS, Some(controller::ConfType::JSON(v))) => {
+                    sensors = Some(parse_sensors(&v)?)
+                }
+                (CONF_QOS, Some(controller::ConfType::ChoiceList(v))) => {
+                    if !(0..QOS_CHOICES.len() as i32).contains(&v) {
+                        return Err(CommonError::new(ErrorType::InvalidInput, "unknown QoS"));
+                    }
+                    qos = v as u8;
+                }
+                (CONF_RECONNECT_INTERVAL, Some(controller::ConfType::Int(v))) => {
+                    if v <= 0 {
+                        return Err(CommonError::new(
+                            ErrorType::InvalidInput,
+                            "reconnect interval must be positive",
+                        ));
+                    }
+                    reconnect_interval = v;
+                }
+                (CONF_SENSORS, _) => return Err(builtin::invalid_conf_type("sensors")),
+                (CONF_QOS, _) => return Err(builtin::invalid_conf_type("qos")),
+                (CONF_RECONNECT_INTERVAL, _) => {
+                    return Err(builtin::invalid_conf_type("reconnect interval"))
+                }
+                (id, _) => return Err(builtin::unknown_conf_entry(id)),
+            }
+        }
+
+        self.conf.sensors = sensors.ok_or_else(|| {
+            CommonError::new(ErrorType::InvalidInput, "sensors must be configured")
+        })?;
+        self.conf.qos = qos;
+        self.conf.reconnect_interval = reconnect_interval;
+
+        builtin::save_conf(&self.data_dir, CONF_FILE_NAME, &self.conf)
+    }
+
+    fn obtain_sensor_type_infos(&mut self) -> Result<Vec<controller::Sensor>, CommonError> {
+        let sensors = self
+            .conf
+            .sensors
+            .iter()
+            .map(|sensor| {
+                let mut data_map = HashMap::with_capacity(sensor.fields.len() + 1);
+                data_map.extend([builtin::timestamp_entry()]);
+
+                for field in sensor.fields.iter() {
+                    data_map.insert(
+                        field.name.clone(),
+                        controller::SensorDataEntry {
+                            name: field.name.clone(),
+                            typ: field.typ.into(),
+                        },
+                    );
+                }
+
+                controller::Sensor {
+                    name: sensor.name.clone(),
+                    data_map,
+                }
+            })
+            .collect();
+
+        Ok(sensors)
+    }
+
+    fn obtain_command_infos(&mut self) -> Result<Vec<controller::CommandInfo>, CommonError> {
+        Ok(Vec::new())
+    }
+
+    fn send_command(&mut self, _cmd: &controller::Command) -> Result<(), CommonError> {
+        Err(CommonError::new(
+            ErrorType::FailedPrecondition,
+            "the module doesn't support commands",
+        ))
+    }
+
+    fn start<H: MsgHandler + 'static>(&mut self, msg_handler: H) -> Result<(), CommonError> {
+        self.stop()?;
+
+        if self.conf.sensors.is_empty() {
+            return Err(CommonError::new(
+                ErrorType::FailedPrecondition,
+                "the module is not configured",
+            ));
+        }
+
+        let (client, connection) = Client::new(
+            mqtt_options(&self.conf.broker),
+            self.conf.sensors.len() + 10,
+        );
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let receiver = Receiver {
+            client: client.clone(),
+            sensors: self.conf.sensors.clone(),
+            qos: qos_from_u8(self.conf.qos),
+            reconnect_interval: Duration::from_secs(self.conf.reconnect_interval as u64),
+            stop: stop.clone(),
+            msg_handler,
+        };
+
+        let handle = thread::Builder::new()
+            .name("mqtt".to_string())
+            .spawn(move || receiver.run(connection))
+            .map_err(|err| {
+                CommonError::new(ErrorType::Internal, "failed to spawn MQTT thread")
+                    .with_source(err)
+            })?;
+
+        self.worker = Some(Worker {
+            client,
+            stop,
+            handle,
+        });
+
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), CommonError> {
+        if let Some(worker) = self.worker.take() {
+            worker.stop.store(true, Ordering::SeqCst);
+            let _ = worker.client.try_disconnect();
+            worker
+                .handle
+                .join()
+                .map_err(|_| CommonError::new(ErrorType::Internal, "MQTT thread has panicked"))?;
+        }
+
+        Ok(())
+    }
+
+    fn module_info(&self) -> Option<controller::ModuleInfo> {
+        Some(controller::ModuleInfo {
+            name: MQTT_MODULE_NAME.to_string(),
+            vendor: Some("MoniSens".to_string()),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            description: Some("Built-in MQTT subscriber".to_string()),
+            os: None,
+            arch: None,
+            capabilities: controller::ModuleCapabilities {
+                commands: false,
+                hot_reconfigure: false,
+                persisted_config: true,
+            },
+        })
+    }
+}
+
+/// `Receiver` runs on a separate thread and polls the MQTT connection until the module stops.
+struct Receiver<H: MsgHandler> {
+    client: Client,
+    sensors: Vec<SensorConf>,
+    qos: QoS,
+    reconnect_interval: Duration,
+    stop: Arc<AtomicBool>,
+    msg_handler: H,
+}
+
+impl<H: MsgHandler> Receiver<H> {
+    fn run(self, mut connection: rumqttc::Connection) {
+        let mut connected = false;
+
+        while !self.stop.load(Ordering::SeqCst) {
+            match connection.recv_timeout(POLL_INTERVAL) {
+                Ok(Ok(Event::Incoming(Packet::ConnAck(_)))) => {
+                    connected = true;
+                    self.subscribe();
+                }
+                Ok(Ok(Event::Incoming(Packet::Publish(p)))) => {
+                    for msg in build_messages(&self.sensors, &p.topic, &p.payload) {
+                        self.msg_handler.handle_msg(msg);
+                    }
+                }
+                Ok(Ok(_)) => {}
+                Ok(Err(err)) => {
+                    if self.stop.load(Ordering::SeqCst) {
+                        break;
+                    }
+
+                    if connected {
+                        self.send_common(
+                            controller::MsgCode::Error,
+                            format!("connection to MQTT broker lost: {err}"),
+                        );
+                    } else if !matches!(err, ConnectionError::NetworkTimeout) {
+                        self.send_common(
+                            controller::MsgCode::Warn,
+                            format!("failed to connect to MQTT broker: {err}"),
+                        );
+                    }
+                    connected = false;
+
+                    self.wait_reconnect();
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    }
+
+    /// `subscribe` subscribes to sensors' topics. It's called after every connection,
+    /// because the session is clean and the broker doesn't keep subscriptions.
+    fn subscribe(&self) {
+        let mut filters: Vec<_> = self.sensors.iter().map(|s| s.topic.clone()).collect();
+        filters.sort();
+        filters.dedup();
+
+        let filters = filters
+            .into_iter()
+            .map(|topic| SubscribeFilter::new(topic, self.qos));
+
+        if let Err(err) = self.client.try_subscribe_many(filters) {
+            self.send_common(
+                controller::MsgCode::Error,
+                format!("failed to subscribe to MQTT topics: {err}"),
+            );
+        }
+    }
+
+    fn wait_reconnect(&self) {
+        let started = Instant::now();
+        while started.elapsed() < self.reconnect_interval && !self.stop.load(Ordering::SeqCst) {
+            thread::sleep(POLL_INTERVAL.min(self.reconnect_interval));
+        }
+    }
+
+    fn send_common(&self, code: controller::MsgCode, msg: String) {
+        self.msg_handler.handle_msg(controller::Message {
+            msg: controller::MessageType::Common(controller::CommonMsg { code, msg }),
+        });
+    }
+}
+
+/// `build_messages` turns an MQTT message into messages of all sensors bound to its topic.
+pub(super) fn build_messages(
+    sensors: &[SensorConf],
+    topic: &str,
+    payload: &[u8],
+) -> Vec<controller::Message> {
+    let mut res = Vec::new();
+
+    let sensors: Vec<_> = sensors
+        .iter()
+        .filter(|s| rumqttc::matches(topic, &s.topic))
+        .collect();
+    if sensors.is_empty() {
+        return res;
+    }
+
+    // A payload which isn't JSON is taken as a string
+    let payload = serde_json::from_slice(payload)
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(payload).into_owned()));
+
+    for sensor in sensors {
+        let mut data = Vec::with_capacity(sensor.fields.len() + 1);
+        data.push(builtin::timestamp_data());
+        let mut missing = Vec::new();
+
+        for field in sensor.fields.iter() {
+            let value = match json_path(&payload, &field.path) {
+                Some(v) if !v.is_null() => v,
+                _ => {
+                    missing.push(field.name.as_str());
+                    continue;
+                }
+            };
+
+            match controller::SensorDataTypeValue::from_json(&field.typ.into(), value) {
+                Some(v) => data.push(controller::SensorData {
+                    name: field.name.clone(),
+                    data: v,
+                }),
+                None => res.push(controller::Message {
+                    msg: controller::MessageType::Common(controller::CommonMsg {
+                        code: controller::MsgCode::Warn,
+                        msg: format!(
+                            "value '{value}' of field '{}' of sensor '{}' can't be converted to {:?}",
+                            field.name, sensor.name, field.typ
+                        ),
+                    }),
+                }),
+            }
+        }
+
+        // Every field of a sensor must have a value to be saved
+        if !missing.is_empty() {
+            res.push(controller::Message {
+                msg: controller::MessageType::Common(controller::CommonMsg {
+                    code: controller::MsgCode::Warn,
+                    msg: format!(
+                        "message on topic '{topic}' doesn't contain fields '{}' of sensor '{}'",
+                        missing.join("', '"),
+                        sensor.name
+                    ),
+                }),
+            });
+            continue;
+        }
+        if data.len() != sensor.fields.len() + 1 {
+            continue;
+        }
+
+        res.push(controller::Message {
+            msg: controller::MessageType::Sensor(controller::SensorMsg {
+                name: sensor.name.clone(),
+                data,
+            }),
+        });
+    }
+
+    res
+}
+
+/// `json_path` finds a value by a dot-separated path. Array elements are referenced by index.
+fn json_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    if path.is_empty() {
+        return Some(value);
+    }
+
+    path.split('.').try_fold(value, |v, key| match v {
+        Value::Object(m) => m.get(key),
+        Value::Array(a) => key.parse::<usize>().ok().and_then(|i| a.get(i)),
+        _ => None,
+    })
+}
+
+pub(super) fn parse_sensors(data: &str) -> Result<Vec<SensorConf>, CommonError> {
+    let sensors: Vec<SensorConf> = serde_json::from_str(data).map_err(|err| {
+        CommonError::new(ErrorType::InvalidInput, "failed to parse MQTT sensors").with_source(err)
+    })?;
+
+    let names: Vec<_> = sensors
+        .iter()
+        .map(|s| {
+            (
+                s.name.as_str(),
+                s.fields.iter().map(|f| f.name.as_str()).collect(),
+            )
+        })
+        .collect();
+    builtin::validate_sensor_names(&names)?;
+
+    for sensor in sensors.iter() {
+        if !rumqttc::valid_filter(&sensor.topic) {
+            return Err(CommonError::new(
+                ErrorType::InvalidInput,
+                format!(
+                    "invalid topic '{}' of sensor '{}'",
+                    sensor.topic, sensor.name
+                ),
+            ));
+        }
+    }
+
+    Ok(sensors)
+}
+
+fn check_connection(broker: &BrokerConf) -> Result<(), CommonError> {
+    let (client, mut connection) = Client::new(mqtt_options(broker), 10);
+    let started = Instant::now();
+
+    let res = loop {
+        let left = match CONNECT_TIMEOUT.checked_sub(started.elapsed()) {
+            Some(left) => left,
+            None => {
+                break Err(CommonError::new(
+                    ErrorType::Timeout,
+                    "timed out connecting to MQTT broker",
+                ))
+            }
+        };
+
+        match connection.recv_timeout(left) {
+            Ok(Ok(Event::Incoming(Packet::ConnAck(_)))) => break Ok(()),
+            Ok(Ok(_)) => {}
+            Ok(Err(err)) => {
+                break Err(
+                    CommonError::new(ErrorType::IO, "failed to connect to MQTT broker")
+                        .with_source(err),
+                )
+            }
+            Err(_) => {
+                break Err(CommonError::new(
+                    ErrorType::Timeout,
+                    "timed out connecting to MQTT broker",
+                ))
+            }
+        }
+    };
+
+    let _ = client.try_disconnect();
+
+    res
+}
+
+fn mqtt_options(broker: &BrokerConf) -> MqttOptions {
+    let mut opts = MqttOptions::new(&broker.client_id, &broker.host, broker.port);
+    opts.set_keep_alive(Duration::from_secs(broker.keep_alive as u64));
+    opts.set_clean_session(true);
+
+    if let Some(username) = &broker.username {
+        opts.set_credentials(username, broker.password.clone().unwrap_or_default());
+    }
+
+    opts
+}
+
+fn qos_from_u8(qos: u8) -> QoS {
+    match qos {
+        1 => QoS::AtLeastOnce,
+        2 => QoS::ExactlyOnce,
+        _ => QoS::AtMostOnce,
+    }
+}
+
+fn rand_suffix() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+
+    let mut h = std::collections::hash_map::RandomState::new().build_hasher();
+    h.write_u32(std::process::id());
+
+    h.finish()
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct MqttConf {
+    broker: BrokerConf,
+    qos: u8,
+    reconnect_interval: i32,
+    sensors: Vec<SensorConf>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BrokerConf {
+    host: String,
+    port: u16,
+    client_id: String,
+    username: Option<String>,
+    password: Option<String>,
+    keep_alive: i32,
+}
+
+impl Default for BrokerConf {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: DEFAULT_PORT as u16,
+            client_id: String::new(),
+            username: None,
+            password: None,
+            keep_alive: DEFAULT_KEEP_ALIVE,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(super) struct SensorConf {
+    name: String,
+    /// Topic filter, may contain `+` and `#` wildcards
+    topic: String,
+    fields: Vec<FieldConf>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct FieldConf {
+    name: String,
+    #[serde(default)]
+    path: String,
+    #[serde(rename = "type")]
+    typ: builtin::FieldType,
+}
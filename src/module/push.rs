@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::controller;
+use crate::controller::error::{CommonError, ErrorType};
+use crate::controller::interface::module::{
+    IModule, MsgHandler, PUSH_MODULE_NAME, PUSH_SENSORS_CONF_ID,
+};
+
+use super::builtin;
+
+const CONF_FILE_NAME: &str = "push.json";
+
+const CONF_SENSORS: i32 = PUSH_SENSORS_CONF_ID;
+
+const DEFAULT_SENSORS: &str = r#"[
+    {
+        "name": "room",
+        "fields": [
+            {"name": "temperature", "type": "float64"},
+            {"name": "humidity", "type": "float32"}
+        ]
+    }
+]"#;
+
+/// `PushModule` is a module built into MoniSens for devices which send their data themselves
+/// to the ingest endpoint. The module only declares sensors of the device: data is validated
+/// and saved by the controller, so starting and stopping the module does nothing.
+///
+/// The configuration is stored in the device's data dir.
+pub struct PushModule {
+    data_dir: PathBuf,
+    conf: PushConf,
+}
+
+impl PushModule {
+    pub fn new<P: AsRef<Path>>(data_dir: P) -> Result<Self, CommonError> {
+        let data_dir = data_dir.as_ref().to_path_buf();
+        let conf = builtin::load_conf(&data_dir, CONF_FILE_NAME)?.unwrap_or_default();
+
+        Ok(Self { data_dir, conf })
+    }
+}
+
+impl IModule for PushModule {
+    fn obtain_device_conn_info(&mut self) -> Result<controller::ConfInfo, CommonError> {
+        Ok(Vec::new())
+    }
+
+    fn connect_device(&mut self, confs: Vec<controller::ConfEntry>) -> Result<(), CommonError> {
+        match confs.first() {
+            Some(entry) => Err(builtin::unknown_conf_entry(entry.id)),
+            None => Ok(()),
+        }
+    }
+
+    fn obtain_device_conf_info(&mut self) -> Result<controller::ConfInfo, CommonError> {
+        Ok(vec![controller::ConfInfoEntry {
+            id: CONF_SENSORS,
+            name: "Sensors and fields".to_string(),
+            data: controller::ConfInfoEntryType::JSON(controller::ConfInfoEntryJSON {
+                required: true,
+                default: Some(DEFAULT_SENSORS.to_string()),
+            }),
+        }])
+    }
+
+    fn configure_device(&mut self, confs: Vec<controller::ConfEntry>) -> Result<(), CommonError> {
+        let mut sensors = None;
+
+        for entry in confs {
+            match (entry.id, entry.data) {
+                (_, None) => {}
+                (CONF_SENSORS, Some(controller::ConfType::JSON(v))) => {
+                    sensors = Some(parse_sensors(&v)?)
+                }
+                (CONF_SENSORS, _) => return Err(builtin::invalid_conf_type("sensors")),
+                (id, _) => return Err(builtin::unknown_conf_entry(id)),
+            }
+        }
+
+        self.conf.sensors = sensors.ok_or_else(|| {
+            CommonError::new(ErrorType::InvalidInput, "sensors must be configured")
+        })?;
+
+        builtin::save_conf(&self.data_dir, CONF_FILE_NAME, &self.conf)
+    }
+
+    fn obtain_sensor_type_infos(&mut self) -> Result<Vec<controller::Sensor>, CommonError> {
+        let sensors = self
+            .conf
+            .sensors
+            .iter()
+            .map(|sensor| {
+                let mut data_map = HashMap::with_capacity(sensor.fields.len() + 1);
+                data_map.extend([builtin::timestamp_entry()]);
+
+                for field in sensor.fields.iter() {
+                    data_map.insert(
+                        field.name.clone(),
+                        controller::SensorDataEntry {
+                            name: field.name.clone(),
+                            typ: field.typ.into(),
+                        },
+                    );
+                }
+
+                controller::Sensor {
+                    name: sensor.name.clone(),
+                    data_map,
+                }
+            })
+            .collect();
+
+        Ok(sensors)
+    }
+
+    fn obtain_command_infos(&mut self) -> Result<Vec<controller::CommandInfo>, CommonError> {
+        Ok(Vec::new())
+    }
+
+    fn send_command(&mut self, _cmd: &controller::Command) -> Result<(), CommonError> {
+        Err(CommonError::new(
+            ErrorType::FailedPrecondition,
+            "the module doesn't support commands",
+        ))
+    }
+
+    fn start<H: MsgHandler + 'static>(&mut self, _msg_handler: H) -> Result<(), CommonError> {
+        if self.conf.sensors.is_empty() {
+            return Err(CommonError::new(
+                ErrorType::FailedPrecondition,
+                "the module is not configured",
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), CommonError> {
+        Ok(())
+    }
+
+    fn module_info(&self) -> Option<controller::ModuleInfo> {
+        Some(controller::ModuleInfo {
+            name: PUSH_MODULE_NAME.to_string(),
+            vendor: Some("MoniSens".to_string()),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            description: Some("Built-in HTTP push device".to_string()),
+            os: None,
+            arch: None,
+            capabilities: controller::ModuleCapabilities {
+                commands: false,
+                hot_reconfigure: false,
+                persisted_config: true,
+            },
+        })
+    }
+}
+
+fn parse_sensors(data: &str) -> Result<Vec<SensorConf>, CommonError> {
+    let sensors: Vec<SensorConf> = serde_json::from_str(data).map_err(|err| {
+        CommonError::new(ErrorType::InvalidInput, "failed to parse push sensors").with_source(err)
+    })?;
+
+    let names: Vec<_> = sensors
+        .iter()
+        .map(|s| {
+            (
+                s.name.as_str(),
+                s.fields.iter().map(|f| f.name.as_str()).collect(),
+            )
+        })
+        .collect();
+    builtin::validate_sensor_names(&names)?;
+
+    Ok(sensors)
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct PushConf {
+    sensors: Vec<SensorConf>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SensorConf {
+    name: String,
+    fields: Vec<FieldConf>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FieldConf {
+    name: String,
+    #[serde(rename = "type")]
+    typ: builtin::FieldType,
+}
@@ -0,0 +1,670 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::controller;
+use crate::controller::error::{CommonError, ErrorType};
+use crate::controller::interface::module::{IModule, MsgHandler};
+
+use super::builtin;
+
+/// `SIMULATOR_MODULE_NAME` is a reserved module name of the built-in [`Simulator`].
+pub const SIMULATOR_MODULE_NAME: &str = "builtin:simulator";
+
+const CONF_FILE_NAME: &str = "simulator.json";
+/// Number of messages skipped by a stall fault
+const STALL_TICKS: u32 = 10;
+
+const CONN_CONF_SEED: i32 = 1;
+
+const CONF_SIGNALS: i32 = 1;
+const CONF_INTERVAL: i32 = 2;
+const CONF_FAULT_PROBABILITY: i32 = 3;
+const CONF_FAULT_KIND: i32 = 4;
+
+const DEFAULT_INTERVAL_MS: i32 = 1000;
+const DEFAULT_SIGNALS: &str = r#"[
+    {
+        "name": "environment",
+        "fields": [
+            {"name": "temperature", "type": "float64", "signal": "sine", "amplitude": 5, "period": 60, "offset": 20},
+            {"name": "humidity", "type": "float32", "signal": "random_walk", "start": 50, "step": 0.5, "min": 0, "max": 100}
+        ]
+    },
+    {
+        "name": "line",
+        "fields": [
+            {"name": "running", "type": "int16", "signal": "step", "low": 0, "high": 1, "period": 120},
+            {"name": "produced", "type": "int64", "signal": "counter", "start": 0, "step": 1}
+        ]
+    }
+]"#;
+
+const CMD_RESET: &str = "reset";
+
+/// `Simulator` is a module built into MoniSens which doesn't need any hardware.
+/// It generates synthetic signals on configured sensors, so MoniSens can be demoed
+/// and tested without an external library.
+///
+/// Every field of a sensor is driven by one of the signals:
+/// - `sine`: `offset + amplitude * sin(2π * t / period)`, `t` and `period` in seconds;
+/// - `random_walk`: starts at `start` and moves by up to `step` every message, limited by `min` and `max`;
+/// - `step`: `high` during the first half of every `period` and `low` during the second one;
+/// - `counter`: starts at `start` and grows by `step` every message.
+///
+/// Faults are injected with the configured probability per message: the message is replaced
+/// by an error message, dropped or the device stalls for several intervals.
+///
+/// The configuration is stored in the device's data dir, so the simulator generates the same
+/// sensors after a restart.
+pub struct Simulator {
+    data_dir: PathBuf,
+    conf: SimConf,
+    generator: Arc<Mutex<Generator>>,
+    worker: Option<builtin::Worker>,
+}
+
+impl Simulator {
+    pub fn new<P: AsRef<Path>>(data_dir: P) -> Result<Self, CommonError> {
+        let data_dir = data_dir.as_ref().to_path_buf();
+        let conf = builtin::load_conf(&data_dir, CONF_FILE_NAME)?.unwrap_or_default();
+
+        Ok(Self {
+            data_dir,
+            generator: Arc::new(Mutex::new(Generator::new(&conf))),
+            conf,
+            worker: None,
+        })
+    }
+
+    fn reset_generator(&mut self) {
+        *self.generator.lock().unwrap() = Generator::new(&self.conf);
+    }
+}
+
+impl Drop for Simulator {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+impl IModule for Simulator {
+    fn obtain_device_conn_info(&mut self) -> Result<controller::ConfInfo, CommonError> {
+        Ok(vec![controller::ConfInfoEntry {
+            id: CONN_CONF_SEED,
+            name: "Random seed (empty for a random one)".to_string(),
+            data: controller::ConfInfoEntryType::Int(controller::ConfInfoEntryInt {
+                required: false,
+                default: None,
+                lt: None,
+                gt: None,
+                neq: None,
+            }),
+        }])
+    }
+
+    fn connect_device(&mut self, confs: Vec<controller::ConfEntry>) -> Result<(), CommonError> {
+        for conf in confs {
+            if conf.id == CONN_CONF_SEED {
+                self.conf.seed = match conf.data {
+                    Some(controller::ConfType::Int(v)) => Some(v),
+                    None => None,
+                    Some(_) => return Err(builtin::invalid_conf_type("seed")),
+                };
+            }
+        }
+
+        self.reset_generator();
+
+        Ok(())
+    }
+
+    fn obtain_device_conf_info(&mut self) -> Result<controller::ConfInfo, CommonError> {
+        Ok(vec![
+            controller::ConfInfoEntry {
+                id: CONF_SIGNALS,
+                name: "Sensors and signals".to_string(),
+                data: controller::ConfInfoEntryType::JSON(controller::ConfInfoEntryJSON {
+                    required: false,
+                    default: Some(DEFAULT_SIGNALS.to_string()),
+                }),
+            },
+            controller::ConfInfoEntry {
+                id: CONF_INTERVAL,
+                name: "Message interval, ms".to_string(),
+                data: controller::ConfInfoEntryType::Int(controller::ConfInfoEntryInt {
+                    required: false,
+                    default: Some(DEFAULT_INTERVAL_MS),
+                    lt: None,
+                    gt: Some(0),
+                    neq: None,
+                }),
+            },
+            controller::ConfInfoEntry {
+                id: CONF_FAULT_PROBABILITY,
+                name: "Fault probability, %".to_string(),
+                data: controller::ConfInfoEntryType::Int(controller::ConfInfoEntryInt {
+                    required: false,
+                    default: Some(0),
+                    lt: Some(101),
+                    gt: Some(-1),
+                    neq: None,
+                }),
+            },
+            controller::ConfInfoEntry {
+                id: CONF_FAULT_KIND,
+                name: "Fault kind".to_string(),
+                data: controller::ConfInfoEntryType::ChoiceList(
+                    controller::ConfInfoEntryChoiceList {
+                        required: false,
+                        default: Some(0),
+                        choices: FaultKind::ALL
+                            .iter()
+                            .map(|kind| kind.name().to_string())
+                            .collect(),
+                    },
+                ),
+            },
+        ])
+    }
+
+    fn configure_device(&mut self, confs: Vec<controller::ConfEntry>) -> Result<(), CommonError> {
+        let mut conf = SimConf {
+            seed: self.conf.seed,
+            ..Default::default()
+        };
+
+        for entry in confs {
+            match (entry.id, entry.data) {
+                (_, None) => {}
+                (CONF_SIGNALS, Some(controller::ConfType::JSON(v))) => {
+                    conf.sensors = parse_sensors(&v)?;
+                }
+                (CONF_INTERVAL, Some(controller::ConfType::Int(v))) => {
+                    if v <= 0 {
+                        return Err(CommonError::new(
+                            ErrorType::InvalidInput,
+                            "message interval must be positive",
+                        ));
+                    }
+                    conf.interval_ms = v;
+                }
+                (CONF_FAULT_PROBABILITY, Some(controller::ConfType::Int(v))) => {
+                    if !(0..=100).contains(&v) {
+                        return Err(CommonError::new(
+                            ErrorType::InvalidInput,
+                            "fault probability must be in range [0, 100]",
+                        ));
+                    }
+                    conf.fault_probability = v;
+                }
+                (CONF_FAULT_KIND, Some(controller::ConfType::ChoiceList(v))) => {
+                    conf.fault_kind = *usize::try_from(v)
+                        .ok()
+                        .and_then(|i| FaultKind::ALL.get(i))
+                        .ok_or_else(|| {
+                            CommonError::new(ErrorType::InvalidInput, "unknown fault kind")
+                        })?;
+                }
+                (CONF_SIGNALS, _) => return Err(builtin::invalid_conf_type("signals")),
+                (CONF_INTERVAL, _) => return Err(builtin::invalid_conf_type("interval")),
+                (CONF_FAULT_PROBABILITY, _) => {
+                    return Err(builtin::invalid_conf_type("fault probability"))
+                }
+                (CONF_FAULT_KIND, _) => return Err(builtin::invalid_conf_type("fault kind")),
+                (id, _) => return Err(builtin::unknown_conf_entry(id)),
+            }
+        }
+
+        self.conf = conf;
+        builtin::save_conf(&self.data_dir, CONF_FILE_NAME, &self.conf)?;
+        self.reset_generator();
+
+        Ok(())
+    }
+
+    fn obtain_sensor_type_infos(&mut self) -> Result<Vec<controller::Sensor>, CommonError> {
+        let sensors = self
+            .conf
+            .sensors
+            .iter()
+            .map(|sensor| {
+                let mut data_map = HashMap::with_capacity(sensor.fields.len() + 1);
+                data_map.extend([builtin::timestamp_entry()]);
+
+                for field in sensor.fields.iter() {
+                    data_map.insert(
+                        field.name.clone(),
+                        controller::SensorDataEntry {
+                            name: field.name.clone(),
+                            typ: field.typ.into(),
+                        },
+                    );
+                }
+
+                controller::Sensor {
+                    name: sensor.name.clone(),
+                    data_map,
+                }
+            })
+            .collect();
+
+        Ok(sensors)
+    }
+
+    fn obtain_command_infos(&mut self) -> Result<Vec<controller::CommandInfo>, CommonError> {
+        Ok(vec![controller::CommandInfo {
+            name: CMD_RESET.to_string(),
+            description: Some("Reset all signals to their initial values".to_string()),
+            args: Vec::new(),
+        }])
+    }
+
+    fn send_command(&mut self, cmd: &controller::Command) -> Result<(), CommonError> {
+        match cmd.name.as_str() {
+            CMD_RESET => {
+                self.generator.lock().unwrap().reset();
+
+                Ok(())
+            }
+            name => Err(CommonError::new(
+                ErrorType::InvalidInput,
+                format!("unknown command '{name}'"),
+            )),
+        }
+    }
+
+    fn start<H: MsgHandler + 'static>(&mut self, msg_handler: H) -> Result<(), CommonError> {
+        self.stop()?;
+
+        let generator = self.generator.clone();
+        let interval = Duration::from_millis(self.conf.interval_ms as u64);
+
+        self.worker = Some(builtin::Worker::spawn("simulator", move |stop| {
+            run(generator, interval, stop, msg_handler)
+        })?);
+
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), CommonError> {
+        if let Some(worker) = self.worker.take() {
+            worker.stop()?;
+        }
+
+        Ok(())
+    }
+
+    fn module_info(&self) -> Option<controller::ModuleInfo> {
+        Some(controller::ModuleInfo {
+            name: SIMULATOR_MODULE_NAME.to_string(),
+            vendor: Some("MoniSens".to_string()),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            description: Some("Built-in device simulator generating synthetic signals".to_string()),
+            os: None,
+            arch: None,
+            capabilities: controller::ModuleCapabilities {
+                commands: true,
+                hot_reconfigure: false,
+                persisted_config: true,
+            },
+        })
+    }
+}
+
+fn run<H: MsgHandler>(
+    generator: Arc<Mutex<Generator>>,
+    interval: Duration,
+    stop: mpsc::Receiver<()>,
+    msg_handler: H,
+) {
+    let mut stalled = 0;
+
+    while let Err(mpsc::RecvTimeoutError::Timeout) = stop.recv_timeout(interval) {
+        if stalled > 0 {
+            stalled -= 1;
+            continue;
+        }
+
+        let tick = generator.lock().unwrap().tick();
+        match tick {
+            Tick::Data(msgs) => {
+                for msg in msgs {
+                    msg_handler.handle_msg(msg);
+                }
+            }
+            Tick::Fault(FaultKind::ErrorMessage) => msg_handler.handle_msg(controller::Message {
+                msg: controller::MessageType::Common(controller::CommonMsg {
+                    code: controller::MsgCode::Error,
+                    msg: "simulated device fault".to_string(),
+                }),
+            }),
+            Tick::Fault(FaultKind::DroppedMessage) => {}
+            Tick::Fault(FaultKind::Stall) => stalled = STALL_TICKS,
+        }
+    }
+}
+
+fn parse_sensors(data: &str) -> Result<Vec<SensorConf>, CommonError> {
+    let sensors: Vec<SensorConf> = serde_json::from_str(data).map_err(|err| {
+        CommonError::new(ErrorType::InvalidInput, "failed to parse simulator signals")
+            .with_source(err)
+    })?;
+
+    let names: Vec<_> = sensors
+        .iter()
+        .map(|s| {
+            (
+                s.name.as_str(),
+                s.fields.iter().map(|f| f.name.as_str()).collect(),
+            )
+        })
+        .collect();
+    builtin::validate_sensor_names(&names)?;
+
+    for sensor in sensors.iter() {
+        for field in sensor.fields.iter() {
+            if let Signal::Sine { period, .. } | Signal::Step { period, .. } = field.signal {
+                if period <= 0.0 {
+                    return Err(CommonError::new(
+                        ErrorType::InvalidInput,
+                        format!("period of field '{}' must be positive", field.name),
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(sensors)
+}
+
+#[derive(Serialize, Deserialize)]
+struct SimConf {
+    seed: Option<i32>,
+    interval_ms: i32,
+    fault_probability: i32,
+    fault_kind: FaultKind,
+    sensors: Vec<SensorConf>,
+}
+
+impl Default for SimConf {
+    fn default() -> Self {
+        Self {
+            seed: None,
+            interval_ms: DEFAULT_INTERVAL_MS,
+            fault_probability: 0,
+            fault_kind: FaultKind::ErrorMessage,
+            sensors: serde_json::from_str(DEFAULT_SIGNALS).unwrap(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct SensorConf {
+    name: String,
+    fields: Vec<FieldConf>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct FieldConf {
+    name: String,
+    #[serde(rename = "type")]
+    typ: FieldType,
+    #[serde(flatten)]
+    signal: Signal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum FieldType {
+    Int16,
+    Int32,
+    Int64,
+    Float32,
+    Float64,
+}
+
+impl From<FieldType> for controller::SensorDataType {
+    fn from(value: FieldType) -> Self {
+        match value {
+            FieldType::Int16 => controller::SensorDataType::Int16,
+            FieldType::Int32 => controller::SensorDataType::Int32,
+            FieldType::Int64 => controller::SensorDataType::Int64,
+            FieldType::Float32 => controller::SensorDataType::Float32,
+            FieldType::Float64 => controller::SensorDataType::Float64,
+        }
+    }
+}
+
+impl FieldType {
+    fn value(self, v: f64) -> controller::SensorDataTypeValue {
+        match self {
+            FieldType::Int16 => controller::SensorDataTypeValue::Int16(v.round() as i16),
+            FieldType::Int32 => controller::SensorDataTypeValue::Int32(v.round() as i32),
+            FieldType::Int64 => controller::SensorDataTypeValue::Int64(v.round() as i64),
+            FieldType::Float32 => controller::SensorDataTypeValue::Float32(v as f32),
+            FieldType::Float64 => controller::SensorDataTypeValue::Float64(v),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "signal", rename_all = "snake_case")]
+enum Signal {
+    Sine {
+        #[serde(default = "default_one")]
+        amplitude: f64,
+        #[serde(default = "default_period")]
+        period: f64,
+        #[serde(default)]
+        offset: f64,
+    },
+    RandomWalk {
+        #[serde(default)]
+        start: f64,
+        #[serde(default = "default_one")]
+        step: f64,
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+    Step {
+        #[serde(default)]
+        low: f64,
+        #[serde(default = "default_one")]
+        high: f64,
+        #[serde(default = "default_period")]
+        period: f64,
+    },
+    Counter {
+        #[serde(default)]
+        start: f64,
+        #[serde(default = "default_one")]
+        step: f64,
+    },
+}
+
+fn default_one() -> f64 {
+    1.0
+}
+
+fn default_period() -> f64 {
+    60.0
+}
+
+impl Signal {
+    fn initial(&self) -> f64 {
+        match *self {
+            Signal::RandomWalk { start, .. } | Signal::Counter { start, .. } => start,
+            Signal::Sine { .. } | Signal::Step { .. } => 0.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum FaultKind {
+    ErrorMessage,
+    DroppedMessage,
+    Stall,
+}
+
+impl FaultKind {
+    const ALL: [FaultKind; 3] = [
+        FaultKind::ErrorMessage,
+        FaultKind::DroppedMessage,
+        FaultKind::Stall,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            FaultKind::ErrorMessage => "Error message",
+            FaultKind::DroppedMessage => "Dropped message",
+            FaultKind::Stall => "Stall",
+        }
+    }
+}
+
+enum Tick {
+    Data(Vec<controller::Message>),
+    Fault(FaultKind),
+}
+
+/// `Generator` keeps the state of all signals between messages.
+struct Generator {
+    sensors: Vec<SensorConf>,
+    values: Vec<Vec<f64>>,
+    started: Instant,
+    rng: Rng,
+    fault_probability: i32,
+    fault_kind: FaultKind,
+}
+
+impl Generator {
+    fn new(conf: &SimConf) -> Self {
+        let seed = match conf.seed {
+            Some(seed) => seed as u64,
+            None => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or_default(),
+        };
+
+        let mut res = Self {
+            sensors: conf.sensors.clone(),
+            values: Vec::new(),
+            started: Instant::now(),
+            rng: Rng::new(seed),
+            fault_probability: conf.fault_probability,
+            fault_kind: conf.fault_kind,
+        };
+        res.reset();
+
+        res
+    }
+
+    fn reset(&mut self) {
+        self.started = Instant::now();
+        self.values = self
+            .sensors
+            .iter()
+            .map(|sensor| sensor.fields.iter().map(|f| f.signal.initial()).collect())
+            .collect();
+    }
+
+    fn tick(&mut self) -> Tick {
+        if self.fault_probability > 0 && self.rng.next_f64() * 100.0 < self.fault_probability as f64
+        {
+            return Tick::Fault(self.fault_kind);
+        }
+
+        let t = self.started.elapsed().as_secs_f64();
+
+        let mut msgs = Vec::with_capacity(self.sensors.len());
+        for (sensor, values) in self.sensors.iter().zip(self.values.iter_mut()) {
+            let mut data = Vec::with_capacity(sensor.fields.len() + 1);
+            data.push(builtin::timestamp_data());
+
+            for (field, value) in sensor.fields.iter().zip(values.iter_mut()) {
+                let v = next_value(&field.signal, value, t, &mut self.rng);
+                data.push(controller::SensorData {
+                    name: field.name.clone(),
+                    data: field.typ.value(v),
+                });
+            }
+
+            msgs.push(controller::Message {
+                msg: controller::MessageType::Sensor(controller::SensorMsg {
+                    name: sensor.name.clone(),
+                    data,
+                }),
+            });
+        }
+
+        Tick::Data(msgs)
+    }
+}
+
+/// `next_value` returns the signal's value at time `t` (in seconds since start)
+/// and updates its state.
+fn next_value(signal: &Signal, state: &mut f64, t: f64, rng: &mut Rng) -> f64 {
+    match *signal {
+        Signal::Sine {
+            amplitude,
+            period,
+            offset,
+        } => offset + amplitude * (2.0 * std::f64::consts::PI * t / period).sin(),
+        Signal::RandomWalk { step, min, max, .. } => {
+            let mut v = *state + step * (2.0 * rng.next_f64() - 1.0);
+            if let Some(min) = min {
+                v = v.max(min);
+            }
+            if let Some(max) = max {
+                v = v.min(max);
+            }
+            *state = v;
+
+            v
+        }
+        Signal::Step { low, high, period } => {
+            if t % period < period / 2.0 {
+                high
+            } else {
+                low
+            }
+        }
+        Signal::Counter { step, .. } => {
+            let v = *state;
+            *state += step;
+
+            v
+        }
+    }
+}
+
+/// `Rng` is a small xorshift generator: the simulator doesn't need a strong one.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // xorshift gets stuck in zero state
+        Self((seed ^ 0x9E37_79B9_7F4A_7C15).max(1))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+
+        x
+    }
+
+    /// `next_f64` returns a number in range [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
@@ -0,0 +1,344 @@
+#[cfg(test)]
+use super::bindings_gen as bg;
+#[cfg(test)]
+use super::conv::{slice_from_raw, try_str_from_c_char};
+#[cfg(test)]
+use super::error::ModuleError;
+#[cfg(test)]
+use super::modbus;
+#[cfg(test)]
+use super::model::{build_module_info, catch_panic, validate_module_info, OptFunctions};
+#[cfg(test)]
+use super::mqtt;
+#[cfg(test)]
+use super::simulator::Simulator;
+#[cfg(test)]
+use crate::controller::{
+    interface::module::{IModule, MsgHandler},
+    ConfEntry, ConfType, Message, MessageType, ModuleCapabilities, ModuleInfo, SensorDataTypeValue,
+};
+#[cfg(test)]
+use std::sync::{mpsc, Arc, Mutex};
+
+// Test that raw arrays from a module are checked before being read
+#[test]
+fn slice_from_raw_checks() {
+    let data = [1, 2, 3];
+
+    assert_eq!(slice_from_raw(data.as_ptr(), 3, "data").unwrap(), &data);
+    assert!(slice_from_raw::<i32>(std::ptr::null(), 0, "data")
+        .unwrap()
+        .is_empty());
+    assert!(matches!(
+        slice_from_raw::<i32>(std::ptr::null(), 2, "data"),
+        Err(ModuleError::InvalidPointer("data"))
+    ));
+    assert!(matches!(
+        slice_from_raw(data.as_ptr(), -1, "data"),
+        Err(ModuleError::InvalidLength("data", -1))
+    ));
+    assert!(matches!(
+        try_str_from_c_char(std::ptr::null_mut(), "name"),
+        Err(ModuleError::InvalidPointer("name"))
+    ));
+}
+
+// Test that a panic in a callback is turned into an error
+#[test]
+fn catch_panic_in_callback() {
+    let res: Result<(), _> = catch_panic(|| panic!("callback failed"));
+
+    match res {
+        Err(ModuleError::Panic(msg)) => assert_eq!(msg, "callback failed"),
+        _ => panic!("expected panic error"),
+    }
+}
+
+/// `ChannelHandler` sends received messages to a channel, so a test waits for them
+#[cfg(test)]
+#[derive(Clone)]
+struct ChannelHandler(Arc<Mutex<mpsc::Sender<Message>>>);
+
+#[cfg(test)]
+impl MsgHandler for ChannelHandler {
+    fn handle_msg(&self, msg: Message) {
+        let _ = self.0.lock().unwrap().send(msg);
+    }
+}
+
+// Test that the simulator generates configured signals and keeps its configuration
+#[test]
+fn simulator_signals() {
+    let data_dir = std::env::temp_dir().join(format!("monisens-simulator-{}", std::process::id()));
+    std::fs::create_dir_all(&data_dir).unwrap();
+
+    let signals = r#"[{"name": "s", "fields": [{"name": "n", "type": "int32", "signal": "counter", "start": 5, "step": 2}]}]"#;
+
+    let mut sim = Simulator::new(&data_dir).unwrap();
+    sim.connect_device(vec![]).unwrap();
+    sim.configure_device(vec![
+        ConfEntry {
+            id: 1,
+            data: Some(ConfType::JSON(signals.to_string())),
+        },
+        ConfEntry {
+            id: 2,
+            data: Some(ConfType::Int(10)),
+        },
+    ])
+    .unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    sim.start(ChannelHandler(Arc::new(Mutex::new(tx)))).unwrap();
+
+    // The first two ticks are awaited, however long they take
+    let values: Vec<i32> = (0..2)
+        .map(|_| {
+            let msg = rx.recv_timeout(std::time::Duration::from_secs(10)).unwrap();
+            match msg.msg {
+                MessageType::Sensor(msg) => match msg.data[1].data {
+                    SensorDataTypeValue::Int32(v) => v,
+                    _ => panic!("unexpected data type"),
+                },
+                _ => panic!("unexpected message"),
+            }
+        })
+        .collect();
+    sim.stop().unwrap();
+    assert_eq!(values, vec![5, 7]);
+
+    let mut sim = Simulator::new(&data_dir).unwrap();
+    let sensors = sim.obtain_sensor_type_infos().unwrap();
+    assert_eq!(sensors.len(), 1);
+    assert_eq!(sensors[0].data_map.len(), 2);
+
+    std::fs::remove_dir_all(&data_dir).unwrap();
+
+    let invalid = Simulator::new(std::env::temp_dir())
+        .unwrap()
+        .configure_device(vec![ConfEntry {
+            id: 1,
+            data: Some(ConfType::JSON("[]".to_string())),
+        }]);
+    assert!(invalid.is_err());
+}
+
+// Test that MQTT payloads are mapped to sensors by topic filters and JSON paths
+#[test]
+fn mqtt_payload_mapping() {
+    let sensors = mqtt::parse_sensors(
+        r#"[
+            {"name": "room", "topic": "home/+/state", "fields": [
+                {"name": "temp", "path": "env.temp", "type": "float64"},
+                {"name": "count", "path": "values.1", "type": "int16"}
+            ]},
+            {"name": "raw", "topic": "raw", "fields": [{"name": "v", "type": "int32"}]}
+        ]"#,
+    )
+    .unwrap();
+
+    let msgs = mqtt::build_messages(
+        &sensors,
+        "home/kitchen/state",
+        br#"{"env": {"temp": 21.5}, "values": [1, "7"]}"#,
+    );
+    assert_eq!(msgs.len(), 1);
+    match &msgs[0].msg {
+        MessageType::Sensor(msg) => {
+            assert_eq!(msg.name, "room");
+            assert!(matches!(msg.data[1].data, SensorDataTypeValue::Float64(v) if v == 21.5));
+            assert!(matches!(msg.data[2].data, SensorDataTypeValue::Int16(7)));
+        }
+        _ => panic!("unexpected message"),
+    }
+
+    let msgs = mqtt::build_messages(&sensors, "raw", b"42");
+    assert!(matches!(&msgs[0].msg, MessageType::Sensor(msg)
+        if matches!(msg.data[1].data, SensorDataTypeValue::Int32(42))));
+
+    let msgs = mqtt::build_messages(&sensors, "raw", b"not a number");
+    assert!(msgs
+        .iter()
+        .all(|msg| matches!(msg.msg, MessageType::Common(_))));
+
+    // Messages with missing fields are not saved
+    let msgs = mqtt::build_messages(&sensors, "home/hall/state", br#"{"env": {"temp": 20}}"#);
+    assert_eq!(msgs.len(), 1);
+    assert!(matches!(msgs[0].msg, MessageType::Common(_)));
+
+    assert!(mqtt::build_messages(&sensors, "other", b"1").is_empty());
+    assert!(mqtt::parse_sensors(r#"[{"name": "s", "topic": "a/#/b", "fields": []}]"#).is_err());
+}
+
+// Test that Modbus registers are read and decoded according to the register map
+#[test]
+fn modbus_register_decoding() {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    let groups = modbus::parse_groups(
+        r#"[{"name": "meter", "table": "holding", "address": 10, "count": 5, "fields": [
+            {"name": "voltage", "address": 10, "type": "int16", "scale": 0.1},
+            {"name": "power", "address": 11, "type": "float32"},
+            {"name": "energy", "address": 13, "type": "int32", "word_order": "little"}
+        ]}]"#,
+    )
+    .unwrap();
+
+    // A device with registers 10..15 set to the values below
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut req = [0u8; 12];
+        stream.read_exact(&mut req).unwrap();
+        assert_eq!(&req[6..], &[1, 0x03, 0, 10, 0, 5]);
+
+        let regs: [u16; 5] = [250, 0x3FC0, 0x0000, 0x0002, 0x0001];
+        let mut resp = vec![req[0], req[1], 0, 0, 0, 13, 1, 0x03, 10];
+        for r in regs {
+            resp.extend_from_slice(&r.to_be_bytes());
+        }
+        stream.write_all(&resp).unwrap();
+    });
+
+    let mut client = modbus::ModbusClient::new(TcpStream::connect(addr).unwrap(), 1);
+    let msgs = modbus::poll(&mut client, &groups).unwrap();
+    server.join().unwrap();
+
+    match &msgs[0].msg {
+        MessageType::Sensor(msg) => {
+            assert!(
+                matches!(msg.data[1].data, SensorDataTypeValue::Float64(v) if (v - 25.0).abs() < 1e-9)
+            );
+            assert!(matches!(msg.data[2].data, SensorDataTypeValue::Float32(v) if v == 1.5));
+            assert!(matches!(
+                msg.data[3].data,
+                SensorDataTypeValue::Int32(0x0001_0002)
+            ));
+        }
+        _ => panic!("unexpected message"),
+    }
+
+    assert!(modbus::parse_groups(
+        r#"[{"name": "g", "table": "coil", "address": 0, "count": 1, "fields": [
+            {"name": "f", "address": 0, "type": "int16"}
+        ]}]"#
+    )
+    .is_err());
+}
+
+#[cfg(test)]
+unsafe extern "C" fn test_obtain_command_infos(
+    _: *mut std::os::raw::c_void,
+    _: *mut std::os::raw::c_void,
+    _: bg::command_infos_callback,
+) -> u8 {
+    0
+}
+
+#[cfg(test)]
+unsafe extern "C" fn test_send_command(_: *mut std::os::raw::c_void, _: *mut bg::Command) -> u8 {
+    0
+}
+
+// Test that a module built for another platform or with inconsistent capabilities is rejected
+#[test]
+fn module_info_validation() {
+    let info = |os: Option<&str>, arch: Option<&str>, commands: bool| ModuleInfo {
+        name: "test".to_string(),
+        vendor: None,
+        version: "1.2.3".to_string(),
+        description: None,
+        os: os.map(str::to_string),
+        arch: arch.map(str::to_string),
+        capabilities: ModuleCapabilities {
+            commands,
+            ..Default::default()
+        },
+    };
+    let no_commands = OptFunctions {
+        obtain_command_infos: None,
+        send_command: None,
+    };
+    let commands = OptFunctions {
+        obtain_command_infos: Some(test_obtain_command_infos),
+        send_command: Some(test_send_command),
+    };
+    let current = (Some(std::env::consts::OS), Some(std::env::consts::ARCH));
+    let is_invalid = |res: Result<(), ModuleError>, msg: &str| matches!(res, Err(ModuleError::InvalidModuleInfo(ref err)) if err.contains(msg));
+
+    assert!(validate_module_info(&info(current.0, current.1, false), &no_commands).is_ok());
+    assert!(validate_module_info(&info(None, None, false), &no_commands).is_ok());
+    assert!(validate_module_info(&info(current.0, current.1, true), &commands).is_ok());
+
+    assert!(is_invalid(
+        validate_module_info(&info(Some("plan9"), current.1, false), &no_commands),
+        "built for OS 'plan9'"
+    ));
+    assert!(is_invalid(
+        validate_module_info(&info(current.0, Some("sparc"), false), &no_commands),
+        "built for architecture 'sparc'"
+    ));
+
+    // The capability must match the exported functions both ways
+    assert!(is_invalid(
+        validate_module_info(&info(current.0, current.1, true), &no_commands),
+        "'commands' capability"
+    ));
+    assert!(is_invalid(
+        validate_module_info(
+            &info(current.0, current.1, true),
+            &OptFunctions {
+                obtain_command_infos: Some(test_obtain_command_infos),
+                send_command: None,
+            }
+        ),
+        "'commands' capability"
+    ));
+    assert!(is_invalid(
+        validate_module_info(&info(current.0, current.1, false), &commands),
+        "'commands' capability"
+    ));
+}
+
+// Test that module info is read from the C struct with its capability flags
+#[test]
+fn module_info_building() {
+    let c = |s: &str| std::ffi::CString::new(s).unwrap();
+    let (name, version, os) = (c("test"), c("1.0.0"), c("linux"));
+    let mut raw = bg::ModuleInfo {
+        name: name.as_ptr() as _,
+        vendor: std::ptr::null_mut(),
+        version: version.as_ptr() as _,
+        description: std::ptr::null_mut(),
+        os: os.as_ptr() as _,
+        arch: std::ptr::null_mut(),
+        capabilities: bg::ModuleCapability::ModuleCapabilityCommands as u32
+            | bg::ModuleCapability::ModuleCapabilityPersistedConfig as u32,
+    };
+
+    let info = build_module_info(&mut raw).unwrap();
+    assert_eq!(info.name, "test");
+    assert_eq!(info.version, "1.0.0");
+    assert_eq!(info.os.as_deref(), Some("linux"));
+    assert_eq!((info.vendor, info.arch), (None, None));
+    assert_eq!(
+        info.capabilities,
+        ModuleCapabilities {
+            commands: true,
+            hot_reconfigure: false,
+            persisted_config: true,
+        }
+    );
+
+    raw.version = std::ptr::null_mut();
+    assert!(matches!(
+        build_module_info(&mut raw),
+        Err(ModuleError::InvalidPointer("module_info.version"))
+    ));
+    assert!(matches!(
+        build_module_info(std::ptr::null_mut()),
+        Err(ModuleError::InvalidPointer("module_info"))
+    ));
+}
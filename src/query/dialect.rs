@@ -0,0 +1,18 @@
+use super::tool;
+
+/// `Dialect` is an SQL dialect of a database queries are built for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dialect {
+    Postgres,
+    Sqlite,
+}
+
+impl Dialect {
+    /// `placeholders` replaces positional `?` placeholders of a built query with the dialect's ones
+    pub fn placeholders(&self, sql: &str) -> String {
+        match self {
+            Dialect::Postgres => tool::replace_pos_placeholders(sql, "$"),
+            Dialect::Sqlite => sql.to_string(),
+        }
+    }
+}
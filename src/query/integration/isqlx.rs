@@ -3,8 +3,9 @@ use crate::query::expr;
 use crate::query::sqlizer::Sqlizer;
 use chrono;
 use sqlx::database::HasArguments;
-use sqlx::postgres::Postgres;
+use sqlx::postgres::{PgQueryResult, Postgres};
 use sqlx::query::Query;
+use sqlx::sqlite::{Sqlite, SqliteQueryResult};
 use std::fmt;
 use std::rc::Rc;
 
@@ -13,6 +14,11 @@ pub trait ArgType: fmt::Debug {
         &'q self,
         q: Query<'q, Postgres, <Postgres as HasArguments<'q>>::Arguments>,
     ) -> Query<'q, Postgres, <Postgres as HasArguments<'q>>::Arguments>;
+
+    fn bind_sqlite<'q>(
+        &'q self,
+        q: Query<'q, Sqlite, <Sqlite as HasArguments<'q>>::Arguments>,
+    ) -> Query<'q, Sqlite, <Sqlite as HasArguments<'q>>::Arguments>;
 }
 
 #[macro_export]
@@ -33,6 +39,21 @@ macro_rules! ref_arg_type {
             > {
                 q.bind(self)
             }
+
+            fn bind_sqlite<'q>(
+                &'q self,
+                q: sqlx::query::Query<
+                    'q,
+                    sqlx::sqlite::Sqlite,
+                    <sqlx::sqlite::Sqlite as sqlx::database::HasArguments<'q>>::Arguments,
+                >,
+            ) -> sqlx::query::Query<
+                'q,
+                sqlx::sqlite::Sqlite,
+                <sqlx::sqlite::Sqlite as sqlx::database::HasArguments<'q>>::Arguments,
+            > {
+                q.bind(self)
+            }
         }
     };
 }
@@ -51,6 +72,9 @@ ref_arg_type!(String);
 
 ref_arg_type!(chrono::NaiveDateTime);
 
+ref_arg_type!(Option<String>);
+ref_arg_type!(Option<i32>);
+
 pub type GenericArg = Box<dyn ArgType + 'static>;
 
 #[macro_export]
@@ -78,6 +102,9 @@ arg_from_ty!(String);
 
 arg_from_ty!(chrono::NaiveDateTime);
 
+arg_from_ty!(Option<String>);
+arg_from_ty!(Option<i32>);
+
 pub type StatementBuilder = query::StatementBuilder<GenericArg>;
 
 macro_rules! static_arg_expr {
@@ -105,15 +132,59 @@ pub fn inq<T: ArgType + 'static>(col: String, vals: Vec<T>) -> Rc<dyn Sqlizer<Ge
     expr::inq(col, v)
 }
 
-pub fn query<'a>(
+/// `ArgDatabase` is a database which [`GenericArg`]s are bound for
+pub trait ArgDatabase: sqlx::Database {
+    const DIALECT: query::Dialect;
+
+    fn bind_arg<'q>(
+        arg: &'q GenericArg,
+        q: Query<'q, Self, <Self as HasArguments<'q>>::Arguments>,
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>;
+
+    fn rows_affected(res: &Self::QueryResult) -> u64;
+}
+
+impl ArgDatabase for Postgres {
+    const DIALECT: query::Dialect = query::Dialect::Postgres;
+
+    fn bind_arg<'q>(
+        arg: &'q GenericArg,
+        q: Query<'q, Self, <Self as HasArguments<'q>>::Arguments>,
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        arg.bind(q)
+    }
+
+    fn rows_affected(res: &PgQueryResult) -> u64 {
+        res.rows_affected()
+    }
+}
+
+impl ArgDatabase for Sqlite {
+    const DIALECT: query::Dialect = query::Dialect::Sqlite;
+
+    fn bind_arg<'q>(
+        arg: &'q GenericArg,
+        q: Query<'q, Self, <Self as HasArguments<'q>>::Arguments>,
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        arg.bind_sqlite(q)
+    }
+
+    fn rows_affected(res: &SqliteQueryResult) -> u64 {
+        res.rows_affected()
+    }
+}
+
+/// `query` creates a query with the bound arguments. Placeholders of `sql`
+/// must already be replaced with the ones of the database
+pub fn query<'a, DB: ArgDatabase>(
     sql: &'a str,
     args: &'a Option<Vec<Rc<GenericArg>>>,
-) -> Query<'a, Postgres, <Postgres as HasArguments<'a>>::Arguments> {
-    let mut q = sqlx::query(&sql);
+) -> Query<'a, DB, <DB as HasArguments<'a>>::Arguments> {
+    let mut q = sqlx::query(sql);
 
     if let Some(args) = args {
         for i in args.iter() {
-            q = i.bind(q);
+            q = DB::bind_arg(i, q);
         }
     }
 
@@ -1,4 +1,5 @@
 pub mod builder;
+mod dialect;
 pub mod error;
 mod expr;
 pub mod integration;
@@ -6,6 +7,7 @@ pub mod sqlizer;
 mod tool;
 
 use builder::Builder;
+pub use dialect::*;
 pub use expr::*;
 use sqlizer::{Part, PredType, Sqlizer, Values};
 use std::error::Error;
@@ -204,7 +206,7 @@ impl<A: 'static> Sqlizer<A> for SelectBuilder<A> {
             sql.push_str(&suffix.sql()?.0);
         }
 
-        Ok((tool::replace_pos_placeholders(&sql, "$"), Some(args)))
+        Ok((sql, Some(args)))
     }
 }
 
@@ -253,7 +255,7 @@ impl<A: 'static> Sqlizer<A> for InsertBuilder<A> {
             sql.push_str(&suffix.sql()?.0);
         }
 
-        Ok((tool::replace_pos_placeholders(&sql, "$"), Some(args)))
+        Ok((sql, Some(args)))
     }
 }
 
@@ -285,7 +287,7 @@ impl<A: 'static> Sqlizer<A> for DeleteBuilder<A> {
             sql.push_str(&suffix.sql()?.0);
         }
 
-        Ok((tool::replace_pos_placeholders(&sql, "$"), Some(args)))
+        Ok((sql, Some(args)))
     }
 }
 
@@ -329,6 +331,6 @@ impl<A: 'static> Sqlizer<A> for UpdateBuilder<A> {
             sql.push_str(&suffix.sql()?.0);
         }
 
-        Ok((tool::replace_pos_placeholders(&sql, "$"), Some(args)))
+        Ok((sql, Some(args)))
     }
 }
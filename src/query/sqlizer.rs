@@ -3,6 +3,8 @@ use super::tool;
 use std::error::Error;
 use std::rc::Rc;
 
+/// `Sqlizer` builds a query with positional `?` placeholders. They are replaced
+/// with the ones of the database by [`super::Dialect::placeholders`]
 pub trait Sqlizer<A: 'static> {
     fn sql(&self) -> Result<(String, Option<Vec<Rc<A>>>), Box<dyn Error>>;
 }
@@ -36,7 +36,12 @@ impl RepoError {
                         Some(code) => {
                             let val = code.as_ref();
                             match val {
-                                "23505" => ErrorType::AlreadyExists,
+                                // unique_violation of Postgres, SQLITE_CONSTRAINT_UNIQUE
+                                // and SQLITE_CONSTRAINT_PRIMARYKEY of SQLite
+                                "23505" | "2067" | "1555" => ErrorType::AlreadyExists,
+                                // foreign_key_violation of Postgres and
+                                // SQLITE_CONSTRAINT_FOREIGNKEY of SQLite
+                                "23503" | "787" => ErrorType::FailedPrecondition,
                                 _ => ErrorType::Internal,
                             }
                         }
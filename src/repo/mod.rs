@@ -1,118 +1,376 @@
 mod error;
 mod querier;
 
+use std::collections::HashMap;
+use std::str::FromStr;
 use std::time::Duration;
 
+use serde::{Deserialize, Serialize};
 use sqlx::{
-    postgres::{PgPoolOptions, PgQueryResult, PgRow},
-    FromRow, Pool, Postgres,
+    migrate::Migrator,
+    postgres::{PgPoolOptions, PgRow},
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteRow},
+    FromRow, Pool, Postgres, Sqlite,
 };
 
-use crate::query::integration::isqlx::ArgType;
+use crate::query::integration::isqlx::{ArgType, StatementBuilder};
+use crate::query::Dialect;
 use crate::{query::sqlizer::Sqlizer, table::Table};
 
 pub use error::*;
 
+/// `Storage` is a database the repository keeps its data in
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Storage {
+    /// PostgreSQL server, the DSN is its URL
+    Postgres,
+    /// Embedded SQLite, the DSN is a path to the database file
+    Sqlite,
+}
+
+impl Storage {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "postgres" => Some(Storage::Postgres),
+            "sqlite" => Some(Storage::Sqlite),
+            _ => None,
+        }
+    }
+}
+
+/// `FromAnyRow` is a row type which can be read from any [`Storage`]
+pub trait FromAnyRow: for<'r> FromRow<'r, PgRow> + for<'r> FromRow<'r, SqliteRow> {}
+
+impl<T> FromAnyRow for T where T: for<'r> FromRow<'r, PgRow> + for<'r> FromRow<'r, SqliteRow> {}
+
+/// `PoolConf` is the settings of the connection pool of the repository
+#[derive(Clone, Debug)]
+pub struct PoolConf {
+    pub max_connections: u32,
+    pub min_connections: u32,
+    /// Maximum time to wait for a free connection
+    pub acquire_timeout: Duration,
+}
+
+impl Default for PoolConf {
+    fn default() -> Self {
+        Self {
+            max_connections: 5,
+            min_connections: 0,
+            acquire_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+const APPLIED_MIGRATIONS: &str = "SELECT version FROM _sqlx_migrations WHERE success";
+
+/// `Migration` is a schema migration of the repository
+#[derive(Clone, Debug)]
+pub struct Migration {
+    pub version: i64,
+    pub description: String,
+}
+
 #[derive(Clone)]
 pub struct Repository {
-    pool: Pool<Postgres>,
+    pool: AnyPool,
+}
+
+#[derive(Clone)]
+enum AnyPool {
+    Postgres(Pool<Postgres>),
+    Sqlite(Pool<Sqlite>),
 }
 
 impl Repository {
-    pub async fn new(dsn: &str) -> Result<Self, RepoError> {
-        // TODO: advanced configuration?
-        let pool = PgPoolOptions::new()
-            .max_connections(5)
-            .acquire_timeout(Duration::from_secs(5))
-            .connect(dsn)
-            .await?;
+    pub async fn new(storage: Storage, dsn: &str, pool_conf: &PoolConf) -> Result<Self, RepoError> {
+        let pool = match storage {
+            Storage::Postgres => AnyPool::Postgres(
+                PgPoolOptions::new()
+                    .max_connections(pool_conf.max_connections)
+                    .min_connections(pool_conf.min_connections)
+                    .acquire_timeout(pool_conf.acquire_timeout)
+                    .connect(dsn)
+                    .await?,
+            ),
+            Storage::Sqlite => {
+                let opts = SqliteConnectOptions::from_str(dsn)?
+                    .create_if_missing(true)
+                    .foreign_keys(true)
+                    // Readers don't block the writer, e.g. while sensor data is exported
+                    .journal_mode(SqliteJournalMode::Wal);
+
+                AnyPool::Sqlite(
+                    SqlitePoolOptions::new()
+                        .max_connections(pool_conf.max_connections)
+                        .min_connections(pool_conf.min_connections)
+                        .acquire_timeout(pool_conf.acquire_timeout)
+                        .connect_with(opts)
+                        .await?,
+                )
+            }
+        };
 
         Ok(Self { pool })
     }
 
+    pub fn dialect(&self) -> Dialect {
+        match self.pool {
+            AnyPool::Postgres(_) => Dialect::Postgres,
+            AnyPool::Sqlite(_) => Dialect::Sqlite,
+        }
+    }
+
     pub async fn create_table(&self, table: Table) -> Result<(), RepoError> {
-        querier::create_table(&self.pool, table).await
+        match &self.pool {
+            AnyPool::Postgres(p) => querier::create_table(p, table).await,
+            AnyPool::Sqlite(p) => querier::create_table(p, table).await,
+        }
     }
 
-    pub async fn exec<S: Sqlizer<Box<dyn ArgType>>>(
-        &self,
-        q: S,
-    ) -> Result<PgQueryResult, RepoError> {
-        querier::exec(&self.pool, q).await
+    /// `exec` executes the query and returns the number of affected rows
+    pub async fn exec<S: Sqlizer<Box<dyn ArgType>>>(&self, q: S) -> Result<u64, RepoError> {
+        match &self.pool {
+            AnyPool::Postgres(p) => querier::exec(p, q).await,
+            AnyPool::Sqlite(p) => querier::exec(p, q).await,
+        }
     }
 
     pub async fn get<S, T>(&self, q: S) -> Result<T, RepoError>
     where
         S: Sqlizer<Box<dyn ArgType>>,
-        T: for<'r> FromRow<'r, PgRow>,
+        T: FromAnyRow,
     {
-        querier::get(&self.pool, q).await
+        match &self.pool {
+            AnyPool::Postgres(p) => querier::get(p, q).await,
+            AnyPool::Sqlite(p) => querier::get(p, q).await,
+        }
     }
 
     pub async fn select<S, T>(&self, q: S) -> Result<Vec<T>, RepoError>
     where
         S: Sqlizer<Box<dyn ArgType>>,
-        T: for<'r> FromRow<'r, PgRow>,
+        T: FromAnyRow,
     {
-        querier::select(&self.pool, q).await
+        match &self.pool {
+            AnyPool::Postgres(p) => querier::select(p, q).await,
+            AnyPool::Sqlite(p) => querier::select(p, q).await,
+        }
     }
 
-    pub async fn exec_raw(&self, sql: &str) -> Result<PgQueryResult, RepoError> {
-        querier::exec_raw(&self.pool, sql).await
+    pub async fn exec_raw(&self, sql: &str) -> Result<u64, RepoError> {
+        match &self.pool {
+            AnyPool::Postgres(p) => querier::exec_raw(p, sql).await,
+            AnyPool::Sqlite(p) => querier::exec_raw(p, sql).await,
+        }
     }
 
     pub async fn migrate(&self) -> Result<(), RepoError> {
-        sqlx::migrate!().run(&self.pool).await?;
+        match &self.pool {
+            AnyPool::Postgres(p) => self.migrator().run(p).await?,
+            AnyPool::Sqlite(p) => self.migrator().run(p).await?,
+        }
 
         Ok(())
     }
 
+    /// `pending_migrations` returns migrations which aren't applied to the database yet
+    /// without applying them
+    pub async fn pending_migrations(&self) -> Result<Vec<Migration>, RepoError> {
+        let applied: Vec<i64> = match &self.pool {
+            AnyPool::Postgres(p) => {
+                let exists: bool =
+                    sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+                        .fetch_one(p)
+                        .await?;
+                match exists {
+                    true => sqlx::query_scalar(APPLIED_MIGRATIONS).fetch_all(p).await?,
+                    false => Vec::new(),
+                }
+            }
+            AnyPool::Sqlite(p) => {
+                let exists: bool = sqlx::query_scalar(
+                    "SELECT EXISTS (SELECT 1 FROM sqlite_master \
+                    WHERE type = 'table' AND name = '_sqlx_migrations')",
+                )
+                .fetch_one(p)
+                .await?;
+                match exists {
+                    true => sqlx::query_scalar(APPLIED_MIGRATIONS).fetch_all(p).await?,
+                    false => Vec::new(),
+                }
+            }
+        };
+
+        Ok(self
+            .migrator()
+            .iter()
+            .filter(|m| !m.migration_type.is_down_migration() && !applied.contains(&m.version))
+            .map(|m| Migration {
+                version: m.version,
+                description: m.description.to_string(),
+            })
+            .collect())
+    }
+
+    fn migrator(&self) -> Migrator {
+        match &self.pool {
+            AnyPool::Postgres(_) => sqlx::migrate!(),
+            AnyPool::Sqlite(_) => sqlx::migrate!("migrations/sqlite"),
+        }
+    }
+
     pub async fn tx(&self) -> Result<Transaction, RepoError> {
-        let tx = self.pool.begin().await?;
+        let tx = match &self.pool {
+            AnyPool::Postgres(p) => AnyTransaction::Postgres(Box::new(p.begin().await?)),
+            AnyPool::Sqlite(p) => AnyTransaction::Sqlite(p.begin().await?, HashMap::new()),
+        };
 
         Ok(Transaction(tx))
     }
 }
 
-pub struct Transaction<'c>(sqlx::Transaction<'c, Postgres>);
+pub struct Transaction<'c>(AnyTransaction<'c>);
+
+enum AnyTransaction<'c> {
+    // A connection to Postgres is much larger than a handle of SQLite's worker
+    Postgres(Box<sqlx::Transaction<'c, Postgres>>),
+    /// SQLite has no cursors, so they're emulated. See [`SqliteCursor`]
+    Sqlite(sqlx::Transaction<'c, Sqlite>, HashMap<String, SqliteCursor>),
+}
+
+/// `SqliteCursor` keeps rowids of the selected rows in a temporary table, which is dropped
+/// with the rollback of its transaction. Every fetch reads the rows after the last fetched one
+/// by the rowid of the temporary table, so the query isn't executed again
+struct SqliteCursor {
+    /// Query of the rows joined with the temporary table
+    sql: String,
+    last_rowid: i64,
+}
 
 impl<'c> Transaction<'c> {
     pub async fn create_table(&mut self, table: Table) -> Result<(), RepoError> {
-        querier::create_table(&mut self.0, table).await
+        match &mut self.0 {
+            AnyTransaction::Postgres(tx) => querier::create_table(&mut **tx, table).await,
+            AnyTransaction::Sqlite(tx, _) => querier::create_table(&mut *tx, table).await,
+        }
     }
 
-    pub async fn exec<S: Sqlizer<Box<dyn ArgType>>>(
-        &mut self,
-        q: S,
-    ) -> Result<PgQueryResult, RepoError> {
-        querier::exec(&mut self.0, q).await
+    /// `exec` executes the query and returns the number of affected rows
+    pub async fn exec<S: Sqlizer<Box<dyn ArgType>>>(&mut self, q: S) -> Result<u64, RepoError> {
+        match &mut self.0 {
+            AnyTransaction::Postgres(tx) => querier::exec(&mut **tx, q).await,
+            AnyTransaction::Sqlite(tx, _) => querier::exec(&mut *tx, q).await,
+        }
     }
 
     pub async fn get<S, T>(&mut self, q: S) -> Result<T, RepoError>
     where
         S: Sqlizer<Box<dyn ArgType>>,
-        T: for<'r> FromRow<'r, PgRow>,
+        T: FromAnyRow,
     {
-        querier::get(&mut self.0, q).await
+        match &mut self.0 {
+            AnyTransaction::Postgres(tx) => querier::get(&mut **tx, q).await,
+            AnyTransaction::Sqlite(tx, _) => querier::get(&mut *tx, q).await,
+        }
     }
 
     pub async fn select<S, T>(&mut self, q: S) -> Result<Vec<T>, RepoError>
     where
         S: Sqlizer<Box<dyn ArgType>>,
-        T: for<'r> FromRow<'r, PgRow>,
+        T: FromAnyRow,
     {
-        querier::select(&mut self.0, q).await
+        match &mut self.0 {
+            AnyTransaction::Postgres(tx) => querier::select(&mut **tx, q).await,
+            AnyTransaction::Sqlite(tx, _) => querier::select(&mut *tx, q).await,
+        }
+    }
+
+    pub async fn exec_raw(&mut self, sql: &str) -> Result<u64, RepoError> {
+        match &mut self.0 {
+            AnyTransaction::Postgres(tx) => querier::exec_raw(&mut **tx, sql).await,
+            AnyTransaction::Sqlite(tx, _) => querier::exec_raw(&mut *tx, sql).await,
+        }
     }
 
-    pub async fn exec_raw(&mut self, sql: &str) -> Result<PgQueryResult, RepoError> {
-        querier::exec_raw(&mut self.0, sql).await
+    /// `declare_cursor` declares a cursor for `columns` of `table` selected by `b`.
+    /// `b` mustn't have a table, columns or a `LIMIT` clause
+    pub async fn declare_cursor<S: AsRef<str>>(
+        &mut self,
+        name: &str,
+        table: &str,
+        columns: &[S],
+        mut b: StatementBuilder,
+    ) -> Result<(), RepoError> {
+        b.table(table.to_string());
+
+        match &mut self.0 {
+            AnyTransaction::Postgres(tx) => {
+                b.columns(columns);
+                querier::declare_cursor(&mut **tx, name, b.select()).await
+            }
+            AnyTransaction::Sqlite(tx, cursors) => {
+                // Rows of the temporary table get rowids in the order of the query
+                b.column("rowid AS \"row\"");
+                let (sql, args) = b.select().sql()?;
+                let sql = format!("CREATE TEMP TABLE \"{name}\" AS {sql}");
+                querier::exec_sql(&mut *tx, &sql, &args).await?;
+
+                let columns: Vec<_> = columns
+                    .iter()
+                    .map(|col| format!("{table}.{}", col.as_ref()))
+                    .collect();
+                cursors.insert(
+                    name.to_string(),
+                    SqliteCursor {
+                        sql: format!(
+                            "SELECT {} FROM temp.\"{name}\" JOIN {table} ON {table}.rowid = \"{name}\".\"row\"",
+                            columns.join(", ")
+                        ),
+                        last_rowid: 0,
+                    },
+                );
+
+                Ok(())
+            }
+        }
+    }
+
+    pub async fn fetch_cursor<T>(&mut self, name: &str, count: usize) -> Result<Vec<T>, RepoError>
+    where
+        T: FromAnyRow,
+    {
+        match &mut self.0 {
+            AnyTransaction::Postgres(tx) => querier::fetch_cursor(&mut **tx, name, count).await,
+            AnyTransaction::Sqlite(tx, cursors) => {
+                let cursor = cursors.get_mut(name).ok_or_else(|| {
+                    RepoError::Other(format!("cursor '{name}' is not declared").into())
+                })?;
+
+                let sql = format!(
+                    "{} WHERE \"{name}\".rowid > {} ORDER BY \"{name}\".rowid LIMIT {count}",
+                    cursor.sql, cursor.last_rowid
+                );
+                let rows: Vec<T> = querier::select_sql(&mut *tx, &sql, &None).await?;
+                cursor.last_rowid += rows.len() as i64;
+
+                Ok(rows)
+            }
+        }
     }
 
     pub async fn commit(self) -> Result<(), sqlx::Error> {
-        self.0.commit().await
+        match self.0 {
+            AnyTransaction::Postgres(tx) => tx.commit().await,
+            AnyTransaction::Sqlite(tx, _) => tx.commit().await,
+        }
     }
 
     pub async fn rollback(self) -> Result<(), sqlx::Error> {
-        self.0.rollback().await
+        match self.0 {
+            AnyTransaction::Postgres(tx) => tx.rollback().await,
+            AnyTransaction::Sqlite(tx, _) => tx.rollback().await,
+        }
     }
 }
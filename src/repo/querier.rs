@@ -1,49 +1,54 @@
-use sqlx::{
-    postgres::{PgQueryResult, PgRow},
-    Executor, FromRow, Postgres,
-};
+use std::rc::Rc;
+
+use sqlx::{database::HasArguments, postgres::PgRow, Executor, FromRow, IntoArguments, Postgres};
 
 use crate::{
     query::{
-        integration::isqlx::{self as sq, ArgType},
+        integration::isqlx::{self as sq, ArgDatabase, GenericArg},
         sqlizer::Sqlizer,
+        Dialect,
     },
     table::Table,
 };
 
 use super::error::RepoError;
 
-pub async fn create_table<'e, E>(e: E, table: Table) -> Result<(), RepoError>
+pub async fn create_table<'e, DB, E>(e: E, table: Table) -> Result<(), RepoError>
 where
-    E: Executor<'e, Database = Postgres>,
+    DB: ArgDatabase,
+    E: Executor<'e, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
 {
-    let table_q = table.parse()?;
+    let table_q = table.parse(DB::DIALECT)?;
 
     sqlx::query(&table_q).execute(e).await?;
 
     Ok(())
 }
 
-pub async fn exec<'e, E, S: Sqlizer<Box<dyn ArgType>>>(
-    e: E,
-    q: S,
-) -> Result<PgQueryResult, RepoError>
+pub async fn exec<'e, DB, E, S: Sqlizer<Box<dyn sq::ArgType>>>(e: E, q: S) -> Result<u64, RepoError>
 where
-    E: Executor<'e, Database = Postgres>,
+    DB: ArgDatabase,
+    E: Executor<'e, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
 {
     let (sql, args) = q.sql()?;
+    let sql = DB::DIALECT.placeholders(&sql);
     let res = sq::query(&sql, &args).execute(e).await?;
 
-    Ok(res)
+    Ok(DB::rows_affected(&res))
 }
 
-pub async fn get<'e, E, S, T>(e: E, q: S) -> Result<T, RepoError>
+pub async fn get<'e, DB, E, S, T>(e: E, q: S) -> Result<T, RepoError>
 where
-    E: Executor<'e, Database = Postgres>,
-    S: Sqlizer<Box<dyn ArgType>>,
-    T: for<'r> FromRow<'r, PgRow>,
+    DB: ArgDatabase,
+    E: Executor<'e, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    S: Sqlizer<Box<dyn sq::ArgType>>,
+    T: for<'r> FromRow<'r, DB::Row>,
 {
     let (sql, args) = q.sql()?;
+    let sql = DB::DIALECT.placeholders(&sql);
     let row = sq::query(&sql, &args).fetch_one(e).await?;
 
     let res = T::from_row(&row)?;
@@ -51,14 +56,33 @@ where
     Ok(res)
 }
 
-pub async fn select<'e, E, S, T>(e: E, q: S) -> Result<Vec<T>, RepoError>
+pub async fn select<'e, DB, E, S, T>(e: E, q: S) -> Result<Vec<T>, RepoError>
 where
-    E: Executor<'e, Database = Postgres>,
-    S: Sqlizer<Box<dyn ArgType>>,
-    T: for<'r> FromRow<'r, PgRow>,
+    DB: ArgDatabase,
+    E: Executor<'e, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    S: Sqlizer<Box<dyn sq::ArgType>>,
+    T: for<'r> FromRow<'r, DB::Row>,
 {
     let (sql, args) = q.sql()?;
-    let rows = sq::query(&sql, &args).fetch_all(e).await?;
+
+    select_sql(e, &sql, &args).await
+}
+
+/// `select_sql` is [`select`] of a built query
+pub async fn select_sql<'e, DB, E, T>(
+    e: E,
+    sql: &str,
+    args: &Option<Vec<Rc<GenericArg>>>,
+) -> Result<Vec<T>, RepoError>
+where
+    DB: ArgDatabase,
+    E: Executor<'e, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    T: for<'r> FromRow<'r, DB::Row>,
+{
+    let sql = DB::DIALECT.placeholders(sql);
+    let rows = sq::query(&sql, args).fetch_all(e).await?;
 
     let mut res = Vec::with_capacity(rows.len());
 
@@ -70,11 +94,69 @@ where
     Ok(res)
 }
 
-pub async fn exec_raw<'e, E>(e: E, sql: &str) -> Result<PgQueryResult, RepoError>
+pub async fn exec_sql<'e, DB, E>(
+    e: E,
+    sql: &str,
+    args: &Option<Vec<Rc<GenericArg>>>,
+) -> Result<u64, RepoError>
+where
+    DB: ArgDatabase,
+    E: Executor<'e, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+{
+    let sql = DB::DIALECT.placeholders(sql);
+    let res = sq::query(&sql, args).execute(e).await?;
+
+    Ok(DB::rows_affected(&res))
+}
+
+/// `declare_cursor` declares a server-side cursor for the query. It must be called in a transaction.
+pub async fn declare_cursor<'e, E, S: Sqlizer<Box<dyn sq::ArgType>>>(
+    e: E,
+    name: &str,
+    q: S,
+) -> Result<(), RepoError>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let (sql, args) = q.sql()?;
+    let sql = format!(
+        "declare {name} no scroll cursor for {}",
+        Dialect::Postgres.placeholders(&sql)
+    );
+    sq::query(&sql, &args).execute(e).await?;
+
+    Ok(())
+}
+
+/// `fetch_cursor` fetches at most `count` next rows from the cursor
+pub async fn fetch_cursor<'e, E, T>(e: E, name: &str, count: usize) -> Result<Vec<T>, RepoError>
 where
     E: Executor<'e, Database = Postgres>,
+    T: for<'r> FromRow<'r, PgRow>,
 {
-    let res = sqlx::query(sql).execute(e).await?;
+    // The statement is the same for cursors of different queries,
+    // so its cached description of columns can't be reused
+    let sql = format!("fetch forward {count} from {name}");
+    let rows = sqlx::query(&sql).persistent(false).fetch_all(e).await?;
+
+    let mut res = Vec::with_capacity(rows.len());
+
+    for row in rows {
+        let entry = T::from_row(&row)?;
+        res.push(entry);
+    }
 
     Ok(res)
 }
+
+pub async fn exec_raw<'e, DB, E>(e: E, sql: &str) -> Result<u64, RepoError>
+where
+    DB: ArgDatabase,
+    E: Executor<'e, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+{
+    let res = sqlx::query(sql).execute(e).await?;
+
+    Ok(DB::rows_affected(&res))
+}
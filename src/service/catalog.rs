@@ -0,0 +1,135 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWriteExt};
+
+use crate::app;
+use crate::controller::interface::module::{is_builtin_module, MODULE_FILE_EXT};
+
+const UPLOAD_BUF_SIZE: usize = 64 * 1024;
+
+static UPLOAD_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// `ModuleCatalog` stores module libraries that are uploaded once and shared between devices.
+/// Every library is stored under its SHA-256 content hash, so uploading the same file twice
+/// doesn't create a copy.
+///
+/// Created structure:
+/// ```
+/// <app_dir>/
+///     module/
+///         <sha256>/
+///             lib.<so|dylib|dll>
+/// ```
+#[derive(Clone)]
+pub struct ModuleCatalog {
+    base_dir: Arc<PathBuf>,
+}
+
+impl ModuleCatalog {
+    pub fn new() -> Self {
+        Self {
+            base_dir: Arc::new(check_and_return_catalog_dir()),
+        }
+    }
+
+    /// `save_module` writes the module file to the catalog and returns its hash and size.
+    ///
+    /// If a library with the same content is already in the catalog, the existing file is kept.
+    pub async fn save_module<F>(&self, module_file: &mut F) -> Result<(String, i64), Box<dyn Error>>
+    where
+        F: AsyncRead + Unpin + ?Sized,
+    {
+        let tmp_path = self.base_dir.join(upload_tmp_name());
+
+        let res = write_and_hash(&tmp_path, module_file).await;
+        let (hash, size) = match res {
+            Ok(v) => v,
+            Err(err) => {
+                let _ = fs::remove_file(&tmp_path).await;
+                return Err(err.into());
+            }
+        };
+
+        let module_file_path = self.module_file_path(&hash);
+        if module_file_path.is_file() {
+            fs::remove_file(&tmp_path).await?;
+        } else {
+            fs::create_dir_all(self.base_dir.join(&hash)).await?;
+            fs::rename(&tmp_path, &module_file_path).await?;
+        }
+
+        Ok((hash, size))
+    }
+
+    pub async fn delete_module(&self, hash: &str) -> io::Result<()> {
+        fs::remove_dir_all(self.base_dir.join(hash)).await
+    }
+
+    /// `module_file_path` returns the path of module's library. A built-in module has no library,
+    /// so its reserved name is returned as is.
+    pub fn module_file_path(&self, hash: &str) -> PathBuf {
+        if is_builtin_module(hash) {
+            return PathBuf::from(hash);
+        }
+
+        let mut p = self.base_dir.join(hash);
+        p.push("lib".to_string() + MODULE_FILE_EXT);
+
+        p
+    }
+}
+
+impl Default for ModuleCatalog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn check_and_return_catalog_dir() -> PathBuf {
+    let path = app::data_dir().join("module");
+
+    if !path.is_dir() {
+        std::fs::create_dir(&path)
+            .unwrap_or_else(|_| panic!("failed to create catalog dir: '{path:?}'"));
+    }
+
+    path
+}
+
+fn upload_tmp_name() -> String {
+    format!(
+        ".upload-{}-{}",
+        std::process::id(),
+        UPLOAD_COUNTER.fetch_add(1, Ordering::SeqCst)
+    )
+}
+
+async fn write_and_hash<R: AsyncRead + Unpin + ?Sized, P: AsRef<Path>>(
+    path: P,
+    data: &mut R,
+) -> io::Result<(String, i64)> {
+    let mut file = fs::File::create(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; UPLOAD_BUF_SIZE];
+    let mut size: i64 = 0;
+
+    loop {
+        let n = data.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+
+        hasher.update(&buf[..n]);
+        file.write_all(&buf[..n]).await?;
+        size += n as i64;
+    }
+
+    file.flush().await?;
+
+    Ok((format!("{:x}", hasher.finalize()), size))
+}
@@ -1,8 +1,10 @@
+use std::collections::HashMap;
 use std::vec;
 
 use chrono;
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgRow;
+use sqlx::sqlite::SqliteRow;
 use sqlx::{types::Json, Column, FromRow, Row, TypeInfo};
 
 use crate::controller as ctrl;
@@ -58,6 +60,18 @@ pub struct Device {
     pub data_dir: String,
     #[column]
     pub init_state: DeviceInitState,
+    #[column]
+    pub module_info: Option<Json<ModuleInfo>>,
+    #[column]
+    pub module_hash: Option<String>,
+    #[column]
+    pub watchdog_interval: Option<i32>,
+    #[column]
+    pub watchdog_restart: bool,
+    #[column]
+    pub ingest_token_hash: Option<String>,
+    #[column]
+    pub stopped: bool,
 }
 
 impl Device {
@@ -76,10 +90,298 @@ impl ValuesTrait for Device {
             self.module_dir.into(),
             self.data_dir.into(),
             self.init_state.into(),
+            self.module_info.into(),
+            self.module_hash.into(),
+            self.watchdog_interval.into(),
+            self.watchdog_restart.into(),
+            self.ingest_token_hash.into(),
+            self.stopped.into(),
         ]);
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModuleInfo {
+    pub name: String,
+    pub vendor: Option<String>,
+    pub version: String,
+    pub description: Option<String>,
+    pub os: Option<String>,
+    pub arch: Option<String>,
+    pub capabilities: ModuleCapabilities,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModuleCapabilities {
+    pub commands: bool,
+    pub hot_reconfigure: bool,
+    pub persisted_config: bool,
+}
+
+ref_arg_type!(Option<Json<ModuleInfo>>);
+arg_from_ty!(Option<Json<ModuleInfo>>);
+
+impl From<ctrl::ModuleInfo> for ModuleInfo {
+    fn from(v: ctrl::ModuleInfo) -> Self {
+        ModuleInfo {
+            name: v.name,
+            vendor: v.vendor,
+            version: v.version,
+            description: v.description,
+            os: v.os,
+            arch: v.arch,
+            capabilities: ModuleCapabilities {
+                commands: v.capabilities.commands,
+                hot_reconfigure: v.capabilities.hot_reconfigure,
+                persisted_config: v.capabilities.persisted_config,
+            },
+        }
+    }
+}
+
+impl From<ModuleInfo> for ctrl::ModuleInfo {
+    fn from(v: ModuleInfo) -> Self {
+        ctrl::ModuleInfo {
+            name: v.name,
+            vendor: v.vendor,
+            version: v.version,
+            description: v.description,
+            os: v.os,
+            arch: v.arch,
+            capabilities: ctrl::ModuleCapabilities {
+                commands: v.capabilities.commands,
+                hot_reconfigure: v.capabilities.hot_reconfigure,
+                persisted_config: v.capabilities.persisted_config,
+            },
+        }
+    }
+}
+
+#[derive(FromRow, Table)]
+pub struct CatalogModule {
+    #[column]
+    pub id: i32,
+    #[column]
+    pub name: String,
+    #[column]
+    pub hash: String,
+    #[column]
+    pub size: i64,
+    #[column]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl CatalogModule {
+    pub fn table_name() -> String {
+        "module_catalog".into()
+    }
+
+    pub fn insert_columns() -> &'static [&'static str] {
+        &["name", "hash", "size"]
+    }
+}
+
+impl From<CatalogModule> for ctrl::CatalogModule {
+    fn from(v: CatalogModule) -> Self {
+        ctrl::CatalogModule {
+            id: v.id,
+            name: v.name,
+            hash: v.hash,
+            size: v.size,
+            created_at: v.created_at,
+        }
+    }
+}
+
+#[derive(FromRow, Table)]
+pub struct User {
+    #[column]
+    pub id: i32,
+    #[column]
+    pub username: String,
+    #[column]
+    pub password_hash: String,
+    #[column]
+    pub role: UserRole,
+    #[column]
+    pub device_scoped: bool,
+    #[column]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl User {
+    pub fn table_name() -> String {
+        "app_user".into()
+    }
+
+    pub fn insert_columns() -> &'static [&'static str] {
+        &["username", "password_hash", "role", "device_scoped"]
+    }
+
+    /// `into_ctrl` converts the user. `devices` are ids of its devices from [`UserDevice`]
+    pub fn into_ctrl(self, devices: Vec<i32>) -> ctrl::User {
+        ctrl::User {
+            id: self.id,
+            username: self.username,
+            password_hash: self.password_hash,
+            role: ctrl::Role::from(self.role),
+            devices: self.device_scoped.then_some(devices),
+            scopes: None,
+            created_at: self.created_at,
+        }
+    }
+}
+
+#[derive(sqlx::Type, Debug, PartialEq)]
+#[sqlx(type_name = "user_role", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum UserRole {
+    Viewer,
+    Operator,
+    Engineer,
+    Admin,
+}
+
+ref_arg_type!(UserRole);
+arg_from_ty!(UserRole);
+
+impl From<ctrl::Role> for UserRole {
+    fn from(v: ctrl::Role) -> Self {
+        match v {
+            ctrl::Role::Viewer => UserRole::Viewer,
+            ctrl::Role::Operator => UserRole::Operator,
+            ctrl::Role::Engineer => UserRole::Engineer,
+            ctrl::Role::Admin => UserRole::Admin,
+        }
+    }
+}
+
+impl From<UserRole> for ctrl::Role {
+    fn from(v: UserRole) -> Self {
+        match v {
+            UserRole::Viewer => ctrl::Role::Viewer,
+            UserRole::Operator => ctrl::Role::Operator,
+            UserRole::Engineer => ctrl::Role::Engineer,
+            UserRole::Admin => ctrl::Role::Admin,
+        }
+    }
+}
+
+/// UserDevice gives a device scoped user access to a device
+#[derive(FromRow, Table)]
+pub struct UserDevice {
+    #[column]
+    pub user_id: i32,
+    #[column]
+    pub device_id: i32,
+}
+
+impl UserDevice {
+    pub fn table_name() -> String {
+        "user_device".into()
+    }
+}
+
+#[derive(FromRow, Table)]
+pub struct ApiKey {
+    #[column]
+    pub id: i32,
+    #[column]
+    pub user_id: i32,
+    #[column]
+    pub name: String,
+    #[column]
+    pub scopes: Json<Vec<ApiKeyScope>>,
+    #[column]
+    pub created_at: chrono::NaiveDateTime,
+    #[column]
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+}
+
+impl ApiKey {
+    pub fn table_name() -> String {
+        "api_key".into()
+    }
+
+    pub fn insert_columns() -> &'static [&'static str] {
+        &["user_id", "name", "key_hash", "scopes"]
+    }
+}
+
+impl From<ApiKey> for ctrl::ApiKey {
+    fn from(v: ApiKey) -> Self {
+        ctrl::ApiKey {
+            id: v.id,
+            user_id: v.user_id,
+            name: v.name,
+            scopes: v
+                .scopes
+                .0
+                .into_iter()
+                .map(ctrl::ApiKeyScope::from)
+                .collect(),
+            created_at: v.created_at,
+            last_used_at: v.last_used_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ApiKeyScope {
+    ReadData,
+    Ingest,
+    Admin,
+}
+
+ref_arg_type!(Json<Vec<ApiKeyScope>>);
+arg_from_ty!(Json<Vec<ApiKeyScope>>);
+
+impl From<ctrl::ApiKeyScope> for ApiKeyScope {
+    fn from(v: ctrl::ApiKeyScope) -> Self {
+        match v {
+            ctrl::ApiKeyScope::ReadData => ApiKeyScope::ReadData,
+            ctrl::ApiKeyScope::Ingest => ApiKeyScope::Ingest,
+            ctrl::ApiKeyScope::Admin => ApiKeyScope::Admin,
+        }
+    }
+}
+
+impl From<ApiKeyScope> for ctrl::ApiKeyScope {
+    fn from(v: ApiKeyScope) -> Self {
+        match v {
+            ApiKeyScope::ReadData => ctrl::ApiKeyScope::ReadData,
+            ApiKeyScope::Ingest => ctrl::ApiKeyScope::Ingest,
+            ApiKeyScope::Admin => ctrl::ApiKeyScope::Admin,
+        }
+    }
+}
+
+#[derive(FromRow, Table)]
+pub struct Session {
+    #[column]
+    pub token_hash: String,
+    #[column]
+    pub user_id: i32,
+    #[column]
+    pub expires_at: chrono::NaiveDateTime,
+}
+
+impl Session {
+    pub fn table_name() -> String {
+        "user_session".into()
+    }
+}
+
+impl From<Session> for ctrl::Session {
+    fn from(v: Session) -> Self {
+        ctrl::Session {
+            token_hash: v.token_hash,
+            user_id: v.user_id,
+            expires_at: v.expires_at,
+        }
+    }
+}
+
 #[derive(FromRow, Table)]
 pub struct DeviceSensor {
     #[column]
@@ -107,6 +409,7 @@ impl ValuesTrait for DeviceSensor {
 }
 
 /// For retrieving device's sensors data types from `information_schema.columns`
+/// or `pragma_table_info` in SQLite
 #[derive(FromRow, Table)]
 pub struct ColumnType {
     #[column]
@@ -131,7 +434,7 @@ impl From<SensorData> for ctrl::SensorData {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SensorDataTypeValue {
     Int16(i16),
     Int32(i32),
@@ -143,6 +446,22 @@ pub enum SensorDataTypeValue {
     JSON(String),
 }
 
+/// `bind_value` binds the value of the variant to a query of any database
+macro_rules! bind_value {
+    ($self:ident, $q:ident, $bind:ident) => {
+        match $self {
+            SensorDataTypeValue::Int16(v) => v.$bind($q),
+            SensorDataTypeValue::Int32(v) => v.$bind($q),
+            SensorDataTypeValue::Int64(v) => v.$bind($q),
+            SensorDataTypeValue::Float32(v) => v.$bind($q),
+            SensorDataTypeValue::Float64(v) => v.$bind($q),
+            SensorDataTypeValue::Timestamp(v) => v.$bind($q),
+            SensorDataTypeValue::String(v) => v.$bind($q),
+            SensorDataTypeValue::JSON(v) => v.$bind($q),
+        }
+    };
+}
+
 impl crate::query::integration::isqlx::ArgType for SensorDataTypeValue {
     fn bind<'q>(
         &'q self,
@@ -156,16 +475,22 @@ impl crate::query::integration::isqlx::ArgType for SensorDataTypeValue {
         sqlx::postgres::Postgres,
         <sqlx::postgres::Postgres as sqlx::database::HasArguments<'q>>::Arguments,
     > {
-        match self {
-            SensorDataTypeValue::Int16(v) => v.bind(q),
-            SensorDataTypeValue::Int32(v) => v.bind(q),
-            SensorDataTypeValue::Int64(v) => v.bind(q),
-            SensorDataTypeValue::Float32(v) => v.bind(q),
-            SensorDataTypeValue::Float64(v) => v.bind(q),
-            SensorDataTypeValue::Timestamp(v) => v.bind(q),
-            SensorDataTypeValue::String(v) => v.bind(q),
-            SensorDataTypeValue::JSON(v) => v.bind(q),
-        }
+        bind_value!(self, q, bind)
+    }
+
+    fn bind_sqlite<'q>(
+        &'q self,
+        q: sqlx::query::Query<
+            'q,
+            sqlx::sqlite::Sqlite,
+            <sqlx::sqlite::Sqlite as sqlx::database::HasArguments<'q>>::Arguments,
+        >,
+    ) -> sqlx::query::Query<
+        'q,
+        sqlx::sqlite::Sqlite,
+        <sqlx::sqlite::Sqlite as sqlx::database::HasArguments<'q>>::Arguments,
+    > {
+        bind_value!(self, q, bind_sqlite)
     }
 }
 
@@ -248,6 +573,70 @@ impl<'r> FromRow<'r, PgRow> for SensorDataRow {
     }
 }
 
+impl<'r> FromRow<'r, SqliteRow> for SensorDataRow {
+    /// Declared types of columns are mapped to storage classes in SQLite, so values
+    /// are decoded as the widest type of a class. See [`SensorDataRow::cast`]
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        let mut res = Vec::with_capacity(row.len());
+
+        for col in row.columns() {
+            let info = col.type_info();
+            let data = match info.name() {
+                "INTEGER" => Ok(SensorDataTypeValue::Int64(row.get(col.ordinal()))),
+                "REAL" => Ok(SensorDataTypeValue::Float64(row.get(col.ordinal()))),
+                "DATETIME" => Ok(SensorDataTypeValue::Timestamp(row.get(col.ordinal()))),
+                "TEXT" => Ok(SensorDataTypeValue::String(row.get(col.ordinal()))),
+                any => Err(sqlx::Error::ColumnDecode {
+                    index: col.name().to_string(),
+                    source: SensorDataDecodeError::UnsupportedType(any.to_string()).into(),
+                }),
+            }?;
+
+            res.push(SensorData {
+                name: col.name().to_string(),
+                data,
+            })
+        }
+
+        Ok(SensorDataRow(res))
+    }
+}
+
+impl SensorDataRow {
+    /// `cast` converts values to the types of sensor's fields
+    pub fn cast(mut self, types: &HashMap<String, ctrl::SensorDataType>) -> Self {
+        for d in self.0.iter_mut() {
+            if let Some(typ) = types.get(&d.name) {
+                d.data = d.data.clone().cast(typ);
+            }
+        }
+
+        self
+    }
+}
+
+impl SensorDataTypeValue {
+    /// `cast` converts a value decoded as the widest type to the given type.
+    /// Other values are returned as is
+    pub fn cast(self, typ: &ctrl::SensorDataType) -> Self {
+        match (self, typ) {
+            (SensorDataTypeValue::Int64(v), ctrl::SensorDataType::Int16) => {
+                SensorDataTypeValue::Int16(v as i16)
+            }
+            (SensorDataTypeValue::Int64(v), ctrl::SensorDataType::Int32) => {
+                SensorDataTypeValue::Int32(v as i32)
+            }
+            (SensorDataTypeValue::Float64(v), ctrl::SensorDataType::Float32) => {
+                SensorDataTypeValue::Float32(v as f32)
+            }
+            (SensorDataTypeValue::String(v), ctrl::SensorDataType::JSON) => {
+                SensorDataTypeValue::JSON(v)
+            }
+            (v, _) => v,
+        }
+    }
+}
+
 impl From<SensorDataRow> for ctrl::SensorDataList {
     fn from(mut value: SensorDataRow) -> Self {
         value
@@ -517,3 +906,192 @@ impl From<ctrl::MonitorConfListFilter> for MonitorConfListFilter {
         }
     }
 }
+
+pub struct DeviceCommandLog {
+    pub device_id: i32,
+    pub command: String,
+    pub args: Json<Vec<CommandArg>>,
+    pub error: Option<String>,
+}
+
+impl DeviceCommandLog {
+    pub fn table_name() -> String {
+        "device_command_log".into()
+    }
+
+    pub fn insert_columns() -> &'static [&'static str] {
+        &["device_id", "command", "args", "error"]
+    }
+}
+
+impl From<ctrl::DeviceCommandLog> for DeviceCommandLog {
+    fn from(v: ctrl::DeviceCommandLog) -> Self {
+        DeviceCommandLog {
+            device_id: v.device_id.get_raw(),
+            command: v.command.name,
+            args: Json(v.command.args.into_iter().map(CommandArg::from).collect()),
+            error: v.error,
+        }
+    }
+}
+
+ref_arg_type!(Json<Vec<CommandArg>>);
+arg_from_ty!(Json<Vec<CommandArg>>);
+
+impl ValuesTrait for DeviceCommandLog {
+    fn values(self, b: &mut crate::query::integration::isqlx::StatementBuilder) {
+        b.values(vec![
+            self.device_id.into(),
+            self.command.into(),
+            self.args.into(),
+            self.error.into(),
+        ]);
+    }
+}
+
+/// `audit_action_name` returns the name of the action in the storage
+pub fn audit_action_name(action: ctrl::AuditAction) -> &'static str {
+    match action {
+        ctrl::AuditAction::StartDeviceInit => "START_DEVICE_INIT",
+        ctrl::AuditAction::ConnectDevice => "CONNECT_DEVICE",
+        ctrl::AuditAction::ConfigureDevice => "CONFIGURE_DEVICE",
+        ctrl::AuditAction::InterruptDeviceInit => "INTERRUPT_DEVICE_INIT",
+        ctrl::AuditAction::DeleteDevice => "DELETE_DEVICE",
+        ctrl::AuditAction::StopDevice => "STOP_DEVICE",
+        ctrl::AuditAction::StartDevice => "START_DEVICE",
+        ctrl::AuditAction::UpgradeDeviceModule => "UPGRADE_DEVICE_MODULE",
+        ctrl::AuditAction::SetDeviceWatchdogConf => "SET_DEVICE_WATCHDOG_CONF",
+        ctrl::AuditAction::DeviceStale => "DEVICE_STALE",
+        ctrl::AuditAction::DeviceRecovered => "DEVICE_RECOVERED",
+        ctrl::AuditAction::RestartStaleDevice => "RESTART_STALE_DEVICE",
+        ctrl::AuditAction::SendDeviceCommand => "SEND_DEVICE_COMMAND",
+        ctrl::AuditAction::CreatePushDevice => "CREATE_PUSH_DEVICE",
+        ctrl::AuditAction::ResetIngestToken => "RESET_INGEST_TOKEN",
+        ctrl::AuditAction::ImportSensorData => "IMPORT_SENSOR_DATA",
+        ctrl::AuditAction::UploadModule => "UPLOAD_MODULE",
+        ctrl::AuditAction::DeleteModule => "DELETE_MODULE",
+        ctrl::AuditAction::SaveMonitorConf => "SAVE_MONITOR_CONF",
+        ctrl::AuditAction::CreateUser => "CREATE_USER",
+        ctrl::AuditAction::UpdateUser => "UPDATE_USER",
+        ctrl::AuditAction::DeleteUser => "DELETE_USER",
+        ctrl::AuditAction::CreateApiKey => "CREATE_API_KEY",
+        ctrl::AuditAction::RevokeApiKey => "REVOKE_API_KEY",
+    }
+}
+
+fn audit_action_from_name(name: &str) -> Option<ctrl::AuditAction> {
+    ctrl::AuditAction::ALL
+        .iter()
+        .copied()
+        .find(|action| audit_action_name(*action) == name)
+}
+
+#[derive(FromRow, Table)]
+pub struct AuditRecord {
+    #[column]
+    pub id: i32,
+    #[column]
+    pub user_id: Option<i32>,
+    #[column]
+    pub username: String,
+    #[column]
+    pub action: String,
+    #[column]
+    pub device_id: Option<i32>,
+    #[column]
+    pub summary: Json<serde_json::Value>,
+    #[column]
+    pub error: Option<String>,
+    #[column]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl AuditRecord {
+    pub fn table_name() -> String {
+        "audit_log".into()
+    }
+
+    pub fn insert_columns() -> &'static [&'static str] {
+        &[
+            "user_id",
+            "username",
+            "action",
+            "device_id",
+            "summary",
+            "error",
+        ]
+    }
+}
+
+impl TryFrom<AuditRecord> for ctrl::AuditRecord {
+    type Error = String;
+
+    fn try_from(v: AuditRecord) -> Result<Self, Self::Error> {
+        let action = audit_action_from_name(&v.action)
+            .ok_or_else(|| format!("unknown audit action '{}'", v.action))?;
+
+        Ok(ctrl::AuditRecord {
+            id: v.id,
+            user_id: v.user_id,
+            username: v.username,
+            action,
+            device_id: v.device_id,
+            summary: v.summary.0,
+            error: v.error,
+            created_at: v.created_at,
+        })
+    }
+}
+
+ref_arg_type!(Json<serde_json::Value>);
+arg_from_ty!(Json<serde_json::Value>);
+
+pub struct NewAuditRecord {
+    pub user_id: Option<i32>,
+    pub username: String,
+    pub action: String,
+    pub device_id: Option<i32>,
+    pub summary: Json<serde_json::Value>,
+    pub error: Option<String>,
+}
+
+impl From<ctrl::NewAuditRecord> for NewAuditRecord {
+    fn from(v: ctrl::NewAuditRecord) -> Self {
+        NewAuditRecord {
+            user_id: v.user_id,
+            username: v.username,
+            action: audit_action_name(v.action).into(),
+            device_id: v.device_id,
+            summary: Json(v.summary),
+            error: v.error,
+        }
+    }
+}
+
+impl ValuesTrait for NewAuditRecord {
+    fn values(self, b: &mut crate::query::integration::isqlx::StatementBuilder) {
+        b.values(vec![
+            self.user_id.into(),
+            self.username.into(),
+            self.action.into(),
+            self.device_id.into(),
+            self.summary.into(),
+            self.error.into(),
+        ]);
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommandArg {
+    pub name: String,
+    pub data: Option<SensorDataTypeValue>,
+}
+
+impl From<ctrl::CommandArg> for CommandArg {
+    fn from(v: ctrl::CommandArg) -> Self {
+        CommandArg {
+            name: v.name,
+            data: v.data.map(SensorDataTypeValue::from),
+        }
+    }
+}
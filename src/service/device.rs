@@ -1,9 +1,8 @@
 use std::collections::HashMap;
 use std::error::Error;
-use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::atomic::{AtomicI32, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::sync::RwLock;
 
@@ -11,19 +10,15 @@ use tokio::fs;
 use tokio::io;
 use tokio::io::AsyncRead;
 
+use crate::controller::interface::module::{is_builtin_module, MODULE_FILE_EXT};
 use crate::controller::{self as ctrl, DeviceID};
-use crate::{app, debug_from_display};
+use crate::logger;
+use crate::{app, debug_from_display, kv_any, kvs};
 
+use super::catalog::ModuleCatalog;
 use super::db_model;
 
-#[cfg(target_os = "macos")]
-const MODULE_FILE_EXT: &str = ".dylib";
-
-#[cfg(target_os = "linux")]
-const MODULE_FILE_EXT: &str = ".so";
-
-#[cfg(target_os = "windows")]
-const MODULE_FILE_EXT: &str = ".dll";
+static UPGRADE_COUNTER: AtomicU64 = AtomicU64::new(0);
 
 #[derive(thiserror::Error)]
 pub enum DeviceError {
@@ -47,6 +42,15 @@ pub struct Device {
     module_dir: PathBuf,
     data_dir: PathBuf,
     init_state: ctrl::DeviceInitState,
+    module_info: Option<ctrl::ModuleInfo>,
+    /// Hash of the catalog module. If set, `module_dir` is empty and the module file is
+    /// resolved from [`ModuleCatalog`]
+    module_hash: Option<String>,
+    watchdog_conf: ctrl::WatchdogConf,
+    /// SHA-256 of the ingest token. Only push devices have it
+    ingest_token_hash: Option<String>,
+    /// `true` if the device's module isn't started with the service
+    stopped: bool,
 
     /// [`HashMap`]<`sensor's table name`, [`Sensor`]>
     sensor_map: HashMap<String, ctrl::Sensor>,
@@ -72,6 +76,7 @@ pub struct DeviceManager {
     last_id: Arc<AtomicI32>,
     device_map: Arc<RwLock<HashMap<DeviceID, Arc<RwLock<Device>>>>>,
     data_dir: Arc<PathBuf>,
+    catalog: ModuleCatalog,
 }
 
 impl DeviceManager {
@@ -97,6 +102,17 @@ impl DeviceManager {
                     data_dir: PathBuf::from_str(&device.data_dir)?,
                     sensor_map: HashMap::new(),
                     init_state: ctrl::DeviceInitState::from(&device.init_state),
+                    module_info: device
+                        .module_info
+                        .as_ref()
+                        .map(|info| ctrl::ModuleInfo::from(info.0.clone())),
+                    module_hash: device.module_hash.clone(),
+                    watchdog_conf: ctrl::WatchdogConf {
+                        expected_interval: device.watchdog_interval,
+                        restart: device.watchdog_restart,
+                    },
+                    ingest_token_hash: device.ingest_token_hash.clone(),
+                    stopped: device.stopped,
                 })),
             );
 
@@ -155,11 +171,13 @@ impl DeviceManager {
             last_id: Arc::new(AtomicI32::new(last_id)),
             device_map: Arc::new(RwLock::new(device_map)),
             data_dir: Arc::new(check_and_return_base_dir()),
+            catalog: ModuleCatalog::new(),
         };
 
-        // TODO: Replace with logger
-        println!("Inited DeviceManager with data_dir = {:?}", res.data_dir);
-        std::io::stdout().flush().unwrap();
+        logger::info_kv(
+            "device manager initialized",
+            kvs!("data_dir" => kv_any!(res.data_dir.display().to_string())),
+        );
 
         Ok(res)
     }
@@ -186,7 +204,43 @@ impl DeviceManager {
     {
         let id = self.inc_last_id();
 
-        let dir_name = build_device_dir_name(&id, &name);
+        let (module_dir, data_dir) = self.create_device_dirs(&id, &name).await?;
+
+        let full_module_path = self.full_module_file_path(&module_dir);
+        create_file(&full_module_path, module_file).await?;
+
+        Ok(self.add_device(id, name, display_name, module_dir, data_dir, None))
+    }
+
+    /// `start_device_init_from_catalog` works like [`DeviceManager::start_device_init`], but the
+    /// module file isn't copied: device references the catalog module by its hash and
+    /// `module/` directory is left empty.
+    pub async fn start_device_init_from_catalog(
+        &self,
+        name: String,
+        display_name: String,
+        module_hash: String,
+    ) -> Result<ctrl::DeviceInitData, Box<dyn Error>> {
+        let id = self.inc_last_id();
+
+        let (module_dir, data_dir) = self.create_device_dirs(&id, &name).await?;
+
+        Ok(self.add_device(
+            id,
+            name,
+            display_name,
+            module_dir,
+            data_dir,
+            Some(module_hash),
+        ))
+    }
+
+    async fn create_device_dirs(
+        &self,
+        id: &DeviceID,
+        name: &String,
+    ) -> io::Result<(PathBuf, PathBuf)> {
+        let dir_name = build_device_dir_name(id, name);
         self.create_data_dir(&dir_name).await?;
 
         let module_dir = dir_name.join("module");
@@ -195,8 +249,19 @@ impl DeviceManager {
         let data_dir = dir_name.join("data");
         self.create_data_dir(&data_dir).await?;
 
-        let full_module_path = self.full_module_file_path(&module_dir);
-        create_file(&full_module_path, module_file).await?;
+        Ok((module_dir, data_dir))
+    }
+
+    fn add_device(
+        &self,
+        id: DeviceID,
+        name: String,
+        display_name: String,
+        module_dir: PathBuf,
+        data_dir: PathBuf,
+        module_hash: Option<String>,
+    ) -> ctrl::DeviceInitData {
+        let module_file = self.device_module_file_path(&module_dir, &module_hash);
 
         let device = Device {
             name,
@@ -205,20 +270,148 @@ impl DeviceManager {
             data_dir: data_dir.clone(),
             sensor_map: Default::default(),
             init_state: ctrl::DeviceInitState::Device,
+            module_info: None,
+            module_hash,
+            watchdog_conf: Default::default(),
+            ingest_token_hash: None,
+            stopped: false,
         };
 
         (*self.device_map.write().unwrap()).insert(id, Arc::new(RwLock::new(device)));
 
-        Ok(ctrl::DeviceInitData {
+        ctrl::DeviceInitData {
             id,
-            module_file: full_module_path,
+            module_file,
             data_dir: data_dir.clone(),
             full_data_dir: self.full_data_dir(&data_dir),
             module_dir,
             init_state: ctrl::DeviceInitState::Device,
+            stopped: false,
+        }
+    }
+
+    pub fn catalog(&self) -> &ModuleCatalog {
+        &self.catalog
+    }
+
+    /// `stage_device_module` writes a new module file next to device's current module.
+    /// The device keeps using its current module until the upgrade is committed with
+    /// [`DeviceManager::commit_device_module`].
+    ///
+    /// Every staged file gets a unique name, so the dynamic loader never confuses it
+    /// with a library that is already loaded.
+    pub async fn stage_device_module<F>(
+        &self,
+        id: DeviceID,
+        module_file: &mut F,
+    ) -> Result<ctrl::ModuleUpgradeData, Box<dyn Error>>
+    where
+        F: AsyncRead + Unpin + ?Sized,
+    {
+        let (module_dir, data_dir, module_hash) = {
+            let device = self.get_device(&id)?;
+            let device = device.read().unwrap();
+
+            (
+                device.module_dir.clone(),
+                device.data_dir.clone(),
+                device.module_hash.clone(),
+            )
+        };
+
+        let file_name = format!(
+            "upgrade-{}-{}-lib{}",
+            std::process::id(),
+            UPGRADE_COUNTER.fetch_add(1, Ordering::SeqCst),
+            MODULE_FILE_EXT
+        );
+        let staged_path = self.full_data_dir(&module_dir).join(file_name);
+        create_file(&staged_path, module_file).await?;
+
+        Ok(ctrl::ModuleUpgradeData {
+            id,
+            module_file: staged_path,
+            full_data_dir: self.full_data_dir(&data_dir),
+            prev_module_hash: module_hash,
         })
     }
 
+    /// `commit_device_module` replaces device's module with the staged one. The previous
+    /// module is kept as `lib.prev.<so|dylib|dll>` in device's module dir.
+    ///
+    /// `lib.<so|dylib|dll>` is replaced by a rename, so it always points to either the
+    /// previous or the new library. If the device used a catalog module, it gets its own
+    /// module copy and stops referencing the catalog. Returns `true` in this case.
+    pub async fn commit_device_module(
+        &self,
+        data: &ctrl::ModuleUpgradeData,
+    ) -> Result<bool, Box<dyn Error>> {
+        let device = self.get_device(&data.id)?;
+        let (module_dir, module_hash) = {
+            let device = device.read().unwrap();
+
+            (device.module_dir.clone(), device.module_hash.clone())
+        };
+
+        let module_path = self.full_module_file_path(&module_dir);
+        let prev_module_path = self
+            .full_data_dir(&module_dir)
+            .join("lib.prev".to_string() + MODULE_FILE_EXT);
+
+        if prev_module_path.is_file() {
+            fs::remove_file(&prev_module_path).await?;
+        }
+
+        match module_hash {
+            // A built-in module has no library to keep
+            Some(ref hash) if is_builtin_module(hash) => {}
+            Some(ref hash) => {
+                fs::copy(self.catalog.module_file_path(hash), &prev_module_path).await?;
+            }
+            None => fs::hard_link(&module_path, &prev_module_path).await?,
+        }
+
+        fs::rename(&data.module_file, &module_path).await?;
+
+        device.write().unwrap().module_hash = None;
+
+        Ok(module_hash.is_some())
+    }
+
+    /// `rollback_device_module` reverts a committed upgrade. Device gets back
+    /// `lib.prev.<so|dylib|dll>` or the catalog module it used before the upgrade.
+    pub async fn rollback_device_module(
+        &self,
+        data: &ctrl::ModuleUpgradeData,
+    ) -> Result<(), Box<dyn Error>> {
+        let device = self.get_device(&data.id)?;
+        let module_dir = device.read().unwrap().module_dir.clone();
+
+        let module_path = self.full_module_file_path(&module_dir);
+        let prev_module_path = self
+            .full_data_dir(&module_dir)
+            .join("lib.prev".to_string() + MODULE_FILE_EXT);
+
+        match data.prev_module_hash {
+            Some(_) => {
+                fs::remove_file(&module_path).await?;
+                if prev_module_path.is_file() {
+                    fs::remove_file(&prev_module_path).await?;
+                }
+            }
+            None => fs::rename(&prev_module_path, &module_path).await?,
+        }
+
+        device.write().unwrap().module_hash = data.prev_module_hash.clone();
+
+        Ok(())
+    }
+
+    /// `discard_device_module` removes the staged module file.
+    pub async fn discard_device_module(&self, data: &ctrl::ModuleUpgradeData) -> io::Result<()> {
+        fs::remove_file(&data.module_file).await
+    }
+
     pub fn device_sensor_init(
         &self,
         device_id: &DeviceID,
@@ -241,6 +434,90 @@ impl DeviceManager {
         Ok(device.name.clone())
     }
 
+    pub fn set_device_module_info(
+        &self,
+        id: &DeviceID,
+        module_info: Option<ctrl::ModuleInfo>,
+    ) -> Result<(), DeviceError> {
+        let device = self.get_device(id)?;
+        let mut device = device.write().unwrap();
+
+        device.module_info = module_info;
+
+        Ok(())
+    }
+
+    pub fn set_device_watchdog_conf(
+        &self,
+        id: &DeviceID,
+        conf: ctrl::WatchdogConf,
+    ) -> Result<(), DeviceError> {
+        let device = self.get_device(id)?;
+        let mut device = device.write().unwrap();
+
+        device.watchdog_conf = conf;
+
+        Ok(())
+    }
+
+    pub fn get_device_watchdog_conf(
+        &self,
+        id: &DeviceID,
+    ) -> Result<ctrl::WatchdogConf, DeviceError> {
+        let device = self.get_device(id)?;
+        let device = device.read().unwrap();
+
+        Ok(device.watchdog_conf.clone())
+    }
+
+    pub fn set_device_ingest_token_hash(
+        &self,
+        id: &DeviceID,
+        hash: Option<String>,
+    ) -> Result<(), DeviceError> {
+        let device = self.get_device(id)?;
+        let mut device = device.write().unwrap();
+
+        device.ingest_token_hash = hash;
+
+        Ok(())
+    }
+
+    pub fn get_device_ingest_token_hash(
+        &self,
+        id: &DeviceID,
+    ) -> Result<Option<String>, DeviceError> {
+        let device = self.get_device(id)?;
+        let device = device.read().unwrap();
+
+        Ok(device.ingest_token_hash.clone())
+    }
+
+    pub fn set_device_stopped(&self, id: &DeviceID, stopped: bool) -> Result<(), DeviceError> {
+        let device = self.get_device(id)?;
+        let mut device = device.write().unwrap();
+
+        device.stopped = stopped;
+
+        Ok(())
+    }
+
+    pub fn get_device_full_info(&self, id: DeviceID) -> Result<ctrl::DeviceFullInfo, DeviceError> {
+        let device = self.get_device(&id)?;
+        let device = device.read().unwrap();
+
+        Ok(ctrl::DeviceFullInfo {
+            id,
+            name: device.name.clone(),
+            display_name: device.display_name.clone(),
+            init_state: device.init_state.clone(),
+            module_info: device.module_info.clone(),
+            module_hash: device.module_hash.clone(),
+            watchdog_conf: device.watchdog_conf.clone(),
+            stopped: device.stopped,
+        })
+    }
+
     pub fn get_device_init_state(
         &self,
         id: DeviceID,
@@ -285,8 +562,9 @@ impl DeviceManager {
                 module_dir: data.module_dir.clone(),
                 data_dir: data.data_dir.clone(),
                 full_data_dir: self.full_data_dir(&data.data_dir),
-                module_file: self.full_module_file_path(&data.module_dir),
+                module_file: self.device_module_file_path(&data.module_dir, &data.module_hash),
                 init_state: data.init_state.clone(),
+                stopped: data.stopped,
             })
         }
 
@@ -306,6 +584,9 @@ impl DeviceManager {
                 res.push(ctrl::DeviceInfo {
                     id: id.clone(),
                     display_name: data.get_display_name().clone(),
+                    health: None,
+                    faulted: false,
+                    stopped: data.stopped,
                 })
             }
         }
@@ -374,6 +655,17 @@ impl DeviceManager {
 
         p
     }
+
+    fn device_module_file_path<P: AsRef<Path>>(
+        &self,
+        module_dir: P,
+        module_hash: &Option<String>,
+    ) -> PathBuf {
+        match module_hash {
+            Some(hash) => self.catalog.module_file_path(hash),
+            None => self.full_module_file_path(module_dir),
+        }
+    }
 }
 
 impl Default for DeviceManager {
@@ -382,14 +674,12 @@ impl Default for DeviceManager {
             last_id: Default::default(),
             device_map: Default::default(),
             data_dir: Arc::new(check_and_return_base_dir()),
+            catalog: ModuleCatalog::new(),
         }
     }
 }
 
 fn check_and_return_base_dir() -> PathBuf {
-    println!("Initializing base dir...");
-    std::io::stdout().flush().unwrap();
-
     let path = app::data_dir().join("device");
 
     let p = Path::new(&path);
@@ -420,7 +710,8 @@ async fn create_file<'a, R: AsyncRead + Unpin + ?Sized, P: AsRef<Path>>(
 }
 
 fn sensor_data_type_from_udt(udt_name: &str) -> Option<ctrl::SensorDataType> {
-    match udt_name {
+    // SQLite reports some declared types in upper case, e.g. `TEXT`
+    match udt_name.to_ascii_lowercase().as_str() {
         "int2" => Some(ctrl::SensorDataType::Int16),
         "int4" => Some(ctrl::SensorDataType::Int32),
         "int8" => Some(ctrl::SensorDataType::Int64),
@@ -429,6 +720,8 @@ fn sensor_data_type_from_udt(udt_name: &str) -> Option<ctrl::SensorDataType> {
         "timestamp" => Some(ctrl::SensorDataType::Timestamp),
         "text" => Some(ctrl::SensorDataType::String),
         "jsonb" => Some(ctrl::SensorDataType::JSON),
+        // JSON type of SQLite tables, see `table::FieldType::parse`
+        "json_text" => Some(ctrl::SensorDataType::JSON),
         _ => None,
     }
 }
@@ -1,3 +1,4 @@
+mod catalog;
 mod db_model;
 mod device;
 mod error;
@@ -1,8 +1,10 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::path::Path;
 
 use inflections::Inflect;
+use sqlx::types::Json;
+use tokio::sync::mpsc;
 
 use super::db_model;
 use super::device;
@@ -11,14 +13,20 @@ use super::error::ServiceError as InternalServiceError;
 use crate::controller::{
     self as ctrl,
     error::{CommonError, ErrorType},
-    interface::service::IService,
+    interface::{module::is_builtin_module, service::IService},
 };
 use crate::query::integration::isqlx as sq;
 use crate::query::integration::isqlx::ArgType;
+use crate::query::Dialect;
 use crate::tool::query_trait::{ColumnsTrait, ValuesTrait};
 use crate::{repo, table, tool::validation};
 
 const BASE_NAME_MAX_LEN: usize = 255;
+/// Maximum number of arguments of a single insert statement.
+/// PostgreSQL doesn't allow more than 65535 parameters in a query and SQLite more than 32766
+const MAX_INSERT_ARGS: usize = 30000;
+
+const EXPORT_CURSOR_NAME: &str = "sensor_data_export";
 
 #[derive(Clone)]
 pub struct Service {
@@ -65,9 +73,23 @@ impl Service {
         let sensor_types: Vec<db_model::ColumnType> = {
             if sensor_table_names.len() > 0 {
                 let mut b = sq::StatementBuilder::new();
-                b.table("information_schema.columns".into())
-                    .columns(db_model::ColumnType::columns())
-                    .whereq(sq::inq("table_name".into(), sensor_table_names));
+                match repo.dialect() {
+                    Dialect::Postgres => {
+                        b.table("information_schema.columns".into())
+                            .columns(db_model::ColumnType::columns())
+                            .whereq(sq::inq("table_name".into(), sensor_table_names));
+                    }
+                    // Declared types of columns are listed by the function of `table_info` pragma
+                    Dialect::Sqlite => {
+                        b.table("sqlite_master m, pragma_table_info(m.name) p".into())
+                            .columns(&[
+                                "m.name AS table_name",
+                                "p.name AS column_name",
+                                "p.type AS udt_name",
+                            ])
+                            .whereq(sq::inq("m.name".into(), sensor_table_names));
+                    }
+                }
 
                 repo.select(b.select()).await?
             } else {
@@ -79,6 +101,136 @@ impl Service {
 
         Ok(device_manager)
     }
+
+    /// `sensor_data_types` returns types of the sensor's fields. Values read from SQLite
+    /// are converted to them, see [`db_model::SensorDataRow::cast`]
+    fn sensor_data_types(
+        &self,
+        id: ctrl::DeviceID,
+        sensor_name: &str,
+    ) -> Result<HashMap<String, ctrl::SensorDataType>, CommonError> {
+        let sensors = self
+            .device_manager
+            .get_device_sensor_info(id)
+            .map_err(|err| {
+                CommonError::new(ErrorType::NotFound, "failed to get device sensor info")
+                    .with_source(err)
+            })?;
+
+        Ok(sensors
+            .into_iter()
+            .find(|s| s.name == sensor_name)
+            .map(|s| s.data.into_iter().map(|d| (d.name, d.typ)).collect())
+            .unwrap_or_default())
+    }
+
+    async fn insert_device(
+        &self,
+        init_data: &ctrl::DeviceInitData,
+        name: String,
+        display_name: String,
+        module_hash: Option<String>,
+    ) -> Result<(), CommonError> {
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::Device::table_name())
+            .columns(db_model::Device::columns());
+
+        let module_dir = path_to_str(&init_data.module_dir).map_err(|err| {
+            CommonError::new(
+                ErrorType::Internal,
+                "failed to convert module path to string",
+            )
+            .with_source(err)
+        })?;
+
+        let data_dir = path_to_str(&init_data.data_dir).map_err(|err| {
+            CommonError::new(ErrorType::Internal, "failed to convert data path to string")
+                .with_source(err)
+        })?;
+
+        db_model::Device {
+            id: init_data.id.get_raw(),
+            name,
+            display_name,
+            module_dir,
+            data_dir,
+            init_state: db_model::DeviceInitState::Device,
+            module_info: None,
+            module_hash,
+            watchdog_interval: None,
+            watchdog_restart: false,
+            ingest_token_hash: None,
+            stopped: false,
+        }
+        .values(&mut b);
+
+        self.repo
+            .exec(b.insert())
+            .await
+            .map_err(|err| err.to_common_err("failed to save device info"))?;
+
+        Ok(())
+    }
+
+    async fn get_catalog_module(
+        &self,
+        module_id: i32,
+    ) -> Result<db_model::CatalogModule, CommonError> {
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::CatalogModule::table_name())
+            .columns(db_model::CatalogModule::columns())
+            .whereq(sq::eq("id".into(), module_id));
+
+        let mut res: Vec<db_model::CatalogModule> = self
+            .repo
+            .select(b.select())
+            .await
+            .map_err(|err| err.to_common_err("failed to get catalog module"))?;
+
+        res.pop().ok_or(CommonError::new(
+            ErrorType::NotFound,
+            format!("catalog module with id '{module_id}' was not found"),
+        ))
+    }
+
+    /// `load_user_devices` converts users adding ids of their devices to the scoped ones
+    async fn load_user_devices(
+        &self,
+        users: Vec<db_model::User>,
+    ) -> Result<Vec<ctrl::User>, CommonError> {
+        let scoped: Vec<i32> = users
+            .iter()
+            .filter(|user| user.device_scoped)
+            .map(|user| user.id)
+            .collect();
+
+        let mut devices: HashMap<i32, Vec<i32>> = HashMap::new();
+        if !scoped.is_empty() {
+            let mut b = sq::StatementBuilder::new();
+            b.table(db_model::UserDevice::table_name())
+                .columns(db_model::UserDevice::columns())
+                .whereq(sq::inq("user_id".into(), scoped))
+                .order("device_id ASC".into());
+
+            let res: Vec<db_model::UserDevice> = self
+                .repo
+                .select(b.select())
+                .await
+                .map_err(|err| err.to_common_err("failed to get user devices"))?;
+
+            for v in res {
+                devices.entry(v.user_id).or_default().push(v.device_id);
+            }
+        }
+
+        Ok(users
+            .into_iter()
+            .map(|user| {
+                let user_devices = devices.remove(&user.id).unwrap_or_default();
+                user.into_ctrl(user_devices)
+            })
+            .collect())
+    }
 }
 
 impl IService for Service {
@@ -106,41 +258,250 @@ impl IService for Service {
                     .with_source(err)
             })?;
 
-        let mut b = sq::StatementBuilder::new();
-        b.table(db_model::Device::table_name())
-            .columns(db_model::Device::columns());
+        self.insert_device(&res, name, display_name, None).await?;
 
-        let module_dir = path_to_str(&res.module_dir).map_err(|err| {
-            CommonError::new(
-                ErrorType::Internal,
-                "failed to convert module path to string",
+        Ok(res)
+    }
+
+    async fn start_device_init_from_catalog(
+        &self,
+        display_name: String,
+        module_id: i32,
+    ) -> Result<ctrl::DeviceInitData, CommonError> {
+        if let Err(err) = validation::validate_multiple_words(&display_name) {
+            return Err(CommonError::new(
+                ErrorType::InvalidInput,
+                "failed to validate display_name",
             )
-            .with_source(err)
-        })?;
+            .with_source(err));
+        }
 
-        let data_dir = path_to_str(&res.data_dir.clone()).map_err(|err| {
-            CommonError::new(ErrorType::Internal, "failed to convert data path to string")
-                .with_source(err)
-        })?;
+        let module = self.get_catalog_module(module_id).await?;
 
-        db_model::Device {
-            id: res.id.get_raw(),
-            name,
-            display_name,
-            module_dir,
-            data_dir,
-            init_state: db_model::DeviceInitState::Device,
+        let name = display_name.to_snake_case();
+
+        let res = self
+            .device_manager
+            .start_device_init_from_catalog(name.clone(), display_name.clone(), module.hash.clone())
+            .await
+            .map_err(|err| {
+                CommonError::new(ErrorType::Internal, "failed to start device init")
+                    .with_source(err)
+            })?;
+
+        self.insert_device(&res, name, display_name, Some(module.hash))
+            .await?;
+
+        Ok(res)
+    }
+
+    async fn add_catalog_module<F: tokio::io::AsyncRead + Unpin + ?Sized>(
+        &self,
+        name: String,
+        module_file: &mut F,
+    ) -> Result<ctrl::CatalogModule, CommonError> {
+        if let Err(err) = validation::validate_len(&name, BASE_NAME_MAX_LEN) {
+            return Err(
+                CommonError::new(ErrorType::InvalidInput, "failed to validate name")
+                    .with_source(err),
+            );
         }
-        .values(&mut b);
+
+        let (hash, size) = self
+            .device_manager
+            .catalog()
+            .save_module(module_file)
+            .await
+            .map_err(|err| {
+                CommonError::new(ErrorType::Internal, "failed to save module to catalog")
+                    .with_source(err)
+            })?;
+
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::CatalogModule::table_name())
+            .columns(db_model::CatalogModule::insert_columns())
+            .values(vec![name.into(), hash.clone().into(), size.into()])
+            .suffix("ON CONFLICT (hash) DO NOTHING");
 
         self.repo
             .exec(b.insert())
             .await
-            .map_err(|err| err.to_common_err("failed to save device info"))?;
+            .map_err(|err| err.to_common_err("failed to save catalog module"))?;
+
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::CatalogModule::table_name())
+            .columns(db_model::CatalogModule::columns())
+            .whereq(sq::eq("hash".into(), hash));
+
+        let res: db_model::CatalogModule = self
+            .repo
+            .get(b.select())
+            .await
+            .map_err(|err| err.to_common_err("failed to get catalog module"))?;
+
+        Ok(ctrl::CatalogModule::from(res))
+    }
+
+    async fn get_catalog_module_list(&self) -> Result<Vec<ctrl::CatalogModule>, CommonError> {
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::CatalogModule::table_name())
+            .columns(db_model::CatalogModule::columns())
+            .order("id ASC".into());
+
+        let mut res: Vec<db_model::CatalogModule> = self
+            .repo
+            .select(b.select())
+            .await
+            .map_err(|err| err.to_common_err("failed to get catalog module list"))?;
+
+        Ok(res.drain(..).map(ctrl::CatalogModule::from).collect())
+    }
+
+    async fn delete_catalog_module(&self, module_id: i32) -> Result<(), CommonError> {
+        let module = self.get_catalog_module(module_id).await?;
+
+        if is_builtin_module(&module.hash) {
+            return Err(CommonError::new(
+                ErrorType::FailedPrecondition,
+                "built-in module can't be deleted",
+            ));
+        }
+
+        let mut tx = self
+            .repo
+            .tx()
+            .await
+            .map_err(|err| err.to_common_err("failed to start transaction"))?;
+
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::Device::table_name())
+            .column("count(*)")
+            .whereq(sq::eq("module_hash".into(), module.hash.clone()));
+
+        let (device_count,): (i64,) = tx
+            .get(b.select())
+            .await
+            .map_err(|err| err.to_common_err("failed to count devices using catalog module"))?;
+
+        if device_count > 0 {
+            return Err(CommonError::new(
+                ErrorType::FailedPrecondition,
+                format!("catalog module is used by {device_count} device(s)"),
+            ));
+        }
+
+        // A device created after the count still can't be left without its module:
+        // the foreign key fails the delete with FailedPrecondition
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::CatalogModule::table_name())
+            .whereq(sq::eq("id".into(), module_id));
+
+        tx.exec(b.delete())
+            .await
+            .map_err(|err| err.to_common_err("failed to delete catalog module"))?;
+        tx.commit().await.map_err(|err| {
+            CommonError::new(ErrorType::Internal, "failed to commit transaction").with_source(err)
+        })?;
+
+        self.device_manager
+            .catalog()
+            .delete_module(&module.hash)
+            .await
+            .map_err(|err| {
+                CommonError::new(ErrorType::Internal, "failed to delete catalog module file")
+                    .with_source(err)
+            })?;
+
+        Ok(())
+    }
+
+    async fn stage_device_module<F: tokio::io::AsyncRead + Unpin + ?Sized>(
+        &self,
+        device_id: ctrl::DeviceID,
+        module_file: &mut F,
+    ) -> Result<ctrl::ModuleUpgradeData, CommonError> {
+        let res = self
+            .device_manager
+            .stage_device_module(device_id, module_file)
+            .await
+            .map_err(|err| {
+                CommonError::new(ErrorType::Internal, "failed to stage device module")
+                    .with_source(err)
+            })?;
 
         Ok(res)
     }
 
+    async fn commit_device_module(
+        &self,
+        data: &ctrl::ModuleUpgradeData,
+    ) -> Result<(), CommonError> {
+        let detached = self
+            .device_manager
+            .commit_device_module(data)
+            .await
+            .map_err(|err| {
+                CommonError::new(ErrorType::Internal, "failed to replace device module")
+                    .with_source(err)
+            })?;
+
+        if detached {
+            let mut b = sq::StatementBuilder::new();
+            b.table(db_model::Device::table_name())
+                .set("module_hash".into(), Option::<String>::None.into())
+                .whereq(sq::eq("id".into(), data.id.get_raw()));
+
+            self.repo
+                .exec(b.update())
+                .await
+                .map_err(|err| err.to_common_err("failed to detach device from catalog module"))?;
+        }
+
+        Ok(())
+    }
+
+    async fn rollback_device_module(
+        &self,
+        data: &ctrl::ModuleUpgradeData,
+    ) -> Result<(), CommonError> {
+        self.device_manager
+            .rollback_device_module(data)
+            .await
+            .map_err(|err| {
+                CommonError::new(ErrorType::Internal, "failed to restore device module")
+                    .with_source(err)
+            })?;
+
+        if let Some(ref hash) = data.prev_module_hash {
+            let mut b = sq::StatementBuilder::new();
+            b.table(db_model::Device::table_name())
+                .set("module_hash".into(), hash.clone().into())
+                .whereq(sq::eq("id".into(), data.id.get_raw()));
+
+            self.repo
+                .exec(b.update())
+                .await
+                .map_err(|err| err.to_common_err("failed to attach device to catalog module"))?;
+        }
+
+        Ok(())
+    }
+
+    async fn discard_device_module(
+        &self,
+        data: &ctrl::ModuleUpgradeData,
+    ) -> Result<(), CommonError> {
+        self.device_manager
+            .discard_device_module(data)
+            .await
+            .map_err(|err| {
+                CommonError::new(ErrorType::Internal, "failed to delete staged device module")
+                    .with_source(err)
+            })?;
+
+        Ok(())
+    }
+
     async fn device_sensor_init(
         &self,
         device_id: ctrl::DeviceID,
@@ -327,6 +688,72 @@ impl IService for Service {
         Ok(())
     }
 
+    async fn delete_device(&self, id: ctrl::DeviceID) -> Result<(), CommonError> {
+        let mut tx = self
+            .repo
+            .tx()
+            .await
+            .map_err(|err| err.to_common_err("failed to start transaction"))?;
+
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::DeviceSensor::table_name())
+            .column("*")
+            .whereq(sq::eq("device_id".into(), id.get_raw()));
+
+        let device_sensors: Vec<db_model::DeviceSensor> = tx
+            .select(b.select())
+            .await
+            .map_err(|err| err.to_common_err("failed to get device's sensors"))?;
+
+        for device_sensor in device_sensors {
+            tx.exec_raw(&format!(
+                "DROP TABLE IF EXISTS {}",
+                quote_string(&device_sensor.sensor_table_name)
+            ))
+            .await
+            .map_err(|err| err.to_common_err("failed to drop sensor table"))?;
+        }
+
+        // Rows referencing the device go first, device's users are deleted by cascade
+        for table_name in [
+            db_model::DeviceSensor::table_name(),
+            db_model::MonitorConf::table_name(),
+            db_model::DeviceCommandLog::table_name(),
+        ] {
+            let mut b = sq::StatementBuilder::new();
+            b.table(table_name)
+                .whereq(sq::eq("device_id".into(), id.get_raw()));
+
+            tx.exec(b.delete())
+                .await
+                .map_err(|err| err.to_common_err("failed to delete device's records"))?;
+        }
+
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::Device::table_name())
+            .whereq(sq::eq("id".into(), id.get_raw()));
+
+        tx.exec(b.delete())
+            .await
+            .map_err(|err| err.to_common_err("failed to delete device info from DB"))?;
+
+        self.device_manager
+            .delete_device(&id)
+            .await
+            .map_err(|err| {
+                CommonError::new(
+                    ErrorType::Internal,
+                    "failed to delete device from device manager",
+                )
+                .with_source(err)
+            })?;
+        tx.commit().await.map_err(|err| {
+            CommonError::new(ErrorType::Internal, "failed to commit transaction").with_source(err)
+        })?;
+
+        Ok(())
+    }
+
     fn get_device_ids(&self) -> Result<Vec<ctrl::DeviceID>, CommonError> {
         Ok(self.device_manager.get_device_ids())
     }
@@ -362,6 +789,63 @@ impl IService for Service {
         Ok(())
     }
 
+    async fn save_sensor_data_batch(
+        &self,
+        id: ctrl::DeviceID,
+        msgs: Vec<ctrl::SensorMsg>,
+    ) -> Result<(), CommonError> {
+        let mut tx = self
+            .repo
+            .tx()
+            .await
+            .map_err(|err| err.to_common_err("failed to start transaction"))?;
+
+        let mut msgs = msgs.into_iter().peekable();
+        while let Some(msg) = msgs.next() {
+            let table_name = quote_string(&sensor_table_name(id.get_raw(), &msg.name));
+            let cols: Vec<String> = msg.data.iter().map(|d| d.name.clone()).collect();
+            let max_rows = (MAX_INSERT_ARGS / cols.len().max(1)).max(1);
+
+            let mut b = sq::StatementBuilder::new();
+            b.table(table_name).columns(&cols);
+
+            // Rows of the same sensor with the same fields are inserted by one statement
+            let mut rows = vec![msg];
+            while rows.len() < max_rows {
+                match msgs.peek() {
+                    Some(next)
+                        if next.name == rows[0].name
+                            && next.data.iter().map(|d| &d.name).eq(cols.iter()) =>
+                    {
+                        rows.push(msgs.next().unwrap())
+                    }
+                    _ => break,
+                }
+            }
+
+            for row in rows {
+                b.values(
+                    row.data
+                        .into_iter()
+                        .map(|d| {
+                            Box::<db_model::SensorDataTypeValue>::from(d.data) as Box<dyn ArgType>
+                        })
+                        .collect(),
+                );
+            }
+
+            tx.exec(b.insert())
+                .await
+                .map_err(|err| err.to_common_err("failed to save sensor data"))?;
+        }
+
+        tx.commit().await.map_err(|err| {
+            CommonError::new(ErrorType::Internal, "failed to commit transaction").with_source(err)
+        })?;
+
+        Ok(())
+    }
+
     async fn get_sensor_data(
         &self,
         id: ctrl::DeviceID,
@@ -384,16 +868,253 @@ impl IService for Service {
             .await
             .map_err(|err| err.to_common_err("failed to get sensor data"))?;
 
+        let types = self.sensor_data_types(id, &sensor_name)?;
+
         Ok(res
             .drain(..)
-            .map(|r| ctrl::SensorDataList::from(r))
+            .map(|r| ctrl::SensorDataList::from(r.cast(&types)))
             .collect())
     }
 
+    async fn export_sensor_data(
+        &self,
+        id: ctrl::DeviceID,
+        sensor_name: String,
+        fields: Vec<String>,
+        range: ctrl::SensorDataRange,
+        batch_size: usize,
+        tx: mpsc::Sender<Vec<ctrl::SensorDataList>>,
+    ) -> Result<(), CommonError> {
+        let table_name = quote_string(&sensor_table_name(id.get_raw(), &sensor_name));
+        let types = self.sensor_data_types(id, &sensor_name)?;
+
+        let mut b = sq::StatementBuilder::new();
+        if let Some(from) = range.from {
+            b.whereq(sq::gte(range.field.clone(), from));
+        }
+        if let Some(to) = range.to {
+            b.whereq(sq::lt(range.field.clone(), to));
+        }
+        db_model::Sort {
+            field: range.field,
+            order: db_model::SortDir::ASC,
+        }
+        .apply(&mut b);
+
+        // A cursor lives only until the end of its transaction.
+        // The transaction is only read from, so it's rolled back when dropped.
+        let mut repo_tx = self
+            .repo
+            .tx()
+            .await
+            .map_err(|err| err.to_common_err("failed to begin transaction"))?;
+
+        repo_tx
+            .declare_cursor(EXPORT_CURSOR_NAME, &table_name, &fields, b)
+            .await
+            .map_err(|err| err.to_common_err("failed to declare export cursor"))?;
+
+        loop {
+            let rows: Vec<db_model::SensorDataRow> = repo_tx
+                .fetch_cursor(EXPORT_CURSOR_NAME, batch_size)
+                .await
+                .map_err(|err| err.to_common_err("failed to fetch sensor data"))?;
+
+            let done = rows.len() < batch_size;
+            if !rows.is_empty() {
+                let batch = rows
+                    .into_iter()
+                    .map(|r| ctrl::SensorDataList::from(r.cast(&types)))
+                    .collect();
+                if tx.send(batch).await.is_err() {
+                    break;
+                }
+            }
+
+            if done {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
     fn get_device_info_list(&self) -> Result<Vec<ctrl::DeviceInfo>, CommonError> {
         Ok(self.device_manager.get_device_info_list())
     }
 
+    fn get_device_full_info(
+        &self,
+        device_id: ctrl::DeviceID,
+    ) -> Result<ctrl::DeviceFullInfo, CommonError> {
+        let res = self
+            .device_manager
+            .get_device_full_info(device_id)
+            .map_err(|err| {
+                CommonError::new(ErrorType::NotFound, "failed to get device info").with_source(err)
+            })?;
+
+        Ok(res)
+    }
+
+    async fn save_device_module_info(
+        &self,
+        device_id: ctrl::DeviceID,
+        module_info: Option<ctrl::ModuleInfo>,
+    ) -> Result<(), CommonError> {
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::Device::table_name())
+            .set(
+                "module_info".into(),
+                module_info
+                    .clone()
+                    .map(|v| Json(db_model::ModuleInfo::from(v)))
+                    .into(),
+            )
+            .whereq(sq::eq("id".into(), device_id.get_raw()));
+
+        self.repo
+            .exec(b.update())
+            .await
+            .map_err(|err| err.to_common_err("failed to save device's module info"))?;
+
+        self.device_manager
+            .set_device_module_info(&device_id, module_info)
+            .map_err(|err| {
+                CommonError::new(
+                    ErrorType::Internal,
+                    "failed to set device's module info in device manager",
+                )
+                .with_source(err)
+            })?;
+
+        Ok(())
+    }
+
+    async fn save_device_watchdog_conf(
+        &self,
+        device_id: ctrl::DeviceID,
+        conf: ctrl::WatchdogConf,
+    ) -> Result<(), CommonError> {
+        if let Some(interval) = conf.expected_interval {
+            if interval <= 0 {
+                return Err(CommonError::new(
+                    ErrorType::InvalidInput,
+                    "watchdog's expected interval must be positive",
+                ));
+            }
+        }
+
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::Device::table_name())
+            .set("watchdog_interval".into(), conf.expected_interval.into())
+            .set("watchdog_restart".into(), conf.restart.into())
+            .whereq(sq::eq("id".into(), device_id.get_raw()));
+
+        self.repo
+            .exec(b.update())
+            .await
+            .map_err(|err| err.to_common_err("failed to save device's watchdog conf"))?;
+
+        self.device_manager
+            .set_device_watchdog_conf(&device_id, conf)
+            .map_err(|err| {
+                CommonError::new(
+                    ErrorType::Internal,
+                    "failed to set device's watchdog conf in device manager",
+                )
+                .with_source(err)
+            })?;
+
+        Ok(())
+    }
+
+    fn get_device_watchdog_conf(
+        &self,
+        device_id: ctrl::DeviceID,
+    ) -> Result<ctrl::WatchdogConf, CommonError> {
+        let res = self
+            .device_manager
+            .get_device_watchdog_conf(&device_id)
+            .map_err(|err| {
+                CommonError::new(ErrorType::NotFound, "failed to get device's watchdog conf")
+                    .with_source(err)
+            })?;
+
+        Ok(res)
+    }
+
+    async fn save_device_ingest_token_hash(
+        &self,
+        device_id: ctrl::DeviceID,
+        hash: Option<String>,
+    ) -> Result<(), CommonError> {
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::Device::table_name())
+            .set("ingest_token_hash".into(), hash.clone().into())
+            .whereq(sq::eq("id".into(), device_id.get_raw()));
+
+        self.repo
+            .exec(b.update())
+            .await
+            .map_err(|err| err.to_common_err("failed to save device's ingest token"))?;
+
+        self.device_manager
+            .set_device_ingest_token_hash(&device_id, hash)
+            .map_err(|err| {
+                CommonError::new(
+                    ErrorType::Internal,
+                    "failed to set device's ingest token in device manager",
+                )
+                .with_source(err)
+            })?;
+
+        Ok(())
+    }
+
+    async fn save_device_stopped(
+        &self,
+        device_id: ctrl::DeviceID,
+        stopped: bool,
+    ) -> Result<(), CommonError> {
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::Device::table_name())
+            .set("stopped".into(), stopped.into())
+            .whereq(sq::eq("id".into(), device_id.get_raw()));
+
+        self.repo
+            .exec(b.update())
+            .await
+            .map_err(|err| err.to_common_err("failed to save device's stopped flag"))?;
+
+        self.device_manager
+            .set_device_stopped(&device_id, stopped)
+            .map_err(|err| {
+                CommonError::new(
+                    ErrorType::Internal,
+                    "failed to set device's stopped flag in device manager",
+                )
+                .with_source(err)
+            })?;
+
+        Ok(())
+    }
+
+    fn get_device_ingest_token_hash(
+        &self,
+        device_id: ctrl::DeviceID,
+    ) -> Result<Option<String>, CommonError> {
+        let res = self
+            .device_manager
+            .get_device_ingest_token_hash(&device_id)
+            .map_err(|err| {
+                CommonError::new(ErrorType::NotFound, "failed to get device's ingest token")
+                    .with_source(err)
+            })?;
+
+        Ok(res)
+    }
+
     fn get_device_sensor_info(
         &self,
         device_id: ctrl::DeviceID,
@@ -449,6 +1170,460 @@ impl IService for Service {
 
         Ok(res.drain(..).map(|v| ctrl::MonitorConf::from(v)).collect())
     }
+
+    async fn save_device_command_log(
+        &self,
+        log: ctrl::DeviceCommandLog,
+    ) -> Result<(), CommonError> {
+        let log = db_model::DeviceCommandLog::from(log);
+
+        let mut b = sq::StatementBuilder::new();
+
+        b.table(db_model::DeviceCommandLog::table_name())
+            .columns(db_model::DeviceCommandLog::insert_columns());
+        log.values(&mut b);
+
+        self.repo
+            .exec(b.insert())
+            .await
+            .map_err(|err| err.to_common_err("failed to save device command log"))?;
+
+        Ok(())
+    }
+
+    async fn save_audit_record(&self, record: ctrl::NewAuditRecord) -> Result<(), CommonError> {
+        let record = db_model::NewAuditRecord::from(record);
+
+        let mut b = sq::StatementBuilder::new();
+
+        b.table(db_model::AuditRecord::table_name())
+            .columns(db_model::AuditRecord::insert_columns());
+        record.values(&mut b);
+
+        self.repo
+            .exec(b.insert())
+            .await
+            .map_err(|err| err.to_common_err("failed to save audit record"))?;
+
+        Ok(())
+    }
+
+    async fn get_audit_log(
+        &self,
+        filter: ctrl::AuditLogFilter,
+    ) -> Result<Vec<ctrl::AuditRecord>, CommonError> {
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::AuditRecord::table_name())
+            .columns(db_model::AuditRecord::columns())
+            .order("created_at DESC, id DESC".into());
+
+        if let Some(user_id) = filter.user_id {
+            b.whereq(sq::eq("user_id".into(), user_id));
+        }
+        if let Some(device_id) = filter.device_id {
+            b.whereq(sq::eq("device_id".into(), device_id));
+        }
+        if let Some(action) = filter.action {
+            b.whereq(sq::eq("action".into(), db_model::audit_action_name(action)));
+        }
+        if let Some(from) = filter.from {
+            b.whereq(sq::gte("created_at".into(), from));
+        }
+        if let Some(to) = filter.to {
+            b.whereq(sq::lt("created_at".into(), to));
+        }
+        if let Some(limit) = filter.limit {
+            b.limit(limit);
+        }
+
+        let res: Vec<db_model::AuditRecord> = self
+            .repo
+            .select(b.select())
+            .await
+            .map_err(|err| err.to_common_err("failed to get audit log"))?;
+
+        res.into_iter()
+            .map(|v| {
+                ctrl::AuditRecord::try_from(v).map_err(|err| {
+                    CommonError::new(ErrorType::Internal, "failed to decode audit record")
+                        .with_source(err)
+                })
+            })
+            .collect()
+    }
+
+    async fn create_user(&self, user: ctrl::NewUser) -> Result<ctrl::User, CommonError> {
+        if let Err(err) = validation::validate_len(&user.username, BASE_NAME_MAX_LEN) {
+            return Err(
+                CommonError::new(ErrorType::InvalidInput, "failed to validate username")
+                    .with_source(err),
+            );
+        }
+
+        let mut tx = self
+            .repo
+            .tx()
+            .await
+            .map_err(|err| err.to_common_err("failed to start transaction"))?;
+
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::User::table_name())
+            .columns(db_model::User::insert_columns())
+            .values(vec![
+                user.username.clone().into(),
+                user.password_hash.into(),
+                db_model::UserRole::from(user.role).into(),
+                user.devices.is_some().into(),
+            ]);
+
+        tx.exec(b.insert())
+            .await
+            .map_err(|err| match err.get_ctrl_type() {
+                ErrorType::AlreadyExists => CommonError::new(
+                    ErrorType::AlreadyExists,
+                    format!("user '{}' already exists", user.username),
+                ),
+                _ => err.to_common_err("failed to save user"),
+            })?;
+
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::User::table_name())
+            .column("id")
+            .whereq(sq::eq("username".into(), user.username.clone()));
+
+        let (id,): (i32,) = tx
+            .get(b.select())
+            .await
+            .map_err(|err| err.to_common_err("failed to get user"))?;
+
+        save_user_devices(&mut tx, id, user.devices).await?;
+
+        tx.commit().await.map_err(|err| {
+            CommonError::new(ErrorType::Internal, "failed to commit transaction").with_source(err)
+        })?;
+
+        self.get_user(id).await
+    }
+
+    async fn update_user(
+        &self,
+        id: i32,
+        role: ctrl::Role,
+        devices: Option<Vec<i32>>,
+    ) -> Result<ctrl::User, CommonError> {
+        let mut tx = self
+            .repo
+            .tx()
+            .await
+            .map_err(|err| err.to_common_err("failed to start transaction"))?;
+
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::User::table_name())
+            .set("role".into(), db_model::UserRole::from(role).into())
+            .set("device_scoped".into(), devices.is_some().into())
+            .whereq(sq::eq("id".into(), id));
+
+        let updated = tx
+            .exec(b.update())
+            .await
+            .map_err(|err| err.to_common_err("failed to update user"))?;
+
+        if updated == 0 {
+            return Err(CommonError::new(
+                ErrorType::NotFound,
+                format!("user with id '{id}' was not found"),
+            ));
+        }
+
+        save_user_devices(&mut tx, id, devices).await?;
+
+        tx.commit().await.map_err(|err| {
+            CommonError::new(ErrorType::Internal, "failed to commit transaction").with_source(err)
+        })?;
+
+        self.get_user(id).await
+    }
+
+    async fn get_user(&self, id: i32) -> Result<ctrl::User, CommonError> {
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::User::table_name())
+            .columns(db_model::User::columns())
+            .whereq(sq::eq("id".into(), id));
+
+        let res: db_model::User = self
+            .repo
+            .get(b.select())
+            .await
+            .map_err(|err| err.to_common_err("failed to get user"))?;
+
+        Ok(self.load_user_devices(vec![res]).await?.remove(0))
+    }
+
+    async fn get_user_by_name(&self, username: &str) -> Result<ctrl::User, CommonError> {
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::User::table_name())
+            .columns(db_model::User::columns())
+            .whereq(sq::eq("username".into(), username.to_string()));
+
+        let res: db_model::User = self
+            .repo
+            .get(b.select())
+            .await
+            .map_err(|err| err.to_common_err("failed to get user"))?;
+
+        Ok(self.load_user_devices(vec![res]).await?.remove(0))
+    }
+
+    async fn get_user_list(&self) -> Result<Vec<ctrl::User>, CommonError> {
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::User::table_name())
+            .columns(db_model::User::columns())
+            .order("id ASC".into());
+
+        let res: Vec<db_model::User> = self
+            .repo
+            .select(b.select())
+            .await
+            .map_err(|err| err.to_common_err("failed to get user list"))?;
+
+        self.load_user_devices(res).await
+    }
+
+    async fn delete_user(&self, id: i32) -> Result<(), CommonError> {
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::User::table_name())
+            .whereq(sq::eq("id".into(), id));
+
+        let deleted = self
+            .repo
+            .exec(b.delete())
+            .await
+            .map_err(|err| err.to_common_err("failed to delete user"))?;
+
+        if deleted == 0 {
+            return Err(CommonError::new(
+                ErrorType::NotFound,
+                format!("user with id '{id}' was not found"),
+            ));
+        }
+
+        Ok(())
+    }
+
+    async fn create_api_key(&self, key: ctrl::NewApiKey) -> Result<ctrl::ApiKey, CommonError> {
+        if let Err(err) = validation::validate_len(&key.name, BASE_NAME_MAX_LEN) {
+            return Err(
+                CommonError::new(ErrorType::InvalidInput, "failed to validate name")
+                    .with_source(err),
+            );
+        }
+
+        let scopes: Vec<db_model::ApiKeyScope> = key
+            .scopes
+            .into_iter()
+            .map(db_model::ApiKeyScope::from)
+            .collect();
+
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::ApiKey::table_name())
+            .columns(db_model::ApiKey::insert_columns())
+            .values(vec![
+                key.user_id.into(),
+                key.name.clone().into(),
+                key.key_hash.clone().into(),
+                Json(scopes).into(),
+            ]);
+
+        self.repo
+            .exec(b.insert())
+            .await
+            .map_err(|err| match err.get_ctrl_type() {
+                ErrorType::AlreadyExists => CommonError::new(
+                    ErrorType::AlreadyExists,
+                    format!("API key '{}' already exists", key.name),
+                ),
+                _ => err.to_common_err("failed to save API key"),
+            })?;
+
+        self.get_api_key_by_hash(&key.key_hash).await
+    }
+
+    async fn get_api_key(&self, id: i32) -> Result<ctrl::ApiKey, CommonError> {
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::ApiKey::table_name())
+            .columns(db_model::ApiKey::columns())
+            .whereq(sq::eq("id".into(), id));
+
+        let res: db_model::ApiKey = self
+            .repo
+            .get(b.select())
+            .await
+            .map_err(|err| err.to_common_err("failed to get API key"))?;
+
+        Ok(ctrl::ApiKey::from(res))
+    }
+
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<ctrl::ApiKey, CommonError> {
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::ApiKey::table_name())
+            .columns(db_model::ApiKey::columns())
+            .whereq(sq::eq("key_hash".into(), key_hash.to_string()));
+
+        let res: db_model::ApiKey = self
+            .repo
+            .get(b.select())
+            .await
+            .map_err(|err| err.to_common_err("failed to get API key"))?;
+
+        Ok(ctrl::ApiKey::from(res))
+    }
+
+    async fn get_api_key_list(
+        &self,
+        user_id: Option<i32>,
+    ) -> Result<Vec<ctrl::ApiKey>, CommonError> {
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::ApiKey::table_name())
+            .columns(db_model::ApiKey::columns())
+            .order("id ASC".into());
+
+        if let Some(user_id) = user_id {
+            b.whereq(sq::eq("user_id".into(), user_id));
+        }
+
+        let mut res: Vec<db_model::ApiKey> = self
+            .repo
+            .select(b.select())
+            .await
+            .map_err(|err| err.to_common_err("failed to get API key list"))?;
+
+        Ok(res.drain(..).map(ctrl::ApiKey::from).collect())
+    }
+
+    async fn delete_api_key(&self, id: i32) -> Result<(), CommonError> {
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::ApiKey::table_name())
+            .whereq(sq::eq("id".into(), id));
+
+        self.repo
+            .exec(b.delete())
+            .await
+            .map_err(|err| err.to_common_err("failed to delete API key"))?;
+
+        Ok(())
+    }
+
+    async fn save_api_key_last_used(
+        &self,
+        id: i32,
+        last_used_at: chrono::NaiveDateTime,
+    ) -> Result<(), CommonError> {
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::ApiKey::table_name())
+            .set("last_used_at".into(), last_used_at.into())
+            .whereq(sq::eq("id".into(), id));
+
+        self.repo
+            .exec(b.update())
+            .await
+            .map_err(|err| err.to_common_err("failed to save API key last use"))?;
+
+        Ok(())
+    }
+
+    async fn save_session(&self, session: ctrl::Session) -> Result<(), CommonError> {
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::Session::table_name())
+            .columns(db_model::Session::columns())
+            .values(vec![
+                session.token_hash.into(),
+                session.user_id.into(),
+                session.expires_at.into(),
+            ]);
+
+        self.repo
+            .exec(b.insert())
+            .await
+            .map_err(|err| err.to_common_err("failed to save session"))?;
+
+        Ok(())
+    }
+
+    async fn get_session(&self, token_hash: &str) -> Result<ctrl::Session, CommonError> {
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::Session::table_name())
+            .columns(db_model::Session::columns())
+            .whereq(sq::eq("token_hash".into(), token_hash.to_string()))
+            .whereq(sq::gt("expires_at".into(), chrono::Utc::now().naive_utc()));
+
+        let res: db_model::Session = self
+            .repo
+            .get(b.select())
+            .await
+            .map_err(|err| err.to_common_err("failed to get session"))?;
+
+        Ok(ctrl::Session::from(res))
+    }
+
+    async fn delete_session(&self, token_hash: &str) -> Result<(), CommonError> {
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::Session::table_name())
+            .whereq(sq::eq("token_hash".into(), token_hash.to_string()));
+
+        self.repo
+            .exec(b.delete())
+            .await
+            .map_err(|err| err.to_common_err("failed to delete session"))?;
+
+        Ok(())
+    }
+
+    async fn delete_expired_sessions(&self) -> Result<(), CommonError> {
+        let mut b = sq::StatementBuilder::new();
+        b.table(db_model::Session::table_name())
+            .whereq(sq::lte("expires_at".into(), chrono::Utc::now().naive_utc()));
+
+        self.repo
+            .exec(b.delete())
+            .await
+            .map_err(|err| err.to_common_err("failed to delete expired sessions"))?;
+
+        Ok(())
+    }
+}
+
+/// `save_user_devices` replaces devices which the user has access to.
+/// `None` removes all of them, as the user has access to all devices.
+async fn save_user_devices(
+    tx: &mut repo::Transaction<'_>,
+    user_id: i32,
+    devices: Option<Vec<i32>>,
+) -> Result<(), CommonError> {
+    let mut b = sq::StatementBuilder::new();
+    b.table(db_model::UserDevice::table_name())
+        .whereq(sq::eq("user_id".into(), user_id));
+
+    tx.exec(b.delete())
+        .await
+        .map_err(|err| err.to_common_err("failed to delete user devices"))?;
+
+    let devices = devices.unwrap_or_default();
+    if devices.is_empty() {
+        return Ok(());
+    }
+
+    let mut b = sq::StatementBuilder::new();
+    b.table(db_model::UserDevice::table_name())
+        .columns(db_model::UserDevice::columns());
+    for device_id in devices {
+        b.values(vec![user_id.into(), device_id.into()]);
+    }
+
+    tx.exec(b.insert())
+        .await
+        .map_err(|err| err.to_common_err("failed to save user devices"))?;
+
+    Ok(())
 }
 
 fn path_to_str<P: AsRef<Path>>(path: P) -> Result<String, InternalServiceError> {
@@ -0,0 +1,183 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Deserialize;
+
+use crate::controller::error::{CommonError, ErrorType};
+
+/// `SinksConf` is the configuration file of sinks
+#[derive(Deserialize, Default)]
+pub struct SinksConf {
+    #[serde(default)]
+    pub sinks: Vec<SinkConf>,
+}
+
+impl SinksConf {
+    pub fn validate(&self) -> Result<(), CommonError> {
+        let mut names = HashSet::new();
+
+        for sink in self.sinks.iter() {
+            let invalid = |msg: &str| {
+                CommonError::new(
+                    ErrorType::InvalidInput,
+                    format!("sink '{}': {msg}", sink.name),
+                )
+            };
+
+            if sink.name.is_empty() {
+                return Err(CommonError::new(
+                    ErrorType::InvalidInput,
+                    "name of a sink must not be empty",
+                ));
+            }
+            if !names.insert(sink.name.as_str()) {
+                return Err(invalid("name is not unique"));
+            }
+            if sink.buffer.capacity == 0 || sink.buffer.batch_size == 0 {
+                return Err(invalid("buffer capacity and batch size must be positive"));
+            }
+            if sink.buffer.flush_interval_ms == 0 {
+                return Err(invalid("flush interval must be positive"));
+            }
+            if sink.retry.max_attempts == 0 {
+                return Err(invalid("max attempts must be positive"));
+            }
+
+            match &sink.destination {
+                DestinationConf::Influxdb(InfluxConf { url, .. })
+                | DestinationConf::PrometheusRemoteWrite(HttpConf { url, .. })
+                | DestinationConf::Webhook(HttpConf { url, .. }) => {
+                    reqwest::Url::parse(url).map_err(|_| invalid("invalid url"))?;
+                }
+                DestinationConf::Mqtt(conf) => {
+                    if conf.host.is_empty() {
+                        return Err(invalid("host must not be empty"));
+                    }
+                    if conf.qos > 2 {
+                        return Err(invalid("qos must be 0, 1 or 2"));
+                    }
+                    if conf.topic.contains(['+', '#']) {
+                        return Err(invalid("topic must not contain wildcards"));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// `SinkConf` describes a single destination and the data sent to it
+#[derive(Deserialize)]
+pub struct SinkConf {
+    pub name: String,
+    /// Sensors forwarded to the sink. All sensors are forwarded if there are no routes
+    #[serde(default)]
+    pub routes: Vec<Route>,
+    #[serde(default)]
+    pub buffer: BufferConf,
+    #[serde(default)]
+    pub retry: RetryConf,
+    #[serde(flatten)]
+    pub destination: DestinationConf,
+}
+
+/// `Route` matches sensors of a device. An absent device or sensor matches any
+#[derive(Deserialize, Default, Clone)]
+pub struct Route {
+    pub device_id: Option<i32>,
+    pub sensor: Option<String>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct BufferConf {
+    /// Number of points kept while the destination is unavailable.
+    /// New points are dropped when the buffer is full
+    pub capacity: usize,
+    /// Max number of points sent at once
+    pub batch_size: usize,
+    /// How long points are collected before an incomplete batch is sent
+    pub flush_interval_ms: u64,
+}
+
+impl Default for BufferConf {
+    fn default() -> Self {
+        Self {
+            capacity: 10000,
+            batch_size: 500,
+            flush_interval_ms: 1000,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct RetryConf {
+    /// Number of attempts to send a batch before it is dropped
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RetryConf {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 30000,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DestinationConf {
+    Influxdb(InfluxConf),
+    PrometheusRemoteWrite(HttpConf),
+    Mqtt(MqttConf),
+    Webhook(HttpConf),
+}
+
+/// `InfluxConf` is the configuration of InfluxDB write API.
+/// The url includes the database or the bucket, e.g. `http://localhost:8086/api/v2/write?bucket=monisens`
+#[derive(Deserialize)]
+pub struct InfluxConf {
+    pub url: String,
+    pub token: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct HttpConf {
+    pub url: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+pub struct MqttConf {
+    pub host: String,
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+    pub client_id: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Topic of published points. `{device_id}` and `{sensor}` are replaced
+    #[serde(default = "default_mqtt_topic")]
+    pub topic: String,
+    #[serde(default = "default_mqtt_qos")]
+    pub qos: u8,
+    #[serde(default)]
+    pub retain: bool,
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+fn default_mqtt_topic() -> String {
+    "monisens/{device_id}/{sensor}".to_string()
+}
+
+fn default_mqtt_qos() -> u8 {
+    1
+}
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+use crate::controller::error::{CommonError, ErrorType};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Max length of a response body included in an error
+const MAX_ERROR_BODY_LEN: usize = 256;
+
+pub fn client() -> Result<reqwest::Client, CommonError> {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|err| {
+            CommonError::new(ErrorType::Internal, "failed to create HTTP client").with_source(err)
+        })
+}
+
+pub fn headers(headers: &HashMap<String, String>) -> Result<HeaderMap, CommonError> {
+    let mut res = HeaderMap::with_capacity(headers.len());
+
+    for (name, value) in headers {
+        let invalid =
+            || CommonError::new(ErrorType::InvalidInput, format!("invalid header '{name}'"));
+
+        res.insert(
+            HeaderName::try_from(name).map_err(|_| invalid())?,
+            HeaderValue::try_from(value).map_err(|_| invalid())?,
+        );
+    }
+
+    Ok(res)
+}
+
+/// `send` sends a request and checks the status of the response.
+/// Network errors, 5xx and 429 responses are temporary, other 4xx responses are
+/// [`ErrorType::InvalidInput`], so the batch isn't sent again.
+pub async fn send(req: reqwest::RequestBuilder) -> Result<(), CommonError> {
+    let resp = req.send().await.map_err(|err| {
+        let typ = if err.is_timeout() {
+            ErrorType::Timeout
+        } else {
+            ErrorType::IO
+        };
+        CommonError::new(typ, "failed to send request").with_source(err)
+    })?;
+
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(());
+    }
+
+    let typ = if status.is_client_error() && status != reqwest::StatusCode::TOO_MANY_REQUESTS {
+        ErrorType::InvalidInput
+    } else {
+        ErrorType::IO
+    };
+
+    let mut body = resp.text().await.unwrap_or_default();
+    if body.len() > MAX_ERROR_BODY_LEN {
+        let mut end = MAX_ERROR_BODY_LEN;
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        body.truncate(end);
+    }
+
+    Err(CommonError::new(
+        typ,
+        format!("destination responded with {status}: {}", body.trim()),
+    ))
+}
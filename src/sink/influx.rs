@@ -0,0 +1,120 @@
+use std::fmt::Write;
+
+use crate::controller;
+use crate::controller::error::CommonError;
+
+use super::http;
+use super::{format_time, InfluxConf, Point};
+
+/// `InfluxSink` writes points to InfluxDB in line protocol. The measurement is the sensor's name,
+/// the device is a `device_id` tag and the rest of the message are fields.
+pub struct InfluxSink {
+    client: reqwest::Client,
+    url: String,
+    token: Option<String>,
+}
+
+impl InfluxSink {
+    pub fn new(conf: &InfluxConf) -> Result<Self, CommonError> {
+        Ok(Self {
+            client: http::client()?,
+            url: conf.url.clone(),
+            token: conf.token.clone(),
+        })
+    }
+
+    pub async fn send(&mut self, points: &[Point]) -> Result<(), CommonError> {
+        let body = encode(points);
+        if body.is_empty() {
+            return Ok(());
+        }
+
+        let mut req = self
+            .client
+            .post(&self.url)
+            .query(&[("precision", "ns")])
+            .header(reqwest::header::CONTENT_TYPE, "text/plain; charset=utf-8")
+            .body(body);
+        if let Some(token) = self.token.as_ref() {
+            req = req.header(reqwest::header::AUTHORIZATION, format!("Token {token}"));
+        }
+
+        http::send(req).await
+    }
+}
+
+/// `encode` encodes points in line protocol with nanosecond timestamps.
+/// Floats which aren't finite can't be written and are skipped, as well as points without fields.
+pub fn encode(points: &[Point]) -> String {
+    let mut res = String::new();
+
+    for point in points {
+        let mut fields = String::new();
+        for d in point.data.iter() {
+            let Some(value) = field_value(&d.data) else {
+                continue;
+            };
+
+            if !fields.is_empty() {
+                fields.push(',');
+            }
+            escape(&mut fields, &d.name, &[',', '=', ' ']);
+            fields.push('=');
+            fields.push_str(&value);
+        }
+
+        if fields.is_empty() {
+            continue;
+        }
+
+        escape(&mut res, &point.sensor, &[',', ' ']);
+        let _ = write!(res, ",device_id={} {fields}", point.device_id);
+        if let Some(ns) = point.time.and_utc().timestamp_nanos_opt() {
+            let _ = write!(res, " {ns}");
+        }
+        res.push('\n');
+    }
+
+    res
+}
+
+fn field_value(v: &controller::SensorDataTypeValue) -> Option<String> {
+    use controller::SensorDataTypeValue;
+
+    let res = match v {
+        SensorDataTypeValue::Int16(v) => format!("{v}i"),
+        SensorDataTypeValue::Int32(v) => format!("{v}i"),
+        SensorDataTypeValue::Int64(v) => format!("{v}i"),
+        SensorDataTypeValue::Float32(v) if v.is_finite() => format!("{v:?}"),
+        SensorDataTypeValue::Float64(v) if v.is_finite() => format!("{v:?}"),
+        SensorDataTypeValue::Float32(_) | SensorDataTypeValue::Float64(_) => return None,
+        SensorDataTypeValue::Timestamp(v) => string_value(&format_time(v)),
+        SensorDataTypeValue::String(v) | SensorDataTypeValue::JSON(v) => string_value(v),
+    };
+
+    Some(res)
+}
+
+fn string_value(v: &str) -> String {
+    let mut res = String::with_capacity(v.len() + 2);
+    res.push('"');
+    escape(&mut res, v, &['"']);
+    res.push('"');
+    res
+}
+
+/// `escape` writes `v` escaping backslashes and the given chars. Line breaks are replaced with
+/// spaces, because line protocol can't contain them.
+fn escape(out: &mut String, v: &str, chars: &[char]) {
+    for c in v.chars() {
+        match c {
+            '\n' | '\r' => out.push(' '),
+            '\\' => out.push_str("\\\\"),
+            c if chars.contains(&c) => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+}
@@ -0,0 +1,249 @@
+mod conf;
+mod http;
+pub mod influx;
+pub mod mqtt;
+pub mod prometheus;
+mod test;
+pub mod webhook;
+mod worker;
+
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+
+use crate::controller;
+use crate::controller::error::{CommonError, ErrorType};
+use crate::controller::interface::sink::ISink;
+use crate::logger;
+use crate::{kv_any, kvs};
+
+pub use self::conf::*;
+use self::influx::InfluxSink;
+use self::mqtt::MqttSink;
+use self::prometheus::RemoteWriteSink;
+use self::webhook::WebhookSink;
+
+/// Field of a sensor message which is taken as the time of its point
+pub const TIME_FIELD: &str = "timestamp";
+
+/// `Point` is a saved message of device's sensor on its way to a destination
+#[derive(Clone, Debug)]
+pub struct Point {
+    pub device_id: i32,
+    pub sensor: String,
+    /// Value of the message's `timestamp` field or the time it was forwarded at
+    pub time: chrono::NaiveDateTime,
+    /// Fields of the message except the time
+    pub data: Vec<controller::SensorData>,
+}
+
+impl Point {
+    pub fn new(device_id: controller::DeviceID, msg: &controller::SensorMsg) -> Self {
+        let mut time = None;
+        let mut data = Vec::with_capacity(msg.data.len());
+
+        for d in msg.data.iter() {
+            match d.data {
+                controller::SensorDataTypeValue::Timestamp(v) if d.name == TIME_FIELD => {
+                    time = Some(v)
+                }
+                _ => data.push(d.clone()),
+            }
+        }
+
+        Self {
+            device_id: device_id.get_raw(),
+            sensor: msg.name.clone(),
+            time: time.unwrap_or_else(|| chrono::Utc::now().naive_utc()),
+            data,
+        }
+    }
+
+    /// `to_json` returns the point as a JSON object with UTC timestamps
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "device_id": self.device_id,
+            "sensor": self.sensor,
+            "timestamp": format_time(&self.time),
+            "data": self
+                .data
+                .iter()
+                .map(|d| (d.name.clone(), d.data.to_json()))
+                .collect::<serde_json::Map<_, _>>(),
+        })
+    }
+}
+
+pub fn format_time(v: &chrono::NaiveDateTime) -> String {
+    v.and_utc()
+        .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true)
+}
+
+/// `Destination` is an external store where points are sent to
+pub enum Destination {
+    Influx(InfluxSink),
+    RemoteWrite(RemoteWriteSink),
+    Mqtt(MqttSink),
+    Webhook(WebhookSink),
+}
+
+impl Destination {
+    pub fn new(conf: &DestinationConf, sink_name: &str) -> Result<Self, CommonError> {
+        Ok(match conf {
+            DestinationConf::Influxdb(conf) => Destination::Influx(InfluxSink::new(conf)?),
+            DestinationConf::PrometheusRemoteWrite(conf) => {
+                Destination::RemoteWrite(RemoteWriteSink::new(conf)?)
+            }
+            DestinationConf::Mqtt(conf) => Destination::Mqtt(MqttSink::new(conf, sink_name)?),
+            DestinationConf::Webhook(conf) => Destination::Webhook(WebhookSink::new(conf)?),
+        })
+    }
+
+    /// `send` sends a batch of points. Errors of type other than [`ErrorType::InvalidInput`]
+    /// are temporary, so sending may be retried.
+    pub async fn send(&mut self, points: &[Point]) -> Result<(), CommonError> {
+        match self {
+            Destination::Influx(s) => s.send(points).await,
+            Destination::RemoteWrite(s) => s.send(points).await,
+            Destination::Mqtt(s) => s.send(points).await,
+            Destination::Webhook(s) => s.send(points).await,
+        }
+    }
+}
+
+/// `Sinks` routes saved sensor data to the configured sinks.
+/// Every sink has its own buffer and a task which sends batches of points to the destination.
+#[derive(Clone, Default)]
+pub struct Sinks {
+    sinks: Arc<Vec<SinkHandle>>,
+    /// Set to `true` when the sinks are closed
+    closing: Arc<Option<watch::Sender<bool>>>,
+}
+
+struct SinkHandle {
+    name: String,
+    routes: Vec<Route>,
+    tx: mpsc::Sender<Point>,
+    /// Number of points dropped because the buffer was full
+    dropped: AtomicU64,
+    /// `None` once the worker is waited for on close
+    worker: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Sinks {
+    /// `new` starts the sinks on the current tokio runtime
+    pub fn new(conf: SinksConf) -> Result<Self, CommonError> {
+        conf.validate()?;
+
+        let (closing_tx, closing_rx) = watch::channel(false);
+        let mut sinks = Vec::with_capacity(conf.sinks.len());
+        for sink in conf.sinks {
+            let dest = Destination::new(&sink.destination, &sink.name)?;
+            let (tx, rx) = mpsc::channel(sink.buffer.capacity);
+
+            let worker = tokio::spawn(worker::run(
+                sink.name.clone(),
+                dest,
+                sink.buffer.clone(),
+                sink.retry.clone(),
+                rx,
+                closing_rx.clone(),
+            ));
+
+            sinks.push(SinkHandle {
+                name: sink.name,
+                routes: sink.routes,
+                tx,
+                dropped: AtomicU64::new(0),
+                worker: Mutex::new(Some(worker)),
+            });
+        }
+
+        Ok(Self {
+            sinks: Arc::new(sinks),
+            closing: Arc::new(Some(closing_tx)),
+        })
+    }
+
+    /// `from_file` reads the configuration of sinks from a JSON file and starts them
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, CommonError> {
+        let data = std::fs::read(path).map_err(|err| {
+            CommonError::new(ErrorType::IO, "failed to read sinks configuration").with_source(err)
+        })?;
+        let conf: SinksConf = serde_json::from_slice(&data).map_err(|err| {
+            CommonError::new(
+                ErrorType::InvalidInput,
+                "failed to parse sinks configuration",
+            )
+            .with_source(err)
+        })?;
+
+        Self::new(conf)
+    }
+}
+
+impl ISink for Sinks {
+    fn forward(&self, device_id: controller::DeviceID, msg: &controller::SensorMsg) {
+        let mut point = None;
+
+        for sink in self.sinks.iter() {
+            if !routes_match(&sink.routes, device_id.get_raw(), &msg.name) {
+                continue;
+            }
+
+            let point = point.get_or_insert_with(|| Point::new(device_id, msg));
+            if let Err(mpsc::error::TrySendError::Full(_)) = sink.tx.try_send(point.clone()) {
+                let dropped = sink.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+
+                // Every point isn't logged not to flood the log while the destination is down
+                if dropped % DROPPED_LOG_INTERVAL == 1 {
+                    logger::warn_kv(
+                        "sink buffer is full, points are dropped",
+                        kvs!("sink" => kv_any!(&sink.name), "dropped" => kv_any!(dropped)),
+                    );
+                }
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    async fn close(&self, timeout: Duration) {
+        if let Some(ref closing) = *self.closing {
+            closing.send_replace(true);
+        }
+
+        for sink in self.sinks.iter() {
+            let Some(worker) = sink.worker.lock().unwrap().take() else {
+                continue;
+            };
+
+            match tokio::time::timeout(timeout, worker).await {
+                Ok(_) => logger::info_kv("sink flushed", kvs!("sink" => kv_any!(&sink.name))),
+                Err(_) => logger::warn_kv(
+                    "sink wasn't flushed in time, its buffered points are lost",
+                    kvs!("sink" => kv_any!(&sink.name)),
+                ),
+            }
+        }
+    }
+}
+
+/// Only every `DROPPED_LOG_INTERVAL`-th dropped point is logged
+const DROPPED_LOG_INTERVAL: u64 = 1000;
+
+/// `routes_match` checks whether the sensor of the device is routed to a sink.
+/// A sink without routes gets data of all sensors
+pub fn routes_match(routes: &[Route], device_id: i32, sensor: &str) -> bool {
+    routes.is_empty()
+        || routes.iter().any(|r| {
+            r.device_id.is_none_or(|id| id == device_id)
+                && r.sensor.as_ref().is_none_or(|s| s == sensor)
+        })
+}
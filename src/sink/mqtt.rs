@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use rumqttc::{AsyncClient, ConnectionError, EventLoop, MqttOptions, QoS};
+
+use crate::controller::error::{CommonError, ErrorType};
+use crate::logger;
+use crate::{kv_any, kvs};
+
+use super::{MqttConf, Point};
+
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+/// How long a point waits to be queued while the broker is unavailable
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(10);
+/// Capacity of the client's queue of requests
+const REQUESTS_CAP: usize = 100;
+
+/// `MqttSink` publishes every point as a JSON object to a topic of its device and sensor.
+/// The connection is polled by a separate task, which reconnects after failures.
+pub struct MqttSink {
+    client: AsyncClient,
+    topic: String,
+    qos: QoS,
+    retain: bool,
+}
+
+impl MqttSink {
+    pub fn new(conf: &MqttConf, sink_name: &str) -> Result<Self, CommonError> {
+        let client_id = conf
+            .client_id
+            .clone()
+            .unwrap_or_else(|| format!("monisens-sink-{sink_name}"));
+
+        let mut opts = MqttOptions::new(client_id, &conf.host, conf.port);
+        opts.set_keep_alive(KEEP_ALIVE);
+        if let Some(username) = &conf.username {
+            opts.set_credentials(username, conf.password.clone().unwrap_or_default());
+        }
+
+        let qos = match conf.qos {
+            0 => QoS::AtMostOnce,
+            1 => QoS::AtLeastOnce,
+            2 => QoS::ExactlyOnce,
+            _ => {
+                return Err(CommonError::new(
+                    ErrorType::InvalidInput,
+                    "qos must be 0, 1 or 2",
+                ))
+            }
+        };
+
+        let (client, eventloop) = AsyncClient::new(opts, REQUESTS_CAP);
+        tokio::spawn(poll(sink_name.to_string(), eventloop));
+
+        Ok(Self {
+            client,
+            topic: conf.topic.clone(),
+            qos,
+            retain: conf.retain,
+        })
+    }
+
+    /// `send` queues points for publishing. Points of a batch which were queued before
+    /// an error are published again on retry.
+    pub async fn send(&mut self, points: &[Point]) -> Result<(), CommonError> {
+        for point in points {
+            let payload = point.to_json().to_string();
+            let publish =
+                self.client
+                    .publish(topic(&self.topic, point), self.qos, self.retain, payload);
+
+            match tokio::time::timeout(PUBLISH_TIMEOUT, publish).await {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => {
+                    return Err(
+                        CommonError::new(ErrorType::IO, "failed to publish").with_source(err)
+                    )
+                }
+                Err(_) => {
+                    return Err(CommonError::new(
+                        ErrorType::Timeout,
+                        "MQTT broker is unavailable",
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// `topic` replaces `{device_id}` and `{sensor}` in the template
+pub fn topic(template: &str, point: &Point) -> String {
+    template
+        .replace("{device_id}", &point.device_id.to_string())
+        .replace("{sensor}", &point.sensor)
+}
+
+/// `poll` drives the connection until the sink's client is dropped
+async fn poll(name: String, mut eventloop: EventLoop) {
+    let mut connected = true;
+
+    loop {
+        match eventloop.poll().await {
+            Ok(_) => connected = true,
+            Err(ConnectionError::RequestsDone) => break,
+            Err(err) => {
+                // Only the first error is logged until the connection is restored
+                if connected {
+                    logger::warn_kv(
+                        "connection of sink to MQTT broker failed",
+                        kvs!("sink" => kv_any!(&name), "error" => kv_any!(err.to_string())),
+                    );
+                }
+                connected = false;
+
+                tokio::time::sleep(RECONNECT_INTERVAL).await;
+            }
+        }
+    }
+}
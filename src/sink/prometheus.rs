@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_ENCODING, CONTENT_TYPE};
+
+use crate::controller;
+use crate::controller::error::{CommonError, ErrorType};
+
+use super::http;
+use super::{HttpConf, Point};
+
+const METRIC_NAME_LABEL: &str = "__name__";
+const DEVICE_ID_LABEL: &str = "device_id";
+
+/// `RemoteWriteSink` sends points with Prometheus remote write protocol 1.0.
+/// Every numeric field becomes a series `<sensor>_<field>` with `device_id` label,
+/// other fields are skipped.
+pub struct RemoteWriteSink {
+    client: reqwest::Client,
+    url: String,
+    headers: HeaderMap,
+}
+
+impl RemoteWriteSink {
+    pub fn new(conf: &HttpConf) -> Result<Self, CommonError> {
+        let mut headers = http::headers(&conf.headers)?;
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("snappy"));
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/x-protobuf"),
+        );
+        headers.insert(
+            "X-Prometheus-Remote-Write-Version",
+            HeaderValue::from_static("0.1.0"),
+        );
+
+        Ok(Self {
+            client: http::client()?,
+            url: conf.url.clone(),
+            headers,
+        })
+    }
+
+    pub async fn send(&mut self, points: &[Point]) -> Result<(), CommonError> {
+        let req = encode(points);
+        if req.is_empty() {
+            return Ok(());
+        }
+
+        let body = snap::raw::Encoder::new()
+            .compress_vec(&req)
+            .map_err(|err| {
+                CommonError::new(ErrorType::Internal, "failed to compress write request")
+                    .with_source(err)
+            })?;
+
+        http::send(
+            self.client
+                .post(&self.url)
+                .headers(self.headers.clone())
+                .body(body),
+        )
+        .await
+    }
+}
+
+/// Labels of a series sorted by name
+type Labels<'a> = [(&'a str, String); 2];
+
+/// `encode` encodes points to a protobuf `WriteRequest`. Samples of a series are sorted by time.
+/// An empty vector is returned if there are no samples.
+pub fn encode(points: &[Point]) -> Vec<u8> {
+    // Samples of series as (timestamp in ms, value)
+    let mut series: BTreeMap<Labels, Vec<(i64, f64)>> = BTreeMap::new();
+
+    for point in points {
+        let timestamp = point.time.and_utc().timestamp_millis();
+
+        for d in point.data.iter() {
+            let Some(value) = sample_value(&d.data) else {
+                continue;
+            };
+
+            let labels = [
+                (
+                    METRIC_NAME_LABEL,
+                    metric_name(&format!("{}_{}", point.sensor, d.name)),
+                ),
+                (DEVICE_ID_LABEL, point.device_id.to_string()),
+            ];
+            series.entry(labels).or_default().push((timestamp, value));
+        }
+    }
+
+    let mut res = Vec::new();
+    for (labels, mut samples) in series {
+        samples.sort_by_key(|s| s.0);
+
+        let mut ts = Vec::new();
+        for (name, value) in labels.iter() {
+            let mut label = Vec::new();
+            write_bytes(&mut label, 1, name.as_bytes());
+            write_bytes(&mut label, 2, value.as_bytes());
+            write_bytes(&mut ts, 1, &label);
+        }
+        for (timestamp, value) in samples {
+            let mut sample = Vec::new();
+            write_key(&mut sample, 1, WIRE_FIXED64);
+            sample.extend_from_slice(&value.to_le_bytes());
+            write_key(&mut sample, 2, WIRE_VARINT);
+            write_varint(&mut sample, timestamp as u64);
+            write_bytes(&mut ts, 2, &sample);
+        }
+
+        write_bytes(&mut res, 1, &ts);
+    }
+
+    res
+}
+
+fn sample_value(v: &controller::SensorDataTypeValue) -> Option<f64> {
+    use controller::SensorDataTypeValue;
+
+    match v {
+        SensorDataTypeValue::Int16(v) => Some(*v as f64),
+        SensorDataTypeValue::Int32(v) => Some(*v as f64),
+        SensorDataTypeValue::Int64(v) => Some(*v as f64),
+        SensorDataTypeValue::Float32(v) => Some(*v as f64),
+        SensorDataTypeValue::Float64(v) => Some(*v),
+        _ => None,
+    }
+}
+
+/// `metric_name` replaces chars which aren't allowed in a metric name with `_`
+fn metric_name(v: &str) -> String {
+    v.chars()
+        .enumerate()
+        .map(|(i, c)| match c {
+            'a'..='z' | 'A'..='Z' | '_' | ':' => c,
+            '0'..='9' if i > 0 => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+const WIRE_VARINT: u8 = 0;
+const WIRE_FIXED64: u8 = 1;
+const WIRE_LEN: u8 = 2;
+
+fn write_key(out: &mut Vec<u8>, field: u8, wire_type: u8) {
+    out.push(field << 3 | wire_type);
+}
+
+fn write_varint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn write_bytes(out: &mut Vec<u8>, field: u8, v: &[u8]) {
+    write_key(out, field, WIRE_LEN);
+    write_varint(out, v.len() as u64);
+    out.extend_from_slice(v);
+}
@@ -0,0 +1,361 @@
+#[cfg(test)]
+use super::{influx, mqtt, prometheus, routes_match, Point, Route, Sinks, SinksConf};
+#[cfg(test)]
+use crate::controller::{
+    interface::sink::ISink, DeviceID, SensorData, SensorDataTypeValue, SensorMsg,
+};
+#[cfg(test)]
+use std::time::Duration;
+#[cfg(test)]
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+#[cfg(test)]
+use tokio::net::TcpListener;
+#[cfg(test)]
+use tokio::sync::mpsc;
+
+#[cfg(test)]
+fn test_msg(temperature: f64) -> SensorMsg {
+    SensorMsg {
+        name: "room".to_string(),
+        data: vec![
+            SensorData {
+                name: "timestamp".to_string(),
+                data: SensorDataTypeValue::Timestamp(
+                    chrono::DateTime::from_timestamp(1_700_000_000, 500_000_000)
+                        .unwrap()
+                        .naive_utc(),
+                ),
+            },
+            SensorData {
+                name: "temperature".to_string(),
+                data: SensorDataTypeValue::Float64(temperature),
+            },
+            SensorData {
+                name: "count".to_string(),
+                data: SensorDataTypeValue::Int32(3),
+            },
+            SensorData {
+                name: "state".to_string(),
+                data: SensorDataTypeValue::String("on \"x\"".to_string()),
+            },
+        ],
+    }
+}
+
+/// `Request` is a request captured by [`http_stand_in`]
+#[cfg(test)]
+struct Request {
+    head: String,
+    body: Vec<u8>,
+}
+
+/// `http_stand_in` starts an HTTP server which responds with the given statuses in turn
+/// (the last one repeats) and sends received requests to the channel
+#[cfg(test)]
+async fn http_stand_in(statuses: Vec<u16>) -> (String, mpsc::UnboundedReceiver<Request>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        for i in 0.. {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+
+            let mut head = String::new();
+            loop {
+                let mut line = String::new();
+                stream.read_line(&mut line).await.unwrap();
+                if line == "\r\n" || line.is_empty() {
+                    break;
+                }
+                head.push_str(&line.to_lowercase());
+            }
+
+            let len = head
+                .lines()
+                .find_map(|l| l.strip_prefix("content-length: "))
+                .map_or(0, |v| v.trim().parse().unwrap());
+            let mut body = vec![0; len];
+            stream.read_exact(&mut body).await.unwrap();
+
+            let status = statuses[i.min(statuses.len() - 1)];
+            let resp =
+                format!("HTTP/1.1 {status} X\r\ncontent-length: 0\r\nconnection: close\r\n\r\n");
+            stream.get_mut().write_all(resp.as_bytes()).await.unwrap();
+
+            let _ = tx.send(Request { head, body });
+        }
+    });
+
+    (format!("http://{addr}"), rx)
+}
+
+#[cfg(test)]
+async fn recv<T>(rx: &mut mpsc::UnboundedReceiver<T>) -> T {
+    tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("nothing received")
+        .unwrap()
+}
+
+#[cfg(test)]
+fn start_sinks(conf: serde_json::Value) -> Sinks {
+    let conf: SinksConf = serde_json::from_value(conf).unwrap();
+    Sinks::new(conf).unwrap()
+}
+
+// Test that messages are routed to sinks by device and sensor
+#[test]
+fn routes_matching() {
+    let route = |device_id, sensor: Option<&str>| Route {
+        device_id,
+        sensor: sensor.map(str::to_string),
+    };
+
+    assert!(routes_match(&[], 1, "room"));
+
+    let routes = [route(Some(1), None), route(None, Some("room"))];
+    assert!(routes_match(&routes, 1, "hall"));
+    assert!(routes_match(&routes, 2, "room"));
+    assert!(!routes_match(&routes, 2, "hall"));
+
+    let routes = [route(Some(1), Some("room"))];
+    assert!(routes_match(&routes, 1, "room"));
+    assert!(!routes_match(&routes, 1, "hall"));
+    assert!(!routes_match(&routes, 2, "room"));
+}
+
+// Test that invalid configurations are rejected
+#[test]
+fn sinks_conf_validation() {
+    let validate = |conf: serde_json::Value| {
+        serde_json::from_value::<SinksConf>(conf)
+            .unwrap()
+            .validate()
+    };
+
+    let webhook = |name: &str| serde_json::json!({"name": name, "type": "webhook", "url": "http://localhost/"});
+    assert!(validate(serde_json::json!({"sinks": [webhook("a"), webhook("b")]})).is_ok());
+    assert!(validate(serde_json::json!({"sinks": [webhook("a"), webhook("a")]})).is_err());
+    assert!(validate(serde_json::json!({"sinks": [
+        {"name": "a", "type": "webhook", "url": "localhost"}
+    ]}))
+    .is_err());
+    assert!(validate(serde_json::json!({"sinks": [
+        {"name": "a", "type": "mqtt", "host": "localhost", "qos": 3}
+    ]}))
+    .is_err());
+    assert!(validate(serde_json::json!({"sinks": [
+        {"name": "a", "type": "mqtt", "host": "localhost", "topic": "data/#"}
+    ]}))
+    .is_err());
+    assert!(validate(serde_json::json!({"sinks": [
+        {"name": "a", "type": "influxdb", "url": "http://localhost/", "buffer": {"batch_size": 0}}
+    ]}))
+    .is_err());
+}
+
+// Test that points are written in line protocol with escaping
+#[test]
+fn influx_encoding() {
+    let mut nan = Point::new(DeviceID::new(2), &test_msg(f64::NAN));
+    nan.data.truncate(1);
+
+    let body = influx::encode(&[Point::new(DeviceID::new(1), &test_msg(21.5)), nan]);
+
+    assert_eq!(
+        body,
+        "room,device_id=1 temperature=21.5,count=3i,state=\"on \\\"x\\\"\" 1700000000500000000\n"
+    );
+}
+
+// Test that numeric fields become series of a remote write request
+#[test]
+fn remote_write_encoding() {
+    let mut point = Point::new(DeviceID::new(1), &test_msg(21.5));
+    point.data.truncate(1);
+
+    let req = prometheus::encode(&[point]);
+
+    let label = |name: &str, value: &str| {
+        let mut res = vec![0x0a, name.len() as u8];
+        res.extend_from_slice(name.as_bytes());
+        res.extend([0x12, value.len() as u8]);
+        res.extend_from_slice(value.as_bytes());
+        res
+    };
+    let mut sample = vec![0x09];
+    sample.extend(21.5f64.to_le_bytes());
+    // 1700000000500 as varint
+    sample.extend([0x10, 0xf4, 0xd3, 0x95, 0xff, 0xbc, 0x31]);
+
+    let mut ts = Vec::new();
+    for l in [
+        label("__name__", "room_temperature"),
+        label("device_id", "1"),
+    ] {
+        ts.extend([0x0a, l.len() as u8]);
+        ts.extend(l);
+    }
+    ts.extend([0x12, sample.len() as u8]);
+    ts.extend(sample);
+
+    let mut expected = vec![0x0a, ts.len() as u8];
+    expected.extend(ts);
+
+    assert_eq!(req, expected);
+    assert!(prometheus::encode(&[]).is_empty());
+}
+
+// Test that points are sent to HTTP destinations and failed batches are retried
+#[tokio::test]
+async fn http_sinks_send_points() {
+    let (influx_url, mut influx_rx) = http_stand_in(vec![204]).await;
+    let (webhook_url, mut webhook_rx) = http_stand_in(vec![500, 200]).await;
+    let (remote_write_url, mut remote_write_rx) = http_stand_in(vec![200]).await;
+
+    let sinks = start_sinks(serde_json::json!({"sinks": [
+        {
+            "name": "influx",
+            "type": "influxdb",
+            "url": format!("{influx_url}/api/v2/write?bucket=test"),
+            "token": "secret",
+            "routes": [{"device_id": 1}],
+            "buffer": {"batch_size": 2, "flush_interval_ms": 5000},
+        },
+        {
+            "name": "webhook",
+            "type": "webhook",
+            "url": webhook_url,
+            "headers": {"X-Key": "abc"},
+            "buffer": {"flush_interval_ms": 10},
+            "retry": {"initial_backoff_ms": 10},
+        },
+        {
+            "name": "remote_write",
+            "type": "prometheus_remote_write",
+            "url": remote_write_url,
+            "routes": [{"sensor": "room"}],
+            "buffer": {"flush_interval_ms": 10},
+        },
+    ]}));
+    assert!(!sinks.is_empty());
+    assert!(Sinks::default().is_empty());
+
+    sinks.forward(DeviceID::new(1), &test_msg(21.5));
+    sinks.forward(DeviceID::new(2), &test_msg(22.5));
+    sinks.forward(DeviceID::new(1), &test_msg(23.5));
+
+    // The batch is full before the flush interval
+    let req = recv(&mut influx_rx).await;
+    assert!(req
+        .head
+        .starts_with("post /api/v2/write?bucket=test&precision=ns "));
+    assert!(req.head.contains("authorization: token secret\r\n"));
+    let body = String::from_utf8(req.body).unwrap();
+    assert_eq!(body.lines().count(), 2);
+    assert!(body.contains("temperature=21.5,") && body.contains("temperature=23.5,"));
+
+    let first = recv(&mut webhook_rx).await;
+    let retried = recv(&mut webhook_rx).await;
+    assert_eq!(first.body, retried.body);
+    assert!(retried.head.contains("x-key: abc\r\n"));
+    let body: serde_json::Value = serde_json::from_slice(&retried.body).unwrap();
+    assert_eq!(
+        body[0],
+        serde_json::json!({
+            "device_id": 1,
+            "sensor": "room",
+            "timestamp": "2023-11-14T22:13:20.500Z",
+            "data": {"temperature": 21.5, "count": 3, "state": "on \"x\""},
+        })
+    );
+
+    let req = recv(&mut remote_write_rx).await;
+    assert!(req.head.contains("content-encoding: snappy\r\n"));
+    let body = snap::raw::Decoder::new().decompress_vec(&req.body).unwrap();
+    assert!(body.windows(16).any(|w| w == b"room_temperature"));
+}
+
+// Test that points are published to an MQTT broker
+#[tokio::test]
+async fn mqtt_sink_publishes() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    // The broker acknowledges the connection and reports PUBLISH packets with QoS 0
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        loop {
+            let typ = stream.read_u8().await.unwrap();
+            let mut len = 0usize;
+            for shift in (0..).step_by(7) {
+                let b = stream.read_u8().await.unwrap();
+                len |= ((b & 0x7f) as usize) << shift;
+                if b & 0x80 == 0 {
+                    break;
+                }
+            }
+            let mut data = vec![0; len];
+            stream.read_exact(&mut data).await.unwrap();
+
+            match typ >> 4 {
+                1 => stream.write_all(&[0x20, 0x02, 0x00, 0x00]).await.unwrap(),
+                3 => {
+                    let topic_len = u16::from_be_bytes([data[0], data[1]]) as usize;
+                    let topic = String::from_utf8(data[2..2 + topic_len].to_vec()).unwrap();
+                    let payload = data[2 + topic_len..].to_vec();
+                    let _ = tx.send((topic, payload));
+                }
+                _ => {}
+            }
+        }
+    });
+
+    let sinks = start_sinks(serde_json::json!({"sinks": [{
+        "name": "mqtt",
+        "type": "mqtt",
+        "host": "127.0.0.1",
+        "port": port,
+        "qos": 0,
+        "topic": "export/{device_id}/{sensor}",
+        "buffer": {"flush_interval_ms": 10},
+    }]}));
+
+    sinks.forward(DeviceID::new(7), &test_msg(21.5));
+
+    let (topic, payload) = recv(&mut rx).await;
+    assert_eq!(topic, "export/7/room");
+    let payload: serde_json::Value = serde_json::from_slice(&payload).unwrap();
+    assert_eq!(payload["device_id"], 7);
+    assert_eq!(payload["data"]["temperature"], 21.5);
+
+    let point = Point::new(DeviceID::new(7), &test_msg(21.5));
+    assert_eq!(mqtt::topic("{sensor}/{device_id}", &point), "room/7");
+}
+
+// Test that closing sinks sends the buffered points without waiting for the flush interval
+#[tokio::test]
+async fn close_flushes_buffered_points() {
+    let (url, mut rx) = http_stand_in(vec![200]).await;
+
+    let sinks = start_sinks(serde_json::json!({"sinks": [{
+        "name": "webhook",
+        "type": "webhook",
+        "url": url,
+        "buffer": {"batch_size": 100, "flush_interval_ms": 60000},
+    }]}));
+
+    sinks.forward(DeviceID::new(1), &test_msg(21.5));
+    sinks.forward(DeviceID::new(1), &test_msg(22.5));
+
+    tokio::time::timeout(Duration::from_secs(5), sinks.close(Duration::from_secs(5)))
+        .await
+        .expect("sinks weren't closed");
+
+    let req = recv(&mut rx).await;
+    let body: serde_json::Value = serde_json::from_slice(&req.body).unwrap();
+    assert_eq!(body.as_array().unwrap().len(), 2);
+}
@@ -0,0 +1,36 @@
+use reqwest::header::HeaderMap;
+
+use crate::controller::error::CommonError;
+
+use super::http;
+use super::{HttpConf, Point};
+
+/// `WebhookSink` posts batches of points as a JSON array
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+    headers: HeaderMap,
+}
+
+impl WebhookSink {
+    pub fn new(conf: &HttpConf) -> Result<Self, CommonError> {
+        Ok(Self {
+            client: http::client()?,
+            url: conf.url.clone(),
+            headers: http::headers(&conf.headers)?,
+        })
+    }
+
+    pub async fn send(&mut self, points: &[Point]) -> Result<(), CommonError> {
+        let body: Vec<_> = points.iter().map(Point::to_json).collect();
+
+        http::send(
+            self.client
+                .post(&self.url)
+                .headers(self.headers.clone())
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(serde_json::Value::Array(body).to_string()),
+        )
+        .await
+    }
+}
@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use tokio::sync::{mpsc, watch};
+use tokio::time::Instant;
+
+use crate::controller::error::{CommonError, ErrorType};
+use crate::logger;
+use crate::{kv_any, kvs};
+
+use super::{BufferConf, Destination, Point, RetryConf};
+
+/// `run` collects points of a sink into batches and sends them to the destination
+/// until all senders are dropped or the sink is closed. A batch is sent when it is full
+/// or the flush interval passed. On close, buffered points are sent without waiting.
+pub async fn run(
+    name: String,
+    mut dest: Destination,
+    buffer: BufferConf,
+    retry: RetryConf,
+    mut rx: mpsc::Receiver<Point>,
+    mut closing: watch::Receiver<bool>,
+) {
+    let flush_interval = Duration::from_millis(buffer.flush_interval_ms);
+    let mut batch = Vec::with_capacity(buffer.batch_size);
+
+    loop {
+        let Some(point) = recv(&mut rx, &mut closing).await else {
+            break;
+        };
+        batch.push(point);
+
+        let deadline = Instant::now() + flush_interval;
+        let mut closed = false;
+        while batch.len() < buffer.batch_size {
+            match tokio::time::timeout_at(deadline, recv(&mut rx, &mut closing)).await {
+                Ok(Some(point)) => batch.push(point),
+                Ok(None) => {
+                    closed = true;
+                    break;
+                }
+                Err(_) => break,
+            }
+        }
+
+        send_batch(&name, &mut dest, &retry, &batch).await;
+        batch.clear();
+
+        if closed {
+            break;
+        }
+    }
+}
+
+/// `recv` returns the next point. Once the sink is closing, the channel is closed,
+/// so the points already in it are returned without waiting and then `None`
+async fn recv(
+    rx: &mut mpsc::Receiver<Point>,
+    closing: &mut watch::Receiver<bool>,
+) -> Option<Point> {
+    if !*closing.borrow() {
+        tokio::select! {
+            point = rx.recv() => return point,
+            _ = closing.wait_for(|closing| *closing) => (),
+        }
+    }
+
+    rx.close();
+    rx.recv().await
+}
+
+/// `send_batch` sends a batch retrying temporary errors with exponential backoff.
+/// The batch is dropped if it can't be sent.
+async fn send_batch(name: &str, dest: &mut Destination, retry: &RetryConf, batch: &[Point]) {
+    let mut backoff = Duration::from_millis(retry.initial_backoff_ms);
+    let max_backoff = Duration::from_millis(retry.max_backoff_ms);
+
+    for attempt in 1..=retry.max_attempts {
+        // The error isn't kept across awaits: it isn't `Send`
+        let (error, retryable) = match dest.send(batch).await {
+            Ok(()) => return,
+            Err(err) => (describe(&err), err.error_type != ErrorType::InvalidInput),
+        };
+
+        if !retryable || attempt == retry.max_attempts {
+            logger::error_kv(
+                "failed to send points to sink, they are dropped",
+                kvs!(
+                    "sink" => kv_any!(name),
+                    "points" => kv_any!(batch.len()),
+                    "attempts" => kv_any!(attempt),
+                    "error" => kv_any!(error)
+                ),
+            );
+            return;
+        }
+
+        logger::warn_kv(
+            "failed to send points to sink, retrying",
+            kvs!(
+                "sink" => kv_any!(name),
+                "attempt" => kv_any!(attempt),
+                "error" => kv_any!(error)
+            ),
+        );
+
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(max_backoff);
+    }
+}
+
+fn describe(err: &CommonError) -> String {
+    match err.source {
+        Some(ref source) => format!("{}: {source}", err.msg),
+        None => err.msg.clone(),
+    }
+}
@@ -1,3 +1,4 @@
+use crate::query::Dialect;
 use crate::tool::validation::validate_chars;
 use std::collections::HashSet;
 
@@ -38,30 +39,49 @@ impl Field {
         }
     }
 
-    pub fn parse_size(&self) -> usize {
-        let mut opts_size = 0;
-        for opt in self.opts.iter() {
-            opts_size += opt.parse_size();
-        }
+    pub fn parse_size(&self, dialect: Dialect) -> usize {
+        // <space> + <opt_len>
+        let opts_size: usize = self.parsed_opts(dialect).map(|opt| 1 + opt.len()).sum();
 
-        // <quotes_count> + <name_len> + <space> + <type_len> + <spaces_between_opts> + <opts_len>
-        2 + self.name.len() + 1 + self.typ.parse_size() + self.opts.len() + opts_size
+        // <quotes_count> + <name_len> + <space> + <type_len> + <opts_len>
+        2 + self.name.len() + 1 + self.typ_name(dialect).len() + opts_size
     }
 
-    pub fn parse(&self) -> Result<String, FieldError> {
-        let mut s = String::with_capacity(self.parse_size());
+    pub fn parse(&self, dialect: Dialect) -> Result<String, FieldError> {
+        let mut s = String::with_capacity(self.parse_size(dialect));
 
         s.push('"');
         s.push_str(&self.name);
         s.push_str("\" ");
-        s.push_str(&self.typ.parse());
-        for opt in self.opts.iter() {
+        s.push_str(self.typ_name(dialect));
+        for opt in self.parsed_opts(dialect) {
             s.push(' ');
-            s.push_str(&opt.parse());
+            s.push_str(opt);
         }
 
         Ok(s)
     }
+
+    /// `parsed_opts` returns options of the field in the dialect
+    fn parsed_opts(&self, dialect: Dialect) -> impl Iterator<Item = &str> {
+        // An auto-incremented field of SQLite is a primary key already, see `FieldOption::parse`
+        let skip_primary_key =
+            dialect == Dialect::Sqlite && self.opts.contains(&FieldOption::AutoIncrement);
+
+        self.opts
+            .iter()
+            .filter(move |opt| !(skip_primary_key && **opt == FieldOption::PrimaryKey))
+            .map(move |opt| opt.parse(dialect))
+    }
+
+    fn typ_name(&self, dialect: Dialect) -> &'static str {
+        // Only an `integer primary key` column is filled with a new rowid in SQLite
+        if dialect == Dialect::Sqlite && self.opts.contains(&FieldOption::AutoIncrement) {
+            return "integer";
+        }
+
+        self.typ.parse(dialect)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -77,26 +97,31 @@ pub enum FieldType {
 }
 
 impl FieldType {
-    pub fn parse_size(&self) -> usize {
-        match *self {
-            FieldType::Int16 | FieldType::Int32 | FieldType::Int64 => 4,
-            FieldType::Float32 | FieldType::Float64 => 6,
-            FieldType::Timestamp => 9,
-            FieldType::Text => 4,
-            FieldType::JSON => 5,
-        }
-    }
-
-    pub fn parse(&self) -> &'static str {
-        match *self {
-            FieldType::Int16 => "int2",
-            FieldType::Int32 => "int4",
-            FieldType::Int64 => "int8",
-            FieldType::Float32 => "float4",
-            FieldType::Float64 => "float8",
-            FieldType::Timestamp => "timestamp",
-            FieldType::Text => "text",
-            FieldType::JSON => "jsonb",
+    pub fn parse(&self, dialect: Dialect) -> &'static str {
+        match dialect {
+            Dialect::Postgres => match *self {
+                FieldType::Int16 => "int2",
+                FieldType::Int32 => "int4",
+                FieldType::Int64 => "int8",
+                FieldType::Float32 => "float4",
+                FieldType::Float64 => "float8",
+                FieldType::Timestamp => "timestamp",
+                FieldType::Text => "text",
+                FieldType::JSON => "jsonb",
+            },
+            // Names are kept close to Postgres ones, so the type of a column can be
+            // recovered from its declared type. They are mapped to storage classes
+            // by the rules of type affinity, e.g. `json_text` is stored as TEXT
+            Dialect::Sqlite => match *self {
+                FieldType::Int16 => "int2",
+                FieldType::Int32 => "int4",
+                FieldType::Int64 => "int8",
+                FieldType::Float32 => "float4",
+                FieldType::Float64 => "float8",
+                FieldType::Timestamp => "timestamp",
+                FieldType::Text => "text",
+                FieldType::JSON => "json_text",
+            },
         }
     }
 }
@@ -111,21 +136,20 @@ pub enum FieldOption {
 }
 
 impl FieldOption {
-    pub fn parse_size(&self) -> usize {
-        match *self {
-            FieldOption::PrimaryKey => 11,
-            FieldOption::Unique => 6,
-            FieldOption::NotNull => 8,
-            FieldOption::AutoIncrement => 32,
-        }
+    pub fn parse_size(&self, dialect: Dialect) -> usize {
+        self.parse(dialect).len()
     }
 
-    pub fn parse(&self) -> &str {
+    pub fn parse(&self, dialect: Dialect) -> &str {
         match *self {
             FieldOption::PrimaryKey => "PRIMARY KEY",
             FieldOption::Unique => "UNIQUE",
             FieldOption::NotNull => "NOT NULL",
-            FieldOption::AutoIncrement => "GENERATED BY DEFAULT AS IDENTITY",
+            FieldOption::AutoIncrement => match dialect {
+                Dialect::Postgres => "GENERATED BY DEFAULT AS IDENTITY",
+                // The field becomes `integer PRIMARY KEY`, see `Field::typ_name`
+                Dialect::Sqlite => "PRIMARY KEY",
+            },
         }
     }
 
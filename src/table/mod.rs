@@ -3,6 +3,7 @@ mod field;
 mod index;
 mod test;
 
+use crate::query::Dialect;
 use crate::tool::validation::validate_chars;
 
 pub use error::*;
@@ -44,10 +45,10 @@ impl Table {
     //     <field>,
     //     ...
     // );
-    pub fn parse_size(&self) -> usize {
+    pub fn parse_size(&self, dialect: Dialect) -> usize {
         let mut field_size = 0;
         for (i, field) in self.fields.iter().enumerate() {
-            field_size += 2 + field.parse_size();
+            field_size += 2 + field.parse_size(dialect);
             if i != self.fields.len() - 1 {
                 field_size += 1;
             }
@@ -60,15 +61,15 @@ impl Table {
         15 + self.name.len() + 2 + field_size + 3
     }
 
-    pub fn parse(&self) -> Result<String, TableError> {
-        let mut s = String::with_capacity(self.parse_size());
+    pub fn parse(&self, dialect: Dialect) -> Result<String, TableError> {
+        let mut s = String::with_capacity(self.parse_size(dialect));
 
         s.push_str("CREATE TABLE \"");
         s.push_str(&self.name);
         s.push_str("\" (");
 
         for (i, field) in self.fields.iter().enumerate() {
-            match field.parse() {
+            match field.parse(dialect) {
                 Ok(field_str) => {
                     s.push_str("\n\t");
                     s.push_str(&field_str)
@@ -1,5 +1,7 @@
 #[cfg(test)]
 use super::{Field, FieldError, FieldOption, FieldType, Table};
+#[cfg(test)]
+use crate::query::Dialect;
 use crate::tool::validation::ValidationError;
 
 // Test that `Field`'s capacity is being calculated properly
@@ -11,24 +13,26 @@ fn field_capacity() {
     f.add_opt(FieldOption::Unique).unwrap();
     f.add_opt(FieldOption::AutoIncrement).unwrap();
 
-    let calc_size = f.parse_size();
-    let parsed = f.parse().unwrap();
-
-    assert_eq!(
-        parsed.len(),
-        parsed.capacity(),
-        "len: {}, cap: {}",
-        parsed.len(),
-        parsed.capacity()
-    );
-
-    assert_eq!(
-        calc_size,
-        parsed.capacity(),
-        "calc_size: {}, parsed.capacity(): {}",
-        calc_size,
-        parsed.capacity()
-    );
+    for dialect in [Dialect::Postgres, Dialect::Sqlite] {
+        let calc_size = f.parse_size(dialect);
+        let parsed = f.parse(dialect).unwrap();
+
+        assert_eq!(
+            parsed.len(),
+            parsed.capacity(),
+            "len: {}, cap: {}",
+            parsed.len(),
+            parsed.capacity()
+        );
+
+        assert_eq!(
+            calc_size,
+            parsed.capacity(),
+            "calc_size: {}, parsed.capacity(): {}",
+            calc_size,
+            parsed.capacity()
+        );
+    }
 }
 
 // Test that `Table`'s capacity is being calculated properly
@@ -51,23 +55,56 @@ fn table_capacity() {
     table.add_field(name_field).unwrap();
     table.add_field(another_field).unwrap();
 
-    let calc_size = table.parse_size();
-    let parsed = table.parse().unwrap();
+    for dialect in [Dialect::Postgres, Dialect::Sqlite] {
+        let calc_size = table.parse_size(dialect);
+        let parsed = table.parse(dialect).unwrap();
+
+        assert_eq!(
+            parsed.len(),
+            parsed.capacity(),
+            "len: {}, cap: {}",
+            parsed.len(),
+            parsed.capacity()
+        );
+
+        assert_eq!(
+            calc_size,
+            parsed.capacity(),
+            "calc_size: {}, parsed.capacity(): {}",
+            calc_size,
+            parsed.capacity()
+        );
+    }
+}
+
+// Test that types and options are named by the dialect
+#[test]
+fn table_dialect() {
+    let mut id_field = Field::new("id".to_string(), FieldType::Int32).unwrap();
+    id_field.add_opt(FieldOption::AutoIncrement).unwrap();
+
+    let data_field = Field::new("data".to_string(), FieldType::JSON).unwrap();
+
+    let mut table = Table::new("test_table".to_string()).unwrap();
+    table.add_field(id_field).unwrap();
+    table.add_field(data_field).unwrap();
 
     assert_eq!(
-        parsed.len(),
-        parsed.capacity(),
-        "len: {}, cap: {}",
-        parsed.len(),
-        parsed.capacity()
+        table.parse(Dialect::Postgres).unwrap(),
+        "CREATE TABLE \"test_table\" (\n\t\"id\" int4 GENERATED BY DEFAULT AS IDENTITY,\n\t\"data\" jsonb\n);"
+    );
+    assert_eq!(
+        table.parse(Dialect::Sqlite).unwrap(),
+        "CREATE TABLE \"test_table\" (\n\t\"id\" integer PRIMARY KEY,\n\t\"data\" json_text\n);"
     );
 
+    // Primary key isn't repeated
+    let mut id_field = Field::new("id".to_string(), FieldType::Int64).unwrap();
+    id_field.add_opt(FieldOption::PrimaryKey).unwrap();
+    id_field.add_opt(FieldOption::AutoIncrement).unwrap();
     assert_eq!(
-        calc_size,
-        parsed.capacity(),
-        "calc_size: {}, parsed.capacity(): {}",
-        calc_size,
-        parsed.capacity()
+        id_field.parse(Dialect::Sqlite).unwrap(),
+        "\"id\" integer PRIMARY KEY"
     );
 }
 
@@ -2,4 +2,5 @@ mod test;
 
 pub mod macros;
 pub mod query_trait;
+pub mod secret;
 pub mod validation;
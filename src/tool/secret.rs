@@ -0,0 +1,108 @@
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+/// Length of a generated token in bytes
+const TOKEN_LEN: usize = 32;
+/// Scheme of password hashes: `pbkdf2-sha256$<iterations>$<salt>$<hash>`, salt and hash are hex-encoded
+const PASSWORD_SCHEME: &str = "pbkdf2-sha256";
+/// Number of PBKDF2 iterations recommended by OWASP for HMAC-SHA-256
+#[cfg(not(test))]
+const PASSWORD_ITERATIONS: u32 = 600_000;
+/// Hashes keep their number of iterations, so tests may use a cheaper one
+#[cfg(test)]
+const PASSWORD_ITERATIONS: u32 = 1_000;
+/// Length of a password's salt in bytes
+const SALT_LEN: usize = 16;
+
+/// `generate_token` returns a random hex-encoded token.
+pub fn generate_token() -> String {
+    let mut buf = [0u8; TOKEN_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut buf);
+
+    to_hex(&buf)
+}
+
+/// `hash_token` returns a hex-encoded SHA-256 of the token. Only hashes of tokens are stored.
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// `verify_token` checks the token against its hash in constant time.
+pub fn verify_token(token: &str, hash: &str) -> bool {
+    let token_hash = hash_token(token);
+    if token_hash.len() != hash.len() {
+        return false;
+    }
+
+    constant_time_eq(token_hash.as_bytes(), hash.as_bytes())
+}
+
+/// `hash_password` returns a salted PBKDF2-HMAC-SHA256 hash of the password.
+/// It takes a while, so it mustn't be called on an async runtime's thread.
+pub fn hash_password(password: &str) -> String {
+    let mut salt = [0u8; SALT_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut salt);
+
+    hash_password_with(password, &salt, PASSWORD_ITERATIONS)
+}
+
+pub(super) fn hash_password_with(password: &str, salt: &[u8], iterations: u32) -> String {
+    format!(
+        "{PASSWORD_SCHEME}${iterations}${}${}",
+        to_hex(salt),
+        to_hex(&pbkdf2_sha256(password.as_bytes(), salt, iterations))
+    )
+}
+
+/// `verify_password` checks the password against its hash made by [`hash_password`].
+/// Hashes with other parameters are supported, so they can be changed later.
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    let mut parts = hash.split('$');
+    let (Some(PASSWORD_SCHEME), Some(iterations), Some(salt), Some(expected), None) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) else {
+        return false;
+    };
+
+    let (Ok(iterations), Some(salt)) = (iterations.parse::<u32>(), from_hex(salt)) else {
+        return false;
+    };
+    if iterations == 0 {
+        return false;
+    }
+
+    let actual = to_hex(&pbkdf2_sha256(password.as_bytes(), &salt, iterations));
+    constant_time_eq(actual.as_bytes(), expected.as_bytes())
+}
+
+/// `pbkdf2_sha256` derives a key of the digest's length (RFC 8018)
+fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32) -> [u8; 32] {
+    pbkdf2::pbkdf2_hmac_array::<Sha256, 32>(password, salt, iterations)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+fn to_hex(v: &[u8]) -> String {
+    v.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn from_hex(v: &str) -> Option<Vec<u8>> {
+    if !v.len().is_multiple_of(2) || !v.is_ascii() {
+        return None;
+    }
+
+    (0..v.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&v[i..i + 2], 16).ok())
+        .collect()
+}
@@ -1,5 +1,9 @@
 #[cfg(test)]
-use super::validation::validate_chars;
+use super::secret::{
+    generate_token, hash_password_with, hash_token, verify_password, verify_token,
+};
+#[cfg(test)]
+use super::validation::{validate_chars, validate_semver};
 
 #[test]
 fn test_validate_word() {
@@ -17,3 +21,49 @@ fn test_validate_word() {
     let res = validate_chars("?unknown_chars");
     assert!(res.is_err());
 }
+
+#[test]
+fn test_validate_semver() {
+    // Success
+    assert!(validate_semver("1.2.3").is_ok());
+    assert!(validate_semver("0.1.0-alpha.1").is_ok());
+    assert!(validate_semver("10.20.30-rc.1+build.5").is_ok());
+
+    // Failure
+    assert!(validate_semver("1.2").is_err());
+    assert!(validate_semver("v1.2.3").is_err());
+    assert!(validate_semver("1.2.3 beta").is_err());
+}
+
+#[test]
+fn test_token() {
+    let token = generate_token();
+    assert_eq!(token.len(), 64);
+    assert_ne!(token, generate_token());
+
+    let hash = hash_token(&token);
+    assert!(verify_token(&token, &hash));
+    assert!(!verify_token(&generate_token(), &hash));
+    assert!(!verify_token(&token, ""));
+}
+
+#[test]
+fn test_password() {
+    // RFC 7914 test vector of PBKDF2-HMAC-SHA256
+    let hash = hash_password_with("passwd", b"salt", 1);
+    assert_eq!(
+        hash,
+        "pbkdf2-sha256$1$73616c74$55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc"
+    );
+    assert!(verify_password("passwd", &hash));
+    assert!(!verify_password("password", &hash));
+
+    // Hashes with other parameters are verified
+    let hash = hash_password_with("secret", &[1, 2, 3, 4], 1000);
+    assert!(verify_password("secret", &hash));
+    assert_ne!(hash, hash_password_with("secret", &[1, 2, 3, 5], 1000));
+
+    assert!(!verify_password("secret", ""));
+    assert!(!verify_password("secret", "pbkdf2-sha256$0$73616c74$"));
+    assert!(!verify_password("secret", "md5$1$73616c74$55ac"));
+}
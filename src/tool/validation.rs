@@ -7,6 +7,8 @@ lazy_static! {
     static ref RE_SINGLE_WORD: Regex = Regex::new(r"^[a-zA-Z0-9_\-]+$").unwrap();
     static ref RE_MULTIPLE_WORDS: Regex = Regex::new(r"^[a-zA-Z0-9_\- ]+$").unwrap();
     static ref RE_SNAKE_CASE: Regex = Regex::new(r"^[a-zA-Z0-9]+(_[a-zA-Z0-9]+)*$").unwrap();
+    static ref RE_SEMVER: Regex =
+        Regex::new(r"^\d+\.\d+\.\d+(-[0-9A-Za-z\-\.]+)?(\+[0-9A-Za-z\-\.]+)?$").unwrap();
 }
 
 #[derive(Error)]
@@ -17,6 +19,8 @@ pub enum ValidationError {
     UnsupportedChars,
     #[error("word is not in snake_case")]
     NotSnakeCase,
+    #[error("version is not a semantic version")]
+    NotSemver,
 }
 
 debug_from_display!(ValidationError);
@@ -52,3 +56,11 @@ pub fn validate_snake_case(s: &str) -> Result<(), ValidationError> {
 
     Err(ValidationError::NotSnakeCase)
 }
+
+pub fn validate_semver(s: &str) -> Result<(), ValidationError> {
+    if RE_SEMVER.is_match(s) {
+        return Ok(());
+    }
+
+    Err(ValidationError::NotSemver)
+}
@@ -1,4 +1,22 @@
+mod test;
+
+use std::fmt;
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use actix_web::http::Uri;
+
+pub const DEFAULT_HOST: &str = "localhost:8888";
+/// Allows cross-origin requests from any origin
+pub const ANY_ORIGIN: &str = "*";
+pub const DEFAULT_MAX_UPLOAD_SIZE: usize = 50 * 1024 * 1024;
+pub const DEFAULT_MAX_JSON_SIZE: usize = 2 * 1024 * 1024;
+pub const DEFAULT_MAX_IMPORT_SIZE: usize = 1024 * 1024 * 1024;
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+pub const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(5);
+
+const UNIX_SOCKET_PREFIX: &str = "unix:";
 
 #[derive(Clone)]
 pub struct AppConfig {
@@ -32,3 +50,226 @@ impl AppConfig {
         &self.favicon_file
     }
 }
+
+/// `TlsConfig` enables HTTPS on the web server
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    cert_file: PathBuf,
+    key_file: PathBuf,
+    redirect_host: Option<String>,
+}
+
+impl TlsConfig {
+    pub fn new(cert_file: PathBuf, key_file: PathBuf, redirect_host: Option<String>) -> TlsConfig {
+        TlsConfig {
+            cert_file,
+            key_file,
+            redirect_host,
+        }
+    }
+
+    pub fn cert_file(&self) -> &PathBuf {
+        &self.cert_file
+    }
+
+    pub fn key_file(&self) -> &PathBuf {
+        &self.key_file
+    }
+
+    /// `redirect_host` is the address of the plain HTTP listener redirecting to HTTPS
+    pub fn redirect_host(&self) -> Option<&String> {
+        self.redirect_host.as_ref()
+    }
+}
+
+/// `BindAddr` is an address the web server listens on
+#[derive(Clone, Debug, PartialEq)]
+pub enum BindAddr {
+    /// `host:port`, e.g. `0.0.0.0:8888` or `[::]:8888`
+    Tcp(String),
+    /// Path of a Unix socket, written as `unix:/path/to/socket`
+    Unix(PathBuf),
+}
+
+impl FromStr for BindAddr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix(UNIX_SOCKET_PREFIX) {
+            Some("") => Err(format!("empty unix socket path: '{s}'")),
+            #[cfg(unix)]
+            Some(path) => Ok(BindAddr::Unix(path.into())),
+            #[cfg(not(unix))]
+            Some(_) => Err(format!(
+                "unix sockets are supported only on unix platforms: '{s}'"
+            )),
+            None if s.is_empty() => Err("empty bind address".to_string()),
+            None => Ok(BindAddr::Tcp(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for BindAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BindAddr::Tcp(host) => write!(f, "{host}"),
+            BindAddr::Unix(path) => write!(f, "{UNIX_SOCKET_PREFIX}{}", path.display()),
+        }
+    }
+}
+
+/// `parse_cors_origin` checks that `origin` is [`ANY_ORIGIN`] or `scheme://host[:port]`
+/// and returns it in the form browsers send in the `Origin` header
+pub fn parse_cors_origin(origin: &str) -> Result<String, String> {
+    if origin == ANY_ORIGIN {
+        return Ok(origin.to_string());
+    }
+
+    let origin = origin.trim_end_matches('/');
+    let uri = origin
+        .parse::<Uri>()
+        .map_err(|_| format!("invalid CORS origin: '{origin}'"))?;
+    if uri.scheme().is_none()
+        || uri.host().is_none()
+        || uri.path_and_query().is_some_and(|p| p != "/")
+    {
+        return Err(format!(
+            "invalid CORS origin: '{origin}', expected scheme://host[:port]"
+        ));
+    }
+
+    Ok(origin.to_string())
+}
+
+/// `ServerConfig` holds the listening and transport settings of the web server
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    binds: Vec<BindAddr>,
+    cors_origins: Vec<String>,
+    workers: Option<usize>,
+    max_upload_size: usize,
+    max_json_size: usize,
+    max_import_size: usize,
+    request_timeout: Duration,
+    keep_alive: Duration,
+    tls: Option<TlsConfig>,
+}
+
+impl ServerConfig {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_binds(mut self, binds: Vec<BindAddr>) -> Self {
+        self.binds = binds;
+
+        self
+    }
+
+    pub fn binds(&self) -> &[BindAddr] {
+        &self.binds
+    }
+
+    /// Origins allowed to make cross-origin requests. Empty means same-origin only
+    pub fn with_cors_origins(mut self, cors_origins: Vec<String>) -> Self {
+        self.cors_origins = cors_origins;
+
+        self
+    }
+
+    pub fn cors_origins(&self) -> &[String] {
+        &self.cors_origins
+    }
+
+    /// Number of worker threads. The number of CPUs is used by default
+    pub fn with_workers(mut self, workers: Option<usize>) -> Self {
+        self.workers = workers;
+
+        self
+    }
+
+    pub fn workers(&self) -> Option<usize> {
+        self.workers
+    }
+
+    /// Maximum size of a module upload in bytes
+    pub fn with_max_upload_size(mut self, max_upload_size: usize) -> Self {
+        self.max_upload_size = max_upload_size;
+
+        self
+    }
+
+    pub fn max_upload_size(&self) -> usize {
+        self.max_upload_size
+    }
+
+    /// Maximum size of a JSON request body in bytes
+    pub fn with_max_json_size(mut self, max_json_size: usize) -> Self {
+        self.max_json_size = max_json_size;
+
+        self
+    }
+
+    pub fn max_json_size(&self) -> usize {
+        self.max_json_size
+    }
+
+    /// Maximum size of a sensor data import in bytes
+    pub fn with_max_import_size(mut self, max_import_size: usize) -> Self {
+        self.max_import_size = max_import_size;
+
+        self
+    }
+
+    pub fn max_import_size(&self) -> usize {
+        self.max_import_size
+    }
+
+    /// Time for a client to send request headers
+    pub fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+
+        self
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        self.request_timeout
+    }
+
+    /// Time an idle connection is kept open. Zero disables keep-alive
+    pub fn with_keep_alive(mut self, keep_alive: Duration) -> Self {
+        self.keep_alive = keep_alive;
+
+        self
+    }
+
+    pub fn keep_alive(&self) -> Duration {
+        self.keep_alive
+    }
+
+    pub fn with_tls(mut self, tls: Option<TlsConfig>) -> Self {
+        self.tls = tls;
+
+        self
+    }
+
+    pub fn tls(&self) -> Option<&TlsConfig> {
+        self.tls.as_ref()
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            binds: vec![BindAddr::Tcp(DEFAULT_HOST.to_string())],
+            cors_origins: Vec::new(),
+            workers: None,
+            max_upload_size: DEFAULT_MAX_UPLOAD_SIZE,
+            max_json_size: DEFAULT_MAX_JSON_SIZE,
+            max_import_size: DEFAULT_MAX_IMPORT_SIZE,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            keep_alive: DEFAULT_KEEP_ALIVE,
+            tls: None,
+        }
+    }
+}
@@ -0,0 +1,42 @@
+#[cfg(test)]
+use super::{parse_cors_origin, BindAddr};
+
+#[test]
+fn bind_addr_parsing() {
+    assert_eq!(
+        "0.0.0.0:8888".parse(),
+        Ok(BindAddr::Tcp("0.0.0.0:8888".to_string()))
+    );
+    assert_eq!(
+        "[::]:8888".parse(),
+        Ok(BindAddr::Tcp("[::]:8888".to_string()))
+    );
+    #[cfg(unix)]
+    assert_eq!(
+        "unix:/run/monisens.sock".parse(),
+        Ok(BindAddr::Unix("/run/monisens.sock".into()))
+    );
+    #[cfg(not(unix))]
+    assert!("unix:/run/monisens.sock".parse::<BindAddr>().is_err());
+    assert!("unix:".parse::<BindAddr>().is_err());
+    assert!("".parse::<BindAddr>().is_err());
+
+    let addr = BindAddr::Unix("/run/monisens.sock".into());
+    assert_eq!(addr.to_string(), "unix:/run/monisens.sock");
+}
+
+#[test]
+fn cors_origin_parsing() {
+    assert_eq!(parse_cors_origin("*"), Ok("*".to_string()));
+    assert_eq!(
+        parse_cors_origin("https://example.com/"),
+        Ok("https://example.com".to_string())
+    );
+    assert_eq!(
+        parse_cors_origin("http://localhost:3000"),
+        Ok("http://localhost:3000".to_string())
+    );
+    assert!(parse_cors_origin("example.com").is_err());
+    assert!(parse_cors_origin("https://example.com/app").is_err());
+    assert!(parse_cors_origin("https://").is_err());
+}
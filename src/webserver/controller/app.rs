@@ -1,6 +1,6 @@
 use crate::webserver::model::AppState;
 use actix_files::NamedFile;
-use actix_web::{get, web, HttpRequest, Result};
+use actix_web::{get, web, HttpRequest, HttpResponse, Result};
 
 #[get("/app/{_:.*}")]
 pub async fn index(data: web::Data<AppState>) -> Result<NamedFile> {
@@ -20,6 +20,14 @@ pub async fn serve_favicon(data: web::Data<AppState>) -> Result<NamedFile> {
     serve_file(data.conf.favicon_file())
 }
 
+/// `login` serves the login page, which sets the session cookie for the app
+#[get("/login")]
+pub async fn login() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(mime::TEXT_HTML_UTF_8)
+        .body(include_str!("login.html"))
+}
+
 fn serve_file<P: AsRef<std::path::Path>>(path: P) -> Result<NamedFile> {
     Ok(NamedFile::open(path)?)
 }
@@ -0,0 +1,74 @@
+use actix_web::{
+    cookie::{time, Cookie, SameSite},
+    post, web, HttpRequest, HttpResponse, Responder,
+};
+use actix_web_validator::Json;
+
+use crate::webserver::model::{contract, ServiceState};
+
+use super::super::middleware::auth::{session_token, SESSION_COOKIE};
+use super::super::model::error::WebError;
+
+#[utoipa::path(
+    context_path = "/auth",
+    request_body(content = LoginRequest, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Ok response with a session token. The token is also set as a cookie", body = LoginResponse),
+        (status = 401, description = "Username or password is invalid", body = WebError),
+        (status = 429, description = "Too many failed logins, retry later", body = WebError),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[post("/login")]
+pub async fn login(
+    data: web::Data<ServiceState>,
+    http_req: HttpRequest,
+    req: Json<contract::LoginRequest>,
+) -> Result<impl Responder, WebError> {
+    let req = req.into_inner();
+    // The peer address is used, because forwarded headers can be forged by the client
+    let client = http_req
+        .peer_addr()
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_default();
+    let session = data
+        .ctrl
+        .login(&req.username, req.password, &client)
+        .await?;
+
+    let max_age = session.expires_at - chrono::Utc::now().naive_utc();
+    let cookie = Cookie::build(SESSION_COOKIE, session.token.clone())
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .secure(http_req.connection_info().scheme() == "https")
+        .max_age(time::Duration::seconds(max_age.num_seconds()))
+        .finish();
+
+    Ok(HttpResponse::Ok()
+        .cookie(cookie)
+        .json(contract::LoginResponse::from(session)))
+}
+
+#[utoipa::path(
+    context_path = "/auth",
+    responses(
+        (status = 200, description = "Ok response. The session cookie is removed"),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[post("/logout")]
+pub async fn logout(
+    data: web::Data<ServiceState>,
+    http_req: HttpRequest,
+) -> Result<impl Responder, WebError> {
+    if let Some(token) = session_token(&http_req) {
+        data.ctrl.logout(&token).await?;
+    }
+
+    let mut res = HttpResponse::Ok().finish();
+    res.add_removal_cookie(&Cookie::build(SESSION_COOKIE, "").path("/").finish())
+        .map_err(Box::<dyn std::error::Error>::from)?;
+
+    Ok(res)
+}
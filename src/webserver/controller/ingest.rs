@@ -0,0 +1,38 @@
+use actix_web::{http::StatusCode, post, web, HttpRequest, Responder};
+
+use crate::webserver::model::{contract, ServiceState};
+
+use super::super::middleware::auth::bearer_token;
+use super::super::model::error::WebError;
+
+#[utoipa::path(
+    context_path = "/ingest",
+    params(("device_id" = i32, Path, description = "Id of a push device")),
+    request_body(content = IngestRequest, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Ok response with the number of saved rows", body = IngestResponse),
+        (status = 401, description = "Ingest token or API key is missing or invalid", body = WebError),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[post("/{device_id}")]
+pub async fn ingest(
+    data: web::Data<ServiceState>,
+    device_id: web::Path<i32>,
+    http_req: HttpRequest,
+    req: web::Json<contract::IngestRequest>,
+) -> Result<impl Responder, WebError> {
+    let token = bearer_token(&http_req).ok_or_else(|| {
+        WebError::new(
+            StatusCode::UNAUTHORIZED,
+            "ingest token is required".to_string(),
+        )
+    })?;
+
+    let saved = data
+        .ctrl
+        .ingest_sensor_data(device_id.into_inner(), token, req.into_inner().into())
+        .await?;
+
+    Ok(web::Json(contract::IngestResponse { saved }))
+}
@@ -1,2 +1,4 @@
 pub mod app;
+pub mod auth;
+pub mod ingest;
 pub mod service;
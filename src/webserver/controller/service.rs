@@ -1,11 +1,22 @@
 use actix_multipart::form::MultipartForm;
-use actix_web::{get, post, web, HttpResponse, Responder};
+use actix_web::{
+    get,
+    http::header::{ContentDisposition, DispositionParam, DispositionType},
+    http::StatusCode,
+    post, web, HttpResponse, Responder,
+};
 use actix_web_validator::Json;
+use futures_util::{stream, StreamExt};
+use tokio::sync::mpsc;
 
+use crate::controller::{self, Permission};
 use crate::webserver::model::{contract, ServiceState};
 
 use super::super::model::error::WebError;
 
+/// Number of encoded chunks of an export waiting to be sent
+const EXPORT_CHANNEL_LEN: usize = 4;
+
 #[utoipa::path(
     context_path = "/service",
     request_body(content = DeviceStartInitRequest, content_type = "multipart/form-data"),
@@ -17,15 +28,18 @@ use super::super::model::error::WebError;
 #[post("/start-device-init")]
 pub async fn start_device_init(
     data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
     MultipartForm(form): MultipartForm<contract::DeviceStartInitRequest>,
 ) -> Result<impl Responder, WebError> {
+    user.authorize_all_devices(Permission::ManageDevices)?;
+
     let mut file = tokio::fs::File::open(form.module_file.file.path())
         .await
         .map_err(|err| Box::<dyn std::error::Error>::from(err))?;
 
     let res = data
         .ctrl
-        .start_device_init(form.device_name.to_string(), &mut file)
+        .start_device_init(&user, form.device_name.to_string(), &mut file)
         .await?;
 
     Ok(web::Json(contract::DeviceStartInitResponse::from(res)))
@@ -42,12 +56,18 @@ pub async fn start_device_init(
 #[post("/connect-device")]
 pub async fn connect_device(
     data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
     mut req: web::Json<contract::ConnectDeviceRequest>,
 ) -> Result<impl Responder, WebError> {
-    data.ctrl.connect_device(
-        req.device_id,
-        req.connect_conf.drain(..).map(|v| v.into()).collect(),
-    )?;
+    user.authorize_device(Permission::ManageDevices, req.device_id)?;
+
+    data.ctrl
+        .connect_device(
+            &user,
+            req.device_id,
+            req.connect_conf.drain(..).map(|v| v.into()).collect(),
+        )
+        .await?;
 
     Ok(HttpResponse::Ok())
 }
@@ -63,9 +83,12 @@ pub async fn connect_device(
 #[post("/obtain-device-conf-info")]
 pub async fn obtain_device_conf_info(
     data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
     req: web::Json<contract::ObtainDeviceConfInfoRequest>,
 ) -> Result<impl Responder, WebError> {
-    let mut res = data.ctrl.obtain_device_conf_info(req.device_id)?;
+    user.authorize_device(Permission::ManageDevices, req.device_id)?;
+
+    let mut res = data.ctrl.obtain_device_conf_info(req.device_id).await?;
 
     Ok(web::Json(contract::ObtainDeviceConfInfoResponse {
         device_conf_info: res.drain(..).map(|v| v.into()).collect(),
@@ -83,10 +106,14 @@ pub async fn obtain_device_conf_info(
 #[post("/configure-device")]
 pub async fn configure_device(
     data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
     mut req: web::Json<contract::ConfigureDeviceRequest>,
 ) -> Result<impl Responder, WebError> {
+    user.authorize_device(Permission::ManageDevices, req.device_id)?;
+
     data.ctrl
         .configure_device(
+            &user,
             req.device_id,
             req.confs.drain(..).map(|v| v.into()).collect(),
         )
@@ -106,9 +133,14 @@ pub async fn configure_device(
 #[post("/interrupt-device-init")]
 pub async fn interrupt_device_init(
     data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
     req: web::Json<contract::InterruptDeviceInitRequest>,
 ) -> Result<impl Responder, WebError> {
-    data.ctrl.interrupt_device_init(req.device_id).await?;
+    user.authorize_device(Permission::ManageDevices, req.device_id)?;
+
+    data.ctrl
+        .interrupt_device_init(&user, req.device_id)
+        .await?;
 
     Ok(HttpResponse::Ok())
 }
@@ -124,8 +156,11 @@ pub async fn interrupt_device_init(
 #[post("/get-sensor-data")]
 pub async fn get_sensor_data(
     data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
     req: Json<contract::GetSensorDataRequest>,
 ) -> Result<impl Responder, WebError> {
+    user.authorize_device(Permission::ViewData, req.device_id)?;
+
     let res = data.ctrl.get_sensor_data(req.0.clone().into()).await?;
 
     Ok(web::Json::<contract::GetSensorDataResponse>(res.into()))
@@ -139,8 +174,14 @@ pub async fn get_sensor_data(
     ),
 )]
 #[get("/get-device-list")]
-pub async fn get_device_list(data: web::Data<ServiceState>) -> Result<impl Responder, WebError> {
-    let mut res = data.ctrl.get_device_info_list()?;
+pub async fn get_device_list(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+) -> Result<impl Responder, WebError> {
+    user.authorize(Permission::ViewData)?;
+
+    let mut res = data.ctrl.get_device_info_list().await?;
+    res.retain(|device| user.can_access_device(device.id.get_raw()));
 
     res.sort_unstable_by(|a, b| a.id.partial_cmp(&b.id).unwrap());
 
@@ -158,9 +199,12 @@ pub async fn get_device_list(data: web::Data<ServiceState>) -> Result<impl Respo
 #[post("/get-device-sensor-info")]
 pub async fn get_device_sensor_info(
     data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
     req: Json<contract::GetDeviceSensorInfoRequest>,
 ) -> Result<impl Responder, WebError> {
-    let res = data.ctrl.get_device_sensor_info(req.device_id)?;
+    user.authorize_device(Permission::ViewData, req.device_id)?;
+
+    let res = data.ctrl.get_device_sensor_info(req.device_id).await?;
 
     Ok(web::Json::<contract::GetDeviceSensorInfoResponse>(
         res.into(),
@@ -178,9 +222,12 @@ pub async fn get_device_sensor_info(
 #[post("/save-monitor-conf")]
 pub async fn save_monitor_conf(
     data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
     req: Json<contract::SaveMonitorConfRequest>,
 ) -> Result<impl Responder, WebError> {
-    let id = data.ctrl.save_monitor_conf(req.0.into()).await?;
+    user.authorize_device(Permission::EditPanels, req.device_id)?;
+
+    let id = data.ctrl.save_monitor_conf(&user, req.0.into()).await?;
 
     Ok(web::Json(contract::SaveMonitorConfResponse { id }))
 }
@@ -196,9 +243,597 @@ pub async fn save_monitor_conf(
 #[post("/get-monitor-conf-list")]
 pub async fn get_monitor_conf_list(
     data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
     req: Json<contract::MonitorConfListRequest>,
 ) -> Result<impl Responder, WebError> {
+    user.authorize_device(Permission::ViewData, req.filter.device_id)?;
+
     let res = data.ctrl.get_monitor_conf_list(req.0.filter.into()).await?;
 
     Ok(web::Json::<contract::MonitorConfListResponse>(res.into()))
 }
+
+#[utoipa::path(
+    context_path = "/service",
+    request_body(content = GetDeviceCommandInfoRequest, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Ok response with commands supported by the device", body = GetDeviceCommandInfoResponse),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[post("/get-device-command-info")]
+pub async fn get_device_command_info(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+    req: Json<contract::GetDeviceCommandInfoRequest>,
+) -> Result<impl Responder, WebError> {
+    user.authorize_device(Permission::ViewData, req.device_id)?;
+
+    let res = data.ctrl.get_device_command_info(req.device_id).await?;
+
+    Ok(web::Json::<contract::GetDeviceCommandInfoResponse>(
+        res.into(),
+    ))
+}
+
+#[utoipa::path(
+    context_path = "/service",
+    request_body(content = SendDeviceCommandRequest, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Ok response"),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[post("/send-device-command")]
+pub async fn send_device_command(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+    req: Json<contract::SendDeviceCommandRequest>,
+) -> Result<impl Responder, WebError> {
+    user.authorize_device(Permission::SendCommands, req.device_id)?;
+
+    data.ctrl
+        .send_device_command(&user, req.device_id, req.0.into())
+        .await?;
+
+    Ok(HttpResponse::Ok())
+}
+
+#[utoipa::path(
+    context_path = "/service",
+    request_body(content = GetDeviceInfoRequest, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Ok response with device info and its module build", body = GetDeviceInfoResponse),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[post("/get-device-info")]
+pub async fn get_device_info(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+    req: Json<contract::GetDeviceInfoRequest>,
+) -> Result<impl Responder, WebError> {
+    user.authorize_device(Permission::ViewData, req.device_id)?;
+
+    let res = data.ctrl.get_device_full_info(req.device_id).await?;
+
+    Ok(web::Json::<contract::GetDeviceInfoResponse>(res.into()))
+}
+
+#[utoipa::path(
+    context_path = "/service",
+    request_body(content = UploadModuleRequest, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Ok response with the catalog module", body = CatalogModule),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[post("/upload-module")]
+pub async fn upload_module(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+    MultipartForm(form): MultipartForm<contract::UploadModuleRequest>,
+) -> Result<impl Responder, WebError> {
+    user.authorize_all_devices(Permission::ManageDevices)?;
+
+    let mut file = tokio::fs::File::open(form.module_file.file.path())
+        .await
+        .map_err(Box::<dyn std::error::Error>::from)?;
+
+    let res = data
+        .ctrl
+        .add_catalog_module(&user, form.name.to_string(), &mut file)
+        .await?;
+
+    Ok(web::Json(contract::CatalogModule::from(res)))
+}
+
+#[utoipa::path(
+    context_path = "/service",
+    responses(
+        (status = 200, description = "Ok response with the module catalog", body = GetModuleCatalogResponse),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[get("/get-module-catalog")]
+pub async fn get_module_catalog(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+) -> Result<impl Responder, WebError> {
+    user.authorize(Permission::ViewData)?;
+
+    let res = data.ctrl.get_catalog_module_list().await?;
+
+    Ok(web::Json::<contract::GetModuleCatalogResponse>(res.into()))
+}
+
+#[utoipa::path(
+    context_path = "/service",
+    request_body(content = DeleteModuleRequest, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Ok response"),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[post("/delete-module")]
+pub async fn delete_module(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+    req: Json<contract::DeleteModuleRequest>,
+) -> Result<impl Responder, WebError> {
+    user.authorize_all_devices(Permission::ManageDevices)?;
+
+    data.ctrl
+        .delete_catalog_module(&user, req.module_id)
+        .await?;
+
+    Ok(HttpResponse::Ok())
+}
+
+#[utoipa::path(
+    context_path = "/service",
+    request_body(content = DeviceStartInitFromCatalogRequest, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Ok response with device id and connection params", body = DeviceStartInitResponse),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[post("/start-device-init-from-catalog")]
+pub async fn start_device_init_from_catalog(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+    req: Json<contract::DeviceStartInitFromCatalogRequest>,
+) -> Result<impl Responder, WebError> {
+    user.authorize_all_devices(Permission::ManageDevices)?;
+
+    let res = data
+        .ctrl
+        .start_device_init_from_catalog(&user, req.device_name.clone(), req.module_id)
+        .await?;
+
+    Ok(web::Json(contract::DeviceStartInitResponse::from(res)))
+}
+
+#[utoipa::path(
+    context_path = "/service",
+    request_body(content = UpgradeDeviceModuleRequest, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Ok response with device info and its new module build", body = GetDeviceInfoResponse),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[post("/upgrade-device-module")]
+pub async fn upgrade_device_module(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+    MultipartForm(form): MultipartForm<contract::UpgradeDeviceModuleRequest>,
+) -> Result<impl Responder, WebError> {
+    user.authorize_device(Permission::ManageDevices, *form.device_id)?;
+
+    let mut file = tokio::fs::File::open(form.module_file.file.path())
+        .await
+        .map_err(Box::<dyn std::error::Error>::from)?;
+
+    data.ctrl
+        .upgrade_device_module(&user, *form.device_id, &mut file)
+        .await?;
+
+    let res = data.ctrl.get_device_full_info(*form.device_id).await?;
+
+    Ok(web::Json::<contract::GetDeviceInfoResponse>(res.into()))
+}
+
+#[utoipa::path(
+    context_path = "/service",
+    request_body(content = InspectModuleRequest, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Ok response with the module's version, functions, info and connection params", body = InspectModuleResponse),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[post("/inspect-module")]
+pub async fn inspect_module(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+    MultipartForm(form): MultipartForm<contract::InspectModuleRequest>,
+) -> Result<impl Responder, WebError> {
+    user.authorize_all_devices(Permission::ManageDevices)?;
+
+    let res = data
+        .ctrl
+        .inspect_module(form.module_file.file.path())
+        .await?;
+
+    Ok(web::Json(contract::InspectModuleResponse::from(res)))
+}
+
+#[utoipa::path(
+    context_path = "/service",
+    request_body(content = SetDeviceWatchdogConfRequest, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Ok response"),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[post("/set-device-watchdog-conf")]
+pub async fn set_device_watchdog_conf(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+    req: Json<contract::SetDeviceWatchdogConfRequest>,
+) -> Result<impl Responder, WebError> {
+    user.authorize_device(Permission::ManageDevices, req.device_id)?;
+
+    let req = req.into_inner();
+
+    data.ctrl
+        .save_device_watchdog_conf(&user, req.device_id, req.watchdog_conf.into())
+        .await?;
+
+    Ok(HttpResponse::Ok())
+}
+
+#[utoipa::path(
+    context_path = "/service",
+    request_body(content = CreatePushDeviceRequest, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Ok response with device id and its ingest token", body = CreatePushDeviceResponse),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[post("/create-push-device")]
+pub async fn create_push_device(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+    req: Json<contract::CreatePushDeviceRequest>,
+) -> Result<impl Responder, WebError> {
+    user.authorize_all_devices(Permission::ManageDevices)?;
+
+    let sensors =
+        serde_json::to_string(&req.sensors).map_err(Box::<dyn std::error::Error>::from)?;
+
+    let res = data
+        .ctrl
+        .create_push_device(&user, req.device_name.clone(), sensors)
+        .await?;
+
+    Ok(web::Json(contract::CreatePushDeviceResponse::from(res)))
+}
+
+#[utoipa::path(
+    context_path = "/service",
+    request_body(content = ResetIngestTokenRequest, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Ok response with a new ingest token", body = ResetIngestTokenResponse),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[post("/reset-ingest-token")]
+pub async fn reset_ingest_token(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+    req: Json<contract::ResetIngestTokenRequest>,
+) -> Result<impl Responder, WebError> {
+    user.authorize_device(Permission::ManageDevices, req.device_id)?;
+
+    let ingest_token = data
+        .ctrl
+        .reset_device_ingest_token(&user, req.device_id)
+        .await?;
+
+    Ok(web::Json(contract::ResetIngestTokenResponse {
+        ingest_token,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/import-sensor-data",
+    context_path = "/service",
+    request_body(content = ImportSensorDataRequest, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Ok response with an import report", body = ImportSensorDataResponse),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+// The route is registered in `start_server` with its own limit of the multipart size
+pub async fn import_sensor_data(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+    MultipartForm(form): MultipartForm<contract::ImportSensorDataRequest>,
+) -> Result<impl Responder, WebError> {
+    let payload = form
+        .to_payload()
+        .map_err(|msg| WebError::new(StatusCode::BAD_REQUEST, msg))?;
+    user.authorize_device(Permission::ManageDevices, payload.device_id)?;
+
+    // The uploaded file is opened again, because its handle points to its end
+    let path = form.file.file.path().to_path_buf();
+    let file = web::block(move || std::fs::File::open(path))
+        .await
+        .map_err(Box::<dyn std::error::Error>::from)?
+        .map_err(Box::<dyn std::error::Error>::from)?;
+
+    let res = data.ctrl.import_sensor_data(&user, payload, file).await?;
+
+    Ok(web::Json(contract::ImportSensorDataResponse::from(res)))
+}
+
+#[utoipa::path(
+    context_path = "/service",
+    request_body(content = ExportSensorDataRequest, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Ok response with a file streamed in the requested format"),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[post("/export-sensor-data")]
+pub async fn export_sensor_data(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+    req: Json<contract::ExportSensorDataRequest>,
+) -> Result<impl Responder, WebError> {
+    let payload = req
+        .to_payload()
+        .map_err(|msg| WebError::new(StatusCode::BAD_REQUEST, msg))?;
+    user.authorize_device(Permission::ViewData, payload.device_id)?;
+
+    let export = data.ctrl.prepare_sensor_data_export(payload)?;
+
+    let format = export.format();
+    let file_name = format!(
+        "{}_{}.{}",
+        export.device_id().get_raw(),
+        export.sensor(),
+        format.extension()
+    );
+
+    // The export runs on its own and stops when the client goes away and the channel is closed
+    let (tx, rx) = mpsc::channel(EXPORT_CHANNEL_LEN);
+    let task = actix_web::rt::spawn(async move { data.ctrl.export_sensor_data(export, tx).await });
+
+    let chunks = stream::unfold(rx, |mut rx| async move {
+        rx.recv()
+            .await
+            .map(|chunk| (Ok::<_, actix_web::Error>(web::Bytes::from(chunk)), rx))
+    });
+    // A failed export breaks the response so that the client doesn't get a truncated file
+    let result = stream::once(task).filter_map(|res| async move {
+        match res {
+            Ok(Ok(_)) => None,
+            Ok(Err(err)) => Some(Err(WebError::from(err).into())),
+            Err(err) => Some(Err(WebError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("export task failed: {err}"),
+            )
+            .into())),
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(file_name)],
+        })
+        .streaming(chunks.chain(result)))
+}
+
+#[utoipa::path(
+    context_path = "/service",
+    responses(
+        (status = 200, description = "Ok response with the authenticated user", body = User),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[get("/get-current-user")]
+pub async fn get_current_user(user: web::ReqData<controller::User>) -> impl Responder {
+    web::Json(contract::User::from(user.into_inner()))
+}
+
+#[utoipa::path(
+    context_path = "/service",
+    request_body(content = CreateUserRequest, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Ok response with the created user", body = User),
+        (status = 409, description = "User with the same name already exists", body = WebError),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[post("/create-user")]
+pub async fn create_user(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+    req: Json<contract::CreateUserRequest>,
+) -> Result<impl Responder, WebError> {
+    user.authorize(Permission::ManageUsers)?;
+
+    let req = req.into_inner();
+    let res = data
+        .ctrl
+        .create_user(
+            &user,
+            req.username,
+            req.password,
+            req.role.into(),
+            req.device_ids,
+        )
+        .await?;
+
+    Ok(web::Json(contract::User::from(res)))
+}
+
+#[utoipa::path(
+    context_path = "/service",
+    request_body(content = UpdateUserRequest, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Ok response with the updated user", body = User),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[post("/update-user")]
+pub async fn update_user(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+    req: Json<contract::UpdateUserRequest>,
+) -> Result<impl Responder, WebError> {
+    user.authorize(Permission::ManageUsers)?;
+
+    let req = req.into_inner();
+    let res = data
+        .ctrl
+        .update_user(&user, req.user_id, req.role.into(), req.device_ids)
+        .await?;
+
+    Ok(web::Json(contract::User::from(res)))
+}
+
+#[utoipa::path(
+    context_path = "/service",
+    responses(
+        (status = 200, description = "Ok response with all users", body = GetUserListResponse),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[get("/get-user-list")]
+pub async fn get_user_list(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+) -> Result<impl Responder, WebError> {
+    user.authorize(Permission::ManageUsers)?;
+
+    let res = data.ctrl.get_user_list().await?;
+
+    Ok(web::Json::<contract::GetUserListResponse>(res.into()))
+}
+
+#[utoipa::path(
+    context_path = "/service",
+    request_body(content = DeleteUserRequest, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Ok response"),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[post("/delete-user")]
+pub async fn delete_user(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+    req: Json<contract::DeleteUserRequest>,
+) -> Result<impl Responder, WebError> {
+    user.authorize(Permission::ManageUsers)?;
+
+    data.ctrl.delete_user(&user, req.user_id).await?;
+
+    Ok(HttpResponse::Ok())
+}
+
+#[utoipa::path(
+    context_path = "/service",
+    request_body(content = CreateApiKeyRequest, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Ok response with the created API key. The key is returned only once", body = CreateApiKeyResponse),
+        (status = 409, description = "User already has an API key with the same name", body = WebError),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[post("/create-api-key")]
+pub async fn create_api_key(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+    req: Json<contract::CreateApiKeyRequest>,
+) -> Result<impl Responder, WebError> {
+    user.authorize(Permission::ManageApiKeys)?;
+
+    let req = req.into_inner();
+    let res = data
+        .ctrl
+        .create_api_key(
+            &user,
+            req.name,
+            req.scopes.into_iter().map(|v| v.into()).collect(),
+        )
+        .await?;
+
+    Ok(web::Json(contract::CreateApiKeyResponse::from(res)))
+}
+
+#[utoipa::path(
+    context_path = "/service",
+    responses(
+        (status = 200, description = "Ok response with user's API keys. Admins get keys of all users", body = GetApiKeyListResponse),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[get("/get-api-key-list")]
+pub async fn get_api_key_list(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+) -> Result<impl Responder, WebError> {
+    user.authorize(Permission::ManageApiKeys)?;
+
+    let res = data.ctrl.get_api_key_list(&user).await?;
+
+    Ok(web::Json::<contract::GetApiKeyListResponse>(res.into()))
+}
+
+#[utoipa::path(
+    context_path = "/service",
+    request_body(content = RevokeApiKeyRequest, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Ok response"),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[post("/revoke-api-key")]
+pub async fn revoke_api_key(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+    req: Json<contract::RevokeApiKeyRequest>,
+) -> Result<impl Responder, WebError> {
+    user.authorize(Permission::ManageApiKeys)?;
+
+    data.ctrl.revoke_api_key(&user, req.api_key_id).await?;
+
+    Ok(HttpResponse::Ok())
+}
+
+#[utoipa::path(
+    context_path = "/service",
+    request_body(content = GetAuditLogRequest, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Ok response with audit records from the newest ones", body = GetAuditLogResponse),
+        (status = "default", description = "Server error response", body = WebError),
+    ),
+)]
+#[post("/get-audit-log")]
+pub async fn get_audit_log(
+    data: web::Data<ServiceState>,
+    user: web::ReqData<controller::User>,
+    req: Json<contract::GetAuditLogRequest>,
+) -> Result<impl Responder, WebError> {
+    user.authorize_all_devices(Permission::ViewAuditLog)?;
+
+    let res = data.ctrl.get_audit_log(req.into_inner().into()).await?;
+
+    Ok(web::Json::<contract::GetAuditLogResponse>(res.into()))
+}
@@ -0,0 +1,65 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::{header, StatusCode},
+    middleware::Next,
+    web, Error, HttpMessage, HttpRequest,
+};
+
+use super::super::model::error::WebError;
+use super::super::model::ServiceState;
+
+/// Name of the cookie with the session token
+pub const SESSION_COOKIE: &str = "monisens_session";
+
+/// `auth` lets through only requests with a valid session token. The token is taken
+/// from `Authorization: Bearer <token>` header or from the session cookie.
+///
+/// The authenticated [`crate::controller::User`] is inserted into request's extensions.
+pub async fn auth(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let token = session_token(req.request()).ok_or_else(|| {
+        WebError::new(
+            StatusCode::UNAUTHORIZED,
+            "authentication is required".to_string(),
+        )
+    })?;
+
+    let data = req
+        .app_data::<web::Data<ServiceState>>()
+        .expect("service state must be set for the authenticated scope")
+        .clone();
+
+    let user = data
+        .ctrl
+        .authenticate(&token)
+        .await
+        .map_err(WebError::from)?;
+    req.extensions_mut().insert(user);
+
+    next.call(req).await
+}
+
+/// `session_token` returns the session token from `Authorization` header or from the cookie
+pub fn session_token(req: &HttpRequest) -> Option<String> {
+    bearer_token(req)
+        .map(|token| token.to_string())
+        .or_else(|| {
+            req.cookie(SESSION_COOKIE)
+                .map(|cookie| cookie.value().to_string())
+                .filter(|token| !token.is_empty())
+        })
+}
+
+/// `bearer_token` returns the token from `Authorization: Bearer <token>` header
+pub fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(|token| token.trim())
+        .filter(|token| !token.is_empty())
+}
@@ -3,11 +3,23 @@ pub mod config;
 mod controller;
 mod middleware;
 mod model;
+mod test;
+mod tls;
 
 use std::error::Error;
+use std::future::Future;
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 
 use actix_cors::Cors;
-use actix_web::middleware::ErrorHandlers;
+use actix_multipart::form::MultipartFormConfig;
+use actix_web::dev::ServerHandle;
+use actix_web::http::KeepAlive;
+use actix_web::middleware::{from_fn, ErrorHandlers};
 use actix_web::{web, App, HttpServer};
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
@@ -18,11 +30,15 @@ use model::*;
 use crate::controller::Controller;
 use crate::module::Module;
 use crate::service::Service;
+use crate::sink::Sinks;
 
+/// `start_server` serves the API and the app until `shutdown` completes. Then the server
+/// stops accepting connections and waits for the requests in progress to finish
 pub async fn start_server(
-    host: String,
-    ctrl: Controller<Service, Module, Module>,
+    ctrl: Controller<Service, Module, Module, Sinks>,
     app_config: config::AppConfig,
+    server_config: config::ServerConfig,
+    shutdown: impl Future<Output = ()> + Send + 'static,
 ) -> Result<(), Box<dyn Error>> {
     #[derive(OpenApi)]
     #[openapi(
@@ -37,6 +53,32 @@ pub async fn start_server(
             service::get_device_sensor_info,
             service::save_monitor_conf,
             service::get_monitor_conf_list,
+            service::get_device_command_info,
+            service::send_device_command,
+            service::get_device_info,
+            service::upload_module,
+            service::get_module_catalog,
+            service::delete_module,
+            service::start_device_init_from_catalog,
+            service::upgrade_device_module,
+            service::inspect_module,
+            service::set_device_watchdog_conf,
+            service::create_push_device,
+            service::reset_ingest_token,
+            service::import_sensor_data,
+            service::export_sensor_data,
+            service::get_current_user,
+            service::create_user,
+            service::get_user_list,
+            service::update_user,
+            service::delete_user,
+            service::create_api_key,
+            service::get_api_key_list,
+            service::revoke_api_key,
+            service::get_audit_log,
+            auth::login,
+            auth::logout,
+            ingest::ingest,
         ),
         components(schemas(
             error::WebError,
@@ -81,19 +123,86 @@ pub async fn start_server(
             contract::MonitorConfListResponse,
             contract::MonitorConfListEntry,
             contract::MonitorLineConf,
+            contract::GetDeviceCommandInfoRequest,
+            contract::GetDeviceCommandInfoResponse,
+            contract::CommandInfo,
+            contract::CommandArgInfo,
+            contract::SendDeviceCommandRequest,
+            contract::CommandArg,
+            contract::GetDeviceInfoRequest,
+            contract::GetDeviceInfoResponse,
+            contract::DeviceInitState,
+            contract::ModuleInfo,
+            contract::ModuleCapabilities,
+            contract::UploadModuleRequest,
+            contract::CatalogModule,
+            contract::GetModuleCatalogResponse,
+            contract::DeleteModuleRequest,
+            contract::DeviceStartInitFromCatalogRequest,
+            contract::UpgradeDeviceModuleRequest,
+            contract::InspectModuleRequest,
+            contract::InspectModuleResponse,
+            contract::ModuleFunction,
+            contract::DeviceHealth,
+            contract::WatchdogConf,
+            contract::SetDeviceWatchdogConfRequest,
+            contract::CreatePushDeviceRequest,
+            contract::PushSensor,
+            contract::PushSensorField,
+            contract::PushFieldType,
+            contract::CreatePushDeviceResponse,
+            contract::ResetIngestTokenRequest,
+            contract::ResetIngestTokenResponse,
+            contract::IngestRequest,
+            contract::IngestRow,
+            contract::IngestResponse,
+            contract::ImportSensorDataRequest,
+            contract::ImportSensorDataResponse,
+            contract::ImportRowError,
+            contract::ExportSensorDataRequest,
+            contract::ExportFormat,
+            contract::LoginRequest,
+            contract::LoginResponse,
+            contract::User,
+            contract::GetUserListResponse,
+            contract::Role,
+            contract::CreateUserRequest,
+            contract::UpdateUserRequest,
+            contract::DeleteUserRequest,
+            contract::ApiKeyScope,
+            contract::ApiKey,
+            contract::CreateApiKeyRequest,
+            contract::CreateApiKeyResponse,
+            contract::GetApiKeyListResponse,
+            contract::RevokeApiKeyRequest,
+            contract::AuditAction,
+            contract::AuditRecord,
+            contract::GetAuditLogRequest,
+            contract::GetAuditLogResponse,
         ))
     )]
     struct ApiDoc;
 
-    HttpServer::new(move || {
-        // TODO: replace with conditional code for testing
-        let cors = Cors::permissive();
+    let cors_origins = server_config.cors_origins().to_vec();
+    let max_json_size = server_config.max_json_size();
+    let max_upload_size = server_config.max_upload_size();
+    let max_import_size = server_config.max_import_size();
+
+    let mut server = HttpServer::new(move || {
+        let cors = cors(&cors_origins);
 
         let json_cfg = web::JsonConfig::default()
+            .limit(max_json_size)
             .error_handler(|err, _| model::error::WebError::from(err).into());
+        let validated_json_cfg = actix_web_validator::JsonConfig::default().limit(max_json_size);
+        let multipart_cfg = MultipartFormConfig::default().total_limit(max_upload_size);
+        // Imported files are usually much larger than modules, so they have their own limit
+        let import_multipart_cfg = MultipartFormConfig::default().total_limit(max_import_size);
 
         App::new()
             .app_data(json_cfg)
+            .app_data(validated_json_cfg)
+            .app_data(multipart_cfg)
             .wrap(cors)
             .wrap(ErrorHandlers::new().default_handler(middleware::error_parser::error_parser))
             .service(
@@ -108,23 +217,170 @@ pub async fn start_server(
                     .service(service::get_device_list)
                     .service(service::get_device_sensor_info)
                     .service(service::get_monitor_conf_list)
-                    .service(service::save_monitor_conf),
+                    .service(service::save_monitor_conf)
+                    .service(service::get_device_command_info)
+                    .service(service::send_device_command)
+                    .service(service::get_device_info)
+                    .service(service::upload_module)
+                    .service(service::get_module_catalog)
+                    .service(service::delete_module)
+                    .service(service::start_device_init_from_catalog)
+                    .service(service::upgrade_device_module)
+                    .service(service::inspect_module)
+                    .service(service::set_device_watchdog_conf)
+                    .service(service::create_push_device)
+                    .service(service::reset_ingest_token)
+                    .service(
+                        web::resource("/import-sensor-data")
+                            .app_data(import_multipart_cfg)
+                            .route(web::post().to(service::import_sensor_data)),
+                    )
+                    .service(service::export_sensor_data)
+                    .service(service::get_current_user)
+                    .service(service::create_user)
+                    .service(service::get_user_list)
+                    .service(service::update_user)
+                    .service(service::delete_user)
+                    .service(service::create_api_key)
+                    .service(service::get_api_key_list)
+                    .service(service::revoke_api_key)
+                    .service(service::get_audit_log)
+                    .wrap(from_fn(middleware::auth::auth)),
+            )
+            .service(
+                web::scope("/auth")
+                    .app_data(web::Data::new(ServiceState { ctrl: ctrl.clone() }))
+                    .service(auth::login)
+                    .service(auth::logout),
+            )
+            .service(
+                web::scope("/ingest")
+                    .app_data(web::Data::new(ServiceState { ctrl: ctrl.clone() }))
+                    .service(ingest::ingest),
             )
             .app_data(web::Data::new(AppState {
                 conf: app_config.clone(),
             }))
             .service(app::index)
+            .service(app::login)
             .service(web::redirect("/app", "/app/"))
             .service(app::serve_static)
             .service(SwaggerUi::new("/docs/{_:.*}").url("/swagger.json", ApiDoc::openapi()))
             .service(web::redirect("/docs", "/docs/"))
             .service(web::redirect("/", "/app/"))
     })
-    .bind(host)
-    .unwrap()
-    .run()
-    .await
-    .unwrap();
+    .client_request_timeout(server_config.request_timeout())
+    .keep_alive(keep_alive(server_config.keep_alive()))
+    .disable_signals();
+
+    if let Some(workers) = server_config.workers() {
+        server = server.workers(workers);
+    }
+
+    let tls = match server_config.tls() {
+        Some(tls_config) => {
+            let resolver = Arc::new(tls::CertResolver::new(tls_config.clone())?);
+            // There is no SIGHUP on other platforms, the certificate is loaded once there
+            #[cfg(unix)]
+            tls::reload_on_sighup(resolver.clone())?;
+
+            Some(tls::server_config(resolver)?)
+        }
+        None => None,
+    };
+
+    for addr in server_config.binds() {
+        server = match (addr, &tls) {
+            (config::BindAddr::Tcp(host), Some(tls)) => server.bind_rustls_0_23(host, tls.clone()),
+            (config::BindAddr::Tcp(host), None) => server.bind(host),
+            #[cfg(unix)]
+            (config::BindAddr::Unix(path), _) => {
+                remove_stale_socket(path)?;
+                server.bind_uds(path)
+            }
+            // Such addresses are rejected when the config is parsed
+            #[cfg(not(unix))]
+            (config::BindAddr::Unix(_), _) => {
+                return Err(format!("unix sockets are not supported: {addr}").into())
+            }
+        }
+        .map_err(|err| format!("failed to bind {addr}: {err}"))?;
+    }
+
+    let redirect_host = server_config.tls().and_then(|tls| tls.redirect_host());
+    let Some(redirect_host) = redirect_host else {
+        let server = server.run();
+        stop_on(shutdown, vec![server.handle()]);
+
+        server.await?;
+        return Ok(());
+    };
+
+    let https_port = server
+        .addrs_with_scheme()
+        .into_iter()
+        .find(|(_, scheme)| *scheme == "https")
+        .map(|(addr, _)| addr.port())
+        .unwrap_or(443);
+    let redirect = tls::redirect_server(redirect_host, https_port)
+        .map_err(|err| format!("failed to bind {redirect_host}: {err}"))?;
+
+    let server = server.run();
+    stop_on(shutdown, vec![server.handle(), redirect.handle()]);
+
+    futures_util::try_join!(server, redirect)?;
 
     Ok(())
 }
+
+/// `stop_on` gracefully stops the servers when `shutdown` completes
+fn stop_on(shutdown: impl Future<Output = ()> + Send + 'static, handles: Vec<ServerHandle>) {
+    tokio::spawn(async move {
+        shutdown.await;
+
+        for handle in handles {
+            handle.stop(true).await;
+        }
+    });
+}
+
+/// `cors` allows cross-origin requests only from `origins`.
+/// Requests from other origins get no CORS headers, so browsers block them.
+///
+/// Any origin is allowed without credentials, so other sites can't use the session cookie
+fn cors(origins: &[String]) -> Cors {
+    if origins.iter().any(|origin| origin == config::ANY_ORIGIN) {
+        return Cors::default()
+            .allow_any_origin()
+            .allow_any_method()
+            .allow_any_header()
+            .expose_any_header();
+    }
+
+    origins
+        .iter()
+        .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+        .allow_any_method()
+        .allow_any_header()
+        .expose_any_header()
+        .supports_credentials()
+        .block_on_origin_mismatch(false)
+}
+
+fn keep_alive(dur: Duration) -> KeepAlive {
+    if dur.is_zero() {
+        KeepAlive::Disabled
+    } else {
+        KeepAlive::Timeout(dur)
+    }
+}
+
+/// `remove_stale_socket` removes a socket file left by a previous run,
+/// because binding to an existing path fails
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) -> std::io::Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(path),
+        _ => Ok(()),
+    }
+}
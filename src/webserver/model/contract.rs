@@ -23,6 +23,14 @@ pub struct DeviceStartInitRequest {
     pub module_file: TempFile,
 }
 
+#[derive(Debug, MultipartForm, ToSchema)]
+pub struct UpgradeDeviceModuleRequest {
+    #[schema(value_type = i32)]
+    pub device_id: Text<i32>,
+    #[schema(value_type = String, format = Binary)]
+    pub module_file: TempFile,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct DeviceStartInitResponse {
     pub device_id: i32,
@@ -386,6 +394,13 @@ impl From<Vec<controller::DeviceInfo>> for GetDeviceListResponse {
 pub struct DeviceEntry {
     pub id: i32,
     pub name: String,
+    /// Absent if device is not started
+    pub health: Option<DeviceHealth>,
+    /// A call to device's module has timed out and hasn't returned yet
+    /// or the watchdog has given up restarting the device
+    pub faulted: bool,
+    /// Device was stopped by an administrator and isn't started with the service
+    pub stopped: bool,
 }
 
 impl From<controller::DeviceInfo> for DeviceEntry {
@@ -393,6 +408,31 @@ impl From<controller::DeviceInfo> for DeviceEntry {
         Self {
             id: value.id.get_raw(),
             name: value.display_name,
+            health: value.health.map(|v| v.into()),
+            faulted: value.faulted,
+            stopped: value.stopped,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DeviceHealth {
+    /// UTC time of the last message from device
+    pub last_msg_at: Option<chrono::NaiveDateTime>,
+    pub msg_count: u64,
+    /// Device hasn't sent messages for longer than its expected interval
+    pub stale: bool,
+    /// Number of restarts made by the watchdog
+    pub restart_count: u64,
+}
+
+impl From<controller::DeviceHealth> for DeviceHealth {
+    fn from(value: controller::DeviceHealth) -> Self {
+        Self {
+            last_msg_at: value.last_msg_at,
+            msg_count: value.msg_count,
+            stale: value.stale,
+            restart_count: value.restart_count,
         }
     }
 }
@@ -683,3 +723,947 @@ pub struct MonitorConfListEntry {
     pub typ: MonitorType,
     pub config: MonitorTypeConf,
 }
+
+#[derive(Clone, Debug, Validate, Deserialize, ToSchema)]
+pub struct GetDeviceCommandInfoRequest {
+    #[validate(range(min = 1))]
+    pub device_id: i32,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GetDeviceCommandInfoResponse {
+    pub commands: Vec<CommandInfo>,
+}
+
+impl From<Vec<controller::CommandInfo>> for GetDeviceCommandInfoResponse {
+    fn from(mut value: Vec<controller::CommandInfo>) -> Self {
+        let mut commands: Vec<CommandInfo> = value.drain(..).map(|v| v.into()).collect();
+
+        commands.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+        Self { commands }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CommandInfo {
+    pub name: String,
+    pub description: Option<String>,
+    pub args: Vec<CommandArgInfo>,
+}
+
+impl From<controller::CommandInfo> for CommandInfo {
+    fn from(mut value: controller::CommandInfo) -> Self {
+        Self {
+            name: value.name,
+            description: value.description,
+            args: value.args.drain(..).map(|v| v.into()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CommandArgInfo {
+    pub name: String,
+    pub typ: SensorDataType,
+    pub required: bool,
+}
+
+impl From<controller::CommandArgInfo> for CommandArgInfo {
+    fn from(value: controller::CommandArgInfo) -> Self {
+        Self {
+            name: value.name,
+            typ: value.typ.into(),
+            required: value.required,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Validate, Deserialize, ToSchema)]
+pub struct SendDeviceCommandRequest {
+    #[validate(range(min = 1))]
+    pub device_id: i32,
+    #[validate(length(min = 1))]
+    pub command: String,
+    pub args: Vec<CommandArg>,
+}
+
+impl From<SendDeviceCommandRequest> for controller::Command {
+    fn from(mut value: SendDeviceCommandRequest) -> Self {
+        Self {
+            name: value.command,
+            args: value.args.drain(..).map(|v| v.into()).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, ToSchema)]
+pub struct CommandArg {
+    pub name: String,
+    pub value: Option<SensorData>,
+}
+
+impl From<CommandArg> for controller::CommandArg {
+    fn from(value: CommandArg) -> Self {
+        Self {
+            name: value.name,
+            data: value.value.map(|v| v.into()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Validate, Deserialize, ToSchema)]
+pub struct GetDeviceInfoRequest {
+    #[validate(range(min = 1))]
+    pub device_id: i32,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GetDeviceInfoResponse {
+    pub id: i32,
+    pub name: String,
+    pub display_name: String,
+    pub init_state: DeviceInitState,
+    pub module_info: Option<ModuleInfo>,
+    /// Hash of the catalog module used by the device
+    pub module_hash: Option<String>,
+    pub watchdog_conf: WatchdogConf,
+    /// Device's module isn't started with the service
+    pub stopped: bool,
+}
+
+impl From<controller::DeviceFullInfo> for GetDeviceInfoResponse {
+    fn from(value: controller::DeviceFullInfo) -> Self {
+        Self {
+            id: value.id.get_raw(),
+            name: value.name,
+            display_name: value.display_name,
+            init_state: value.init_state.into(),
+            module_info: value.module_info.map(|v| v.into()),
+            module_hash: value.module_hash,
+            watchdog_conf: value.watchdog_conf.into(),
+            stopped: value.stopped,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub enum DeviceInitState {
+    Device,
+    Sensors,
+}
+
+impl From<controller::DeviceInitState> for DeviceInitState {
+    fn from(value: controller::DeviceInitState) -> Self {
+        match value {
+            controller::DeviceInitState::Device => DeviceInitState::Device,
+            controller::DeviceInitState::Sensors => DeviceInitState::Sensors,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ModuleInfo {
+    pub name: String,
+    pub vendor: Option<String>,
+    pub version: String,
+    pub description: Option<String>,
+    pub os: Option<String>,
+    pub arch: Option<String>,
+    pub capabilities: ModuleCapabilities,
+}
+
+impl From<controller::ModuleInfo> for ModuleInfo {
+    fn from(value: controller::ModuleInfo) -> Self {
+        Self {
+            name: value.name,
+            vendor: value.vendor,
+            version: value.version,
+            description: value.description,
+            os: value.os,
+            arch: value.arch,
+            capabilities: value.capabilities.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ModuleCapabilities {
+    pub commands: bool,
+    pub hot_reconfigure: bool,
+    pub persisted_config: bool,
+}
+
+impl From<controller::ModuleCapabilities> for ModuleCapabilities {
+    fn from(value: controller::ModuleCapabilities) -> Self {
+        Self {
+            commands: value.commands,
+            hot_reconfigure: value.hot_reconfigure,
+            persisted_config: value.persisted_config,
+        }
+    }
+}
+
+#[derive(Debug, MultipartForm, ToSchema)]
+pub struct InspectModuleRequest {
+    #[schema(value_type = String, format = Binary)]
+    pub module_file: TempFile,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct InspectModuleResponse {
+    /// A device can be created with the module
+    pub valid: bool,
+    /// Why a device can't be created with the module
+    pub error: Option<String>,
+    /// Version returned by `mod_version`. `null` if the library can't be loaded
+    pub mod_version: Option<u8>,
+    /// Version of the module API supported by the service
+    pub supported_version: u8,
+    /// Functions of the module. Empty if the library can't be loaded or has another version
+    pub functions: Vec<ModuleFunction>,
+    pub module_info: Option<ModuleInfo>,
+    /// Connection params of the module. `null` if the module can't be initialized
+    pub conn_params: Option<Vec<ConfInfoEntry>>,
+}
+
+impl From<controller::ModuleInspection> for InspectModuleResponse {
+    fn from(value: controller::ModuleInspection) -> Self {
+        Self {
+            valid: value.error.is_none(),
+            error: value.error,
+            mod_version: value.version,
+            supported_version: value.supported_version,
+            functions: value.functions.into_iter().map(|v| v.into()).collect(),
+            module_info: value.module_info.map(|v| v.into()),
+            conn_params: value
+                .conn_info
+                .map(|v| v.into_iter().map(|v| v.into()).collect()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ModuleFunction {
+    pub name: String,
+    pub required: bool,
+    pub present: bool,
+}
+
+impl From<controller::FunctionPresence> for ModuleFunction {
+    fn from(value: controller::FunctionPresence) -> Self {
+        Self {
+            name: value.name.to_string(),
+            required: value.required,
+            present: value.present,
+        }
+    }
+}
+
+#[derive(Debug, MultipartForm, ToSchema)]
+pub struct UploadModuleRequest {
+    #[schema(value_type = String, format = Byte)]
+    pub name: Text<String>,
+    #[schema(value_type = String, format = Binary)]
+    pub module_file: TempFile,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CatalogModule {
+    pub id: i32,
+    pub name: String,
+    pub hash: String,
+    pub size: i64,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl From<controller::CatalogModule> for CatalogModule {
+    fn from(value: controller::CatalogModule) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            hash: value.hash,
+            size: value.size,
+            created_at: value.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GetModuleCatalogResponse {
+    pub result: Vec<CatalogModule>,
+}
+
+impl From<Vec<controller::CatalogModule>> for GetModuleCatalogResponse {
+    fn from(mut value: Vec<controller::CatalogModule>) -> Self {
+        Self {
+            result: value.drain(..).map(|v| v.into()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct DeleteModuleRequest {
+    #[validate(range(min = 1))]
+    pub module_id: i32,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct DeviceStartInitFromCatalogRequest {
+    #[validate(length(min = 1))]
+    pub device_name: String,
+    #[validate(range(min = 1))]
+    pub module_id: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct WatchdogConf {
+    /// Maximum interval between device's messages in seconds. Absent to disable the watchdog
+    #[validate(range(min = 1))]
+    pub expected_interval: Option<i32>,
+    /// Restart device's module when device becomes stale
+    pub restart: bool,
+}
+
+impl From<controller::WatchdogConf> for WatchdogConf {
+    fn from(value: controller::WatchdogConf) -> Self {
+        Self {
+            expected_interval: value.expected_interval,
+            restart: value.restart,
+        }
+    }
+}
+
+impl From<WatchdogConf> for controller::WatchdogConf {
+    fn from(value: WatchdogConf) -> Self {
+        Self {
+            expected_interval: value.expected_interval,
+            restart: value.restart,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct SetDeviceWatchdogConfRequest {
+    #[validate(range(min = 1))]
+    pub device_id: i32,
+    #[validate]
+    pub watchdog_conf: WatchdogConf,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct CreatePushDeviceRequest {
+    #[validate(length(min = 1))]
+    pub device_name: String,
+    /// Sensors of the device. Every sensor also gets a `timestamp` field
+    #[validate(length(min = 1))]
+    pub sensors: Vec<PushSensor>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PushSensor {
+    pub name: String,
+    pub fields: Vec<PushSensorField>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PushSensorField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub typ: PushFieldType,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PushFieldType {
+    Int16,
+    Int32,
+    Int64,
+    Float32,
+    Float64,
+    Timestamp,
+    String,
+    Json,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatePushDeviceResponse {
+    pub device_id: i32,
+    /// Token for the ingest endpoint. It's returned only once
+    pub ingest_token: String,
+}
+
+impl From<controller::PushDevice> for CreatePushDeviceResponse {
+    fn from(value: controller::PushDevice) -> Self {
+        Self {
+            device_id: value.id.get_raw(),
+            ingest_token: value.ingest_token,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ResetIngestTokenRequest {
+    #[validate(range(min = 1))]
+    pub device_id: i32,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ResetIngestTokenResponse {
+    pub ingest_token: String,
+}
+
+/// IngestRequest is a single row or a batch of rows
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum IngestRequest {
+    Row(IngestRow),
+    Batch(Vec<IngestRow>),
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct IngestRow {
+    pub sensor: String,
+    /// Values by field names. Absent `timestamp` is set to the time of receipt
+    #[schema(value_type = Object)]
+    pub data: serde_json::Map<String, serde_json::Value>,
+}
+
+impl From<IngestRow> for controller::IngestRow {
+    fn from(value: IngestRow) -> Self {
+        Self {
+            sensor: value.sensor,
+            data: value.data,
+        }
+    }
+}
+
+impl From<IngestRequest> for Vec<controller::IngestRow> {
+    fn from(value: IngestRequest) -> Self {
+        match value {
+            IngestRequest::Row(row) => vec![row.into()],
+            IngestRequest::Batch(rows) => rows.into_iter().map(|row| row.into()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IngestResponse {
+    /// Number of saved rows
+    pub saved: usize,
+}
+
+#[derive(Debug, MultipartForm, ToSchema)]
+pub struct ImportSensorDataRequest {
+    #[schema(value_type = i32)]
+    pub device_id: Text<i32>,
+    #[schema(value_type = String, format = Byte)]
+    pub sensor: Text<String>,
+    /// `csv` or `line_protocol`
+    #[schema(value_type = String, format = Byte)]
+    pub format: Text<String>,
+    /// JSON object with sensor's field names by CSV columns or line protocol keys
+    #[schema(value_type = Option<String>, format = Byte)]
+    pub mapping: Option<Text<String>>,
+    /// Precision of line protocol timestamps: `ns` (default), `us`, `ms` or `s`
+    #[schema(value_type = Option<String>, format = Byte)]
+    pub precision: Option<Text<String>>,
+    /// Validate the file without saving its data
+    #[schema(value_type = Option<bool>)]
+    pub dry_run: Option<Text<bool>>,
+    #[schema(value_type = String, format = Binary)]
+    pub file: TempFile,
+}
+
+impl ImportSensorDataRequest {
+    pub fn to_payload(&self) -> Result<controller::ImportPayload, String> {
+        let format = controller::ImportFormat::from_name(&self.format)
+            .ok_or_else(|| format!("unknown format '{}'", *self.format))?;
+
+        let mapping = match self.mapping {
+            Some(ref v) if !v.is_empty() => serde_json::from_str(v)
+                .map_err(|err| format!("mapping must be a JSON object of strings: {err}"))?,
+            _ => HashMap::new(),
+        };
+
+        let precision = match self.precision {
+            Some(ref v) => controller::TimestampPrecision::from_name(v)
+                .ok_or_else(|| format!("unknown precision '{}'", **v))?,
+            None => Default::default(),
+        };
+
+        Ok(controller::ImportPayload {
+            device_id: *self.device_id,
+            sensor: self.sensor.to_string(),
+            format,
+            mapping,
+            precision,
+            dry_run: self.dry_run.as_ref().map(|v| **v).unwrap_or_default(),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportSensorDataResponse {
+    pub dry_run: bool,
+    /// Number of data rows in the file
+    pub total: usize,
+    /// Number of saved rows. In dry-run mode, it's the number of valid rows
+    pub imported: usize,
+    pub invalid: usize,
+    /// Errors of the first invalid rows
+    pub errors: Vec<ImportRowError>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportRowError {
+    /// Line in the file starting from 1
+    pub line: u64,
+    pub msg: String,
+}
+
+impl From<controller::ImportReport> for ImportSensorDataResponse {
+    fn from(value: controller::ImportReport) -> Self {
+        Self {
+            dry_run: value.dry_run,
+            total: value.total,
+            imported: value.imported,
+            invalid: value.invalid,
+            errors: value
+                .errors
+                .into_iter()
+                .map(|err| ImportRowError {
+                    line: err.line,
+                    msg: err.msg,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Validate, Deserialize, ToSchema)]
+pub struct ExportSensorDataRequest {
+    #[validate(range(min = 1))]
+    pub device_id: i32,
+    #[validate(length(min = 1))]
+    pub sensor: String,
+    pub format: ExportFormat,
+    /// Fields to export in the given order. All fields are exported if it's empty
+    #[serde(default)]
+    pub fields: Vec<String>,
+    /// Timestamp field which the range and the order of rows apply to
+    pub time_field: Option<String>,
+    /// Start of the range, inclusive
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    /// End of the range, exclusive
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+    /// IANA timezone of timestamps in CSV and JSON Lines, e.g. `Europe/Berlin`. UTC by default
+    pub timezone: Option<String>,
+}
+
+impl ExportSensorDataRequest {
+    pub fn to_payload(&self) -> Result<controller::ExportPayload, String> {
+        let timezone = match self.timezone {
+            Some(ref v) => v
+                .parse::<chrono_tz::Tz>()
+                .map_err(|_| format!("unknown timezone '{v}'"))?,
+            None => chrono_tz::UTC,
+        };
+
+        Ok(controller::ExportPayload {
+            device_id: self.device_id,
+            sensor: self.sensor.clone(),
+            format: self.format.into(),
+            fields: self.fields.clone(),
+            time_field: self.time_field.clone(),
+            from: self.from.map(|v| v.naive_utc()),
+            to: self.to.map(|v| v.naive_utc()),
+            timezone,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    Jsonl,
+    Parquet,
+}
+
+impl From<ExportFormat> for controller::ExportFormat {
+    fn from(value: ExportFormat) -> Self {
+        match value {
+            ExportFormat::Csv => controller::ExportFormat::Csv,
+            ExportFormat::Jsonl => controller::ExportFormat::JsonLines,
+            ExportFormat::Parquet => controller::ExportFormat::Parquet,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct LoginRequest {
+    #[validate(length(min = 1))]
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LoginResponse {
+    /// Session token for `Authorization: Bearer <token>` header. It's also set as a cookie
+    pub token: String,
+    pub expires_at: chrono::NaiveDateTime,
+    pub user: User,
+}
+
+impl From<controller::LoginSession> for LoginResponse {
+    fn from(value: controller::LoginSession) -> Self {
+        Self {
+            token: value.token,
+            expires_at: value.expires_at,
+            user: value.user.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct User {
+    pub id: i32,
+    pub username: String,
+    pub role: Role,
+    /// Ids of devices which the user has access to. `null` gives access to all devices
+    pub device_ids: Option<Vec<i32>>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl From<controller::User> for User {
+    fn from(value: controller::User) -> Self {
+        Self {
+            id: value.id,
+            username: value.username,
+            role: value.role.into(),
+            device_ids: value.devices,
+            created_at: value.created_at,
+        }
+    }
+}
+
+/// Role of a user. Every role has all permissions of the previous ones:
+/// - `viewer` views devices, their data and panels
+/// - `operator` also saves panels
+/// - `engineer` also sends commands, adds, configures and deletes devices and modules
+/// - `admin` also manages users
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Viewer,
+    Operator,
+    Engineer,
+    Admin,
+}
+
+impl From<Role> for controller::Role {
+    fn from(value: Role) -> Self {
+        match value {
+            Role::Viewer => controller::Role::Viewer,
+            Role::Operator => controller::Role::Operator,
+            Role::Engineer => controller::Role::Engineer,
+            Role::Admin => controller::Role::Admin,
+        }
+    }
+}
+
+impl From<controller::Role> for Role {
+    fn from(value: controller::Role) -> Self {
+        match value {
+            controller::Role::Viewer => Role::Viewer,
+            controller::Role::Operator => Role::Operator,
+            controller::Role::Engineer => Role::Engineer,
+            controller::Role::Admin => Role::Admin,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GetUserListResponse {
+    pub result: Vec<User>,
+}
+
+impl From<Vec<controller::User>> for GetUserListResponse {
+    fn from(mut value: Vec<controller::User>) -> Self {
+        Self {
+            result: value.drain(..).map(|v| v.into()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateUserRequest {
+    #[validate(length(min = 1, max = 255))]
+    pub username: String,
+    pub password: String,
+    pub role: Role,
+    /// Ids of devices which the user has access to. If it's absent, the user has access
+    /// to all devices
+    pub device_ids: Option<Vec<i32>>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct UpdateUserRequest {
+    #[validate(range(min = 1))]
+    pub user_id: i32,
+    pub role: Role,
+    /// Ids of devices which the user has access to. If it's absent, the user has access
+    /// to all devices
+    pub device_ids: Option<Vec<i32>>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct DeleteUserRequest {
+    #[validate(range(min = 1))]
+    pub user_id: i32,
+}
+
+/// Scope of an API key. A key can't do more than its user:
+/// - `read_data` views devices, their data and panels
+/// - `ingest` sends sensor data of push devices to the ingest endpoint
+/// - `admin` does everything its user may do
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyScope {
+    ReadData,
+    Ingest,
+    Admin,
+}
+
+impl From<ApiKeyScope> for controller::ApiKeyScope {
+    fn from(value: ApiKeyScope) -> Self {
+        match value {
+            ApiKeyScope::ReadData => controller::ApiKeyScope::ReadData,
+            ApiKeyScope::Ingest => controller::ApiKeyScope::Ingest,
+            ApiKeyScope::Admin => controller::ApiKeyScope::Admin,
+        }
+    }
+}
+
+impl From<controller::ApiKeyScope> for ApiKeyScope {
+    fn from(value: controller::ApiKeyScope) -> Self {
+        match value {
+            controller::ApiKeyScope::ReadData => ApiKeyScope::ReadData,
+            controller::ApiKeyScope::Ingest => ApiKeyScope::Ingest,
+            controller::ApiKeyScope::Admin => ApiKeyScope::Admin,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiKey {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub created_at: chrono::NaiveDateTime,
+    /// `null` if the key was never used
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+}
+
+impl From<controller::ApiKey> for ApiKey {
+    fn from(value: controller::ApiKey) -> Self {
+        Self {
+            id: value.id,
+            user_id: value.user_id,
+            name: value.name,
+            scopes: value.scopes.into_iter().map(|v| v.into()).collect(),
+            created_at: value.created_at,
+            last_used_at: value.last_used_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateApiKeyRequest {
+    #[validate(length(min = 1, max = 255))]
+    pub name: String,
+    #[validate(length(min = 1))]
+    pub scopes: Vec<ApiKeyScope>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreateApiKeyResponse {
+    /// Key for `Authorization: Bearer <key>` header. It's returned only once
+    pub key: String,
+    pub api_key: ApiKey,
+}
+
+impl From<controller::CreatedApiKey> for CreateApiKeyResponse {
+    fn from(value: controller::CreatedApiKey) -> Self {
+        Self {
+            key: value.key,
+            api_key: value.api_key.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GetApiKeyListResponse {
+    pub result: Vec<ApiKey>,
+}
+
+impl From<Vec<controller::ApiKey>> for GetApiKeyListResponse {
+    fn from(mut value: Vec<controller::ApiKey>) -> Self {
+        Self {
+            result: value.drain(..).map(|v| v.into()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct RevokeApiKeyRequest {
+    #[validate(range(min = 1))]
+    pub api_key_id: i32,
+}
+
+/// Configuration or control action recorded in the audit log
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    StartDeviceInit,
+    ConnectDevice,
+    ConfigureDevice,
+    InterruptDeviceInit,
+    DeleteDevice,
+    StopDevice,
+    StartDevice,
+    UpgradeDeviceModule,
+    SetDeviceWatchdogConf,
+    DeviceStale,
+    DeviceRecovered,
+    RestartStaleDevice,
+    SendDeviceCommand,
+    CreatePushDevice,
+    ResetIngestToken,
+    ImportSensorData,
+    UploadModule,
+    DeleteModule,
+    SaveMonitorConf,
+    CreateUser,
+    UpdateUser,
+    DeleteUser,
+    CreateApiKey,
+    RevokeApiKey,
+}
+
+impl From<AuditAction> for controller::AuditAction {
+    fn from(value: AuditAction) -> Self {
+        // Every action has its counterpart, see the conversion below
+        controller::AuditAction::ALL
+            .iter()
+            .copied()
+            .find(|action| AuditAction::from(*action) == value)
+            .unwrap()
+    }
+}
+
+impl From<controller::AuditAction> for AuditAction {
+    fn from(value: controller::AuditAction) -> Self {
+        match value {
+            controller::AuditAction::StartDeviceInit => AuditAction::StartDeviceInit,
+            controller::AuditAction::ConnectDevice => AuditAction::ConnectDevice,
+            controller::AuditAction::ConfigureDevice => AuditAction::ConfigureDevice,
+            controller::AuditAction::InterruptDeviceInit => AuditAction::InterruptDeviceInit,
+            controller::AuditAction::DeleteDevice => AuditAction::DeleteDevice,
+            controller::AuditAction::StopDevice => AuditAction::StopDevice,
+            controller::AuditAction::StartDevice => AuditAction::StartDevice,
+            controller::AuditAction::UpgradeDeviceModule => AuditAction::UpgradeDeviceModule,
+            controller::AuditAction::SetDeviceWatchdogConf => AuditAction::SetDeviceWatchdogConf,
+            controller::AuditAction::DeviceStale => AuditAction::DeviceStale,
+            controller::AuditAction::DeviceRecovered => AuditAction::DeviceRecovered,
+            controller::AuditAction::RestartStaleDevice => AuditAction::RestartStaleDevice,
+            controller::AuditAction::SendDeviceCommand => AuditAction::SendDeviceCommand,
+            controller::AuditAction::CreatePushDevice => AuditAction::CreatePushDevice,
+            controller::AuditAction::ResetIngestToken => AuditAction::ResetIngestToken,
+            controller::AuditAction::ImportSensorData => AuditAction::ImportSensorData,
+            controller::AuditAction::UploadModule => AuditAction::UploadModule,
+            controller::AuditAction::DeleteModule => AuditAction::DeleteModule,
+            controller::AuditAction::SaveMonitorConf => AuditAction::SaveMonitorConf,
+            controller::AuditAction::CreateUser => AuditAction::CreateUser,
+            controller::AuditAction::UpdateUser => AuditAction::UpdateUser,
+            controller::AuditAction::DeleteUser => AuditAction::DeleteUser,
+            controller::AuditAction::CreateApiKey => AuditAction::CreateApiKey,
+            controller::AuditAction::RevokeApiKey => AuditAction::RevokeApiKey,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AuditRecord {
+    pub id: i32,
+    /// `null` if the user was deleted or the action was made from the command line
+    pub user_id: Option<i32>,
+    pub username: String,
+    pub action: AuditAction,
+    /// Device which the action targets
+    pub device_id: Option<i32>,
+    /// Parameters of the action without secrets
+    #[schema(value_type = Object)]
+    pub summary: serde_json::Value,
+    /// `null` if the action succeeded
+    pub error: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl From<controller::AuditRecord> for AuditRecord {
+    fn from(value: controller::AuditRecord) -> Self {
+        Self {
+            id: value.id,
+            user_id: value.user_id,
+            username: value.username,
+            action: value.action.into(),
+            device_id: value.device_id,
+            summary: value.summary,
+            error: value.error,
+            created_at: value.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct GetAuditLogRequest {
+    pub user_id: Option<i32>,
+    pub device_id: Option<i32>,
+    pub action: Option<AuditAction>,
+    /// Start of the range, inclusive
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    /// End of the range, exclusive
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+    /// Maximum number of records. 100 by default
+    #[validate(range(min = 1, max = 1000))]
+    pub limit: Option<i32>,
+}
+
+impl From<GetAuditLogRequest> for controller::AuditLogFilter {
+    fn from(value: GetAuditLogRequest) -> Self {
+        Self {
+            user_id: value.user_id,
+            device_id: value.device_id,
+            action: value.action.map(|v| v.into()),
+            from: value.from.map(|v| v.naive_utc()),
+            to: value.to.map(|v| v.naive_utc()),
+            limit: value.limit,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GetAuditLogResponse {
+    /// Records from the newest ones
+    pub result: Vec<AuditRecord>,
+}
+
+impl From<Vec<controller::AuditRecord>> for GetAuditLogResponse {
+    fn from(mut value: Vec<controller::AuditRecord>) -> Self {
+        Self {
+            result: value.drain(..).map(|v| v.into()).collect(),
+        }
+    }
+}
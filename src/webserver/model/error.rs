@@ -96,6 +96,12 @@ impl From<controller::error::ControllerError> for WebError {
     }
 }
 
+impl From<controller::error::CommonError> for WebError {
+    fn from(value: controller::error::CommonError) -> Self {
+        controller::error::ControllerError::from(value).into()
+    }
+}
+
 impl From<JsonPayloadError> for WebError {
     fn from(value: JsonPayloadError) -> Self {
         Self {
@@ -123,6 +129,9 @@ fn ctrl_err_type_to_actix_code(err: controller::error::ErrorType) -> StatusCode
         controller::error::ErrorType::Timeout => StatusCode::GATEWAY_TIMEOUT,
         controller::error::ErrorType::InvalidInput => StatusCode::BAD_REQUEST,
         controller::error::ErrorType::FailedPrecondition => StatusCode::BAD_REQUEST,
+        controller::error::ErrorType::Unauthenticated => StatusCode::UNAUTHORIZED,
+        controller::error::ErrorType::PermissionDenied => StatusCode::FORBIDDEN,
+        controller::error::ErrorType::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
         controller::error::ErrorType::Unknown => StatusCode::INTERNAL_SERVER_ERROR,
     }
 }
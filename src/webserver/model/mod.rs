@@ -5,9 +5,10 @@ use super::config;
 use crate::controller::Controller;
 use crate::module::Module;
 use crate::service::Service;
+use crate::sink::Sinks;
 
 pub struct ServiceState {
-    pub ctrl: Controller<Service, Module, Module>,
+    pub ctrl: Controller<Service, Module, Module, Sinks>,
 }
 
 pub struct AppState {
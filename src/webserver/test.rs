@@ -0,0 +1,233 @@
+#[cfg(test)]
+use actix_web::{cookie::Cookie, http::StatusCode, middleware::from_fn, web, App, HttpResponse};
+
+#[cfg(test)]
+use crate::{app, controller, repo, service::Service, sink::Sinks};
+
+#[cfg(test)]
+use super::middleware::auth::{self, SESSION_COOKIE};
+
+#[cfg(test)]
+use super::model::contract::AuditAction;
+#[cfg(test)]
+use super::model::ServiceState;
+#[cfg(test)]
+use super::{config::TlsConfig, cors, tls};
+
+#[test]
+fn audit_action_conversion() {
+    for action in controller::AuditAction::ALL.iter().copied() {
+        assert_eq!(
+            controller::AuditAction::from(AuditAction::from(action)),
+            action
+        );
+    }
+}
+
+#[actix_web::test]
+async fn auth_middleware() {
+    app::init_data_dir(std::env::temp_dir().join(format!("monisens-test-{}", std::process::id())))
+        .unwrap();
+    let pool_conf = repo::PoolConf {
+        max_connections: 1,
+        min_connections: 1,
+        ..Default::default()
+    };
+    let repo = repo::Repository::new(repo::Storage::Sqlite, "sqlite::memory:", &pool_conf)
+        .await
+        .unwrap();
+    let ctrl = controller::Controller::new(
+        tokio::runtime::Handle::current(),
+        Service::new(repo).await.unwrap(),
+        Sinks::default(),
+        std::time::Duration::from_secs(5),
+    )
+    .await
+    .unwrap();
+    ctrl.bootstrap_admin("admin".to_string(), "password".to_string())
+        .await
+        .unwrap();
+    let session = ctrl
+        .login("admin", "password".to_string(), "")
+        .await
+        .unwrap();
+
+    let app = actix_web::test::init_service(
+        App::new().service(
+            web::scope("/api")
+                .wrap(from_fn(auth::auth))
+                .app_data(web::Data::new(ServiceState { ctrl }))
+                .route(
+                    "/ping",
+                    web::get().to(|| async { HttpResponse::Ok().finish() }),
+                ),
+        ),
+    )
+    .await;
+
+    let status = |cookie: Option<&str>| {
+        let mut req = actix_web::test::TestRequest::get().uri("/api/ping");
+        if let Some(token) = cookie {
+            req = req.cookie(Cookie::new(SESSION_COOKIE, token.to_string()));
+        }
+        let req = req.to_request();
+        let app = &app;
+        async move {
+            match actix_web::test::try_call_service(app, req).await {
+                Ok(res) => res.status(),
+                Err(err) => err.as_response_error().status_code(),
+            }
+        }
+    };
+
+    assert_eq!(status(None).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(status(Some("")).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(status(Some("invalid")).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(status(Some(&session.token)).await, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn redirect_to_https() {
+    for (https_port, host, uri, location) in [
+        (443, "example.com", "/", "https://example.com/"),
+        (
+            443,
+            "example.com:8080",
+            "/a?b=1",
+            "https://example.com/a?b=1",
+        ),
+        (8443, "example.com:8080", "/a", "https://example.com:8443/a"),
+        (8443, "[::1]:8080", "/a", "https://[::1]:8443/a"),
+        (8443, "[::1]", "/a", "https://[::1]:8443/a"),
+    ] {
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(web::Data::new(https_port as u16))
+                .default_service(web::to(tls::redirect_to_https)),
+        )
+        .await;
+        let req = actix_web::test::TestRequest::get()
+            .uri(uri)
+            .insert_header((actix_web::http::header::HOST, host))
+            .to_request();
+        let res = actix_web::test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(
+            res.headers()
+                .get(actix_web::http::header::LOCATION)
+                .unwrap(),
+            location,
+            "{host}{uri}"
+        );
+    }
+}
+
+#[test]
+fn load_certified_key() {
+    let dir = std::env::temp_dir().join(format!("monisens-tls-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let write = |name: &str, data: &str| {
+        let path = dir.join(name);
+        std::fs::write(&path, data).unwrap();
+        path
+    };
+
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let other = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let cert_file = write("cert.pem", &cert.cert.pem());
+    let key_file = write("key.pem", &cert.key_pair.serialize_pem());
+    let other_key_file = write("other_key.pem", &other.key_pair.serialize_pem());
+    let empty_file = write("empty.pem", "");
+
+    let provider = rustls::crypto::ring::default_provider();
+    let load = |cert_file: &std::path::Path, key_file: &std::path::Path| {
+        tls::load_certified_key(
+            &TlsConfig::new(cert_file.to_path_buf(), key_file.to_path_buf(), None),
+            &provider,
+        )
+        .map_err(|err| err.to_string())
+    };
+
+    let key = load(&cert_file, &key_file).unwrap();
+    assert_eq!(key.cert.len(), 1);
+
+    for (cert_file, key_file, err) in [
+        (
+            dir.join("missing.pem"),
+            key_file.clone(),
+            "failed to open certificate file",
+        ),
+        (
+            empty_file.clone(),
+            key_file.clone(),
+            "contains no certificates",
+        ),
+        (
+            cert_file.clone(),
+            dir.join("missing.pem"),
+            "failed to open key file",
+        ),
+        (
+            cert_file.clone(),
+            empty_file.clone(),
+            "contains no private key",
+        ),
+        (
+            cert_file.clone(),
+            other_key_file.clone(),
+            "invalid certificate or private key",
+        ),
+    ] {
+        let res = load(&cert_file, &key_file);
+        assert!(
+            matches!(res, Err(ref e) if e.contains(err)),
+            "{cert_file:?}, {key_file:?}: expected '{err}'"
+        );
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[actix_web::test]
+async fn cors_origins() {
+    use actix_web::http::header;
+
+    let allowed = |origins: &[&str], origin: &str| {
+        let origins: Vec<String> = origins.iter().map(|o| o.to_string()).collect();
+        let origin = origin.to_string();
+        async move {
+            let app = actix_web::test::init_service(
+                App::new()
+                    .wrap(cors(&origins))
+                    .route("/", web::get().to(|| async { HttpResponse::Ok().finish() })),
+            )
+            .await;
+            let req = actix_web::test::TestRequest::get()
+                .insert_header((header::ORIGIN, origin))
+                .to_request();
+            let res = actix_web::test::call_service(&app, req).await;
+            let headers = res.headers();
+
+            (
+                headers
+                    .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                    .map(|v| v.to_str().unwrap().to_string()),
+                headers.contains_key(header::ACCESS_CONTROL_ALLOW_CREDENTIALS),
+            )
+        }
+    };
+
+    let origin = "https://example.com";
+    assert_eq!(
+        allowed(&[origin], origin).await,
+        (Some(origin.to_string()), true)
+    );
+    assert_eq!(allowed(&[origin], "https://other.com").await.0, None);
+
+    // Any origin is allowed, but not with credentials
+    assert_eq!(
+        allowed(&["*"], origin).await,
+        (Some(origin.to_string()), false)
+    );
+}
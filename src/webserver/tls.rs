@@ -0,0 +1,166 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::sync::{Arc, RwLock};
+
+use actix_web::{dev::Server, http::header, web, App, HttpRequest, HttpResponse, HttpServer};
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::ServerConfig;
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
+
+use crate::logger;
+use crate::{kv_any, kvs};
+
+use super::config::TlsConfig;
+
+/// `CertResolver` serves the certificate loaded from the files of [`TlsConfig`].
+/// The certificate is replaced by [`CertResolver::reload`] without restarting the server
+#[derive(Debug)]
+pub struct CertResolver {
+    conf: TlsConfig,
+    provider: Arc<CryptoProvider>,
+    key: RwLock<Arc<CertifiedKey>>,
+}
+
+impl CertResolver {
+    pub fn new(conf: TlsConfig) -> Result<Self, Box<dyn Error>> {
+        let provider = Arc::new(ring::default_provider());
+        let key = load_certified_key(&conf, &provider)?;
+
+        Ok(Self {
+            conf,
+            provider,
+            key: RwLock::new(Arc::new(key)),
+        })
+    }
+
+    /// `reload` loads the certificate from the files again. The current certificate
+    /// is kept if the files are invalid.
+    pub fn reload(&self) -> Result<(), Box<dyn Error>> {
+        let key = load_certified_key(&self.conf, &self.provider)?;
+        *self.key.write().unwrap() = Arc::new(key);
+
+        Ok(())
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.key.read().unwrap().clone())
+    }
+}
+
+/// `server_config` returns the TLS config of the web server
+pub fn server_config(resolver: Arc<CertResolver>) -> Result<ServerConfig, Box<dyn Error>> {
+    let conf = ServerConfig::builder_with_provider(resolver.provider.clone())
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_cert_resolver(resolver);
+
+    Ok(conf)
+}
+
+/// `reload_on_sighup` reloads the certificate every time the process receives SIGHUP
+#[cfg(unix)]
+pub fn reload_on_sighup(resolver: Arc<CertResolver>) -> io::Result<()> {
+    let mut hangup = signal(SignalKind::hangup())?;
+
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            match resolver.reload() {
+                Ok(_) => logger::info_kv(
+                    "TLS certificate reloaded",
+                    kvs!("cert_file" => kv_any!(resolver.conf.cert_file())),
+                ),
+                Err(err) => logger::error_kv(
+                    "failed to reload TLS certificate, the current one is kept",
+                    kvs!("error" => kv_any!(err.to_string())),
+                ),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// `redirect_server` starts a plain HTTP server which redirects all requests
+/// to the same host and path on `https_port`
+pub fn redirect_server(host: &str, https_port: u16) -> io::Result<Server> {
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(https_port))
+            .default_service(web::to(redirect_to_https))
+    })
+    .disable_signals()
+    .bind(host)?
+    .run();
+
+    Ok(server)
+}
+
+pub(super) async fn redirect_to_https(
+    req: HttpRequest,
+    https_port: web::Data<u16>,
+) -> HttpResponse {
+    let conn_info = req.connection_info();
+    let host = conn_info.host();
+
+    // Host header may contain the port of the HTTP listener
+    let hostname = match host.rsplit_once(':') {
+        Some((name, port)) if !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => host,
+    };
+
+    let location = match **https_port {
+        443 => format!("https://{hostname}{}", req.uri()),
+        port => format!("https://{hostname}:{port}{}", req.uri()),
+    };
+
+    HttpResponse::PermanentRedirect()
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
+
+/// `load_certified_key` reads the certificate chain and its private key from PEM files
+pub(super) fn load_certified_key(
+    conf: &TlsConfig,
+    provider: &CryptoProvider,
+) -> Result<CertifiedKey, Box<dyn Error>> {
+    let mut cert_reader = BufReader::new(File::open(conf.cert_file()).map_err(|err| {
+        format!(
+            "failed to open certificate file {}: {err}",
+            conf.cert_file().display()
+        )
+    })?);
+    let certs = rustls_pemfile::certs(&mut cert_reader)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("failed to read certificate: {err}"))?;
+    if certs.is_empty() {
+        return Err(format!(
+            "certificate file {} contains no certificates",
+            conf.cert_file().display()
+        )
+        .into());
+    }
+
+    let mut key_reader = BufReader::new(File::open(conf.key_file()).map_err(|err| {
+        format!(
+            "failed to open key file {}: {err}",
+            conf.key_file().display()
+        )
+    })?);
+    let key = rustls_pemfile::private_key(&mut key_reader)
+        .map_err(|err| format!("failed to read private key: {err}"))?
+        .ok_or_else(|| {
+            format!(
+                "key file {} contains no private key",
+                conf.key_file().display()
+            )
+        })?;
+
+    CertifiedKey::from_der(certs, key, provider)
+        .map_err(|err| format!("invalid certificate or private key: {err}").into())
+}